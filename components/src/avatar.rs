@@ -1,11 +1,11 @@
-use std::collections::HashMap;
-
 use gloo::utils::document;
 use wasm_bindgen::JsCast;
 use wasm_bindgen::{closure::Closure, JsValue};
 use web_sys::{File, FileReader, HtmlCanvasElement, HtmlImageElement, HtmlInputElement, ImageData};
 use yew::prelude::*;
 
+use crate::touch::{TouchGesture, Touches};
+
 pub struct Avatar {
     canvas_ref: NodeRef,
     reader: Option<FileReader>,
@@ -20,7 +20,7 @@ pub struct Avatar {
     start_x: f64,
     start_y: f64,
     selection_size: f64,
-    touches: HashMap<i32, (f64, f64)>,
+    touches: Touches,
 }
 
 #[derive(Properties, PartialEq, Clone)]
@@ -81,7 +81,7 @@ impl Component for Avatar {
             start_x: 0.0,
             start_y: 0.0,
             selection_size,
-            touches: HashMap::new(),
+            touches: Touches::default(),
         }
     }
 
@@ -135,18 +135,18 @@ impl Component for Avatar {
             }
             Msg::TouchStart(event) => {
                 event.stop_propagation();
-                self.update_touches(&event);
+                self.touches.update(&event);
             }
             Msg::TouchMove(event) => {
                 event.stop_propagation();
                 event.prevent_default();
                 self.handle_touch_move(&event);
-                self.update_touches(&event);
+                self.touches.update(&event);
             }
             Msg::TouchEnd(event) => {
                 event.stop_propagation();
                 event.prevent_default();
-                self.update_touches(&event);
+                self.touches.update(&event);
             }
         }
         false
@@ -278,63 +278,27 @@ impl Avatar {
         (self.x.min(max_x).max(min_x), self.y.min(max_y).max(min_y))
     }
 
-    fn update_touches(&mut self, event: &TouchEvent) {
-        self.touches.clear();
-        for i in 0..event.touches().length() {
-            if let Some(touch) = event.touches().item(i) {
-                self.touches.insert(
-                    touch.identifier(),
-                    (touch.client_x() as f64, touch.client_y() as f64),
-                );
-            }
-        }
-    }
-
     fn handle_touch_move(&mut self, event: &TouchEvent) {
-        let touches: Vec<_> = self.touches.values().cloned().collect();
-        if touches.len() == 2 {
-            let new_touches: Vec<_> = (0..event.touches().length())
-                .map(|i| {
-                    let touch = event.touches().item(i).unwrap();
-                    (touch.client_x() as f64, touch.client_y() as f64)
-                })
-                .collect();
-
-            let old_distance = ((touches[0].0 - touches[1].0).powi(2)
-                + (touches[0].1 - touches[1].1).powi(2))
-            .sqrt();
-            let new_distance = ((new_touches[0].0 - new_touches[1].0).powi(2)
-                + (new_touches[1].1 - new_touches[0].1).powi(2))
-            .sqrt();
-
-            let delta = new_distance - old_distance;
-            if delta.abs() > 1.0 {
-                // 增加一个基本的阈值，避免误判
-                self.zoom(
-                    delta,
-                    (new_touches[0].0 + new_touches[1].0) / 2.0,
-                    (new_touches[0].1 + new_touches[1].1) / 2.0,
-                );
-            }
-        } else if touches.len() == 1 {
-            if let Some((sx, sy)) = self.touches.values().next() {
-                if let Some(touch) = event.touches().item(0) {
-                    let dx = touch.client_x() as f64 - sx;
-                    let dy = touch.client_y() as f64 - sy;
-
-                    self.x += dx;
-                    self.y += dy;
-
-                    if let Some(img) = &self.img {
-                        let canvas = self.canvas_ref.cast::<HtmlCanvasElement>().unwrap();
-                        let result = self.adjust_image_position(img, &canvas);
-                        self.x = result.0;
-                        self.y = result.1;
-                    }
-
-                    self.redraw();
+        match self.touches.gesture(event) {
+            Some(TouchGesture::Pinch {
+                delta,
+                center_x,
+                center_y,
+            }) => self.zoom(delta, center_x, center_y),
+            Some(TouchGesture::Pan { dx, dy }) => {
+                self.x += dx;
+                self.y += dy;
+
+                if let Some(img) = &self.img {
+                    let canvas = self.canvas_ref.cast::<HtmlCanvasElement>().unwrap();
+                    let result = self.adjust_image_position(img, &canvas);
+                    self.x = result.0;
+                    self.y = result.1;
                 }
+
+                self.redraw();
             }
+            None => {}
         }
     }
 
@@ -0,0 +1,99 @@
+use web_sys::Url;
+use yew::prelude::*;
+
+use sandcat_sdk::model::attachment::AttachmentKind;
+
+use crate::right::util::load_attachment;
+
+/// avatar image loaded through the attachment cache
+pub struct AvatarImg {
+    url: Option<AttrValue>,
+}
+
+pub enum AvatarImgMsg {
+    Loaded(AttrValue, AttrValue),
+    None,
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct AvatarImgProps {
+    /// the avatar id on the file server
+    pub avatar: AttrValue,
+    #[prop_or_default]
+    pub class: Classes,
+    #[prop_or_default]
+    pub title: Option<AttrValue>,
+    #[prop_or_default]
+    pub onclick: Option<Callback<MouseEvent>>,
+}
+
+impl AvatarImg {
+    fn load(ctx: &Context<Self>) {
+        let avatar = ctx.props().avatar.clone();
+        if avatar.is_empty() {
+            return;
+        }
+        ctx.link().send_future(async move {
+            // avatars don't belong to any conversation
+            match load_attachment(&avatar, "", AttachmentKind::Avatar, "image/*").await {
+                Ok(url) => AvatarImgMsg::Loaded(avatar, url.into()),
+                Err(err) => {
+                    log::error!("load avatar error: {:?}", err);
+                    AvatarImgMsg::None
+                }
+            }
+        });
+    }
+
+    fn revoke(&mut self) {
+        if let Some(url) = self.url.take() {
+            let _ = Url::revoke_object_url(&url);
+        }
+    }
+}
+
+impl Component for AvatarImg {
+    type Message = AvatarImgMsg;
+    type Properties = AvatarImgProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        Self::load(ctx);
+        Self { url: None }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            AvatarImgMsg::Loaded(avatar, url) => {
+                // the avatar changed while loading
+                if avatar != ctx.props().avatar {
+                    let _ = Url::revoke_object_url(&url);
+                    return false;
+                }
+                self.revoke();
+                self.url = Some(url);
+                true
+            }
+            AvatarImgMsg::None => false,
+        }
+    }
+
+    fn changed(&mut self, ctx: &Context<Self>, old_props: &Self::Properties) -> bool {
+        if ctx.props().avatar != old_props.avatar {
+            self.revoke();
+            Self::load(ctx);
+        }
+        true
+    }
+
+    fn destroy(&mut self, _ctx: &Context<Self>) {
+        self.revoke();
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let props = ctx.props();
+        html! {
+            <img class={props.class.clone()} alt="avatar" title={props.title.clone()}
+                src={self.url.clone()} onclick={props.onclick.clone()} />
+        }
+    }
+}
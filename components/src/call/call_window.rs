@@ -0,0 +1,135 @@
+use js_sys::Function;
+use log::error;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use yew::platform::spawn_local;
+use yew::prelude::*;
+
+use icons::{HangupInNotifyIcon, MicrophoneIcon, MicrophoneMuteIcon, MiniWindowIcon};
+use sandcat_sdk::model::call_window::{
+    CallWindowAction, CallWindowState, CALL_ACTION_EVENT, CALL_STATE_EVENT,
+};
+use utils::tauri;
+
+/// the always-on-top window of the tauri build,
+/// it mirrors the call in the main window and sends the actions back
+pub struct CallWindow {
+    state: CallWindowState,
+    node: NodeRef,
+    _on_state: Closure<dyn FnMut(JsValue)>,
+    unlisten: Option<Function>,
+}
+
+pub enum CallWindowMsg {
+    Listened(Function),
+    StateChanged(CallWindowState),
+    Action(CallWindowAction),
+}
+
+impl Component for CallWindow {
+    type Message = CallWindowMsg;
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let link = ctx.link().clone();
+        let on_state = Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| {
+            match tauri::event_payload(&event)
+                .and_then(|payload| serde_json::from_str::<CallWindowState>(&payload).ok())
+            {
+                Some(state) => link.send_message(CallWindowMsg::StateChanged(state)),
+                None => error!("invalid call state: {:?}", event),
+            }
+        });
+
+        let handler = on_state.as_ref().unchecked_ref::<Function>().clone();
+        ctx.link().send_future_batch(async move {
+            match tauri::listen(CALL_STATE_EVENT, &handler).await {
+                Ok(unlisten) => vec![
+                    CallWindowMsg::Listened(unlisten),
+                    CallWindowMsg::Action(CallWindowAction::Ready),
+                ],
+                Err(e) => {
+                    error!("listen call state error: {:?}", e);
+                    vec![]
+                }
+            }
+        });
+
+        Self {
+            state: CallWindowState::default(),
+            node: NodeRef::default(),
+            _on_state: on_state,
+            unlisten: None,
+        }
+    }
+
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            CallWindowMsg::Listened(unlisten) => {
+                self.unlisten = Some(unlisten);
+                false
+            }
+            CallWindowMsg::StateChanged(state) => {
+                self.state = state;
+                true
+            }
+            CallWindowMsg::Action(action) => {
+                spawn_local(async move {
+                    let payload = serde_json::to_string(&action).unwrap();
+                    if let Err(e) = tauri::emit(CALL_ACTION_EVENT, &payload).await {
+                        error!("send call action error: {:?}", e);
+                    }
+                });
+                false
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let microphone = if self.state.microphone_mute {
+            html!(<MicrophoneMuteIcon />)
+        } else {
+            html!(<MicrophoneIcon />)
+        };
+        let action =
+            |action: CallWindowAction| ctx.link().callback(move |_| CallWindowMsg::Action(action));
+        html! {
+            <div class="call-window" ref={self.node.clone()}>
+                // the window doesn't open the local database, so the avatar isn't cached
+                <img class="call-window-avatar" alt="avatar"
+                    src={utils::get_avatar_url(&self.state.avatar)} />
+                <div class="call-window-info">
+                    <span class="call-window-name">{&self.state.name}</span>
+                    <span class="call-window-status">{&self.state.status}</span>
+                </div>
+                <div class="call-operate">
+                    <span class="switch-microphone"
+                        onclick={action(CallWindowAction::SwitchMicrophoneMute)}>
+                        {microphone}
+                    </span>
+                    <span class="hangup-icon" onclick={action(CallWindowAction::HangUp)}>
+                        <HangupInNotifyIcon />
+                    </span>
+                    <span class="call-mini-switch" onclick={action(CallWindowAction::Expand)}>
+                        <MiniWindowIcon />
+                    </span>
+                </div>
+            </div>
+        }
+    }
+
+    fn rendered(&mut self, _ctx: &Context<Self>, first_render: bool) {
+        // the window has no decorations, drag it by the content
+        if first_render {
+            if let Some(node) = self.node.cast::<web_sys::HtmlDivElement>() {
+                let _ = node.set_attribute("data-tauri-drag-region", "");
+            }
+        }
+    }
+
+    fn destroy(&mut self, _ctx: &Context<Self>) {
+        if let Some(unlisten) = self.unlisten.take() {
+            let _ = unlisten.call0(&JsValue::NULL);
+        }
+    }
+}
@@ -0,0 +1,729 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use fluent::{FluentBundle, FluentResource};
+use gloo::timers::callback::Interval;
+use indexmap::IndexMap;
+use log::{debug, error};
+use nanoid::nanoid;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    HtmlVideoElement, MediaStream, MediaStreamTrack, RtcIceCandidateInit, RtcPeerConnection,
+    RtcSdpType, RtcSessionDescription, RtcSessionDescriptionInit, RtcTrackEvent,
+};
+use yew::prelude::*;
+use yewdux::Dispatch;
+
+use i18n::{en_us, zh_cn, LanguageType};
+use icons::{
+    AnswerPhoneIcon, HangupInNotifyIcon, MicrophoneIcon, MicrophoneMuteIcon, VideoRecordIcon,
+};
+use sandcat_sdk::db;
+use sandcat_sdk::error::Error;
+use sandcat_sdk::model::message::{GroupCall, GroupCallMember, GroupCallSdp, InviteType, Msg};
+use sandcat_sdk::model::notification::Notification;
+use sandcat_sdk::state::{I18nState, MediaDeviceState, MobileState, SendGroupCallState};
+use utils::tr;
+use web_rtc::WebRTC;
+use ws::WebSocketManager;
+
+use crate::avatar_img::AvatarImg;
+use crate::constant::{
+    CALL_BUSY, CALL_FULL, GROUP_CALL_INVITE, OTHER_ERROR, STREAM_ERROR, UNKNOW_ERROR, WAITING,
+};
+use crate::get_platform;
+
+/// every member connects to all the others, so the call is limited to a small group
+const MAX_MEMBERS: usize = 6;
+
+/// group call with a peer connection to every other member(mesh)
+pub struct GroupPhoneCall {
+    /// our member info of the call we are in
+    call: Option<GroupCallMember>,
+    /// the invitation waiting for answer
+    invitation: Option<GroupCallMember>,
+    /// members of the ongoing calls in the groups, used to join the call mid-way
+    ongoing: HashMap<AttrValue, HashSet<AttrValue>>,
+    /// the other members in the call, key is the user id
+    peers: IndexMap<AttrValue, Peer>,
+    stream: Option<MediaStream>,
+    self_video_node: NodeRef,
+    microphone_mute: bool,
+    call_timer: Option<Interval>,
+    call_duration: u32,
+    is_mobile: bool,
+    i18n: FluentBundle<FluentResource>,
+    _call_state_dis: Dispatch<SendGroupCallState>,
+    _i18n_dis: Dispatch<I18nState>,
+}
+
+struct Peer {
+    rtc: WebRTC,
+    stream: Option<MediaStream>,
+    video_node: NodeRef,
+    name: AttrValue,
+    avatar: AttrValue,
+}
+
+#[derive(Properties, Clone, PartialEq, Debug)]
+pub struct GroupPhoneCallProps {
+    pub ws: Rc<RefCell<WebSocketManager>>,
+    pub user_id: AttrValue,
+    pub msg: GroupCall,
+    pub lang: LanguageType,
+}
+
+pub enum GroupPhoneCallMsg {
+    /// start or join the call from the conversation
+    CallStateChange(Rc<SendGroupCallState>),
+    I18nStateChange(Rc<I18nState>),
+    AcceptInvite,
+    IgnoreInvite,
+    /// the local stream is ready, send the invite or join message to the group
+    StreamReady(MediaStream, GroupCall),
+    /// user id, nickname and avatar of the member
+    PeerInfo(AttrValue, AttrValue, AttrValue),
+    OnTrack(AttrValue, RtcTrackEvent),
+    PeerClosed(AttrValue),
+    SwitchMicrophoneMute,
+    Leave,
+    TickCallDuration,
+    Error(String),
+    None,
+}
+
+impl Component for GroupPhoneCall {
+    type Message = GroupPhoneCallMsg;
+    type Properties = GroupPhoneCallProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let _call_state_dis = Dispatch::global()
+            .subscribe_silent(ctx.link().callback(GroupPhoneCallMsg::CallStateChange));
+        let _i18n_dis = Dispatch::global()
+            .subscribe_silent(ctx.link().callback(GroupPhoneCallMsg::I18nStateChange));
+
+        let res = match ctx.props().lang {
+            LanguageType::ZhCN => zh_cn::CALL_COM,
+            LanguageType::EnUS => en_us::CALL_COM,
+        };
+
+        Self {
+            call: None,
+            invitation: None,
+            ongoing: HashMap::new(),
+            peers: IndexMap::new(),
+            stream: None,
+            self_video_node: NodeRef::default(),
+            microphone_mute: false,
+            call_timer: None,
+            call_duration: 0,
+            is_mobile: MobileState::is_mobile(),
+            i18n: utils::create_bundle(res),
+            _call_state_dis,
+            _i18n_dis,
+        }
+    }
+
+    fn changed(&mut self, ctx: &Context<Self>, old_props: &Self::Properties) -> bool {
+        // don't update the ui when the message is empty or the msg is the same
+        if ctx.props().msg == GroupCall::default() || ctx.props().msg == old_props.msg {
+            return false;
+        }
+
+        let user_id = ctx.props().user_id.clone();
+        match ctx.props().msg.clone() {
+            GroupCall::Invite(member) => {
+                self.ongoing
+                    .entry(member.group_id.clone())
+                    .or_default()
+                    .insert(member.send_id.clone());
+                if member.send_id == user_id || self.call.is_some() || self.invitation.is_some() {
+                    return false;
+                }
+                self.invitation = Some(member);
+                true
+            }
+            GroupCall::Join(member) => {
+                self.ongoing
+                    .entry(member.group_id.clone())
+                    .or_default()
+                    .insert(member.send_id.clone());
+                if member.send_id == user_id || !self.in_call(&member.group_id) {
+                    return false;
+                }
+                // we are in the call, connect to the new member
+                if let Err(e) = self.connect_peer(ctx, member) {
+                    error!("connect to group call member error: {:?}", e);
+                }
+                true
+            }
+            GroupCall::Leave(member) => {
+                if let Some(members) = self.ongoing.get_mut(&member.group_id) {
+                    members.remove(&member.send_id);
+                    if members.is_empty() {
+                        self.ongoing.remove(&member.group_id);
+                        // the call is over
+                        if self
+                            .invitation
+                            .as_ref()
+                            .is_some_and(|v| v.group_id == member.group_id)
+                        {
+                            self.invitation = None;
+                        }
+                    }
+                }
+                if self.in_call(&member.group_id) {
+                    if let Some(mut peer) = self.peers.shift_remove(&member.send_id) {
+                        peer.rtc.close();
+                    }
+                }
+                true
+            }
+            GroupCall::Offer(offer) => {
+                if offer.friend_id != user_id || !self.in_call(&offer.group_id) {
+                    return false;
+                }
+                if let Err(e) = self.answer_offer(ctx, offer) {
+                    error!("answer group call offer error: {:?}", e);
+                }
+                true
+            }
+            GroupCall::Answer(answer) => {
+                if let Some(peer) = self.peers.get(&answer.send_id) {
+                    let mut description = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+                    description.sdp(&answer.sdp);
+                    let future = JsFuture::from(peer.rtc.pc().set_remote_description(&description));
+                    spawn_local(async {
+                        if let Err(err) = future.await {
+                            error!("remote desc set failed: {:?}", err);
+                        }
+                    });
+                }
+                false
+            }
+            GroupCall::NewIceCandidate(msg) => {
+                if let Some(peer) = self.peers.get(&msg.send_id) {
+                    let mut candidate = RtcIceCandidateInit::new(&msg.candidate);
+                    candidate.sdp_m_line_index(msg.sdp_m_index);
+                    candidate.sdp_mid(msg.sdp_mid.as_deref());
+                    let future = JsFuture::from(
+                        peer.rtc
+                            .pc()
+                            .add_ice_candidate_with_opt_rtc_ice_candidate_init(Some(&candidate)),
+                    );
+                    spawn_local(async {
+                        if let Err(err) = future.await {
+                            error!("set ice candidate failed: {:?}", err);
+                        }
+                    });
+                }
+                false
+            }
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            GroupPhoneCallMsg::CallStateChange(state) => {
+                if state.msg.group_id.is_empty() {
+                    return false;
+                }
+                self.start_call(ctx, state.msg.clone());
+                true
+            }
+            GroupPhoneCallMsg::I18nStateChange(state) => {
+                let res = match state.lang {
+                    LanguageType::ZhCN => zh_cn::CALL_COM,
+                    LanguageType::EnUS => en_us::CALL_COM,
+                };
+                self.i18n = utils::create_bundle(res);
+                true
+            }
+            GroupPhoneCallMsg::AcceptInvite => {
+                let Some(invitation) = self.invitation.take() else {
+                    return false;
+                };
+                let member = GroupCallMember {
+                    local_id: nanoid!().into(),
+                    send_id: ctx.props().user_id.clone(),
+                    group_id: invitation.group_id,
+                    create_time: chrono::Utc::now().timestamp_millis(),
+                    invite_type: invitation.invite_type,
+                    platform: get_platform(self.is_mobile),
+                    ..Default::default()
+                };
+                self.start_call(ctx, member);
+                true
+            }
+            GroupPhoneCallMsg::IgnoreInvite => {
+                self.invitation = None;
+                true
+            }
+            GroupPhoneCallMsg::StreamReady(stream, msg) => {
+                if self.call.is_none() {
+                    // left before the stream is ready
+                    stop_stream(&stream);
+                    return false;
+                }
+                if let GroupCall::Invite(ref member) | GroupCall::Join(ref member) = msg {
+                    self.ongoing
+                        .entry(member.group_id.clone())
+                        .or_default()
+                        .insert(member.send_id.clone());
+                    self.call = Some(member.clone());
+                }
+                self.stream = Some(stream);
+                WebRTC::send_msg1(ctx.props().ws.clone(), Msg::GroupCall(msg));
+
+                let ctx = ctx.link().clone();
+                self.call_timer = Some(Interval::new(1000, move || {
+                    ctx.send_message(GroupPhoneCallMsg::TickCallDuration);
+                }));
+                true
+            }
+            GroupPhoneCallMsg::PeerInfo(id, name, avatar) => {
+                if let Some(peer) = self.peers.get_mut(&id) {
+                    peer.name = name;
+                    peer.avatar = avatar;
+                    return true;
+                }
+                false
+            }
+            GroupPhoneCallMsg::OnTrack(id, event) => {
+                if let Some(peer) = self.peers.get_mut(&id) {
+                    peer.stream = Some(MediaStream::from(event.streams().get(0)));
+                    return true;
+                }
+                false
+            }
+            GroupPhoneCallMsg::PeerClosed(id) => {
+                debug!("group call peer closed: {}", id);
+                if let Some(mut peer) = self.peers.shift_remove(&id) {
+                    peer.rtc.close();
+                    return true;
+                }
+                false
+            }
+            GroupPhoneCallMsg::SwitchMicrophoneMute => {
+                self.microphone_mute = !self.microphone_mute;
+                if let Some(stream) = self.stream.as_ref() {
+                    for track in stream.get_audio_tracks() {
+                        let track = track.unchecked_into::<MediaStreamTrack>();
+                        track.set_enabled(!self.microphone_mute);
+                    }
+                }
+                true
+            }
+            GroupPhoneCallMsg::Leave => {
+                if let (Some(member), Some(_)) = (self.call.as_ref(), self.stream.as_ref()) {
+                    let msg = GroupCall::Leave(GroupCallMember {
+                        local_id: nanoid!().into(),
+                        create_time: chrono::Utc::now().timestamp_millis(),
+                        ..member.clone()
+                    });
+                    WebRTC::send_msg1(ctx.props().ws.clone(), Msg::GroupCall(msg));
+                }
+                self.finish_call(ctx);
+                true
+            }
+            GroupPhoneCallMsg::TickCallDuration => {
+                self.call_duration += 1;
+                true
+            }
+            GroupPhoneCallMsg::Error(err) => {
+                self.finish_call(ctx);
+                Notification::error(Error::internal_with_details(tr!(self.i18n, &err))).notify();
+                true
+            }
+            GroupPhoneCallMsg::None => false,
+        }
+    }
+
+    fn rendered(&mut self, _ctx: &Context<Self>, _first_render: bool) {
+        // attach the streams to the new video elements
+        if let Some(stream) = self.stream.as_ref() {
+            if let Some(video) = self.self_video_node.cast::<HtmlVideoElement>() {
+                if video.src_object().is_none() {
+                    video.set_muted(true);
+                    attach_stream(&video, stream);
+                }
+            }
+        }
+        for peer in self.peers.values() {
+            if let (Some(stream), Some(video)) = (
+                peer.stream.as_ref(),
+                peer.video_node.cast::<HtmlVideoElement>(),
+            ) {
+                if video.src_object().is_none() {
+                    attach_stream(&video, stream);
+                    super::set_speaker(&video);
+                }
+            }
+        }
+    }
+
+    fn destroy(&mut self, ctx: &Context<Self>) {
+        self.finish_call(ctx);
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let mut notify = html!();
+        if let Some(invitation) = self.invitation.as_ref() {
+            let answer_icon = match invitation.invite_type {
+                InviteType::Video => html!(<VideoRecordIcon />),
+                InviteType::Audio => html!(<AnswerPhoneIcon />),
+            };
+            notify = html! {
+                <div class="video-or-audio-notify box-shadow" >
+                    <AvatarImg avatar={invitation.avatar.clone()} />
+                    <span class="video-or-audio-notify-text" >
+                        {format!("{} {}", invitation.nickname, tr!(self.i18n, GROUP_CALL_INVITE))}
+                    </span>
+                    <div class="video-audio-notify-operate" >
+                        <span onclick={ctx.link().callback(|_| GroupPhoneCallMsg::IgnoreInvite)}>
+                            <HangupInNotifyIcon />
+                        </span>
+                        <span onclick={ctx.link().callback(|_| GroupPhoneCallMsg::AcceptInvite)}>
+                            {answer_icon}
+                        </span>
+                    </div>
+                </div>
+            }
+        }
+
+        let Some(member) = self.call.as_ref() else {
+            return notify;
+        };
+
+        let is_video = member.invite_type == InviteType::Video;
+        let self_tile = tile_html(
+            is_video,
+            &self.self_video_node,
+            &member.nickname,
+            &member.avatar,
+        );
+        let tiles = self
+            .peers
+            .iter()
+            .map(|(id, peer)| {
+                html! {
+                    <div class="group-call-tile" key={id.as_str()}>
+                        {tile_html(is_video, &peer.video_node, &peer.name, &peer.avatar)}
+                    </div>
+                }
+            })
+            .collect::<Html>();
+
+        let duration = if self.stream.is_some() {
+            utils::format_milliseconds(self.call_duration as i64 * 1000)
+        } else {
+            tr!(self.i18n, WAITING)
+        };
+        let microphone = if self.microphone_mute {
+            html!(<MicrophoneMuteIcon />)
+        } else {
+            html!(<MicrophoneIcon />)
+        };
+        let class = if self.is_mobile {
+            "group-call phone-call-size-mobile"
+        } else {
+            "group-call box-shadow phone-call-size"
+        };
+
+        html! {
+            <>
+            {notify}
+            <div {class}>
+                <div class="group-call-grid">
+                    <div class="group-call-tile" key="self">
+                        {self_tile}
+                    </div>
+                    {tiles}
+                </div>
+                <div class="call-duration">{duration}</div>
+                <div class="call-operate">
+                    <span class="switch-microphone"
+                        onclick={ctx.link().callback(|_| GroupPhoneCallMsg::SwitchMicrophoneMute)}>
+                        {microphone}
+                    </span>
+                    <span class="hangup-icon" onclick={ctx.link().callback(|_| GroupPhoneCallMsg::Leave)}>
+                        <HangupInNotifyIcon />
+                    </span>
+                </div>
+            </div>
+            </>
+        }
+    }
+}
+
+impl GroupPhoneCall {
+    fn in_call(&self, group_id: &AttrValue) -> bool {
+        self.stream.is_some() && self.call.as_ref().is_some_and(|v| v.group_id == *group_id)
+    }
+
+    /// join the call if there is one in the group, otherwise start a new one
+    fn start_call(&mut self, ctx: &Context<Self>, mut member: GroupCallMember) {
+        if self.call.is_some() {
+            Notification::warn(tr!(self.i18n, CALL_BUSY)).notify();
+            return;
+        }
+        let members = self.ongoing.get(&member.group_id).map(|v| v.len());
+        if members.is_some_and(|len| len >= MAX_MEMBERS) {
+            Notification::warn(tr!(self.i18n, CALL_FULL)).notify();
+            return;
+        }
+        if self
+            .invitation
+            .as_ref()
+            .is_some_and(|v| v.group_id == member.group_id)
+        {
+            self.invitation = None;
+        }
+        let join = members.is_some();
+        self.call = Some(member.clone());
+
+        let user_id = ctx.props().user_id.clone();
+        ctx.link().send_future(async move {
+            if member.avatar.is_empty() {
+                if let Ok(Some(info)) = db::db_ins()
+                    .group_members
+                    .get_by_group_id_and_friend_id(&member.group_id, &user_id)
+                    .await
+                {
+                    member.avatar = info.avatar;
+                    member.nickname = info.group_name;
+                }
+            }
+            web_rtc::refresh_ice_servers().await;
+            let devices = MediaDeviceState::get();
+            let stream = match member.invite_type {
+                InviteType::Video => {
+                    utils::get_video_stream(devices.camera(), devices.microphone()).await
+                }
+                InviteType::Audio => utils::get_audio_stream(devices.microphone()).await,
+            };
+            match stream {
+                Ok(stream) => {
+                    let msg = if join {
+                        GroupCall::Join(member)
+                    } else {
+                        GroupCall::Invite(member)
+                    };
+                    GroupPhoneCallMsg::StreamReady(stream, msg)
+                }
+                Err(e) => GroupPhoneCallMsg::Error(stream_error(&e).to_string()),
+            }
+        });
+    }
+
+    fn new_rtc(&self, ctx: &Context<Self>, peer_id: &AttrValue, group_id: &AttrValue) -> WebRTC {
+        let id = peer_id.clone();
+        let close_event = ctx
+            .link()
+            .callback(move |_| GroupPhoneCallMsg::PeerClosed(id.clone()));
+        let id = peer_id.clone();
+        let conn_event = ctx
+            .link()
+            .callback(move |event| GroupPhoneCallMsg::OnTrack(id.clone(), event));
+        WebRTC::new_in_group(close_event, conn_event, group_id.clone())
+    }
+
+    /// the new member joined, add our tracks and the offer is sent on negotiation needed
+    fn connect_peer(
+        &mut self,
+        ctx: &Context<Self>,
+        member: GroupCallMember,
+    ) -> Result<(), JsValue> {
+        let Some(stream) = self.stream.as_ref() else {
+            return Ok(());
+        };
+        if let Some(mut peer) = self.peers.shift_remove(&member.send_id) {
+            peer.rtc.close();
+        }
+        let mut rtc = self.new_rtc(ctx, &member.send_id, &member.group_id);
+        rtc.create_pc(
+            ctx.props().ws.clone(),
+            ctx.props().user_id.clone(),
+            member.send_id.clone(),
+        )?;
+        for track in stream.get_tracks() {
+            rtc.pc().add_track_0(&track.into(), stream);
+        }
+        self.peers.insert(
+            member.send_id,
+            Peer {
+                rtc,
+                stream: None,
+                video_node: NodeRef::default(),
+                name: member.nickname,
+                avatar: member.avatar,
+            },
+        );
+        Ok(())
+    }
+
+    /// we are the new member, answer the offer from the member already in the call
+    fn answer_offer(&mut self, ctx: &Context<Self>, offer: GroupCallSdp) -> Result<(), JsValue> {
+        let Some(stream) = self.stream.clone() else {
+            return Ok(());
+        };
+        // the member restarted the ice of the connection we already have
+        if let Some(peer) = self.peers.get(&offer.send_id) {
+            spawn_local(send_answer(
+                peer.rtc.pc().clone(),
+                None,
+                ctx.props().ws.clone(),
+                offer,
+            ));
+            return Ok(());
+        }
+        let mut rtc = self.new_rtc(ctx, &offer.send_id, &offer.group_id);
+        let ws = ctx.props().ws.clone();
+        rtc.create_pc(
+            ws.clone(),
+            ctx.props().user_id.clone(),
+            offer.send_id.clone(),
+        )?;
+        let pc = rtc.pc().clone();
+
+        let peer_id = offer.send_id.clone();
+        let group_id = offer.group_id.clone();
+        spawn_local(send_answer(pc, Some(stream), ws, offer));
+
+        self.peers.insert(
+            peer_id.clone(),
+            Peer {
+                rtc,
+                stream: None,
+                video_node: NodeRef::default(),
+                name: AttrValue::default(),
+                avatar: AttrValue::default(),
+            },
+        );
+        ctx.link().send_future(async move {
+            match db::db_ins()
+                .group_members
+                .get_by_group_id_and_friend_id(&group_id, &peer_id)
+                .await
+            {
+                Ok(Some(member)) => {
+                    GroupPhoneCallMsg::PeerInfo(peer_id, member.group_name, member.avatar)
+                }
+                _ => GroupPhoneCallMsg::None,
+            }
+        });
+        Ok(())
+    }
+
+    fn finish_call(&mut self, ctx: &Context<Self>) {
+        for (_, mut peer) in self.peers.drain(..) {
+            peer.rtc.close();
+        }
+        if let Some(stream) = self.stream.take() {
+            stop_stream(&stream);
+        }
+        if let Some(member) = self.call.take() {
+            if let Some(members) = self.ongoing.get_mut(&member.group_id) {
+                members.remove(&ctx.props().user_id);
+                if members.is_empty() {
+                    self.ongoing.remove(&member.group_id);
+                }
+            }
+        }
+        self.call_timer = None;
+        self.call_duration = 0;
+        self.microphone_mute = false;
+    }
+}
+
+fn tile_html(is_video: bool, node: &NodeRef, name: &AttrValue, avatar: &AttrValue) -> Html {
+    // the audio of the audio call is played by the hidden video element
+    let (video_class, avatar_html) = if is_video {
+        ("group-call-video", html!())
+    } else {
+        (
+            "group-call-hidden",
+            html!(<AvatarImg avatar={avatar.clone()} class="group-call-avatar" />),
+        )
+    };
+    html! {
+        <>
+            {avatar_html}
+            <video class={video_class} ref={node.clone()} playsinline={true} />
+            <span class="group-call-name">{name}</span>
+        </>
+    }
+}
+
+fn attach_stream(video: &HtmlVideoElement, stream: &MediaStream) {
+    video.set_src_object(Some(stream));
+    let promise = video.play();
+    spawn_local(async move {
+        if let Ok(promise) = promise {
+            if let Err(e) = JsFuture::from(promise).await {
+                error!("play group call stream error: {:?}", e);
+            }
+        }
+    });
+}
+
+fn stop_stream(stream: &MediaStream) {
+    for track in stream.get_tracks() {
+        if let Ok(track) = track.dyn_into::<MediaStreamTrack>() {
+            track.stop();
+        }
+    }
+}
+
+fn stream_error(e: &JsValue) -> &'static str {
+    match e.dyn_ref::<web_sys::DomException>() {
+        Some(exception) if exception.name() == "NotFoundError" => STREAM_ERROR,
+        Some(_) => OTHER_ERROR,
+        None => UNKNOW_ERROR,
+    }
+}
+
+async fn send_answer(
+    pc: RtcPeerConnection,
+    stream: Option<MediaStream>,
+    ws: Rc<RefCell<WebSocketManager>>,
+    offer: GroupCallSdp,
+) {
+    match create_answer(&pc, stream.as_ref(), &offer.sdp).await {
+        Ok(sdp) => {
+            let msg = GroupCall::Answer(GroupCallSdp {
+                sdp: sdp.into(),
+                send_id: offer.friend_id,
+                friend_id: offer.send_id,
+                group_id: offer.group_id,
+                create_time: chrono::Utc::now().timestamp_millis(),
+            });
+            WebRTC::send_msg1(ws, Msg::GroupCall(msg));
+        }
+        Err(e) => error!("create group call answer error: {:?}", e),
+    }
+}
+
+/// add the local tracks if the stream is set, it is none for the ice restart
+async fn create_answer(
+    pc: &RtcPeerConnection,
+    stream: Option<&MediaStream>,
+    sdp: &str,
+) -> Result<String, JsValue> {
+    let mut description = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
+    description.sdp(sdp);
+    JsFuture::from(pc.set_remote_description(&description)).await?;
+    if let Some(stream) = stream {
+        for track in stream.get_tracks() {
+            pc.add_track_0(&track.into(), stream);
+        }
+    }
+    let answer = JsFuture::from(pc.create_answer()).await?;
+    let sdp = RtcSessionDescription::from(answer).sdp();
+    let mut description = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+    description.sdp(&sdp);
+    JsFuture::from(pc.set_local_description(&description)).await?;
+    Ok(sdp)
+}
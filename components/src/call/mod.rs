@@ -1,16 +1,23 @@
+mod call_window;
+mod group_call;
 mod phone_call;
+mod recording;
+pub use call_window::*;
+pub use group_call::*;
 pub use phone_call::*;
 
 use std::fmt::Debug;
 
 use fluent::{FluentBundle, FluentResource};
 use gloo::timers::callback::{Interval, Timeout};
+use js_sys::{Function, Object, Reflect};
 use log::debug;
+use wasm_bindgen::closure::Closure;
 use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::{spawn_local, JsFuture};
 use web_sys::{
-    HtmlAudioElement, HtmlVideoElement, MediaStream, MediaStreamTrack, RtcSdpType,
-    RtcSessionDescriptionInit, RtcSignalingState,
+    HtmlAudioElement, HtmlMediaElement, HtmlVideoElement, MediaStream, MediaStreamTrack,
+    RtcRtpSender, RtcSdpType, RtcSessionDescriptionInit, RtcSignalingState,
 };
 use yew::prelude::*;
 use yewdux::Dispatch;
@@ -19,12 +26,26 @@ use i18n::{en_us, zh_cn, LanguageType};
 use sandcat_sdk::{
     db,
     model::{
-        message::{InviteInfo, InviteType, Message},
-        ItemInfo,
+        call_control::CallControl,
+        call_log::{CallDirection, CallLog},
+        call_window::{CallWindowAction, CallWindowState, CALL_ACTION_EVENT, CALL_STATE_EVENT},
+        message::{InviteInfo, InviteMsg, InviteType, Message},
+        ComponentType, ContentType, ItemInfo,
+    },
+    state::{
+        CallLogState, ComponentTypeState, I18nState, MediaDeviceState, MobileState, Notify,
+        SendCallState, UnreadState,
     },
-    state::{I18nState, MobileState, SendCallState},
 };
-use web_rtc::WebRTC;
+use utils::{tauri, tr};
+use web_rtc::{CallStats, WebRTC};
+
+use crate::constant::{CONNECTING, CONN_ERROR, USER_BUSY, WAITING};
+use recording::{CallRecorder, Recorded};
+
+/// the tauri commands of the always-on-top call window
+const OPEN_CALL_WINDOW: &str = "open_call_window";
+const CLOSE_CALL_WINDOW: &str = "close_call_window";
 
 pub struct PhoneCall {
     /// 显示视频通话
@@ -54,6 +75,17 @@ pub struct PhoneCall {
     rtc: Option<WebRTC>,
     /// 音视频流
     stream: Option<MediaStream>,
+    /// 共享的屏幕流
+    screen_stream: Option<MediaStream>,
+    /// the sender of the screen track, only used in the audio call,
+    /// the video call replaces the camera track instead
+    screen_sender: Option<RtcRtpSender>,
+    /// stop sharing when the user stops it from the browser bar
+    on_screen_ended: Option<Closure<dyn FnMut()>>,
+    /// the screen shared by the friend in the audio call
+    remote_screen_node: NodeRef,
+    show_remote_screen: bool,
+    on_remote_screen_removed: Option<Closure<dyn FnMut()>>,
     /// 通话的好友信息
     call_friend_info: Option<Box<dyn ItemInfo>>,
     /// 邀请计时器，到时间即为未接听
@@ -74,9 +106,60 @@ pub struct PhoneCall {
     is_mobile: bool,
     is_zoom: bool,
     conn_state: ConnectionState,
+    /// the connection is lost and recovering
+    reconnecting: bool,
+    /// the invitation received while in the call, waiting for the user to decide
+    waiting_invite: Option<InviteMsg>,
+    waiting_friend_info: Option<Box<dyn ItemInfo>>,
+    /// the friend replied busy, show it before closing the window
+    peer_busy: bool,
+    /// the latest sample of the call quality
+    stats: Option<CallStats>,
+    /// show the stats overlay
+    show_stats: bool,
+    /// show the camera, microphone and speaker pickers
+    show_devices: bool,
+    /// offer to leave a voice message after the call was not answered
+    voicemail: Option<Voicemail>,
+    /// we are recording the call
+    recording: Option<Recording>,
+    /// the recording finished, offer to send it after the call
+    recorded: Option<Recorded>,
+    /// the friend told us they are recording the call
+    friend_recording: bool,
+    /// shrink the call to the controls, so the user can go on chatting
+    is_mini: bool,
+    /// the friend video is in the picture-in-picture window
+    pip: bool,
+    on_leave_pip: Option<Closure<dyn FnMut()>>,
+    /// the microphone and hangup buttons of the picture-in-picture window
+    on_pip_actions: Vec<Closure<dyn FnMut()>>,
+    /// the always-on-top call window of the tauri build is open
+    call_window: bool,
+    on_call_action: Option<Closure<dyn FnMut(JsValue)>>,
+    unlisten_call_action: Option<Function>,
     i18n: FluentBundle<FluentResource>,
 }
 
+/// the call the voice message relates to
+struct Voicemail {
+    /// the local id of the call message
+    call_id: AttrValue,
+    friend_id: AttrValue,
+    name: AttrValue,
+    avatar: AttrValue,
+    recording: bool,
+}
+
+/// the friend the recording belongs to, kept until the file is emitted after the call
+struct Recording {
+    recorder: CallRecorder,
+    friend_id: AttrValue,
+    name: AttrValue,
+    avatar: AttrValue,
+    is_video: bool,
+}
+
 impl Debug for PhoneCall {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         <NodeRef as Debug>::fmt(&self.friend_video_node, f)
@@ -119,6 +202,12 @@ impl PhoneCall {
             invite_info: None,
             rtc: None,
             stream: None,
+            screen_stream: None,
+            screen_sender: None,
+            on_screen_ended: None,
+            remote_screen_node: NodeRef::default(),
+            show_remote_screen: false,
+            on_remote_screen_removed: None,
             show_notify: false,
             call_friend_info: None,
             call_timeout: None,
@@ -133,6 +222,24 @@ impl PhoneCall {
             is_mobile,
             is_zoom: false,
             conn_state: ConnectionState::Waiting,
+            reconnecting: false,
+            waiting_invite: None,
+            waiting_friend_info: None,
+            peer_busy: false,
+            stats: None,
+            show_stats: false,
+            show_devices: false,
+            voicemail: None,
+            recording: None,
+            recorded: None,
+            friend_recording: false,
+            is_mini: false,
+            pip: false,
+            on_leave_pip: None,
+            on_pip_actions: Vec::new(),
+            call_window: false,
+            on_call_action: None,
+            unlisten_call_action: None,
             i18n,
             _i18n_dis,
         }
@@ -148,6 +255,8 @@ impl PhoneCall {
         }
 
         let mut rtc = web_rtc::WebRTC::new(close_event, conn_event);
+        rtc.set_reconnect_event(ctx.link().callback(PhoneCallMsg::Reconnecting));
+        rtc.set_control_event(ctx.link().callback(PhoneCallMsg::CallControl));
         rtc.create_pc(
             ctx.props().ws.clone(),
             ctx.props().user_id.clone(),
@@ -228,7 +337,43 @@ impl PhoneCall {
         }
     }
 
+    /// the id of the other side of the call
+    fn peer_id(&self) -> Option<AttrValue> {
+        self.invite_info.as_ref().map(|info| {
+            if self.invited {
+                info.send_id.clone()
+            } else {
+                info.friend_id.clone()
+            }
+        })
+    }
+
+    fn stop_screen_stream(&mut self) {
+        if let Some(stream) = self.screen_stream.take() {
+            for track in stream.get_tracks() {
+                if let Ok(track) = track.dyn_into::<MediaStreamTrack>() {
+                    track.set_onended(None);
+                    track.stop();
+                }
+            }
+        }
+        self.on_screen_ended = None;
+        self.screen_sender = None;
+    }
+
     fn finish_call(&mut self) {
+        // the recording is offered to be sent after the call
+        if let Some(recording) = self.recording.as_mut() {
+            recording.recorder.stop();
+        }
+        self.friend_recording = false;
+        self.stop_screen_stream();
+        if let Some(video) = self.remote_screen_node.cast::<HtmlVideoElement>() {
+            video.set_src_object(None);
+        }
+        self.show_remote_screen = false;
+        self.on_remote_screen_removed = None;
+
         if let Some(ref mut rtc) = self.rtc {
             debug!("hang up video clear pc");
             rtc.close();
@@ -277,6 +422,203 @@ impl PhoneCall {
         self.volume_mute = false;
         self.microphone_mute = false;
         self.is_zoom = false;
+        self.reconnecting = false;
+        self.stats = None;
+        self.show_stats = false;
+        self.show_devices = false;
+        self.peer_busy = false;
+        self.close_pip();
+        self.is_mini = false;
+        self.close_call_window();
+    }
+
+    /// the listeners are removed before the closures dropped,
+    /// the leave event fires after the window closed
+    fn close_pip(&mut self) {
+        if let Some(on_leave) = self.on_leave_pip.take() {
+            if let Some(video) = self.friend_video_node.cast::<HtmlVideoElement>() {
+                let _ = video.remove_event_listener_with_callback(
+                    "leavepictureinpicture",
+                    on_leave.as_ref().unchecked_ref(),
+                );
+            }
+        }
+        self.clear_pip_actions();
+        if self.pip {
+            utils::exit_pip();
+        }
+        self.pip = false;
+    }
+
+    fn set_pip_actions(&mut self, ctx: &Context<Self>) {
+        self.clear_pip_actions();
+        for (action, msg) in [
+            ("togglemicrophone", CallWindowAction::SwitchMicrophoneMute),
+            ("hangup", CallWindowAction::HangUp),
+        ] {
+            let link = ctx.link().clone();
+            let handler = Closure::<dyn FnMut()>::new(move || {
+                link.send_message(PhoneCallMsg::MiniAction(msg));
+            });
+            utils::set_media_session_action(action, Some(handler.as_ref().unchecked_ref()));
+            self.on_pip_actions.push(handler);
+        }
+    }
+
+    fn clear_pip_actions(&mut self) {
+        if self.on_pip_actions.is_empty() {
+            return;
+        }
+        utils::set_media_session_action("togglemicrophone", None);
+        utils::set_media_session_action("hangup", None);
+        self.on_pip_actions.clear();
+    }
+
+    /// keep the call controls above the other windows in the tauri build
+    fn open_call_window(&mut self, ctx: &Context<Self>) {
+        if self.call_window || !tauri::is_tauri() {
+            return;
+        }
+        self.call_window = true;
+        let link = ctx.link().clone();
+        let on_action = Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| {
+            match tauri::event_payload(&event)
+                .and_then(|payload| serde_json::from_str::<CallWindowAction>(&payload).ok())
+            {
+                Some(action) => link.send_message(PhoneCallMsg::MiniAction(action)),
+                None => log::error!("invalid call action: {:?}", event),
+            }
+        });
+        let handler = on_action.as_ref().unchecked_ref::<Function>().clone();
+        self.on_call_action = Some(on_action);
+
+        let title = self
+            .call_friend_info
+            .as_ref()
+            .map(|info| info.name())
+            .unwrap_or_default();
+        let args = Object::new();
+        let _ = Reflect::set(
+            &args,
+            &JsValue::from_str("title"),
+            &JsValue::from_str(&title),
+        );
+        ctx.link().send_future(async move {
+            let unlisten = match tauri::listen(CALL_ACTION_EVENT, &handler).await {
+                Ok(unlisten) => unlisten,
+                Err(e) => {
+                    log::error!("listen call action error: {:?}", e);
+                    return PhoneCallMsg::CallWindowOpened(None);
+                }
+            };
+            if let Err(e) = tauri::invoke(OPEN_CALL_WINDOW, args.into()).await {
+                log::error!("open call window error: {:?}", e);
+                let _ = unlisten.call0(&JsValue::NULL);
+                return PhoneCallMsg::CallWindowOpened(None);
+            }
+            PhoneCallMsg::CallWindowOpened(Some(unlisten))
+        });
+    }
+
+    /// mirror the call to the always-on-top call window
+    fn sync_call_window(&self) {
+        if self.unlisten_call_action.is_none() {
+            return;
+        }
+        let Some(info) = self.call_friend_info.as_ref() else {
+            return;
+        };
+        let state = CallWindowState {
+            name: info.name(),
+            avatar: info.avatar(),
+            status: self.call_status().into(),
+            microphone_mute: self.microphone_mute,
+        };
+        spawn_local(async move {
+            let payload = serde_json::to_string(&state).unwrap();
+            if let Err(e) = tauri::emit(CALL_STATE_EVENT, &payload).await {
+                log::error!("send call state error: {:?}", e);
+            }
+        });
+    }
+
+    fn close_call_window(&mut self) {
+        if !self.call_window {
+            return;
+        }
+        self.call_window = false;
+        if let Some(unlisten) = self.unlisten_call_action.take() {
+            let _ = unlisten.call0(&JsValue::NULL);
+        }
+        self.on_call_action = None;
+        spawn_local(async {
+            if let Err(e) = tauri::invoke(CLOSE_CALL_WINDOW, JsValue::NULL).await {
+                log::error!("close call window error: {:?}", e);
+            }
+        });
+    }
+
+    /// switch the camera or microphone in place,
+    /// the friend receives the new track without renegotiation
+    fn replace_local_track(&mut self, track: MediaStreamTrack) {
+        let (Some(rtc), Some(stream)) = (self.rtc.as_ref(), self.stream.as_ref()) else {
+            track.stop();
+            return;
+        };
+        let is_audio = track.kind() == "audio";
+        let old_tracks = if is_audio {
+            track.set_enabled(!self.microphone_mute);
+            stream.get_audio_tracks()
+        } else {
+            stream.get_video_tracks()
+        };
+        for old in old_tracks {
+            let old = old.unchecked_into::<MediaStreamTrack>();
+            stream.remove_track(&old);
+            old.stop();
+        }
+        stream.add_track(&track);
+
+        // the video sender carries the screen while sharing in the video call,
+        // the new camera is restored after the sharing stopped
+        let sharing_camera =
+            !is_audio && self.screen_stream.is_some() && self.screen_sender.is_none();
+        let sender = if is_audio {
+            rtc.audio_sender()
+        } else if sharing_camera {
+            None
+        } else {
+            rtc.video_sender()
+        };
+        if let Some(sender) = sender {
+            let future = JsFuture::from(sender.replace_track(Some(&track)));
+            spawn_local(async move {
+                if let Err(e) = future.await {
+                    log::error!("replace track error: {:?}", e);
+                }
+            });
+        }
+
+        if !is_audio && !sharing_camera {
+            if let Some(video) = self.video_node.cast::<HtmlVideoElement>() {
+                video.set_src_object(None);
+                video.set_src_object(Some(stream));
+                let _ = video.play();
+            }
+        }
+    }
+
+    /// play the friend's audio, video and screen on the picked speaker
+    fn apply_speaker(&self) {
+        if let Some(audio) = self.friend_audio_node.cast::<HtmlAudioElement>() {
+            set_speaker(&audio);
+        }
+        if let Some(video) = self.friend_video_node.cast::<HtmlVideoElement>() {
+            set_speaker(&video);
+        }
+        if let Some(video) = self.remote_screen_node.cast::<HtmlVideoElement>() {
+            set_speaker(&video);
+        }
     }
 
     fn save_call_msg(&self, msg: Message) {
@@ -290,10 +632,131 @@ impl PhoneCall {
         });
     }
 
+    /// must be called before the call finished
+    fn offer_voicemail(&mut self, call_id: AttrValue) {
+        let (Some(info), Some(friend)) =
+            (self.invite_info.as_ref(), self.call_friend_info.as_ref())
+        else {
+            return;
+        };
+        self.voicemail = Some(Voicemail {
+            call_id,
+            friend_id: info.friend_id.clone(),
+            name: friend.name(),
+            avatar: friend.avatar(),
+            recording: false,
+        });
+    }
+
+    fn is_recording(&self) -> bool {
+        self.recording
+            .as_ref()
+            .is_some_and(|recording| recording.recorder.is_recording())
+    }
+
+    /// tell the friend by the control channel of the call
+    fn send_control(&self, control: CallControl) {
+        let Some(rtc) = self.rtc.as_ref() else {
+            return;
+        };
+        if !rtc.send_control(&serde_json::to_string(&control).unwrap()) {
+            log::warn!("send call control failed: {:?}", control);
+        }
+    }
+
+    /// the invited side receives the call
+    fn call_direction(&self) -> CallDirection {
+        if self.invited {
+            CallDirection::Incoming
+        } else {
+            CallDirection::Outgoing
+        }
+    }
+
     fn format_duration(&self) -> String {
         let hours = self.call_duration / 3600;
         let minutes = (self.call_duration % 3600) / 60;
         let secs = self.call_duration % 60;
         format!("{:02}:{:02}:{:02}", hours, minutes, secs)
     }
+
+    /// the duration after connected, otherwise the connection state
+    fn call_status(&self) -> String {
+        if self.peer_busy {
+            return tr!(self.i18n, USER_BUSY);
+        }
+        match self.conn_state {
+            ConnectionState::Waiting => tr!(self.i18n, WAITING),
+            ConnectionState::Connecting => tr!(self.i18n, CONNECTING),
+            ConnectionState::Connected => self.format_duration(),
+            ConnectionState::Error => tr!(self.i18n, CONN_ERROR),
+        }
+    }
+}
+
+/// play the media element on the speaker picked by the user
+fn set_speaker(element: &HtmlMediaElement) {
+    let element = element.clone();
+    let devices = MediaDeviceState::get();
+    spawn_local(async move {
+        if let Err(e) = utils::set_sink_id(&element, devices.speaker()).await {
+            log::warn!("set speaker error: {:?}", e);
+        }
+    });
+}
+
+/// save the call log and notify the calls tab,
+/// the missed call is read already if the calls tab is opened
+pub(crate) async fn put_call_log(mut call_log: CallLog) {
+    if ComponentTypeState::get().component_type == ComponentType::Calls {
+        call_log.is_read = true;
+    }
+    if let Err(err) = db::db_ins().call_logs.put(&call_log).await {
+        log::error!("save call log error: {:?}", err);
+        return;
+    }
+    if call_log.is_missed() && !call_log.is_read {
+        UnreadState::incr_missed_call();
+    }
+    CallLogState { log: call_log }.notify();
+}
+
+pub(crate) fn save_call_log(call_log: CallLog) {
+    spawn_local(put_call_log(call_log));
+}
+
+/// the voice message relating to a call message is a voicemail,
+/// attach it to the log of the call
+pub(crate) async fn mark_voicemail(msg: &mut Message) {
+    if msg.content_type != ContentType::Audio {
+        return;
+    }
+    let Some(call_id) = msg.related_msg_id.clone() else {
+        return;
+    };
+    match db::db_ins().messages.get(&call_id).await {
+        Ok(Some(call))
+            if matches!(
+                call.content_type,
+                ContentType::VideoCall | ContentType::AudioCall
+            ) => {}
+        _ => return,
+    }
+    msg.voicemail = true;
+    attach_voicemail(&call_id, msg.local_id.clone()).await;
+}
+
+pub(crate) async fn attach_voicemail(call_id: &str, voice_id: AttrValue) {
+    match db::db_ins().call_logs.get(call_id).await {
+        Ok(Some(mut call_log)) => {
+            call_log.voicemail = Some(voice_id);
+            if let Err(err) = db::db_ins().call_logs.put(&call_log).await {
+                log::error!("save call log error: {:?}", err);
+                return;
+            }
+            CallLogState { log: call_log }.notify();
+        }
+        Ok(None) => {}
+        Err(err) => log::error!("get call log error: {:?}", err),
+    }
 }
@@ -2,41 +2,57 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 use gloo::timers::callback::{Interval, Timeout};
+use js_sys::Function;
 use log::{debug, error};
 use nanoid::nanoid;
 use sandcat_sdk::error::Error;
+use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
-    HtmlAudioElement, HtmlDivElement, HtmlVideoElement, MediaStream, MouseEvent,
-    RtcIceCandidateInit, RtcSdpType, RtcSessionDescription, RtcSessionDescriptionInit,
+    File, HtmlAudioElement, HtmlDivElement, HtmlVideoElement, MediaDeviceKind, MediaStream,
+    MediaStreamTrack, MouseEvent, RtcIceCandidateInit, RtcPeerConnection, RtcSdpType,
+    RtcSessionDescription, RtcSessionDescriptionInit,
 };
 use yew::platform::spawn_local;
-use yew::{html, AttrValue, Callback, Component, Context, Html, Properties, TouchEvent};
+use yew::{classes, html, AttrValue, Callback, Component, Context, Html, Properties, TouchEvent};
 
 use i18n::{en_us, zh_cn, LanguageType};
 use icons::{
-    AnswerPhoneIcon, AudioZoomInIcon, AudioZoomOutIcon, HangUpLoadingIcon, HangupInNotifyIcon,
-    MicrophoneIcon, MicrophoneMuteIcon, VideoRecordIcon, VolumeIcon, VolumeMuteIcon,
+    AnswerPhoneIcon, AudioZoomInIcon, AudioZoomOutIcon, CallRecordIcon, CloseIcon,
+    HangUpLoadingIcon, HangupInNotifyIcon, MicrophoneIcon, MicrophoneMuteIcon, MiniWindowIcon,
+    PipIcon, ScreenShareIcon, SettingIcon, VideoRecordIcon, VolumeIcon, VolumeMuteIcon,
 };
 use sandcat_sdk::db;
+use sandcat_sdk::model::call_control::CallControl;
+use sandcat_sdk::model::call_log::{CallDirection, CallLog, CallOutcome};
+use sandcat_sdk::model::call_window::CallWindowAction;
 use sandcat_sdk::model::message::{
-    Agree, Hangup, InviteAnswerMsg, InviteCancelMsg, InviteInfo, InviteMsg, InviteNotAnswerMsg,
-    InviteType, Message, Msg, SingleCall,
+    hangup_content, Agree, Hangup, InviteAnswerMsg, InviteCancelMsg, InviteInfo, InviteMsg,
+    InviteNotAnswerMsg, InviteType, Message, Msg, SendStatus, SingleCall,
 };
 use sandcat_sdk::model::notification::Notification;
-use sandcat_sdk::model::ContentType;
+use sandcat_sdk::model::voice::Voice;
 use sandcat_sdk::model::ItemInfo;
-use sandcat_sdk::state::{I18nState, SendCallState};
+use sandcat_sdk::model::{ContentType, RightContentType};
+use sandcat_sdk::state::{AppState, I18nState, MediaDeviceState, Notify, SendCallState};
 use utils::tr;
+use web_rtc::{CallStats, SignalLevel};
 use ws::WebSocketManager;
 
-use crate::call::ConnectionState;
+use crate::avatar_img::AvatarImg;
+use crate::call::recording::{save_recording, send_recording, CallRecorder, Recorded};
+use crate::call::{attach_voicemail, save_call_log, ConnectionState, Recording};
 use crate::constant::{
-    CALL_BUSY, CONNECTING, CONN_ERROR, INCOMING_CALL, OTHER_ERROR, STREAM_ERROR, UNKNOW_ERROR,
-    WAITING,
+    CALL_BUSY, CALL_RECORDED, CALL_WAITING, CAMERA, DECLINE, DEFAULT_DEVICE, DEVICES,
+    END_AND_ACCEPT, FRIEND_RECORDING, INCOMING_CALL, LEAVE_VOICEMAIL, MICROPHONE, MINI_WINDOW,
+    NOT_AVAILABLE, OTHER_ERROR, PIP, RECONNECTING, RECORD_CALL, SEND_AS_FILE, SEND_AS_VIDEO,
+    SHARE_SCREEN, SHARING_SCREEN, SPEAKER, STREAM_ERROR, UNKNOW_ERROR,
 };
+use crate::device_picker::DevicePicker;
 use crate::get_platform;
+use crate::right::recorder::Recorder;
+use crate::right::util::send_voice_msg;
 
 use super::PhoneCall;
 
@@ -86,9 +102,57 @@ pub enum PhoneCallMsg {
     OnTouchStart(TouchEvent),
     OnTouchMove(TouchEvent),
     OnTouchEnd,
+    // start or stop sharing the screen
+    SwitchScreenShare,
+    ScreenShareReady(MediaStream),
+    StopScreenShare,
+    // the friend stopped sharing the screen in the audio call
+    RemoteScreenEnded,
+    // the connection is lost and recovering or recovered
+    Reconnecting(bool),
+    StatsUpdated(CallStats),
+    // an invitation arrived while in the call
+    ShowCallWaiting(Box<dyn ItemInfo>),
+    // reply busy to the waiting invitation
+    DeclineWaiting,
+    // end the current call and accept the waiting invitation
+    AcceptWaiting,
+    // show or hide the stats overlay
+    SwitchStats,
+    // show or hide the camera, microphone and speaker pickers
+    SwitchDevicePanel,
+    SwitchMicrophone(String),
+    SwitchCamera(String),
+    SwitchSpeaker(String),
+    // the track of the picked device is ready
+    ReplaceTrack(MediaStreamTrack),
+    // show the recorder to leave a voice message
+    RecordVoicemail,
+    SendVoicemail(Voice),
+    CloseVoicemail,
+    // shrink the call to the controls or restore it
+    SwitchMini,
+    // float the friend video in the picture-in-picture window
+    SwitchPip,
+    PipChanged(bool),
+    // the buttons of the picture-in-picture window or the tauri call window
+    MiniAction(CallWindowAction),
+    // the tauri call window opened, it carries the function to stop listening
+    CallWindowOpened(Option<Function>),
+    // start or stop recording the call
+    SwitchRecording,
+    // the recorder stopped and emitted the file
+    Recorded(File),
+    // send the recording as a video or a file message
+    SendRecording(bool),
+    CloseRecorded,
+    // the text received on the control channel of the call
+    CallControl(String),
 }
 
 const TIMEOUT: u32 = 120;
+/// seconds to show the busy hint before closing the window
+const BUSY_TIMEOUT: u32 = 3;
 
 impl Component for PhoneCall {
     type Message = PhoneCallMsg;
@@ -109,7 +173,26 @@ impl Component for PhoneCall {
             SingleCall::Invite(msg) => {
                 // 判断是否占线
                 if self.invite_info.is_some() {
-                    // todo回复占线
+                    if self.peer_id().as_ref() == Some(&msg.send_id) {
+                        return false;
+                    }
+                    // show the call waiting prompt while in the call, reply busy otherwise
+                    let connected = self.invite_info.as_ref().is_some_and(|info| info.connected);
+                    if connected && self.waiting_invite.is_none() {
+                        let friend_id = msg.send_id.clone();
+                        self.waiting_invite = Some(msg);
+                        ctx.link().send_future(async move {
+                            let friend = db::db_ins()
+                                .friends
+                                .get(friend_id.as_str())
+                                .await
+                                .unwrap()
+                                .unwrap_or_default();
+                            PhoneCallMsg::ShowCallWaiting(Box::new(friend))
+                        });
+                    } else {
+                        self.reply_busy(ctx, msg);
+                    }
                     return false;
                 }
 
@@ -138,6 +221,21 @@ impl Component for PhoneCall {
             }
             SingleCall::InviteCancel(mut msg) => {
                 debug!("对方取消通话");
+                if self.is_waiting_from(&msg.send_id) {
+                    self.waiting_invite = None;
+                    self.waiting_friend_info = None;
+                    let friend_id = msg.send_id.clone();
+                    msg.send_id = msg.friend_id.clone();
+                    msg.friend_id = friend_id;
+                    let msg: Message = msg.into();
+                    save_call_log(CallLog::new(
+                        &msg,
+                        CallDirection::Incoming,
+                        CallOutcome::Missed,
+                    ));
+                    self.save_call_msg(msg);
+                    return true;
+                }
                 // 判断是否是当前用户
                 if let Some(info) = self.invite_info.as_ref() {
                     if info.send_id == msg.send_id {
@@ -150,7 +248,13 @@ impl Component for PhoneCall {
                         msg.friend_id = friend_id;
                         self.show_notify = false;
                         self.call_friend_info = None;
-                        self.save_call_msg(msg.into());
+                        let msg: Message = msg.into();
+                        save_call_log(CallLog::new(
+                            &msg,
+                            CallDirection::Incoming,
+                            CallOutcome::Missed,
+                        ));
+                        self.save_call_msg(msg);
                         debug!("已经关闭通知");
                         return true;
                     }
@@ -170,6 +274,23 @@ impl Component for PhoneCall {
                     let friend_id = msg.send_id.clone();
                     msg.send_id = msg.friend_id.clone();
                     msg.friend_id = friend_id;
+                    if msg.busy {
+                        // keep the window for a while to show the friend is busy
+                        let msg: Message = msg.into();
+                        save_call_log(CallLog::new(
+                            &msg,
+                            CallDirection::Outgoing,
+                            CallOutcome::Busy,
+                        ));
+                        self.offer_voicemail(msg.local_id.clone());
+                        self.save_call_msg(msg);
+                        self.peer_busy = true;
+                        let link = ctx.link().clone();
+                        self.call_timeout = Some(Timeout::new(BUSY_TIMEOUT * 1000, move || {
+                            link.send_message(PhoneCallMsg::Close);
+                        }));
+                        return true;
+                    }
                     match msg.invite_type {
                         InviteType::Video => {
                             self.show_video = false;
@@ -178,14 +299,34 @@ impl Component for PhoneCall {
                             self.show_audio = false;
                         }
                     }
-                    self.save_call_msg(msg.into());
+                    let msg: Message = msg.into();
+                    save_call_log(CallLog::new(
+                        &msg,
+                        CallDirection::Outgoing,
+                        CallOutcome::Declined,
+                    ));
+                    self.offer_voicemail(msg.local_id.clone());
+                    self.save_call_msg(msg);
                     self.finish_call();
                     return true;
                 }
             }
             SingleCall::Offer(msg) => {
                 // 建立通话连接，收到offer，设置sdp
-                if self.rtc.is_some() {
+                if let Some(rtc) = self.rtc.as_ref() {
+                    // the friend changed the tracks of the connected call, answer it directly
+                    let connected = self.invite_info.as_ref().is_some_and(|info| info.connected);
+                    if connected && self.peer_id().as_ref() == Some(&msg.send_id) {
+                        spawn_local(answer_renegotiation(
+                            rtc.pc().clone(),
+                            ctx.props().ws.clone(),
+                            msg.sdp,
+                            ctx.props().user_id.clone(),
+                            msg.send_id,
+                            get_platform(self.is_mobile),
+                        ));
+                        return false;
+                    }
                     log::warn!("收到邀请，但是占线: {:?}", &msg);
                     return false;
                 }
@@ -209,30 +350,28 @@ impl Component for PhoneCall {
             SingleCall::Agree(msg) => {
                 // 同意通话请求并建立连接，设置sdp
                 // 判断是否是我们发出去的邀请回复
-                if let Some(info) = &self.invite_info {
-                    if info.friend_id == msg.send_id && self.rtc.is_some() {
-                        // 接通
-                        debug!("请求被对方同意");
-                        // todo需要在webrtc状态为Connected下进行回调修改
-                        // self.invite_info.as_mut().unwrap().start_time =
-                        //     chrono::Utc::now().timestamp_millis();
-                        // self.invite_info.as_mut().unwrap().connected = true;
-                        let mut description = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
-                        description.sdp(&msg.sdp.unwrap());
-                        let future = JsFuture::from(
-                            self.rtc
-                                .as_ref()
-                                .unwrap()
-                                .pc()
-                                .set_remote_description(&description),
-                        );
-                        spawn_local(async {
-                            if let Err(err) = future.await {
-                                error!("remote desc set failed: {:?}", err);
-                            }
-                        });
-                        return true;
-                    }
+                if self.peer_id() == Some(msg.send_id) && self.rtc.is_some() {
+                    // 接通
+                    debug!("请求被对方同意");
+                    // todo需要在webrtc状态为Connected下进行回调修改
+                    // self.invite_info.as_mut().unwrap().start_time =
+                    //     chrono::Utc::now().timestamp_millis();
+                    // self.invite_info.as_mut().unwrap().connected = true;
+                    let mut description = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+                    description.sdp(&msg.sdp.unwrap());
+                    let future = JsFuture::from(
+                        self.rtc
+                            .as_ref()
+                            .unwrap()
+                            .pc()
+                            .set_remote_description(&description),
+                    );
+                    spawn_local(async {
+                        if let Err(err) = future.await {
+                            error!("remote desc set failed: {:?}", err);
+                        }
+                    });
+                    return true;
                 }
             }
             SingleCall::NewIceCandidate(msg) => {
@@ -258,6 +397,21 @@ impl Component for PhoneCall {
                 })
             }
             SingleCall::NotAnswer(mut msg) => {
+                if self.is_waiting_from(&msg.send_id) {
+                    self.waiting_invite = None;
+                    self.waiting_friend_info = None;
+                    let friend_id = msg.send_id.clone();
+                    msg.send_id = msg.friend_id.clone();
+                    msg.friend_id = friend_id;
+                    let msg: Message = msg.into();
+                    save_call_log(CallLog::new(
+                        &msg,
+                        CallDirection::Incoming,
+                        CallOutcome::Missed,
+                    ));
+                    self.save_call_msg(msg);
+                    return true;
+                }
                 // 判断是否是当前用户
                 if let Some(info) = self.invite_info.as_ref() {
                     if info.send_id == msg.send_id {
@@ -268,7 +422,13 @@ impl Component for PhoneCall {
                         self.invite_info = None;
                         self.show_notify = false;
                         self.call_friend_info = None;
-                        self.save_call_msg(msg.into());
+                        let msg: Message = msg.into();
+                        save_call_log(CallLog::new(
+                            &msg,
+                            CallDirection::Incoming,
+                            CallOutcome::Missed,
+                        ));
+                        self.save_call_msg(msg);
                         return true;
                     }
                 }
@@ -284,7 +444,14 @@ impl Component for PhoneCall {
                         let create_time = chrono::Utc::now().timestamp_millis();
                         let sustain = create_time - info.start_time;
                         msg.sustain = sustain;
-                        self.save_call_msg(msg.into());
+                        msg.stats = self.stats.as_ref().map(|stats| stats.summary().into());
+                        let msg: Message = msg.into();
+                        let mut call_log =
+                            CallLog::new(&msg, self.call_direction(), CallOutcome::Answered);
+                        call_log.start_time = info.start_time;
+                        call_log.duration = sustain;
+                        save_call_log(call_log);
+                        self.save_call_msg(msg);
                         self.finish_call();
                         return true;
                     }
@@ -310,6 +477,7 @@ impl Component for PhoneCall {
                     Notification::warn(tr!(self.i18n, CALL_BUSY)).notify();
                     return false;
                 }
+                self.voicemail = None;
                 self.invite_info = Some(InviteInfo {
                     send_id: ctx.props().user_id.clone(),
                     friend_id: msg.friend_id.clone(),
@@ -338,9 +506,12 @@ impl Component for PhoneCall {
                         .await
                         .unwrap()
                         .unwrap_or_default();
+                    web_rtc::refresh_ice_servers().await;
 
+                    let devices = MediaDeviceState::get();
+                    let (camera, mic) = (devices.camera(), devices.microphone());
                     match call_type {
-                        InviteType::Video => match utils::get_video_stream().await {
+                        InviteType::Video => match utils::get_video_stream(camera, mic).await {
                             Ok(stream) => {
                                 if let Err(e) = ws
                                     .borrow()
@@ -373,7 +544,7 @@ impl Component for PhoneCall {
                             }
                         },
                         InviteType::Audio => {
-                            match utils::get_audio_stream().await {
+                            match utils::get_audio_stream(mic).await {
                                 Ok(stream) => {
                                     // send invite message
                                     if let Err(e) = ws
@@ -426,20 +597,26 @@ impl Component for PhoneCall {
                 };
                 let invite_type = info.invite_type.clone();
                 ctx.link().send_future(async move {
+                    let msg = Message {
+                        local_id: local_id.clone(),
+                        send_id: send_id.clone(),
+                        friend_id: friend_id.clone(),
+                        content_type,
+                        content: AttrValue::from("cancel"),
+                        create_time,
+                        is_self: true,
+                        ..Default::default()
+                    };
                     let _ = db::db_ins()
                         .messages
-                        .add_message(&Message {
-                            local_id: local_id.clone(),
-                            send_id: send_id.clone(),
-                            friend_id: friend_id.clone(),
-                            content_type,
-                            content: AttrValue::from("cancel"),
-                            create_time,
-                            is_self: true,
-                            ..Default::default()
-                        })
+                        .add_message(&msg)
                         .await
                         .map_err(|err| error!("消息入库失败:{:?}", err));
+                    save_call_log(CallLog::new(
+                        &msg,
+                        CallDirection::Outgoing,
+                        CallOutcome::Canceled,
+                    ));
 
                     PhoneCallMsg::SendMessage(SingleCall::InviteCancel(InviteCancelMsg {
                         local_id,
@@ -461,15 +638,18 @@ impl Component for PhoneCall {
                 // get stream
                 let invite_type = self.invite_info.as_ref().unwrap().invite_type.clone();
                 ctx.link().send_future(async move {
+                    web_rtc::refresh_ice_servers().await;
+                    let devices = MediaDeviceState::get();
+                    let (camera, mic) = (devices.camera(), devices.microphone());
                     match invite_type {
-                        InviteType::Video => match utils::get_video_stream().await {
+                        InviteType::Video => match utils::get_video_stream(camera, mic).await {
                             Ok(stream) => PhoneCallMsg::ConnectedCall(stream),
                             Err(e) => {
                                 Notification::error(Error::js_err(e)).notify();
                                 PhoneCallMsg::Close
                             }
                         },
-                        InviteType::Audio => match utils::get_audio_stream().await {
+                        InviteType::Audio => match utils::get_audio_stream(mic).await {
                             Ok(stream) => PhoneCallMsg::ConnectedCall(stream),
                             Err(e) => {
                                 Notification::error(Error::js_err(e)).notify();
@@ -498,26 +678,32 @@ impl Component for PhoneCall {
                     InviteType::Audio => ContentType::AudioCall,
                 };
                 let invite_type = info.invite_type.clone();
+                let stats = self.stats.as_ref().map(CallStats::summary);
+                let direction = self.call_direction();
 
                 // save message to db
                 ctx.link().send_future(async move {
+                    let msg = Message {
+                        local_id: local_id.clone(),
+                        send_id: send_id.clone(),
+                        friend_id: friend_id.clone(),
+                        content_type,
+                        content: hangup_content(sustain, stats.as_deref()).into(),
+                        create_time,
+                        is_read: 1,
+                        is_self: true,
+                        ..Default::default()
+                    };
                     db::db_ins()
                         .messages
-                        .add_message(&Message {
-                            local_id: local_id.clone(),
-                            send_id: send_id.clone(),
-                            friend_id: friend_id.clone(),
-                            content_type,
-                            content: format!("duration||{}", utils::format_milliseconds(sustain))
-                                .into(),
-                            create_time,
-                            is_read: 1,
-                            is_self: true,
-                            ..Default::default()
-                        })
+                        .add_message(&msg)
                         .await
                         .map_err(|err| error!("消息入库失败:{:?}", err))
                         .unwrap();
+                    let mut call_log = CallLog::new(&msg, direction, CallOutcome::Answered);
+                    call_log.start_time = create_time - sustain;
+                    call_log.duration = sustain;
+                    save_call_log(call_log);
 
                     PhoneCallMsg::SendMessage(SingleCall::HangUp(Hangup {
                         local_id,
@@ -634,21 +820,24 @@ impl Component for PhoneCall {
                 self.invite_info = None;
                 self.invited = false;
                 ctx.link().send_future(async move {
-                    let _ = db::db_ins()
-                        .messages
-                        .add_message(&Message {
-                            local_id: local_id.clone(),
-                            server_id: AttrValue::default(),
-                            send_id: send_id.clone(),
-                            friend_id: friend_id.clone(),
-                            content_type,
-                            content: AttrValue::from("deny"),
-                            create_time,
-                            is_read: 1,
-                            is_self: true,
-                            ..Default::default()
-                        })
-                        .await;
+                    let msg = Message {
+                        local_id: local_id.clone(),
+                        server_id: AttrValue::default(),
+                        send_id: send_id.clone(),
+                        friend_id: friend_id.clone(),
+                        content_type,
+                        content: AttrValue::from("deny"),
+                        create_time,
+                        is_read: 1,
+                        is_self: true,
+                        ..Default::default()
+                    };
+                    let _ = db::db_ins().messages.add_message(&msg).await;
+                    save_call_log(CallLog::new(
+                        &msg,
+                        CallDirection::Incoming,
+                        CallOutcome::Declined,
+                    ));
 
                     PhoneCallMsg::SendMessage(SingleCall::InviteAnswer(InviteAnswerMsg {
                         local_id,
@@ -664,15 +853,122 @@ impl Component for PhoneCall {
                 true
             }
             PhoneCallMsg::DisConnCall => {
+                // the connection could not be recovered, hang up and record the call
+                if self.invite_info.as_ref().is_some_and(|info| info.connected) {
+                    ctx.link().send_message(PhoneCallMsg::HangUpCall);
+                    return false;
+                }
                 // 判断视频窗口是否还存在
-                if self.show_video {
+                if self.show_video || self.show_audio {
                     // 窗口还在说明连接被中断，因为不在了的情况是对端主动发起的挂断请求
                     // 是正常的主动处理
                     self.finish_call();
-                    // TODO 记录中断消息
                 }
                 true
             }
+            PhoneCallMsg::ShowCallWaiting(friend) => {
+                if self.waiting_invite.is_none() {
+                    return false;
+                }
+                self.waiting_friend_info = Some(friend);
+                true
+            }
+            PhoneCallMsg::DeclineWaiting => {
+                if let Some(invite) = self.waiting_invite.take() {
+                    self.reply_busy(ctx, invite);
+                }
+                self.waiting_friend_info = None;
+                true
+            }
+            PhoneCallMsg::AcceptWaiting => {
+                let Some(invite) = self.waiting_invite.take() else {
+                    return false;
+                };
+                let friend = self.waiting_friend_info.take();
+                if self.invite_info.as_ref().is_some_and(|info| info.connected) {
+                    self.update(ctx, PhoneCallMsg::HangUpCall);
+                } else {
+                    self.finish_call();
+                }
+                self.invited = true;
+                self.invite_info = Some(InviteInfo {
+                    send_id: invite.send_id,
+                    friend_id: invite.friend_id,
+                    invite_type: invite.invite_type,
+                    ..Default::default()
+                });
+                self.call_friend_info = friend;
+                self.call_duration = 0;
+                ctx.link().send_message(PhoneCallMsg::AgreeCall);
+                true
+            }
+            PhoneCallMsg::StatsUpdated(stats) => {
+                let render = self.show_stats
+                    || self.stats.as_ref().map(CallStats::level) != Some(stats.level());
+                self.stats = Some(stats);
+                render
+            }
+            PhoneCallMsg::SwitchStats => {
+                self.show_stats = !self.show_stats;
+                true
+            }
+            PhoneCallMsg::SwitchDevicePanel => {
+                self.show_devices = !self.show_devices;
+                true
+            }
+            PhoneCallMsg::SwitchMicrophone(id) => {
+                MediaDeviceState::set_microphone(id.clone());
+                let link = ctx.link().clone();
+                spawn_local(async move {
+                    let device = (!id.is_empty()).then_some(id.as_str());
+                    match utils::get_audio_stream(device).await {
+                        Ok(stream) => {
+                            if let Ok(track) = stream
+                                .get_audio_tracks()
+                                .get(0)
+                                .dyn_into::<MediaStreamTrack>()
+                            {
+                                link.send_message(PhoneCallMsg::ReplaceTrack(track));
+                            }
+                        }
+                        Err(e) => Notification::error(Error::js_err(e)).notify(),
+                    }
+                });
+                true
+            }
+            PhoneCallMsg::SwitchCamera(id) => {
+                MediaDeviceState::set_camera(id.clone());
+                let link = ctx.link().clone();
+                spawn_local(async move {
+                    let device = (!id.is_empty()).then_some(id.as_str());
+                    match utils::get_camera_stream(device).await {
+                        Ok(stream) => {
+                            if let Ok(track) = stream
+                                .get_video_tracks()
+                                .get(0)
+                                .dyn_into::<MediaStreamTrack>()
+                            {
+                                link.send_message(PhoneCallMsg::ReplaceTrack(track));
+                            }
+                        }
+                        Err(e) => Notification::error(Error::js_err(e)).notify(),
+                    }
+                });
+                true
+            }
+            PhoneCallMsg::SwitchSpeaker(id) => {
+                MediaDeviceState::set_speaker(id);
+                self.apply_speaker();
+                true
+            }
+            PhoneCallMsg::ReplaceTrack(track) => {
+                self.replace_local_track(track);
+                false
+            }
+            PhoneCallMsg::Reconnecting(reconnecting) => {
+                self.reconnecting = reconnecting && self.rtc.is_some();
+                true
+            }
             PhoneCallMsg::ShowVideoWindow(stream, friend) => {
                 let video: HtmlVideoElement = self.video_node.cast().unwrap();
                 self.call_friend_info = Some(friend);
@@ -694,6 +990,7 @@ impl Component for PhoneCall {
                         return false;
                     }
                     let local_id = AttrValue::from(nanoid!());
+                    let call_id = local_id.clone();
                     let friend_id = info.friend_id.clone();
                     let create_time = chrono::Utc::now().timestamp_millis();
                     let send_id = ctx.props().user_id.clone();
@@ -704,20 +1001,26 @@ impl Component for PhoneCall {
                     };
                     let invite_type = info.invite_type.clone();
                     ctx.link().send_future(async move {
+                        let msg = Message {
+                            local_id: local_id.clone(),
+                            send_id: send_id.clone(),
+                            friend_id: friend_id.clone(),
+                            content_type,
+                            content: AttrValue::from("not_answer"),
+                            create_time,
+                            is_self: true,
+                            ..Default::default()
+                        };
                         let _ = db::db_ins()
                             .messages
-                            .add_message(&Message {
-                                local_id: local_id.clone(),
-                                send_id: send_id.clone(),
-                                friend_id: friend_id.clone(),
-                                content_type,
-                                content: AttrValue::from("not_answer"),
-                                create_time,
-                                is_self: true,
-                                ..Default::default()
-                            })
+                            .add_message(&msg)
                             .await
                             .map_err(|err| error!("消息入库失败:{:?}", err));
+                        save_call_log(CallLog::new(
+                            &msg,
+                            CallDirection::Outgoing,
+                            CallOutcome::NoAnswer,
+                        ));
 
                         PhoneCallMsg::SendMessage(SingleCall::NotAnswer(InviteNotAnswerMsg {
                             local_id,
@@ -729,6 +1032,7 @@ impl Component for PhoneCall {
                             ..Default::default()
                         }))
                     });
+                    self.offer_voicemail(call_id);
                     self.finish_call();
 
                     return true;
@@ -793,6 +1097,253 @@ impl Component for PhoneCall {
                 self.finish_call();
                 true
             }
+            PhoneCallMsg::RecordVoicemail => {
+                if let Some(voicemail) = self.voicemail.as_mut() {
+                    voicemail.recording = true;
+                }
+                true
+            }
+            PhoneCallMsg::SendVoicemail(voice) => {
+                let Some(voicemail) = self.voicemail.take() else {
+                    return false;
+                };
+                let user = AppState::get();
+                let msg = Message {
+                    local_id: voice.local_id.clone().into(),
+                    send_id: ctx.props().user_id.clone(),
+                    friend_id: voicemail.friend_id,
+                    content_type: ContentType::Audio,
+                    create_time: chrono::Utc::now().timestamp_millis(),
+                    is_read: 1,
+                    is_self: true,
+                    platform: get_platform(self.is_mobile),
+                    send_status: SendStatus::Sending,
+                    audio_duration: voice.duration,
+                    audio_downloaded: true,
+                    avatar: user.login_user.avatar.clone(),
+                    nickname: user.login_user.name.clone(),
+                    related_msg_id: Some(voicemail.call_id.clone()),
+                    voicemail: true,
+                    ..Default::default()
+                };
+                let voice_id = msg.local_id.clone();
+                spawn_local(async move {
+                    send_voice_msg(RightContentType::Friend, voice, msg).await;
+                    attach_voicemail(&voicemail.call_id, voice_id).await;
+                });
+                true
+            }
+            PhoneCallMsg::CloseVoicemail => {
+                self.voicemail = None;
+                true
+            }
+            PhoneCallMsg::SwitchRecording => {
+                if let Some(recording) = self.recording.as_mut() {
+                    recording.recorder.stop();
+                    self.send_control(CallControl::Recording(false));
+                    return true;
+                }
+                let (Some(info), Some(stream), Some(friend)) = (
+                    self.invite_info.as_ref(),
+                    self.stream.as_ref(),
+                    self.call_friend_info.as_ref(),
+                ) else {
+                    return false;
+                };
+                if !info.connected {
+                    return false;
+                }
+                let is_video = info.invite_type == InviteType::Video;
+                let (remote, videos) = if is_video {
+                    let friend_video = self.friend_video_node.cast::<HtmlVideoElement>();
+                    let remote = friend_video.as_ref().and_then(|video| video.src_object());
+                    (remote, friend_video.zip(self.video_node.cast()))
+                } else {
+                    let friend_audio = self.friend_audio_node.cast::<HtmlAudioElement>();
+                    (friend_audio.and_then(|audio| audio.src_object()), None)
+                };
+                let Some(remote) = remote else {
+                    return false;
+                };
+                match CallRecorder::start(
+                    stream,
+                    &remote,
+                    videos,
+                    ctx.link().callback(PhoneCallMsg::Recorded),
+                ) {
+                    Ok(recorder) => {
+                        self.recording = Some(Recording {
+                            recorder,
+                            friend_id: self.peer_id().unwrap_or_default(),
+                            name: friend.name(),
+                            avatar: friend.avatar(),
+                            is_video,
+                        });
+                        self.send_control(CallControl::Recording(true));
+                    }
+                    Err(e) => {
+                        error!("start call recording error: {:?}", e);
+                        Notification::error(Error::js_err(e)).notify();
+                    }
+                }
+                true
+            }
+            PhoneCallMsg::Recorded(file) => {
+                let Some(recording) = self.recording.take() else {
+                    return false;
+                };
+                let friend_id = recording.friend_id.clone();
+                let cached = file.clone();
+                spawn_local(async move {
+                    if let Err(e) = save_recording(&friend_id, &cached).await {
+                        error!("save call recording error: {:?}", e);
+                    }
+                });
+                self.recorded = Some(Recorded {
+                    file,
+                    friend_id: recording.friend_id,
+                    name: recording.name,
+                    avatar: recording.avatar,
+                    is_video: recording.is_video,
+                });
+                true
+            }
+            PhoneCallMsg::SendRecording(as_video) => {
+                let Some(recorded) = self.recorded.take() else {
+                    return false;
+                };
+                let user = AppState::get();
+                let msg = Message {
+                    local_id: nanoid!().into(),
+                    send_id: ctx.props().user_id.clone(),
+                    friend_id: recorded.friend_id,
+                    create_time: chrono::Utc::now().timestamp_millis(),
+                    is_read: 1,
+                    is_self: true,
+                    platform: get_platform(self.is_mobile),
+                    send_status: SendStatus::Sending,
+                    avatar: user.login_user.avatar.clone(),
+                    nickname: user.login_user.name.clone(),
+                    ..Default::default()
+                };
+                spawn_local(send_recording(msg, recorded.file, as_video));
+                true
+            }
+            PhoneCallMsg::CloseRecorded => {
+                self.recorded = None;
+                true
+            }
+            PhoneCallMsg::CallControl(text) => match serde_json::from_str(&text) {
+                Ok(CallControl::Recording(recording)) => {
+                    self.friend_recording = recording;
+                    true
+                }
+                Err(e) => {
+                    error!("parse call control error: {:?}", e);
+                    false
+                }
+            },
+            PhoneCallMsg::SwitchMini => {
+                self.is_mini = !self.is_mini;
+                // the dragged position doesn't fit the other size
+                if let Some(div) = self.wrapper_node.cast::<HtmlDivElement>() {
+                    let _ = div.style().remove_property("top");
+                    let _ = div.style().remove_property("left");
+                }
+                if self.is_mini {
+                    self.open_call_window(ctx);
+                } else {
+                    self.close_call_window();
+                }
+                true
+            }
+            PhoneCallMsg::SwitchPip => {
+                if self.pip {
+                    self.close_pip();
+                    return true;
+                }
+                let Some(video) = self.friend_video_node.cast::<HtmlVideoElement>() else {
+                    return false;
+                };
+                if self.on_leave_pip.is_none() {
+                    let link = ctx.link().clone();
+                    let on_leave = Closure::<dyn FnMut()>::new(move || {
+                        link.send_message(PhoneCallMsg::PipChanged(false));
+                    });
+                    let _ = video.add_event_listener_with_callback(
+                        "leavepictureinpicture",
+                        on_leave.as_ref().unchecked_ref(),
+                    );
+                    self.on_leave_pip = Some(on_leave);
+                }
+                ctx.link().send_future(async move {
+                    match utils::request_pip(&video).await {
+                        Ok(_) => PhoneCallMsg::PipChanged(true),
+                        Err(e) => {
+                            error!("request picture-in-picture error: {:?}", e);
+                            PhoneCallMsg::PipChanged(false)
+                        }
+                    }
+                });
+                false
+            }
+            PhoneCallMsg::PipChanged(pip) => {
+                if self.pip == pip {
+                    return false;
+                }
+                self.pip = pip;
+                if pip {
+                    // the video floats, keep only the controls in the app
+                    if !self.is_mini {
+                        ctx.link().send_message(PhoneCallMsg::SwitchMini);
+                    }
+                    self.set_pip_actions(ctx);
+                } else {
+                    self.clear_pip_actions();
+                }
+                true
+            }
+            PhoneCallMsg::MiniAction(action) => {
+                match action {
+                    // re-render to send the state
+                    CallWindowAction::Ready => return true,
+                    CallWindowAction::SwitchMicrophoneMute => {
+                        ctx.link().send_message(PhoneCallMsg::SwitchMicrophoneMute);
+                    }
+                    CallWindowAction::HangUp => {
+                        let connected =
+                            self.invite_info.as_ref().is_some_and(|info| info.connected);
+                        if connected {
+                            ctx.link().send_message(PhoneCallMsg::HangUpCall);
+                        } else {
+                            ctx.link().send_message(PhoneCallMsg::SendInviteCancel);
+                        }
+                    }
+                    CallWindowAction::Expand => {
+                        if self.is_mini {
+                            ctx.link().send_message(PhoneCallMsg::SwitchMini);
+                        }
+                    }
+                }
+                false
+            }
+            PhoneCallMsg::CallWindowOpened(unlisten) => {
+                match unlisten {
+                    Some(unlisten) => {
+                        self.unlisten_call_action = Some(unlisten);
+                        // the call finished or restored before the window opened
+                        if !self.call_window || !self.is_mini {
+                            self.call_window = true;
+                            self.close_call_window();
+                        }
+                    }
+                    None => {
+                        self.call_window = false;
+                        self.on_call_action = None;
+                    }
+                }
+                true
+            }
             PhoneCallMsg::OnMouseDown(event) => {
                 event.stop_propagation();
                 event.prevent_default();
@@ -888,7 +1439,33 @@ impl Component for PhoneCall {
                 false
             }
             PhoneCallMsg::OnConnect(event) => {
+                // the screen shared by the friend in the audio call
+                let info = self.invite_info.as_ref().unwrap();
+                if info.connected
+                    && info.invite_type == InviteType::Audio
+                    && event.track().kind() == "video"
+                {
+                    let stream: MediaStream = event.streams().get(0).into();
+                    let link = ctx.link().clone();
+                    let on_removed = Closure::wrap(Box::new(move || {
+                        link.send_message(PhoneCallMsg::RemoteScreenEnded);
+                    }) as Box<dyn FnMut()>);
+                    stream.set_onremovetrack(Some(on_removed.as_ref().unchecked_ref()));
+                    self.on_remote_screen_removed = Some(on_removed);
+                    self.show_remote_screen = true;
+                    if let Some(video) = self.remote_screen_node.cast::<HtmlVideoElement>() {
+                        video.set_src_object(Some(&stream));
+                        let _ = video.play();
+                        super::set_speaker(&video);
+                    }
+                    return true;
+                }
                 // truly connected
+                if !self.invite_info.as_ref().unwrap().connected {
+                    if let Some(rtc) = self.rtc.as_mut() {
+                        rtc.start_stats(ctx.link().callback(PhoneCallMsg::StatsUpdated));
+                    }
+                }
                 self.invite_info.as_mut().unwrap().connected = true;
                 self.invite_info.as_mut().unwrap().start_time =
                     chrono::Utc::now().timestamp_millis();
@@ -909,8 +1486,129 @@ impl Component for PhoneCall {
                         let _ = friend_audio.play().expect("friend video play error");
                     }
                 }
+                self.apply_speaker();
                 false
             }
+            PhoneCallMsg::SwitchScreenShare => {
+                if self.screen_stream.is_some() {
+                    ctx.link().send_message(PhoneCallMsg::StopScreenShare);
+                    return false;
+                }
+                if self.rtc.is_none() {
+                    return false;
+                }
+                let link = ctx.link().clone();
+                spawn_local(async move {
+                    match utils::get_display_stream().await {
+                        Ok(stream) => link.send_message(PhoneCallMsg::ScreenShareReady(stream)),
+                        // the user closed the picker
+                        Err(e)
+                            if e.dyn_ref::<web_sys::DomException>()
+                                .is_some_and(|e| e.name() == "NotAllowedError") =>
+                        {
+                            debug!("screen share canceled");
+                        }
+                        Err(e) => Notification::error(Error::js_err(e)).notify(),
+                    }
+                });
+                false
+            }
+            PhoneCallMsg::ScreenShareReady(stream) => {
+                let Some(rtc) = self.rtc.as_mut() else {
+                    // the call finished before the user picked the screen
+                    for track in stream.get_tracks() {
+                        track.unchecked_into::<MediaStreamTrack>().stop();
+                    }
+                    return false;
+                };
+                let Some(track) = stream
+                    .get_video_tracks()
+                    .get(0)
+                    .dyn_into::<MediaStreamTrack>()
+                    .ok()
+                else {
+                    return false;
+                };
+
+                let link = ctx.link().clone();
+                let on_ended = Closure::wrap(Box::new(move || {
+                    link.send_message(PhoneCallMsg::StopScreenShare);
+                }) as Box<dyn FnMut()>);
+                track.set_onended(Some(on_ended.as_ref().unchecked_ref()));
+
+                // swap the camera track in the video call, add a new one in the audio call,
+                // then send a fresh offer to the friend
+                match self.invite_info.as_ref().unwrap().invite_type {
+                    InviteType::Video => {
+                        if let Some(sender) = rtc.video_sender() {
+                            let future = JsFuture::from(sender.replace_track(Some(&track)));
+                            spawn_local(async move {
+                                if let Err(e) = future.await {
+                                    error!("replace track with screen error: {:?}", e);
+                                }
+                            });
+                        }
+                        if let Some(video) = self.video_node.cast::<HtmlVideoElement>() {
+                            video.set_src_object(Some(&stream));
+                        }
+                    }
+                    InviteType::Audio => {
+                        self.screen_sender = Some(rtc.pc().add_track_0(&track, &stream));
+                    }
+                }
+                rtc.renegotiate();
+                self.on_screen_ended = Some(on_ended);
+                self.screen_stream = Some(stream);
+                true
+            }
+            PhoneCallMsg::StopScreenShare => {
+                if self.screen_stream.is_none() {
+                    return false;
+                }
+                let screen_sender = self.screen_sender.take();
+                self.stop_screen_stream();
+                let Some(rtc) = self.rtc.as_mut() else {
+                    return true;
+                };
+                match screen_sender {
+                    // audio call, remove the screen track
+                    Some(sender) => rtc.pc().remove_track(&sender),
+                    // video call, switch back to the camera
+                    None => {
+                        let camera = self.stream.as_ref().and_then(|stream| {
+                            stream
+                                .get_video_tracks()
+                                .get(0)
+                                .dyn_into::<MediaStreamTrack>()
+                                .ok()
+                        });
+                        if let (Some(sender), Some(camera)) = (rtc.video_sender(), camera) {
+                            let future = JsFuture::from(sender.replace_track(Some(&camera)));
+                            spawn_local(async move {
+                                if let Err(e) = future.await {
+                                    error!("replace track with camera error: {:?}", e);
+                                }
+                            });
+                        }
+                        if let Some(video) = self.video_node.cast::<HtmlVideoElement>() {
+                            video.set_src_object(self.stream.as_ref());
+                        }
+                    }
+                }
+                rtc.renegotiate();
+                true
+            }
+            PhoneCallMsg::RemoteScreenEnded => {
+                if !self.show_remote_screen {
+                    return false;
+                }
+                if let Some(video) = self.remote_screen_node.cast::<HtmlVideoElement>() {
+                    video.set_src_object(None);
+                }
+                self.on_remote_screen_removed = None;
+                self.show_remote_screen = false;
+                true
+            }
             PhoneCallMsg::TickCallDuration => {
                 self.call_duration += 1;
                 true
@@ -923,6 +1621,13 @@ impl Component for PhoneCall {
         }
     }
 
+    fn rendered(&mut self, _ctx: &Context<Self>, _first_render: bool) {
+        if self.pip {
+            utils::set_microphone_active(!self.microphone_mute);
+        }
+        self.sync_call_window();
+    }
+
     fn view(&self, ctx: &Context<Self>) -> Html {
         // todo error notification
         if self.conn_state == ConnectionState::Error {
@@ -954,7 +1659,7 @@ impl Component for PhoneCall {
             video_or_audio_notify = html! {
                 <div class="video-or-audio-notify box-shadow" >
                     // 头像。昵称。挂断。接听
-                    <img alt="avatar" src={utils::get_avatar_url(&info.avatar())}/>
+                    <AvatarImg avatar={info.avatar()} />
                     <span class="video-or-audio-notify-text" >
                         {format!("{} {}", info.name(), tr!(self.i18n, INCOMING_CALL))}
                     </span>
@@ -973,14 +1678,12 @@ impl Component for PhoneCall {
         let mut video = html!();
         let mut audio = html!();
 
-        let (hangup_icon, duration) = match self.conn_state {
-            ConnectionState::Waiting => (html!(<HangupInNotifyIcon/>), tr!(self.i18n, WAITING)),
-            ConnectionState::Connecting => {
-                (html!(<HangUpLoadingIcon/>), tr!(self.i18n, CONNECTING))
-            }
-            ConnectionState::Connected => (html!(<HangupInNotifyIcon/>), self.format_duration()),
-            ConnectionState::Error => (html!(<HangupInNotifyIcon/>), tr!(self.i18n, CONN_ERROR)),
+        let hangup_icon = if self.conn_state == ConnectionState::Connecting {
+            html!(<HangUpLoadingIcon/>)
+        } else {
+            html!(<HangupInNotifyIcon/>)
         };
+        let duration = self.call_status();
 
         if self.show_video || self.show_audio {
             if let Some(info) = self.invite_info.as_ref() {
@@ -1002,9 +1705,83 @@ impl Component for PhoneCall {
                     html!(<MicrophoneIcon />)
                 };
 
+                let reconnecting = if self.reconnecting {
+                    html!(<div class="call-reconnecting">{tr!(self.i18n, RECONNECTING)}</div>)
+                } else {
+                    html!()
+                };
+
+                let stats = self.stats_html(ctx);
+
                 let volume_click = ctx.link().callback(|_| PhoneCallMsg::SwitchVolume);
                 let microphone_click = ctx.link().callback(|_| PhoneCallMsg::SwitchMicrophoneMute);
 
+                // screen sharing is available after the call connected
+                let mut share_screen = html!();
+                let mut sharing_tip = html!();
+                if info.connected && !self.is_mobile {
+                    let share_class = if self.screen_stream.is_some() {
+                        sharing_tip = html!(
+                            <div class="sharing-screen-tip">{tr!(self.i18n, SHARING_SCREEN)}</div>
+                        );
+                        "share-screen share-screen-active"
+                    } else {
+                        "share-screen"
+                    };
+                    share_screen = html!(
+                        <span class={share_class}
+                            title={tr!(self.i18n, SHARE_SCREEN)}
+                            onclick={ctx.link().callback(|_| PhoneCallMsg::SwitchScreenShare)} >
+                            <ScreenShareIcon />
+                        </span>
+                    );
+                }
+
+                // the friend is told when we start or stop recording
+                let mut record = html!();
+                if info.connected {
+                    let record_class = if self.is_recording() {
+                        "call-record call-record-active"
+                    } else {
+                        "call-record"
+                    };
+                    record = html!(
+                        <span class={record_class} title={tr!(self.i18n, RECORD_CALL)}
+                            onclick={ctx.link().callback(|_| PhoneCallMsg::SwitchRecording)} >
+                            <CallRecordIcon />
+                        </span>
+                    );
+                }
+                let recording_tip = if self.friend_recording {
+                    html!(<div class="call-recording-tip">{tr!(self.i18n, FRIEND_RECORDING)}</div>)
+                } else {
+                    html!()
+                };
+
+                let devices = self.devices_html(ctx);
+
+                let mini = html!(
+                    <span class="call-mini-switch" title={tr!(self.i18n, MINI_WINDOW)}
+                        onclick={ctx.link().callback(|_| PhoneCallMsg::SwitchMini)} >
+                        <MiniWindowIcon />
+                    </span>
+                );
+                // float the friend video above the other windows
+                let mut pip = html!();
+                if self.show_video && info.connected && utils::is_pip_enabled() {
+                    let pip_class = if self.pip {
+                        "call-pip call-pip-active"
+                    } else {
+                        "call-pip"
+                    };
+                    pip = html!(
+                        <span class={pip_class} title={tr!(self.i18n, PIP)}
+                            onclick={ctx.link().callback(|_| PhoneCallMsg::SwitchPip)} >
+                            <PipIcon />
+                        </span>
+                    );
+                }
+
                 let mut ontouchstart = None;
                 let mut ontouchmove = None;
                 let mut ontouchend = None;
@@ -1029,6 +1806,11 @@ impl Component for PhoneCall {
                     )
                 };
 
+                // only the controls are left, the user goes on chatting
+                if self.is_mini {
+                    class = "box-shadow call-mini";
+                }
+
                 let call_duration_class = if self.is_zoom {
                     "call-duration-zoom"
                 } else {
@@ -1050,6 +1832,11 @@ impl Component for PhoneCall {
                             {zoom}
                             <video class="video-self" style={self_video_style} ref={self.video_node.clone()} playsinline={true} />
                             <video class="video-friend" ref={self.friend_video_node.clone()}  playsinline={true} />
+                            {sharing_tip}
+                            {recording_tip}
+                            {reconnecting}
+                            {stats}
+                            {devices}
                             <div class="call-duration">{duration}</div>
                             <div class="call-operate" >
                                 <span class="switch-microphone" onclick={microphone_click} >
@@ -1061,6 +1848,10 @@ impl Component for PhoneCall {
                                 <span class="call-volume" onclick={volume_click} >
                                     {volume}
                                 </span>
+                                {share_screen}
+                                {record}
+                                {pip}
+                                {mini}
                             </div>
                         </div>
                     };
@@ -1087,8 +1878,15 @@ impl Component for PhoneCall {
                             {ontouchmove}
                             {ontouchend}>
                             {zoom}
-                            <img class="audio-avatar" alt="avatar" src={utils::get_avatar_url(&avatar)} />
+                            <AvatarImg avatar={avatar.clone()} class="audio-avatar" />
                             <audio ref={self.friend_audio_node.clone()}/>
+                            <video class="remote-screen" ref={self.remote_screen_node.clone()}
+                                hidden={!self.show_remote_screen} muted={true} playsinline={true} />
+                            {sharing_tip}
+                            {recording_tip}
+                            {reconnecting}
+                            {stats}
+                            {devices}
                             <div class={call_duration_class}>{duration}</div>
                             <div class="call-operate" >
                                     <span class="switch-microphone" onclick={microphone_click} >
@@ -1100,6 +1898,9 @@ impl Component for PhoneCall {
                                     <span class="call-volume" onclick={volume_click} >
                                         {volume}
                                     </span>
+                                    {share_screen}
+                                    {record}
+                                    {mini}
                                 </div>
                         </div>
                     }
@@ -1111,7 +1912,310 @@ impl Component for PhoneCall {
                 {video}
                 {audio}
                 {video_or_audio_notify}
+                {self.call_waiting_html(ctx)}
+                {self.voicemail_html(ctx)}
+                {self.recorded_html(ctx)}
             </>
         }
     }
 }
+
+impl PhoneCall {
+    fn is_waiting_from(&self, send_id: &AttrValue) -> bool {
+        self.waiting_invite
+            .as_ref()
+            .is_some_and(|invite| &invite.send_id == send_id)
+    }
+
+    /// reject the invitation because we are in another call
+    fn reply_busy(&self, ctx: &Context<Self>, invite: InviteMsg) {
+        let local_id = AttrValue::from(nanoid!());
+        let send_id = ctx.props().user_id.clone();
+        let friend_id = invite.send_id;
+        let create_time = chrono::Utc::now().timestamp_millis();
+        let invite_type = invite.invite_type;
+        let content_type = match invite_type {
+            InviteType::Video => ContentType::VideoCall,
+            InviteType::Audio => ContentType::AudioCall,
+        };
+        ctx.link().send_future(async move {
+            // it is a missed call for us
+            let msg = Message {
+                local_id: local_id.clone(),
+                send_id: send_id.clone(),
+                friend_id: friend_id.clone(),
+                content_type,
+                content: AttrValue::from("not_answer"),
+                create_time,
+                is_read: 1,
+                is_self: true,
+                ..Default::default()
+            };
+            let _ = db::db_ins()
+                .messages
+                .add_message(&msg)
+                .await
+                .map_err(|err| error!("消息入库失败:{:?}", err));
+            save_call_log(CallLog::new(
+                &msg,
+                CallDirection::Incoming,
+                CallOutcome::Busy,
+            ));
+
+            PhoneCallMsg::SendMessage(SingleCall::InviteAnswer(InviteAnswerMsg {
+                local_id,
+                send_id,
+                friend_id,
+                create_time,
+                invite_type,
+                busy: true,
+                is_self: true,
+                ..Default::default()
+            }))
+        });
+    }
+
+    fn voicemail_html(&self, ctx: &Context<Self>) -> Html {
+        let Some(voicemail) = self.voicemail.as_ref() else {
+            return html!();
+        };
+        if self.show_video || self.show_audio || self.show_notify {
+            return html!();
+        }
+        let close = ctx.link().callback(|_| PhoneCallMsg::CloseVoicemail);
+        let operate = if voicemail.recording {
+            html!(<Recorder send_voice={ctx.link().callback(PhoneCallMsg::SendVoicemail)} />)
+        } else {
+            html! {
+                <div class="video-audio-notify-operate">
+                    <button class="btn" onclick={ctx.link().callback(|_| PhoneCallMsg::RecordVoicemail)}>
+                        {tr!(self.i18n, LEAVE_VOICEMAIL)}
+                    </button>
+                </div>
+            }
+        };
+        html! {
+            <div class="video-or-audio-notify voicemail-notify box-shadow">
+                <AvatarImg avatar={voicemail.avatar.clone()} />
+                <span class="video-or-audio-notify-text">
+                    {format!("{} {}", voicemail.name, tr!(self.i18n, NOT_AVAILABLE))}
+                </span>
+                {operate}
+                <span class="voicemail-close pointer" onclick={close}>
+                    <CloseIcon />
+                </span>
+            </div>
+        }
+    }
+
+    fn recorded_html(&self, ctx: &Context<Self>) -> Html {
+        let Some(recorded) = self.recorded.as_ref() else {
+            return html!();
+        };
+        if self.show_video || self.show_audio || self.show_notify {
+            return html!();
+        }
+        // the audio recording can only be sent as a file
+        let send_video = if recorded.is_video {
+            html! {
+                <button class="btn" onclick={ctx.link().callback(|_| PhoneCallMsg::SendRecording(true))}>
+                    {tr!(self.i18n, SEND_AS_VIDEO)}
+                </button>
+            }
+        } else {
+            html!()
+        };
+        html! {
+            <div class="video-or-audio-notify voicemail-notify box-shadow">
+                <AvatarImg avatar={recorded.avatar.clone()} />
+                <span class="video-or-audio-notify-text">
+                    {format!("{} {}", recorded.name, tr!(self.i18n, CALL_RECORDED))}
+                </span>
+                <div class="video-audio-notify-operate">
+                    {send_video}
+                    <button class="btn" onclick={ctx.link().callback(|_| PhoneCallMsg::SendRecording(false))}>
+                        {tr!(self.i18n, SEND_AS_FILE)}
+                    </button>
+                </div>
+                <span class="voicemail-close pointer" onclick={ctx.link().callback(|_| PhoneCallMsg::CloseRecorded)}>
+                    <CloseIcon />
+                </span>
+            </div>
+        }
+    }
+
+    fn call_waiting_html(&self, ctx: &Context<Self>) -> Html {
+        let (Some(invite), Some(info)) = (
+            self.waiting_invite.as_ref(),
+            self.waiting_friend_info.as_ref(),
+        ) else {
+            return html!();
+        };
+        let answer_icon = match invite.invite_type {
+            InviteType::Video => html!(<VideoRecordIcon />),
+            InviteType::Audio => html!(<AnswerPhoneIcon/>),
+        };
+        html! {
+            <div class="video-or-audio-notify call-waiting-notify box-shadow" >
+                <AvatarImg avatar={info.avatar()} />
+                <span class="video-or-audio-notify-text" >
+                    {format!("{} {}", tr!(self.i18n, CALL_WAITING), info.name())}
+                </span>
+                <div class="video-audio-notify-operate" >
+                    <span title={tr!(self.i18n, DECLINE)}
+                        onclick={ctx.link().callback(|_| PhoneCallMsg::DeclineWaiting)}>
+                        <HangupInNotifyIcon />
+                    </span>
+                    <span title={tr!(self.i18n, END_AND_ACCEPT)}
+                        onclick={ctx.link().callback(|_| PhoneCallMsg::AcceptWaiting)}>
+                        {answer_icon}
+                    </span>
+                </div>
+            </div>
+        }
+    }
+
+    /// switch the camera, microphone and speaker during the call
+    fn devices_html(&self, ctx: &Context<Self>) -> Html {
+        if self.rtc.is_none() {
+            return html!();
+        }
+        let panel = if self.show_devices {
+            let devices = MediaDeviceState::get();
+            let camera = match self.invite_info.as_ref().map(|info| &info.invite_type) {
+                Some(InviteType::Video) => html! {
+                    <DevicePicker
+                        kind={MediaDeviceKind::Videoinput}
+                        value={AttrValue::from(devices.camera.clone())}
+                        default_label={tr!(self.i18n, DEFAULT_DEVICE)}
+                        name={tr!(self.i18n, CAMERA)}
+                        onchange={ctx.link().callback(PhoneCallMsg::SwitchCamera)} />
+                },
+                _ => html!(),
+            };
+            html! {
+                <div class="call-devices-panel">
+                    <DevicePicker
+                        kind={MediaDeviceKind::Audioinput}
+                        value={AttrValue::from(devices.microphone.clone())}
+                        default_label={tr!(self.i18n, DEFAULT_DEVICE)}
+                        name={tr!(self.i18n, MICROPHONE)}
+                        onchange={ctx.link().callback(PhoneCallMsg::SwitchMicrophone)} />
+                    <DevicePicker
+                        kind={MediaDeviceKind::Audiooutput}
+                        value={AttrValue::from(devices.speaker.clone())}
+                        default_label={tr!(self.i18n, DEFAULT_DEVICE)}
+                        name={tr!(self.i18n, SPEAKER)}
+                        onchange={ctx.link().callback(PhoneCallMsg::SwitchSpeaker)} />
+                    {camera}
+                </div>
+            }
+        } else {
+            html!()
+        };
+        html! {
+            <div class="call-devices">
+                <span title={tr!(self.i18n, DEVICES)}
+                    onclick={ctx.link().callback(|_| PhoneCallMsg::SwitchDevicePanel)}>
+                    <SettingIcon />
+                </span>
+                {panel}
+            </div>
+        }
+    }
+
+    /// signal strength bars, click to show the stats overlay
+    fn stats_html(&self, ctx: &Context<Self>) -> Html {
+        let Some(stats) = self.stats.as_ref() else {
+            return html!();
+        };
+        let level = match stats.level() {
+            SignalLevel::Good => "signal-good",
+            SignalLevel::Fair => "signal-fair",
+            SignalLevel::Poor => "signal-poor",
+        };
+        let overlay = if self.show_stats {
+            let mut resolution = html!();
+            if stats.width > 0 {
+                resolution = html! {
+                    <>
+                        <span>{"Resolution"}</span>
+                        <span>{format!("{}x{}", stats.width, stats.height)}</span>
+                        <span>{"Frame rate"}</span>
+                        <span>{format!("{:.0} fps", stats.frame_rate)}</span>
+                    </>
+                };
+            }
+            html! {
+                <div class="call-stats">
+                    <span>{"RTT"}</span>
+                    <span>{format!("{:.0} ms", stats.rtt)}</span>
+                    <span>{"Jitter"}</span>
+                    <span>{format!("{:.0} ms", stats.jitter)}</span>
+                    <span>{"Packet loss"}</span>
+                    <span>{format!("{:.1} %", stats.packet_loss)}</span>
+                    <span>{"Bitrate"}</span>
+                    <span>{format!("{:.0} kbps", stats.bitrate)}</span>
+                    {resolution}
+                    <span>{"Candidate"}</span>
+                    <span>{stats.candidate_type.to_string()}</span>
+                </div>
+            }
+        } else {
+            html!()
+        };
+        html! {
+            <>
+                <div class={classes!("call-signal", level)}
+                    onclick={ctx.link().callback(|_| PhoneCallMsg::SwitchStats)}>
+                    <span></span>
+                    <span></span>
+                    <span></span>
+                </div>
+                {overlay}
+            </>
+        }
+    }
+}
+
+/// answer the offer sent by the friend after the tracks of the connected call changed
+async fn answer_renegotiation(
+    pc: RtcPeerConnection,
+    ws: Rc<RefCell<WebSocketManager>>,
+    sdp: AttrValue,
+    send_id: AttrValue,
+    friend_id: AttrValue,
+    platform: i32,
+) {
+    let mut description = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
+    description.sdp(&sdp);
+    if let Err(e) = JsFuture::from(pc.set_remote_description(&description)).await {
+        error!("renegotiation set remote desc failed: {:?}", e);
+        return;
+    }
+    let answer = match JsFuture::from(pc.create_answer()).await {
+        Ok(answer) => RtcSessionDescription::from(answer),
+        Err(e) => {
+            error!("renegotiation create answer failed: {:?}", e);
+            return;
+        }
+    };
+    let mut desc = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+    desc.sdp(&answer.sdp());
+    if let Err(e) = JsFuture::from(pc.set_local_description(&desc)).await {
+        error!("renegotiation set local desc failed: {:?}", e);
+        return;
+    }
+    if let Err(e) = ws
+        .borrow()
+        .send_message(Msg::SingleCall(SingleCall::Agree(Agree {
+            sdp: Some(answer.sdp()),
+            send_id,
+            friend_id,
+            create_time: chrono::Utc::now().timestamp_millis(),
+            platform,
+        })))
+    {
+        error!("send message error: {:?}", e);
+    }
+}
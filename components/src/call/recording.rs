@@ -0,0 +1,258 @@
+use gloo::timers::callback::Interval;
+use gloo::utils::document;
+use js_sys::{Array, Uint8Array};
+use log::error;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    AudioContext, BlobEvent, CanvasRenderingContext2d, File, FilePropertyBag, HtmlCanvasElement,
+    HtmlVideoElement, MediaRecorder, MediaRecorderOptions, MediaStream, MediaStreamTrack,
+    RecordingState,
+};
+use yew::{AttrValue, Callback};
+use yewdux::Dispatch;
+
+use sandcat_sdk::db;
+use sandcat_sdk::error::Result;
+use sandcat_sdk::model::attachment::{Attachment, AttachmentKind};
+use sandcat_sdk::model::file_msg::{FileExt, FileMsg};
+use sandcat_sdk::model::message::Message;
+use sandcat_sdk::model::{ContentType, RightContentType};
+use sandcat_sdk::state::SendAudioMsgState;
+
+use crate::right::sender::read_file_msg;
+use crate::right::upload::start_upload;
+
+/// the recording is kept in the attachment cache by this prefix and the file name
+const RECORDING_PREFIX: &str = "recording-";
+
+/// the first supported type is used
+const VIDEO_MIME_TYPES: [&str; 3] = ["video/webm;codecs=vp8,opus", "video/webm", "video/mp4"];
+const AUDIO_MIME_TYPES: [&str; 3] = ["audio/webm;codecs=opus", "audio/webm", "audio/mp4"];
+/// the recorded data is collected by this interval, in milliseconds
+const TIME_SLICE: i32 = 1000;
+const FRAME_RATE: f64 = 25.0;
+/// the size of the recording before the friend video is loaded
+const DEFAULT_SIZE: (u32, u32) = (640, 480);
+/// the local video is drawn in the corner by this part of the width
+const LOCAL_VIDEO_SCALE: f64 = 0.25;
+const LOCAL_VIDEO_MARGIN: f64 = 12.0;
+
+/// records both sides of the call, the audio is mixed by an audio context;
+/// in the video call the local video is drawn in the corner of the friend video
+pub struct CallRecorder {
+    recorder: MediaRecorder,
+    audio_context: AudioContext,
+    /// the canvas the videos are drawn to, only in the video call
+    canvas_stream: Option<MediaStream>,
+    draw_timer: Option<Interval>,
+    _on_data_available: Closure<dyn FnMut(BlobEvent)>,
+    _on_stop: Closure<dyn FnMut()>,
+}
+
+impl CallRecorder {
+    /// `videos` are the friend and the local video elements of the video call,
+    /// the recorded file is emitted after the recorder stopped
+    pub fn start(
+        local: &MediaStream,
+        remote: &MediaStream,
+        videos: Option<(HtmlVideoElement, HtmlVideoElement)>,
+        on_recorded: Callback<File>,
+    ) -> std::result::Result<Self, JsValue> {
+        let audio_context = AudioContext::new()?;
+        let destination = audio_context.create_media_stream_destination()?;
+        for stream in [local, remote] {
+            // the source can't be created from the stream without audio
+            if stream.get_audio_tracks().length() == 0 {
+                continue;
+            }
+            audio_context
+                .create_media_stream_source(stream)?
+                .connect_with_audio_node(&destination)?;
+        }
+        let tracks = destination.stream().get_audio_tracks();
+
+        let mut canvas_stream = None;
+        let mut draw_timer = None;
+        let mime_types = match videos {
+            Some((friend, own)) => {
+                let (stream, timer) = draw_videos(friend, own)?;
+                for track in stream.get_video_tracks() {
+                    tracks.push(&track);
+                }
+                canvas_stream = Some(stream);
+                draw_timer = Some(timer);
+                VIDEO_MIME_TYPES
+            }
+            None => AUDIO_MIME_TYPES,
+        };
+        let mime_type = mime_types
+            .into_iter()
+            .find(|t| MediaRecorder::is_type_supported(t))
+            .unwrap_or(mime_types[1]);
+
+        let stream = MediaStream::new_with_tracks(&tracks)?;
+        let mut options = MediaRecorderOptions::new();
+        options.mime_type(mime_type);
+        let recorder =
+            MediaRecorder::new_with_media_stream_and_media_recorder_options(&stream, &options)?;
+
+        let chunks = Array::new();
+        let chunks_clone = chunks.clone();
+        let on_data_available = Closure::wrap(Box::new(move |event: BlobEvent| {
+            if let Some(blob) = event.data() {
+                chunks_clone.push(&blob);
+            }
+        }) as Box<dyn FnMut(BlobEvent)>);
+        let on_stop = Closure::wrap(Box::new(move || match to_file(&chunks, mime_type) {
+            Ok(file) => on_recorded.emit(file),
+            Err(e) => error!("create call recording error: {:?}", e),
+        }) as Box<dyn FnMut()>);
+        recorder.set_ondataavailable(Some(on_data_available.as_ref().unchecked_ref()));
+        recorder.set_onstop(Some(on_stop.as_ref().unchecked_ref()));
+        recorder.start_with_time_slice(TIME_SLICE)?;
+
+        Ok(Self {
+            recorder,
+            audio_context,
+            canvas_stream,
+            draw_timer,
+            _on_data_available: on_data_available,
+            _on_stop: on_stop,
+        })
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.state() == RecordingState::Recording
+    }
+
+    /// keep the recorder until the file is emitted, the closures are called after stopping
+    pub fn stop(&mut self) {
+        if !self.is_recording() {
+            return;
+        }
+        if let Err(e) = self.recorder.stop() {
+            error!("stop call recorder error: {:?}", e);
+        }
+        self.draw_timer = None;
+        if let Some(stream) = self.canvas_stream.take() {
+            for track in stream.get_tracks() {
+                if let Ok(track) = track.dyn_into::<MediaStreamTrack>() {
+                    track.stop();
+                }
+            }
+        }
+        let _ = self.audio_context.close();
+    }
+}
+
+/// the recording waits to be sent after the call
+pub struct Recorded {
+    pub file: File,
+    pub friend_id: AttrValue,
+    pub name: AttrValue,
+    pub avatar: AttrValue,
+    pub is_video: bool,
+}
+
+/// put the recording into the attachment cache of the conversation
+pub async fn save_recording(friend_id: &str, file: &File) -> Result<()> {
+    let buffer = JsFuture::from(file.array_buffer()).await?;
+    let kind = if file.type_().starts_with("video/") {
+        AttachmentKind::Video
+    } else {
+        AttachmentKind::File
+    };
+    let attachment = Attachment::new(
+        format!("{RECORDING_PREFIX}{}", file.name()),
+        friend_id.to_string(),
+        kind,
+        file.type_(),
+        Uint8Array::new(&buffer).to_vec(),
+    );
+    db::db_ins().attachments.put(&attachment).await
+}
+
+/// send the recording like the file picked in the sender,
+/// `msg` is filled with the content of the file
+pub async fn send_recording(mut msg: Message, file: File, as_video: bool) {
+    let file = if as_video {
+        let (file_msg, content_type, file_content, file) =
+            read_file_msg(file, &msg.friend_id).await;
+        msg.content = file_msg.to_string().into();
+        msg.content_type = content_type;
+        msg.file_content = file_content.as_string().unwrap_or_default().into();
+        file
+    } else {
+        let name = file.name();
+        let ext = name
+            .split('.')
+            .last()
+            .unwrap_or("")
+            .parse::<FileExt>()
+            .unwrap_or_default();
+        let file_msg = FileMsg::new(name, String::new(), file.size() as usize, ext);
+        msg.content = file_msg.to_string().into();
+        msg.content_type = ContentType::File;
+        file
+    };
+
+    if let Err(err) = db::db_ins().messages.add_message(&msg).await {
+        error!("save recording message error: {:?}", err);
+        return;
+    }
+    // the pending message is inserted into the list like the voice message
+    Dispatch::<SendAudioMsgState>::global().set(SendAudioMsgState { msg: msg.clone() });
+    start_upload(RightContentType::Friend, msg, file);
+}
+
+/// draw the friend video and the local video to a canvas and capture it
+fn draw_videos(
+    friend: HtmlVideoElement,
+    own: HtmlVideoElement,
+) -> std::result::Result<(MediaStream, Interval), JsValue> {
+    let canvas = document()
+        .create_element("canvas")?
+        .dyn_into::<HtmlCanvasElement>()?;
+    let (width, height) = match (friend.video_width(), friend.video_height()) {
+        (0, _) | (_, 0) => DEFAULT_SIZE,
+        size => size,
+    };
+    canvas.set_width(width);
+    canvas.set_height(height);
+    let context = canvas
+        .get_context("2d")?
+        .ok_or_else(|| JsValue::from_str("get canvas context error"))?
+        .dyn_into::<CanvasRenderingContext2d>()?;
+
+    let (w, h) = (width as f64, height as f64);
+    let timer = Interval::new((1000.0 / FRAME_RATE) as u32, move || {
+        let _ = context.draw_image_with_html_video_element_and_dw_and_dh(&friend, 0.0, 0.0, w, h);
+        let (own_width, own_height) = (own.video_width(), own.video_height());
+        if own_width == 0 || own_height == 0 {
+            return;
+        }
+        let dw = w * LOCAL_VIDEO_SCALE;
+        let dh = dw * own_height as f64 / own_width as f64;
+        let _ = context.draw_image_with_html_video_element_and_dw_and_dh(
+            &own,
+            w - dw - LOCAL_VIDEO_MARGIN,
+            h - dh - LOCAL_VIDEO_MARGIN,
+            dw,
+            dh,
+        );
+    });
+    let stream = canvas.capture_stream_with_frame_request_rate(FRAME_RATE)?;
+    Ok((stream, timer))
+}
+
+fn to_file(chunks: &Array, mime_type: &str) -> std::result::Result<File, JsValue> {
+    // drop the codecs part
+    let mime = mime_type.split(';').next().unwrap_or(mime_type);
+    let ext = if mime.ends_with("mp4") { "mp4" } else { "webm" };
+    let mut options = FilePropertyBag::new();
+    options.type_(mime);
+    let name = format!("call_{}.{}", chrono::Utc::now().timestamp_millis(), ext);
+    File::new_with_blob_sequence_and_options(chunks, &name, &options)
+}
@@ -68,6 +68,8 @@ pub const SEND_MOBILE: &str = "send_mobile";
 pub const PRESS: &str = "press";
 pub const RECORD: &str = "record";
 pub const STOP: &str = "stop";
+pub const HOLD_TO_RECORD: &str = "hold_to_record";
+pub const RETAKE: &str = "retake";
 pub const DEL_FRIEND: &str = "del_friend";
 pub const DISMISS: &str = "dismiss";
 pub const QUIT: &str = "quit";
@@ -95,3 +97,56 @@ pub const STREAM_ERROR: &str = "stream_error";
 pub const OTHER_ERROR: &str = "other_error";
 pub const UNKNOW_ERROR: &str = "unkonw_error";
 pub const CALL_BUSY: &str = "busy";
+pub const GROUP_CALL_INVITE: &str = "group_call_invite";
+pub const CALL_FULL: &str = "call_full";
+pub const SHARE_SCREEN: &str = "share_screen";
+pub const SHARING_SCREEN: &str = "sharing_screen";
+pub const RECONNECTING: &str = "reconnecting";
+pub const USER_BUSY: &str = "user_busy";
+pub const CALL_WAITING: &str = "call_waiting";
+pub const DECLINE: &str = "decline";
+pub const END_AND_ACCEPT: &str = "end_and_accept";
+pub const STORAGE: &str = "storage";
+pub const CACHE_QUOTA: &str = "cache_quota";
+pub const CLEAR_CACHE: &str = "clear_cache";
+pub const CLEAR_ALL_CACHE: &str = "clear_all_cache";
+pub const AVATARS: &str = "avatars";
+pub const ICE_SERVER: &str = "ice_server";
+pub const ICE_URLS: &str = "ice_urls";
+pub const ICE_USERNAME: &str = "ice_username";
+pub const ICE_CREDENTIAL: &str = "ice_credential";
+pub const DEVICES: &str = "devices";
+pub const MICROPHONE: &str = "microphone";
+pub const CAMERA: &str = "camera";
+pub const SPEAKER: &str = "speaker";
+pub const DEFAULT_DEVICE: &str = "default_device";
+pub const TEST_DEVICE: &str = "test_device";
+pub const STOP_TEST: &str = "stop_test";
+pub const DOWNLOAD: &str = "download";
+pub const ROTATE: &str = "rotate";
+pub const ZOOM_RESET: &str = "zoom_reset";
+pub const MEDIA: &str = "media";
+pub const FILES: &str = "files";
+pub const LINKS: &str = "links";
+pub const NO_SHARED: &str = "no_shared";
+pub const CALLS: &str = "calls";
+pub const NO_CALLS: &str = "no_calls";
+pub const INCOMING: &str = "incoming";
+pub const OUTGOING: &str = "outgoing";
+pub const MISSED: &str = "missed";
+pub const DECLINED: &str = "declined";
+pub const CANCELED: &str = "canceled";
+pub const NO_ANSWER: &str = "no_answer";
+pub const CALL_BACK: &str = "call_back";
+pub const LEAVE_VOICEMAIL: &str = "leave_voicemail";
+pub const NOT_AVAILABLE: &str = "not_available";
+pub const VOICEMAIL: &str = "voicemail";
+pub const MINI_WINDOW: &str = "mini_window";
+pub const PIP: &str = "pip";
+pub const SEND_DIRECTLY: &str = "send_directly";
+pub const RECORD_CALL: &str = "record_call";
+pub const FRIEND_RECORDING: &str = "friend_recording";
+pub const CALL_RECORDED: &str = "call_recorded";
+pub const SEND_AS_VIDEO: &str = "send_as_video";
+pub const SEND_AS_FILE: &str = "send_as_file";
+pub const SYNCING: &str = "syncing";
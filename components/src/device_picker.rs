@@ -0,0 +1,123 @@
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlSelectElement, MediaDeviceKind};
+use yew::prelude::*;
+
+/// select one of the cameras, microphones or speakers,
+/// the empty value stands for the default device
+pub struct DevicePicker {
+    /// device id with the label
+    devices: Vec<(String, String)>,
+}
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct DevicePickerProps {
+    pub kind: MediaDeviceKind,
+    /// the selected device id
+    pub value: AttrValue,
+    /// label of the default option
+    pub default_label: AttrValue,
+    /// used to name the devices without label, like "Camera 1"
+    pub name: AttrValue,
+    pub onchange: Callback<String>,
+}
+
+pub enum DevicePickerMsg {
+    /// list the devices again, the labels are empty before the permission is granted
+    Enumerate,
+    Enumerated(Vec<(String, String)>),
+    Change(Event),
+}
+
+impl Component for DevicePicker {
+    type Message = DevicePickerMsg;
+    type Properties = DevicePickerProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_message(DevicePickerMsg::Enumerate);
+        Self {
+            devices: Vec::new(),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            DevicePickerMsg::Enumerate => {
+                let kind = ctx.props().kind;
+                let name = ctx.props().name.clone();
+                ctx.link().send_future(async move {
+                    let devices = match utils::enumerate_devices(kind).await {
+                        Ok(devices) => devices,
+                        Err(e) => {
+                            log::warn!("enumerate devices error: {:?}", e);
+                            Vec::new()
+                        }
+                    };
+                    let devices = devices
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, device)| {
+                            let mut label = device.label();
+                            if label.is_empty() {
+                                label = format!("{} {}", name, i + 1);
+                            }
+                            (device.device_id(), label)
+                        })
+                        .collect();
+                    DevicePickerMsg::Enumerated(devices)
+                });
+                false
+            }
+            DevicePickerMsg::Enumerated(devices) => {
+                if self.devices == devices {
+                    return false;
+                }
+                self.devices = devices;
+                true
+            }
+            DevicePickerMsg::Change(event) => {
+                let select = event
+                    .target()
+                    .unwrap()
+                    .dyn_into::<HtmlSelectElement>()
+                    .unwrap();
+                ctx.props().onchange.emit(select.value());
+                false
+            }
+        }
+    }
+
+    fn changed(&mut self, ctx: &Context<Self>, old_props: &Self::Properties) -> bool {
+        // the labels may be available after the parent got a stream
+        if ctx.props().value != old_props.value {
+            ctx.link().send_message(DevicePickerMsg::Enumerate);
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let props = ctx.props();
+        let options = self
+            .devices
+            .iter()
+            // the browser lists the default device with the id "default" in chrome
+            .filter(|(id, _)| id != "default" && !id.is_empty())
+            .map(|(id, label)| {
+                html! {
+                    <option value={id.clone()} selected={props.value.as_str() == id}>
+                        {label}
+                    </option>
+                }
+            })
+            .collect::<Html>();
+        html! {
+            <select class="device-picker"
+                onchange={ctx.link().callback(DevicePickerMsg::Change)}
+                onfocus={ctx.link().callback(|_| DevicePickerMsg::Enumerate)}>
+                <option value="" selected={props.value.is_empty()}>
+                    {props.default_label.clone()}
+                </option>
+                {options}
+            </select>
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use std::rc::Rc;
+
+use chrono::TimeZone;
+use fluent::{FluentBundle, FluentResource};
+use indexmap::IndexMap;
+use log::error;
+use nanoid::nanoid;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yewdux::Dispatch;
+
+use i18n::{en_us, zh_cn, LanguageType};
+use icons::{PhoneIcon, VideoIcon};
+use sandcat_sdk::db;
+use sandcat_sdk::model::call_log::{CallDirection, CallLog, CallOutcome};
+use sandcat_sdk::model::friend::Friend;
+use sandcat_sdk::model::message::{InviteMsg, InviteType};
+use sandcat_sdk::model::ComponentType;
+use sandcat_sdk::state::{
+    AppState, CallLogState, ComponentTypeState, I18nState, MobileState, Notify, SendCallState,
+    UnreadState,
+};
+use utils::tr;
+
+use crate::avatar_img::AvatarImg;
+use crate::constant::{
+    CALLS, CALL_BACK, CALL_BUSY, CANCELED, DECLINED, INCOMING, MISSED, NO_ANSWER, NO_CALLS,
+    OUTGOING, VIDEO_CALL, VOICEMAIL, VOICE_CALL,
+};
+use crate::get_platform;
+
+/// the count of the call logs to show
+const CALL_LOG_LIMIT: usize = 100;
+
+#[derive(Properties, PartialEq, Debug)]
+pub struct CallsProps {
+    pub user_id: AttrValue,
+}
+
+/// the call logs of the single call, the latest first
+pub struct Calls {
+    logs: Vec<CallLog>,
+    friends: IndexMap<AttrValue, Friend>,
+    i18n: FluentBundle<FluentResource>,
+    _lang_dis: Dispatch<I18nState>,
+    _log_dis: Dispatch<CallLogState>,
+    _com_dis: Dispatch<ComponentTypeState>,
+}
+
+pub enum CallsMsg {
+    QueryList((Vec<CallLog>, IndexMap<AttrValue, Friend>)),
+    NewLog(Rc<CallLogState>),
+    FriendQueried(Friend),
+    ComStateChanged(Rc<ComponentTypeState>),
+    SwitchLanguage(Rc<I18nState>),
+    CallBack(AttrValue, InviteType),
+}
+
+impl Component for Calls {
+    type Message = CallsMsg;
+    type Properties = CallsProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_future(async {
+            let logs = db::db_ins()
+                .call_logs
+                .get_list(CALL_LOG_LIMIT)
+                .await
+                .unwrap_or_default();
+            let friends = db::db_ins().friends.get_list().await.unwrap_or_default();
+            CallsMsg::QueryList((logs, friends))
+        });
+
+        let _log_dis = Dispatch::global().subscribe_silent(ctx.link().callback(CallsMsg::NewLog));
+        let _com_dis =
+            Dispatch::global().subscribe_silent(ctx.link().callback(CallsMsg::ComStateChanged));
+        let _lang_dis =
+            Dispatch::global().subscribe_silent(ctx.link().callback(CallsMsg::SwitchLanguage));
+        let i18n = Self::create_i18n(I18nState::get().lang);
+        Self {
+            logs: Vec::new(),
+            friends: IndexMap::new(),
+            i18n,
+            _lang_dis,
+            _log_dis,
+            _com_dis,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            CallsMsg::QueryList((logs, friends)) => {
+                self.logs = logs;
+                self.friends = friends;
+                if ComponentTypeState::get().component_type == ComponentType::Calls {
+                    self.read_all();
+                }
+                true
+            }
+            CallsMsg::NewLog(state) => {
+                let log = state.log.clone();
+                if !self.friends.contains_key(&log.friend_id) {
+                    let friend_id = log.friend_id.clone();
+                    ctx.link().send_future(async move {
+                        match db::db_ins().friends.get(&friend_id).await {
+                            Ok(Some(friend)) => CallsMsg::FriendQueried(friend),
+                            _ => CallsMsg::FriendQueried(Friend {
+                                friend_id: friend_id.clone(),
+                                name: friend_id,
+                                ..Default::default()
+                            }),
+                        }
+                    });
+                }
+                self.logs.retain(|item| item.local_id != log.local_id);
+                self.logs.insert(0, log);
+                self.logs.truncate(CALL_LOG_LIMIT);
+                true
+            }
+            CallsMsg::FriendQueried(friend) => {
+                self.friends.insert(friend.friend_id.clone(), friend);
+                true
+            }
+            CallsMsg::ComStateChanged(state) => {
+                if state.component_type != ComponentType::Calls {
+                    return false;
+                }
+                self.read_all();
+                true
+            }
+            CallsMsg::SwitchLanguage(state) => {
+                self.i18n = Self::create_i18n(state.lang);
+                true
+            }
+            CallsMsg::CallBack(friend_id, invite_type) => {
+                let user = AppState::get();
+                Dispatch::<SendCallState>::global().reduce_mut(|s| {
+                    s.msg = InviteMsg {
+                        local_id: nanoid!().into(),
+                        send_id: ctx.props().user_id.clone(),
+                        friend_id,
+                        create_time: chrono::Utc::now().timestamp_millis(),
+                        invite_type,
+                        platform: get_platform(MobileState::is_mobile()),
+                        avatar: user.login_user.avatar.clone(),
+                        nickname: user.login_user.name.clone(),
+                        ..Default::default()
+                    }
+                });
+                false
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let content = if self.logs.is_empty() {
+            html!(<div class="no-result">{tr!(self.i18n, NO_CALLS)}</div>)
+        } else {
+            self.logs
+                .iter()
+                .map(|log| self.get_item(ctx, log))
+                .collect::<Html>()
+        };
+        html! {
+            <div class="list-wrapper">
+                <div class="calls-header">{tr!(self.i18n, CALLS)}</div>
+                <div class="contacts-list calls-list">
+                    {content}
+                </div>
+            </div>
+        }
+    }
+}
+
+impl Calls {
+    fn create_i18n(lang: LanguageType) -> FluentBundle<FluentResource> {
+        let res = match lang {
+            LanguageType::ZhCN => zh_cn::CALLS,
+            LanguageType::EnUS => en_us::CALLS,
+        };
+        utils::create_bundle(res)
+    }
+
+    /// mark the missed calls as read after the tab opened
+    fn read_all(&mut self) {
+        if self.logs.iter().all(|log| log.is_read)
+            && Dispatch::<UnreadState>::global().get().missed_call_count == 0
+        {
+            return;
+        }
+        self.logs.iter_mut().for_each(|log| log.is_read = true);
+        UnreadState::clear_missed_call();
+        spawn_local(async {
+            if let Err(e) = db::db_ins().call_logs.read_all().await {
+                error!("read call logs error: {:?}", e);
+            }
+        });
+    }
+
+    fn get_item(&self, ctx: &Context<Self>, log: &CallLog) -> Html {
+        let (name, avatar) = match self.friends.get(&log.friend_id) {
+            Some(friend) => (
+                friend.remark.clone().unwrap_or_else(|| friend.name.clone()),
+                friend.avatar.clone(),
+            ),
+            None => (log.friend_id.clone(), AttrValue::default()),
+        };
+
+        let direction = match log.direction {
+            CallDirection::Incoming => tr!(self.i18n, INCOMING),
+            CallDirection::Outgoing => tr!(self.i18n, OUTGOING),
+        };
+        let (invite_type, icon) = match log.invite_type {
+            InviteType::Video => (tr!(self.i18n, VIDEO_CALL), html!(<VideoIcon/>)),
+            InviteType::Audio => (tr!(self.i18n, VOICE_CALL), html!(<PhoneIcon/>)),
+        };
+        let outcome = match log.outcome {
+            CallOutcome::Answered => utils::format_milliseconds(log.duration),
+            CallOutcome::Missed => tr!(self.i18n, MISSED),
+            CallOutcome::Declined => tr!(self.i18n, DECLINED),
+            CallOutcome::Canceled => tr!(self.i18n, CANCELED),
+            CallOutcome::NoAnswer => tr!(self.i18n, NO_ANSWER),
+            CallOutcome::Busy => tr!(self.i18n, CALL_BUSY),
+        };
+
+        let mut classes = Classes::from("item hover");
+        if log.is_missed() {
+            classes.push("call-missed");
+        }
+        if !log.is_read {
+            classes.push("call-unread");
+        }
+        let mut remark = format!("{} {} · {}", direction, invite_type, outcome);
+        if log.voicemail.is_some() {
+            classes.push("call-voicemail");
+            remark = format!("{} · {}", remark, tr!(self.i18n, VOICEMAIL));
+        }
+
+        let friend_id = log.friend_id.clone();
+        let call_type = log.invite_type.clone();
+        let onclick = ctx
+            .link()
+            .callback(move |_| CallsMsg::CallBack(friend_id.clone(), call_type.clone()));
+        html! {
+            <div class={classes} key={log.local_id.as_str()}>
+                <div class="item-avatar" style="--avatar-column: 1">
+                    <AvatarImg avatar={avatar.clone()} class="avatar" />
+                </div>
+                <div class="item-info">
+                    <div class="name-time">
+                        <span>{name}</span>
+                        <span class="time">{Self::get_time_str(log.start_time)}</span>
+                    </div>
+                    <div class="remark">
+                        {remark}
+                    </div>
+                </div>
+                <span class="call-back pointer" title={tr!(self.i18n, CALL_BACK)} {onclick}>
+                    {icon}
+                </span>
+            </div>
+        }
+    }
+
+    fn get_time_str(timestamp: i64) -> String {
+        chrono::Utc
+            .timestamp_millis_opt(timestamp)
+            .single()
+            .map(|time| {
+                time.with_timezone(&chrono::Local)
+                    .format("%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_default()
+    }
+}
@@ -22,14 +22,15 @@ use sandcat_sdk::state::{
     AddFriendState, AddFriendStateItem, ComponentTypeState, CreateGroupConvState, I18nState,
     ItemType, MuteState, RemoveConvState, SendMessageState, UpdateFriendState,
 };
-use sandcat_sdk::state::{ConvState, UnreadState};
+use sandcat_sdk::state::{ConnectState, ConvState, SyncState, UnreadState};
 use utils::tr;
 use ws::WebSocketManager;
 
-use crate::call::PhoneCall;
-use crate::constant::{KNOCK_OFF_MSG, NO_RESULT, OK};
+use crate::call::{GroupPhoneCall, PhoneCall};
+use crate::constant::{KNOCK_OFF_MSG, NO_RESULT, OK, SYNCING};
 use crate::dialog::Dialog;
 use crate::left::right_click_panel::RightClickPanel;
+use crate::right::upload::resume_uploads;
 use crate::select_friends::SelectFriendList;
 use crate::top_bar::TopBar;
 
@@ -91,8 +92,16 @@ pub enum ChatsMsg {
     /// dismiss group positive
     DismissGroup(AttrValue, String),
     RecMsgNotify(Msg),
-    /// handle the lack messages
-    HandleLackMessages(Vec<Conversation>),
+    /// the conversation of the received message, sent only if the message is new in the db
+    NewMsgConv(Conversation),
+    /// the missing messages of a seq gap should be pulled, true for the send seq
+    PullSeqGap(bool),
+    /// the missing messages are pulled, contains whether it's the send seq,
+    /// the end of the pulled range and the conversations of the pulled messages
+    SeqGapPulled((bool, i64, Vec<Conversation>)),
+    /// pulling the missing messages failed, contains whether it's the send seq
+    /// and the missing range, it is pulled again by the timer
+    SeqGapPullFailed((bool, i64, i64)),
     /// switch language by received state
     SwitchLanguage(Rc<I18nState>),
     /// handle touch event for mobile
@@ -108,6 +117,10 @@ pub enum ChatsMsg {
     RefreshToken(bool),
     /// unauthorized, go to login page
     Unauthorized,
+    /// resume the unfinished uploads after connected
+    ConnectStateChanged(Rc<ConnectState>),
+    /// the progress of the offline sync
+    SyncStateChanged(Rc<SyncState>),
 }
 
 #[derive(Properties, PartialEq, Debug)]
@@ -169,6 +182,8 @@ impl Component for Chats {
                 self.list = convs;
                 self.query_complete = true;
                 self.seq = seq;
+                // the messages held before the seq is loaded may be continuous now
+                self.release_held_msgs(ctx);
 
                 // unmount loading
                 Dialog::close_loading();
@@ -218,6 +233,16 @@ impl Component for Chats {
             }
             ChatsMsg::DeleteItem => self.delete_item(),
             ChatsMsg::None => false,
+            ChatsMsg::ConnectStateChanged(state) => {
+                if *state == ConnectState::Connected {
+                    resume_uploads();
+                }
+                false
+            }
+            ChatsMsg::SyncStateChanged(state) => {
+                self.sync_state = state;
+                true
+            }
             ChatsMsg::RemoveConvStateChanged(state) => {
                 if state.id.is_empty() {
                     return false;
@@ -264,7 +289,7 @@ impl Component for Chats {
                 }
                 true
             }
-            ChatsMsg::ReceiveMsg(msg) => self.handle_receive_message(ctx, msg),
+            ChatsMsg::ReceiveMsg(msg) => self.receive_in_order(ctx, msg),
             ChatsMsg::SendMsg(state) => {
                 log::debug!("send message from sender in conversation");
                 let msg = state.msg.clone();
@@ -276,6 +301,7 @@ impl Component for Chats {
                 self.rec_msg_dis.reduce_mut(|s| s.msg = msg.clone());
                 false
             }
+            ChatsMsg::NewMsgConv(conv) => self.handle_conv(ctx, conv),
             ChatsMsg::SendMessage(msg) => {
                 self.handle_sent_msg(ctx, &msg);
                 self.send_msg(msg);
@@ -285,12 +311,16 @@ impl Component for Chats {
                 self.list.shift_insert(0, conv.friend_id.clone(), conv);
                 false
             }
-            ChatsMsg::HandleLackMessages(conv_list) => {
-                // todo 优化
-                conv_list.into_iter().for_each(|conv| {
-                    self.handle_conv(ctx, conv);
-                });
-                true
+            ChatsMsg::PullSeqGap(is_send) => {
+                self.pull_seq_gap(ctx, is_send);
+                false
+            }
+            ChatsMsg::SeqGapPulled((is_send, end, convs)) => {
+                self.handle_seq_gap_pulled(ctx, is_send, end, convs)
+            }
+            ChatsMsg::SeqGapPullFailed((is_send, start, end)) => {
+                self.handle_seq_gap_failed(ctx, is_send, start, end);
+                false
             }
             ChatsMsg::SwitchLanguage(state) => {
                 self.lang_state = state;
@@ -476,6 +506,19 @@ impl Component for Chats {
                 </div>
             }
         }
+        let mut sync_progress = html!();
+        if self.sync_state.is_syncing() {
+            sync_progress = html! {
+                <div class="sync-progress">
+                    {format!(
+                        "{} {}/{}",
+                        tr!(self.i18n, SYNCING),
+                        self.sync_state.synced,
+                        self.sync_state.total
+                    )}
+                </div>
+            }
+        }
         // PhoneCall send message callback
         let send_msg_callback = ctx
             .link()
@@ -489,6 +532,11 @@ impl Component for Chats {
                 msg={self.call_msg.clone()}
                 send_msg={send_msg_callback}
                 lang={self.lang_state.lang}/>
+            <GroupPhoneCall
+                ws={self.ws.clone()}
+                user_id={&ctx.props().user_id}
+                msg={self.group_call_msg.clone()}
+                lang={self.lang_state.lang}/>
 
             <div class="list-wrapper" {ontouchstart} {ontouchend}>
                 {context_menu}
@@ -499,6 +547,7 @@ impl Component for Chats {
                     {clean_callback}
                     {plus_click}
                     lang={self.lang_state.lang}/>
+                {sync_progress}
                 <div class="contacts-list">
                     {content}
                 </div>
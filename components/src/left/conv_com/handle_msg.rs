@@ -11,19 +11,17 @@ use sandcat_sdk::{
         friend::FriendStatus,
         message::{GroupMsg, Msg, RespMsgType, SingleCall},
         notification::Notification,
-        voice::Voice,
         ContentType, FriendShipStateType, RightContentType,
     },
     pb::message::Msg as PbMsg,
-    state::{
-        AudioDownloadedState, CreateConvState, FriendShipState, SendMessageState, SendResultState,
-        UnreadState,
-    },
+    state::{CreateConvState, FriendShipState, SendMessageState, SendResultState, UnreadState},
 };
 
 use super::Chats;
 
+use crate::call::mark_voicemail;
 use crate::left::conv_com::conversations::ChatsMsg;
+use crate::right::{direct_file, util};
 
 /// handle send and receive message
 impl Chats {
@@ -303,58 +301,33 @@ impl Chats {
         self.handle_lack_msg(ctx, end, true);
     }
 
+    /// advance the seq, the gap of the receive seq is handled before the message by the
+    /// `receive_in_order`, the gap of the send seq is pulled after the timeout
     pub fn handle_lack_msg(&mut self, ctx: &Context<Self>, end: i64, is_send: bool) {
         log::debug!("handle lack msg: {}, {}", end, is_send);
         if (!is_send && self.seq.local_seq > end - 1) || (is_send && self.seq.send_seq > end - 1) {
             return;
         }
 
-        let (need_repull, start, other_seq) = if is_send {
-            (
-                self.seq.send_seq < end - 1,
-                self.seq.send_seq,
-                self.seq.local_seq,
-            )
-        } else {
-            (
-                self.seq.local_seq < end - 1,
-                self.seq.local_seq,
-                self.seq.send_seq,
-            )
-        };
-
-        let user_id = ctx.props().user_id.clone();
-
         if is_send {
+            if self.seq.send_seq < end - 1 {
+                self.add_send_gap(ctx, end);
+            }
             self.seq.send_seq = end;
         } else {
             self.seq.local_seq = end;
         }
         let seq = self.seq.clone();
 
-        let ctx = ctx.link().clone();
         spawn_local(async move {
             if let Err(e) = db::db_ins().seq.put(&seq).await {
                 error!("save seq error: {:?}", e);
                 Notification::error(e).notify();
-                return;
-            }
-
-            if need_repull {
-                match Self::pull_offline_msgs(&user_id, is_send, other_seq, start, end).await {
-                    Ok(messages) => ctx.send_message(ChatsMsg::HandleLackMessages(
-                        Self::handle_offline_messages(ctx.clone(), user_id, messages).await,
-                    )),
-                    Err(e) => {
-                        error!("pull offline msg error: {:?}", e);
-                        Notification::error(e).notify();
-                    }
-                }
             }
         });
     }
 
-    async fn pull_offline_msgs(
+    pub async fn pull_offline_msgs(
         user_id: &str,
         is_send: bool,
         send_seq: i64,
@@ -376,14 +349,7 @@ impl Chats {
         local_id: &str,
         duration: u8,
     ) -> Result<(), Error> {
-        // request from file server
-        let data = api::file().download_voice(url).await?;
-
-        let voice = Voice::new(local_id.to_string(), data, duration);
-        db::db_ins().voices.save(&voice).await?;
-
-        Dispatch::<AudioDownloadedState>::global()
-            .reduce_mut(|s| s.local_id = local_id.to_string().into());
+        util::download_voice(url, local_id, duration).await?;
         Ok(())
     }
 
@@ -417,24 +383,42 @@ impl Chats {
                 self.handle_rec_lack_msg(ctx, msg.seq);
                 let scope = ctx.link().clone();
                 spawn_local(async move {
+                    if msg.content_type == ContentType::Audio {
+                        mark_voicemail(&mut msg).await;
+                    }
+                    // save to db, the message delivered again is only merged
+                    let is_new = match db::db_ins().messages.add_message(&msg).await {
+                        Ok(is_new) => is_new,
+                        Err(e) => {
+                            error!("save message to db error: {:?}", e);
+                            Notification::error(e).notify();
+                            true
+                        }
+                    };
+                    if !is_new {
+                        log::debug!("message delivered again: {}", msg.local_id);
+                        return;
+                    }
+                    scope.send_message(ChatsMsg::NewMsgConv(conv));
+
                     // split audio data
                     if msg.content_type == ContentType::Audio {
-                        // request from file server
-                        if let Err(e) = Self::download_voice_and_save(
+                        // request from file server, it is downloaded again when playing if failed
+                        match Self::download_voice_and_save(
                             &msg.content,
                             &msg.local_id,
                             msg.audio_duration,
                         )
                         .await
                         {
-                            Notification::error(e).notify();
+                            Ok(()) => {
+                                msg.audio_downloaded = true;
+                                if let Err(e) = db::db_ins().messages.add_message(&msg).await {
+                                    error!("update voice message error: {:?}", e);
+                                }
+                            }
+                            Err(e) => Notification::error(e).notify(),
                         }
-                        msg.audio_downloaded = true;
-                    }
-                    // save to db
-                    if let Err(e) = db::db_ins().messages.add_message(&msg).await {
-                        error!("save message to db error: {:?}", e);
-                        Notification::error(e).notify();
                     }
 
                     // notify other components we have received new message
@@ -442,8 +426,7 @@ impl Chats {
                         scope.send_message(ChatsMsg::RecMsgNotify(Msg::Single(msg)));
                     }
                 });
-
-                return self.handle_conv(ctx, conv);
+                return false;
             }
             Msg::Group(group_msg) => {
                 match group_msg {
@@ -487,22 +470,38 @@ impl Chats {
                         self.handle_rec_lack_msg(ctx, msg.seq);
                         let scope = ctx.link().clone();
                         spawn_local(async move {
+                            // the message delivered again is only merged
+                            let is_new = match db::db_ins().group_msgs.put(&msg).await {
+                                Ok(is_new) => is_new,
+                                Err(e) => {
+                                    error!("save message to db error: {:?}", e);
+                                    Notification::error(e).notify();
+                                    true
+                                }
+                            };
+                            if !is_new {
+                                log::debug!("group message delivered again: {}", msg.local_id);
+                                return;
+                            }
+                            scope.send_message(ChatsMsg::NewMsgConv(conv));
+
                             if msg.content_type == ContentType::Audio {
-                                // request from file server
-                                if let Err(e) = Self::download_voice_and_save(
+                                // request from file server, it is downloaded again when playing if failed
+                                match Self::download_voice_and_save(
                                     &msg.content,
                                     &msg.local_id,
                                     msg.audio_duration,
                                 )
                                 .await
                                 {
-                                    Notification::error(e).notify();
+                                    Ok(()) => {
+                                        msg.audio_downloaded = true;
+                                        if let Err(e) = db::db_ins().group_msgs.put(&msg).await {
+                                            error!("update voice message error: {:?}", e);
+                                        }
+                                    }
+                                    Err(e) => Notification::error(e).notify(),
                                 }
-                                msg.audio_downloaded = true;
-                            }
-                            if let Err(e) = db::db_ins().group_msgs.put(&msg).await {
-                                error!("save message to db error: {:?}", e);
-                                Notification::error(e).notify();
                             }
                             if is_send {
                                 scope.send_message(ChatsMsg::RecMsgNotify(Msg::Group(
@@ -510,8 +509,7 @@ impl Chats {
                                 )));
                             }
                         });
-
-                        return self.handle_conv(ctx, conv);
+                        return false;
                     }
                     GroupMsg::MemberExit((mem_id, group_id, seq)) => {
                         self.handle_rec_lack_msg(ctx, seq);
@@ -608,7 +606,20 @@ impl Chats {
                 self.handle_receive_single_call(ctx, message, conv_type);
                 return true;
             }
+            Msg::GroupCall(m) => {
+                // group call message is handled by GroupPhoneCall component
+                log::debug!("receive group call message from websocket: {:?}", m);
+                self.group_call_msg = m;
+                return true;
+            }
+            Msg::DirectFile(signal) => {
+                log::debug!("receive direct file signal from websocket: {:?}", signal);
+                direct_file::handle_signal(signal);
+            }
             Msg::FriendshipDeliveredNotice(_) => {}
+            Msg::Notification(content) => Notification::info(content).notify(),
+            Msg::Service(msg) => log::debug!("receive service message: {:?}", msg),
+            Msg::Unknown(msg) => log::warn!("receive unknown message: {:?}", msg),
             Msg::RelationshipRes((friend, seq)) => {
                 self.handle_rec_lack_msg(ctx, seq);
                 // 收到好友同意消息
@@ -685,6 +696,18 @@ impl Chats {
                 });
                 self.handle_rec_lack_msg(ctx, seq);
             }
+            Msg::RecRelationshipBlack((friend_id, seq)) => {
+                spawn_local(async move {
+                    if let Ok(Some(mut friend)) = db::db_ins().friends.get(&friend_id).await {
+                        friend.status = FriendStatus::Blacked as i32;
+                        if let Err(err) = db::db_ins().friends.put_friend(&friend).await {
+                            error!("save friend error:{:?}", err);
+                            Notification::error(err).notify();
+                        }
+                    }
+                });
+                self.handle_rec_lack_msg(ctx, seq);
+            }
         }
         false
     }
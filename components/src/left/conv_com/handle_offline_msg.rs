@@ -6,61 +6,75 @@ use yew::prelude::*;
 
 use sandcat_sdk::{
     api, db,
+    error::Result,
     model::{
+        call_log::{CallDirection, CallLog, CallOutcome},
         conversation::Conversation,
         message::{convert_server_msg, GroupMsg, InviteType, Message, Msg, SingleCall},
+        notification::Notification,
+        seq::Seq,
         ContentType, RightContentType,
     },
     pb::message::Msg as PbMsg,
-    state::{RefreshMsgListState, UnreadState},
+    state::{Notify, ProtocolState, RefreshMsgListState, SyncState, UnreadState},
 };
 use yewdux::Dispatch;
 
+use crate::call::{mark_voicemail, put_call_log};
+use crate::right::voice_queue::enqueue_voices;
+
 use super::Chats;
 
-impl Chats {
-    async fn handle_offline_msg_map(
-        map: &mut HashMap<AttrValue, Conversation>,
-        last_msg: AttrValue,
-        mut msg: Message,
+/// the seqs pulled by one request of the offline sync
+const SYNC_PAGE_SIZE: i64 = 200;
+
+/// the offline messages handled so far, kept across the pages
+#[derive(Default)]
+struct OfflineSync {
+    convs: HashMap<AttrValue, Conversation>,
+    /// relationship's sequence, marked received after the sync
+    relation_list: Vec<i64>,
+    /// the messages of the current page with the last message shown in the conversation,
+    /// saved in one transaction with the seq
+    friend_msgs: Vec<(Message, AttrValue)>,
+    group_msgs: Vec<(Message, AttrValue)>,
+}
+
+impl OfflineSync {
+    /// only the new messages update the conversations,
+    /// the voices are downloaded in background after the messages are saved
+    async fn save_page(&mut self, seq: Option<&Seq>) -> Result<()> {
+        let friend_msgs = std::mem::take(&mut self.friend_msgs);
+        let group_msgs = std::mem::take(&mut self.group_msgs);
+        let msgs = |msgs: &[(Message, AttrValue)]| -> Vec<Message> {
+            msgs.iter().map(|(msg, _)| msg.clone()).collect()
+        };
+        let (friend_is_new, group_is_new) = db::db_ins()
+            .messages
+            .save_sync_page(&msgs(&friend_msgs), &msgs(&group_msgs), seq)
+            .await?;
+        self.add_new_msgs(RightContentType::Friend, friend_msgs, friend_is_new);
+        self.add_new_msgs(RightContentType::Group, group_msgs, group_is_new);
+        Ok(())
+    }
+
+    fn add_new_msgs(
+        &mut self,
         conv_type: RightContentType,
-        cur_user_id: AttrValue,
+        msgs: Vec<(Message, AttrValue)>,
+        is_new: Vec<bool>,
     ) {
-        if msg.content_type == ContentType::Audio {
-            // request from file server
-            if let Err(e) =
-                Self::download_voice_and_save(&msg.content, &msg.local_id, msg.audio_duration).await
-            {
-                error!("download voice error: {}", e);
-            }
-            msg.audio_downloaded = true;
-        }
-
-        match conv_type {
-            RightContentType::Friend => {
-                if cur_user_id != msg.send_id {
-                    let friend_id = msg.send_id.clone();
-                    msg.send_id = msg.friend_id.clone();
-                    msg.friend_id = friend_id;
-                } else {
-                    msg.is_self = true;
-                }
-                if let Err(e) = db::db_ins().messages.add_message(&msg).await {
-                    error!("save message to db error: {:?}", e);
-                }
-            }
-            RightContentType::Group => {
-                msg.is_self = cur_user_id == msg.send_id;
-                if let Err(e) = db::db_ins().group_msgs.put(&msg).await {
-                    error!("save message to db error: {:?}", e);
-                }
-            }
-            // todo handle other types
-            _ => {
-                return;
+        let mut voices = Vec::new();
+        for ((msg, last_msg), _) in msgs.into_iter().zip(is_new).filter(|(_, new)| *new) {
+            self.update_conv(&msg, last_msg, conv_type.clone());
+            if msg.content_type == ContentType::Audio {
+                voices.push(msg);
             }
         }
+        enqueue_voices(conv_type, voices);
+    }
 
+    fn update_conv(&mut self, msg: &Message, last_msg: AttrValue, conv_type: RightContentType) {
         let unread_count = if msg.is_read == 1 || msg.is_self {
             0
         } else {
@@ -76,13 +90,54 @@ impl Chats {
             ..Default::default()
         };
 
-        if let Some(v) = map.get_mut(&conv.friend_id) {
+        if let Some(v) = self.convs.get_mut(&conv.friend_id) {
             v.last_msg = conv.last_msg;
             v.last_msg_time = conv.last_msg_time;
             v.last_msg_type = conv.last_msg_type;
             v.unread_count += conv.unread_count;
         } else {
-            map.insert(conv.friend_id.clone(), conv);
+            self.convs.insert(conv.friend_id.clone(), conv);
+        }
+    }
+}
+
+impl Chats {
+    /// the call canceled or not answered by the friend while we were offline
+    async fn save_missed_call(msg: &Message, cur_user_id: &AttrValue) {
+        if &msg.send_id == cur_user_id {
+            return;
+        }
+        let mut call_log = CallLog::new(msg, CallDirection::Incoming, CallOutcome::Missed);
+        call_log.friend_id = msg.send_id.clone();
+        // the voicemail may follow in the same batch
+        put_call_log(call_log).await;
+    }
+
+    async fn handle_offline_msg_map(
+        sync: &mut OfflineSync,
+        last_msg: AttrValue,
+        mut msg: Message,
+        conv_type: RightContentType,
+        cur_user_id: AttrValue,
+    ) {
+        match conv_type {
+            RightContentType::Friend => {
+                mark_voicemail(&mut msg).await;
+                if cur_user_id != msg.send_id {
+                    let friend_id = msg.send_id.clone();
+                    msg.send_id = msg.friend_id.clone();
+                    msg.friend_id = friend_id;
+                } else {
+                    msg.is_self = true;
+                }
+                sync.friend_msgs.push((msg, last_msg));
+            }
+            RightContentType::Group => {
+                msg.is_self = cur_user_id == msg.send_id;
+                sync.group_msgs.push((msg, last_msg));
+            }
+            // todo handle other types
+            _ => {}
         }
     }
 
@@ -93,7 +148,6 @@ impl Chats {
         }
     }
 
-    // todo handle the friend request and send the group create message to contact
     pub async fn handle_offline_messages(
         ctx: Scope<Self>,
         user_id: AttrValue,
@@ -103,13 +157,83 @@ impl Chats {
             return vec![];
         }
 
-        let mut map: HashMap<AttrValue, Conversation> = HashMap::with_capacity(messages.len());
-        // store relationship's sequence
-        let mut relation_list = Vec::with_capacity(messages.len());
+        let mut sync = OfflineSync::default();
+        Self::handle_offline_page(&ctx, &user_id, messages, &mut sync).await;
+        if let Err(e) = sync.save_page(None).await {
+            error!("save offline messages error: {:?}", e);
+            Notification::error(e).notify();
+        }
+        Self::finish_offline_sync(&user_id, sync).await
+    }
 
+    /// pull the offline messages page by page from the `seq` to the seqs of the server,
+    /// the `seq` is saved with the messages of every page, so the failed sync resumes
+    /// from the last saved page; the progress is notified by the `SyncState` in messages
+    pub async fn sync_offline_messages(
+        ctx: Scope<Self>,
+        user_id: AttrValue,
+        seq: &mut Seq,
+        server_send_seq: i64,
+        server_seq: i64,
+    ) -> Vec<Conversation> {
+        let total = ((server_send_seq - seq.send_seq).max(0) + (server_seq - seq.local_seq).max(0))
+            as usize;
+        let mut synced = 0;
+        SyncState { synced, total }.notify();
+
+        let mut sync = OfflineSync::default();
+        while seq.send_seq < server_send_seq || seq.local_seq < server_seq {
+            // the range of the finished side is empty
+            let send_end = (seq.send_seq + SYNC_PAGE_SIZE)
+                .min(server_send_seq)
+                .max(seq.send_seq);
+            let end = (seq.local_seq + SYNC_PAGE_SIZE)
+                .min(server_seq)
+                .max(seq.local_seq);
+            let messages = match api::messages()
+                .pull_offline_msg(&user_id, seq.send_seq, send_end, seq.local_seq, end)
+                .await
+            {
+                Ok(messages) => messages,
+                Err(e) => {
+                    error!("pull offline messages error: {:?}", e);
+                    Notification::error(e).notify();
+                    break;
+                }
+            };
+            Self::handle_offline_page(&ctx, &user_id, messages, &mut sync).await;
+
+            let next = Seq {
+                send_seq: send_end,
+                local_seq: end,
+                ..seq.clone()
+            };
+            if let Err(e) = sync.save_page(Some(&next)).await {
+                error!("save offline messages error: {:?}", e);
+                Notification::error(e).notify();
+                break;
+            }
+            synced += (send_end - seq.send_seq + end - seq.local_seq) as usize;
+            *seq = next;
+            SyncState { synced, total }.notify();
+        }
+        SyncState::default().notify();
+
+        Self::finish_offline_sync(&user_id, sync).await
+    }
+
+    // todo handle the friend request and send the group create message to contact
+    async fn handle_offline_page(
+        ctx: &Scope<Self>,
+        user_id: &AttrValue,
+        messages: Vec<PbMsg>,
+        sync: &mut OfflineSync,
+    ) {
+        // the server sends the nested payloads by the codec of the socket
+        let codec = ProtocolState::get().codec;
         for item in messages.into_iter() {
             // let friend_id = item.send_id.clone();
-            let msg = match convert_server_msg(item) {
+            let msg = match convert_server_msg(item, codec) {
                 Ok(msg) => msg,
                 Err(e) => {
                     error!("convert_server_msg error: {:?}", e);
@@ -121,7 +245,7 @@ impl Chats {
             match msg {
                 Msg::Single(msg) => {
                     Self::handle_offline_msg_map(
-                        &mut map,
+                        sync,
                         msg.content.clone(),
                         msg,
                         conv_type,
@@ -152,7 +276,7 @@ impl Chats {
                     }
                     GroupMsg::Message(msg) => {
                         Self::handle_offline_msg_map(
-                            &mut map,
+                            sync,
                             msg.content.clone(),
                             msg,
                             conv_type,
@@ -177,10 +301,12 @@ impl Chats {
                 Msg::SingleCall(call_msg) => match call_msg {
                     SingleCall::InviteCancel(msg) => {
                         let last_msg = Self::get_call_content(&msg.invite_type);
+                        let msg = Message::from(msg);
+                        Self::save_missed_call(&msg, user_id).await;
                         Self::handle_offline_msg_map(
-                            &mut map,
+                            sync,
                             last_msg,
-                            Message::from(msg),
+                            msg,
                             conv_type,
                             user_id.clone(),
                         )
@@ -190,7 +316,7 @@ impl Chats {
                         if msg.agree {
                             let last_msg = Self::get_call_content(&msg.invite_type);
                             Self::handle_offline_msg_map(
-                                &mut map,
+                                sync,
                                 last_msg,
                                 Message::from(msg),
                                 conv_type,
@@ -201,10 +327,12 @@ impl Chats {
                     }
                     SingleCall::NotAnswer(msg) => {
                         let last_msg = Self::get_call_content(&msg.invite_type);
+                        let msg = Message::from(msg);
+                        Self::save_missed_call(&msg, user_id).await;
                         Self::handle_offline_msg_map(
-                            &mut map,
+                            sync,
                             last_msg,
-                            Message::from(msg),
+                            msg,
                             conv_type,
                             user_id.clone(),
                         )
@@ -213,7 +341,7 @@ impl Chats {
                     SingleCall::HangUp(msg) => {
                         let last_msg = Self::get_call_content(&msg.invite_type);
                         Self::handle_offline_msg_map(
-                            &mut map,
+                            sync,
                             last_msg,
                             Message::from(msg),
                             conv_type,
@@ -225,14 +353,14 @@ impl Chats {
                 },
                 // handle the friendship related
                 Msg::RecRelationship((_fs, seq)) => {
-                    relation_list.push(seq);
+                    sync.relation_list.push(seq);
                     //
                     // if let Err(err) = db::db_ins().friendships.put_friendship(&fs).await {
                     //     error!("save friend error:{:?}", err);
                     // }
                 }
                 Msg::RelationshipRes((_friend, seq)) => {
-                    relation_list.push(seq);
+                    sync.relation_list.push(seq);
                     // if let Err(err) = db::db_ins()
                     //     .friendships
                     //     .agree_by_friend_id(friend.friend_id.as_str())
@@ -258,8 +386,9 @@ impl Chats {
                     //     map.insert(conv.friend_id.clone(), conv);
                     // }
                 }
-                Msg::RecRelationshipDel((_friend_id, seq)) => {
-                    relation_list.push(seq);
+                Msg::RecRelationshipDel((_friend_id, seq))
+                | Msg::RecRelationshipBlack((_friend_id, seq)) => {
+                    sync.relation_list.push(seq);
                     // let mut friend = db::db_ins().friends.get(&friend_id).await;
                     // if !friend.friend_id.is_empty() {
                     //     friend.status = FriendStatus::Deleted as i32;
@@ -271,10 +400,13 @@ impl Chats {
                 _ => {}
             }
         }
+    }
 
+    /// save the conversations and mark the relationships received after all pages handled
+    async fn finish_offline_sync(user_id: &AttrValue, sync: OfflineSync) -> Vec<Conversation> {
         // send handle finished state to notify main thread
         // sort
-        let mut list: Vec<Conversation> = map.into_values().collect();
+        let mut list: Vec<Conversation> = sync.convs.into_values().collect();
         let mut unread_count = 0;
         // save to db
         for conv in list.iter_mut() {
@@ -300,7 +432,7 @@ impl Chats {
         }
 
         // send relationship received to server
-        if let Err(err) = api::messages().del_msg(&user_id, relation_list).await {
+        if let Err(err) = api::messages().del_msg(user_id, sync.relation_list).await {
             error!("send relationship received to server error:{:?}", err);
         }
 
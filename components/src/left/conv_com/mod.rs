@@ -2,6 +2,7 @@ mod conversations;
 mod handle_group;
 mod handle_msg;
 mod handle_offline_msg;
+mod seq_gap;
 
 use std::{cell::RefCell, rc::Rc};
 
@@ -24,22 +25,23 @@ use sandcat_sdk::{
     model::{
         conversation::Conversation,
         friend::FriendStatus,
-        message::{Msg, SingleCall},
-        notification::Notification,
+        message::{GroupCall, Msg, SingleCall},
         seq::Seq,
         user::Claims,
         CommonProps, ComponentType, ContentType, CurrentItem, RightContentType, REFRESH_TOKEN,
         TOKEN, WS_ADDR,
     },
     state::{
-        ConvState, CreateConvState, CreateGroupConvState, I18nState, MobileState, MuteState,
-        Notify, RecMessageState, RemoveConvState, SendMessageState, UnreadState, UpdateFriendState,
+        ConnectState, ConvState, CreateConvState, CreateGroupConvState, I18nState, MobileState,
+        MuteState, Notify, RecMessageState, RemoveConvState, SendMessageState, SyncState,
+        UnreadState, UpdateFriendState,
     },
 };
 use utils::tr;
 use ws::WebSocketManager;
 
 use self::conversations::ChatsMsg;
+use self::seq_gap::SeqGaps;
 use crate::{
     constant::{AUDIO, AUDIO_CALL, EMOJI, ERROR, FILE, IMAGE, LOADING, VIDEO, VIDEO_CALL},
     dialog::Dialog,
@@ -50,11 +52,15 @@ pub struct Chats {
     /// used to notify the PhoneCall component to make a call
     /// when it changed, the PhoneCall component will be re-rendered
     call_msg: SingleCall,
+    /// used to notify the GroupPhoneCall component
+    group_call_msg: GroupCall,
     /// websocket manager, all messages from the server will be handled by this manager
     ws: Rc<RefCell<WebSocketManager>>,
     /// received messages sequence,
     /// used to determine whether the message is the latest message
     seq: Seq,
+    /// the gaps of the seq found during the live session
+    gaps: SeqGaps,
     /// pin list
     pinned_list: IndexMap<AttrValue, Conversation>,
     /// the list of conversations
@@ -109,6 +115,11 @@ pub struct Chats {
     token_getter: Option<Timeout>,
     /// refresh token
     refresh_token_getter: Option<Timeout>,
+    /// listen the websocket connection state
+    _conn_dis: Dispatch<ConnectState>,
+    /// the progress of the offline sync
+    sync_state: Rc<SyncState>,
+    _sync_dis: Dispatch<SyncState>,
 }
 
 impl Chats {
@@ -138,35 +149,16 @@ impl Chats {
             log::debug!("local seq: {:?}; server seq:{:?}", local_seq, server_seq);
             if local_seq.local_seq < server_seq.seq || local_seq.send_seq < server_seq.send_seq {
                 log::debug!("pull offline messages");
-                // request offline messages
-                match api::messages()
-                    .pull_offline_msg(
-                        user_id.as_str(),
-                        local_seq.send_seq,
-                        server_seq.send_seq,
-                        local_seq.local_seq,
-                        server_seq.seq,
-                    )
-                    .await
-                {
-                    Ok(messages) => {
-                        Self::handle_offline_messages(cloned_ctx.clone(), user_id, messages).await
-                    }
-                    Err(e) => {
-                        error!("pull offline messages error: {:?}", e);
-                        Notification::error(e).notify();
-                        return;
-                    }
-                };
-
-                local_seq.local_seq = server_seq.seq;
-                local_seq.send_seq = server_seq.send_seq;
-
-                if let Err(e) = db::db_ins().seq.put(&local_seq).await {
-                    error!("save local seq error: {:?}", e);
-                    Notification::error(e).notify();
-                    return;
-                }
+                // the list shows the progress of the sync instead of the loading
+                Dialog::close_loading();
+                Self::sync_offline_messages(
+                    cloned_ctx.clone(),
+                    user_id.clone(),
+                    &mut local_seq,
+                    server_seq.send_seq,
+                    server_seq.seq,
+                )
+                .await;
             }
 
             let pined_convs = db::db_ins()
@@ -219,15 +211,24 @@ impl Chats {
 
         let _update_dis = Dispatch::global()
             .subscribe_silent(ctx.link().callback(ChatsMsg::UpdateConvStateChanged));
+        let _conn_dis =
+            Dispatch::global().subscribe_silent(ctx.link().callback(ChatsMsg::ConnectStateChanged));
+        let _sync_dis =
+            Dispatch::global().subscribe_silent(ctx.link().callback(ChatsMsg::SyncStateChanged));
 
         // validate token
         Self::validate_token(ctx, false);
         Self::validate_token(ctx, true);
 
+        // prepare the ice servers for the calls
+        spawn_local(web_rtc::refresh_ice_servers());
+
         Self {
             call_msg: SingleCall::default(),
+            group_call_msg: GroupCall::default(),
             ws,
             seq: Seq::default(),
+            gaps: SeqGaps::default(),
             pinned_list: IndexMap::new(),
             list: IndexMap::new(),
             result: IndexMap::new(),
@@ -253,6 +254,9 @@ impl Chats {
             is_knocked: false,
             token_getter: None,
             refresh_token_getter: None,
+            _conn_dis,
+            sync_state: _sync_dis.get(),
+            _sync_dis,
         }
     }
 
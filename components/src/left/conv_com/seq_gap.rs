@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+
+use gloo::timers::callback::Timeout;
+use log::error;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+use sandcat_sdk::{
+    db,
+    model::{
+        conversation::Conversation,
+        message::{Msg, Sequence},
+        notification::Notification,
+    },
+};
+
+use super::conversations::ChatsMsg;
+use super::Chats;
+
+/// the missing messages are pulled if they don't arrive in this time, in milliseconds
+const GAP_TIMEOUT: u32 = 3000;
+
+/// the gaps of the sequences found during the live session
+#[derive(Default)]
+pub struct SeqGaps {
+    /// the received messages after the gap, they are handled in the order of the seq
+    held: BTreeMap<Sequence, Msg>,
+    rec_timer: Option<Timeout>,
+    /// the missing range of the send seq, start exclusive and end inclusive;
+    /// the response is handled at once, nothing is held for it
+    send_missing: Option<(Sequence, Sequence)>,
+    send_timer: Option<Timeout>,
+}
+
+impl SeqGaps {
+    /// take the held messages following the `seq` continuously
+    fn release(&mut self, mut seq: Sequence) -> Vec<Msg> {
+        let mut msgs = Vec::new();
+        while let Some(entry) = self.held.first_entry() {
+            if *entry.key() > seq + 1 {
+                break;
+            }
+            seq = seq.max(*entry.key());
+            msgs.push(entry.remove());
+        }
+        if self.held.is_empty() {
+            self.rec_timer = None;
+        }
+        msgs
+    }
+
+    /// the missing range of the receive seq, up to the first held message
+    fn rec_missing(&self, local_seq: Sequence) -> Option<(Sequence, Sequence)> {
+        self.held.keys().next().map(|seq| (local_seq, seq - 1))
+    }
+
+    /// merge the range into the missing send seq, the range of a failed pull
+    /// is merged with the gaps found during the pulling
+    fn add_send_missing(&mut self, start: Sequence, end: Sequence) {
+        self.send_missing = Some(match self.send_missing {
+            Some((old_start, old_end)) => (old_start.min(start), old_end.max(end)),
+            None => (start, end),
+        });
+    }
+}
+
+impl Chats {
+    /// the message after a gap of the receive seq is held until the missing ones arrive,
+    /// they are pulled if the gap persists past the timeout
+    pub fn receive_in_order(&mut self, ctx: &Context<Self>, message: Msg) -> bool {
+        let Some(seq) = message.seq() else {
+            return self.handle_receive_message(ctx, message);
+        };
+        if seq > self.seq.local_seq + 1 {
+            log::debug!("rec seq gap: {} -> {}", self.seq.local_seq, seq);
+            self.gaps.held.insert(seq, message);
+            if self.gaps.rec_timer.is_none() {
+                self.start_gap_timer(ctx, false);
+            }
+            return false;
+        }
+        let mut changed = self.handle_receive_message(ctx, message);
+        changed |= self.release_held_msgs(ctx);
+        changed
+    }
+
+    /// record the missing range of the send seq, `end` is the seq just received
+    pub fn add_send_gap(&mut self, ctx: &Context<Self>, end: Sequence) {
+        let start = self.seq.send_seq;
+        log::debug!("send seq gap: {} -> {}", start, end);
+        self.gaps.add_send_missing(start, end - 1);
+        if self.gaps.send_timer.is_none() {
+            self.start_gap_timer(ctx, true);
+        }
+    }
+
+    /// pull the missing range only, the other sequence is left unchanged
+    pub fn pull_seq_gap(&mut self, ctx: &Context<Self>, is_send: bool) {
+        let range = if is_send {
+            self.gaps.send_timer = None;
+            self.gaps.send_missing.take()
+        } else {
+            self.gaps.rec_timer = None;
+            self.gaps.rec_missing(self.seq.local_seq)
+        };
+        let Some((start, end)) = range else {
+            return;
+        };
+        let other_seq = if is_send {
+            self.seq.local_seq
+        } else {
+            self.seq.send_seq
+        };
+        log::debug!("pull seq gap: ({}, {}], send: {}", start, end, is_send);
+
+        let user_id = ctx.props().user_id.clone();
+        let link = ctx.link().clone();
+        spawn_local(async move {
+            match Self::pull_offline_msgs(&user_id, is_send, other_seq, start, end).await {
+                Ok(messages) => {
+                    let convs =
+                        Self::handle_offline_messages(link.clone(), user_id, messages).await;
+                    link.send_message(ChatsMsg::SeqGapPulled((is_send, end, convs)));
+                }
+                Err(e) => {
+                    error!("pull seq gap error: {:?}", e);
+                    Notification::error(e).notify();
+                    link.send_message(ChatsMsg::SeqGapPullFailed((is_send, start, end)));
+                }
+            }
+        });
+    }
+
+    /// the range is kept missing and pulled again, the seq is not moved past it
+    pub fn handle_seq_gap_failed(
+        &mut self,
+        ctx: &Context<Self>,
+        is_send: bool,
+        start: Sequence,
+        end: Sequence,
+    ) {
+        if is_send {
+            self.gaps.add_send_missing(start, end);
+            if self.gaps.send_timer.is_none() {
+                self.start_gap_timer(ctx, true);
+            }
+        } else if !self.gaps.held.is_empty() && self.gaps.rec_timer.is_none() {
+            self.start_gap_timer(ctx, false);
+        }
+    }
+
+    /// the held messages are handled after the missing ones are pulled
+    pub fn handle_seq_gap_pulled(
+        &mut self,
+        ctx: &Context<Self>,
+        is_send: bool,
+        end: Sequence,
+        convs: Vec<Conversation>,
+    ) -> bool {
+        convs.into_iter().for_each(|conv| {
+            self.handle_conv(ctx, conv);
+        });
+        if is_send {
+            return true;
+        }
+
+        if self.seq.local_seq < end {
+            self.seq.local_seq = end;
+            let seq = self.seq.clone();
+            spawn_local(async move {
+                if let Err(e) = db::db_ins().seq.put(&seq).await {
+                    error!("save seq error: {:?}", e);
+                }
+            });
+        }
+        self.release_held_msgs(ctx);
+        if !self.gaps.held.is_empty() {
+            self.start_gap_timer(ctx, false);
+        }
+        true
+    }
+
+    pub fn release_held_msgs(&mut self, ctx: &Context<Self>) -> bool {
+        let mut changed = false;
+        for msg in self.gaps.release(self.seq.local_seq) {
+            changed |= self.handle_receive_message(ctx, msg);
+        }
+        changed
+    }
+
+    fn start_gap_timer(&mut self, ctx: &Context<Self>, is_send: bool) {
+        let link = ctx.link().clone();
+        let timer = Timeout::new(GAP_TIMEOUT, move || {
+            link.send_message(ChatsMsg::PullSeqGap(is_send))
+        });
+        if is_send {
+            self.gaps.send_timer = Some(timer);
+        } else {
+            self.gaps.rec_timer = Some(timer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sandcat_sdk::model::message::Message;
+
+    use super::*;
+
+    fn msg(seq: Sequence) -> Msg {
+        Msg::Single(Message {
+            seq,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_release_held_msgs() {
+        let mut gaps = SeqGaps::default();
+        for seq in [5, 6, 8] {
+            gaps.held.insert(seq, msg(seq));
+        }
+
+        // 4 is still missing
+        assert!(gaps.release(3).is_empty());
+        assert_eq!(gaps.held.len(), 3);
+
+        let released: Vec<_> = gaps.release(4).iter().filter_map(Msg::seq).collect();
+        assert_eq!(released, vec![5, 6]);
+        assert_eq!(gaps.held.keys().copied().collect::<Vec<_>>(), vec![8]);
+
+        // the held one already handled is released as well
+        gaps.held.insert(7, msg(7));
+        let released: Vec<_> = gaps.release(7).iter().filter_map(Msg::seq).collect();
+        assert_eq!(released, vec![7, 8]);
+        assert!(gaps.held.is_empty());
+    }
+
+    #[test]
+    fn test_failed_pull_retries_same_range() {
+        let mut gaps = SeqGaps::default();
+        for seq in [5, 6] {
+            gaps.held.insert(seq, msg(seq));
+        }
+        let local_seq = 3;
+        assert_eq!(gaps.rec_missing(local_seq), Some((3, 4)));
+
+        // the pull failed, the seq is not moved and nothing is released
+        assert!(gaps.release(local_seq).is_empty());
+        assert_eq!(gaps.held.len(), 2);
+        assert_eq!(gaps.rec_missing(local_seq), Some((3, 4)));
+
+        // the send range is taken by the pull and restored after it failed,
+        // merged with the gap found during the pulling
+        gaps.add_send_missing(10, 12);
+        let (start, end) = gaps.send_missing.take().unwrap();
+        gaps.add_send_missing(14, 15);
+        gaps.add_send_missing(start, end);
+        assert_eq!(gaps.send_missing, Some((10, 15)));
+    }
+}
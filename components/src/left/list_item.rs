@@ -18,6 +18,8 @@ use sandcat_sdk::{
     },
 };
 
+use crate::avatar_img::AvatarImg;
+
 pub struct ListItem {
     node_ref: NodeRef,
     conv_state: Rc<ConvState>,
@@ -249,7 +251,7 @@ impl ListItem {
         let avatar = iter
             .map(|v| {
                 html! {
-                    <img class="avatar" alt="avatar" src={utils::get_avatar_url(v)} />
+                    <AvatarImg avatar={v.to_string()} class="avatar" />
                 }
             })
             .collect::<Html>();
@@ -342,7 +344,7 @@ impl ListItem {
                     </>
                 }
             }
-            ComponentType::Setting | ComponentType::Default => html! {},
+            ComponentType::Setting | ComponentType::Calls | ComponentType::Default => html! {},
         }
     }
 
@@ -381,7 +383,7 @@ impl ListItem {
         match ctx.props().component_type {
             ComponentType::Contacts => ctx.link().callback(|_| ListItemMsg::FriendItemClicked),
             ComponentType::Messages => ctx.link().callback(|_| ListItemMsg::CleanUnreadCount),
-            ComponentType::Setting | ComponentType::Default => {
+            ComponentType::Setting | ComponentType::Calls | ComponentType::Default => {
                 ctx.link().callback(|_| ListItemMsg::GoToSetting)
             }
         }
@@ -1,4 +1,5 @@
 pub mod add_friend;
+pub mod calls;
 pub mod contacts;
 pub mod conv_com;
 pub mod list_item;
@@ -19,6 +20,7 @@ use yewdux::Dispatch;
 use sandcat_sdk::model::ComponentType;
 use sandcat_sdk::state::{AppState, ComponentTypeState, MobileState, Notify};
 
+use crate::left::calls::Calls;
 use crate::left::contacts::Contacts;
 use crate::left::conv_com::Chats;
 use crate::left::top::Top;
@@ -154,6 +156,9 @@ impl Component for Left {
                 classes = "slider ";
                 // <Messages />
             }
+            ComponentType::Calls => {
+                classes = "slider move-left-calls";
+            }
             ComponentType::Setting => {}
             ComponentType::Default => {}
         };
@@ -179,6 +184,7 @@ impl Component for Left {
                         user_id={&ctx.props().user_id}
                         avatar={&self.app_state.login_user.avatar}
                         nickname={&self.app_state.login_user.name}/>
+                    <Calls user_id={&ctx.props().user_id} />
                     </div>
                 </div>
                 {resizer}
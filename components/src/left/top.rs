@@ -9,7 +9,8 @@ use yewdux::Dispatch;
 
 use i18n::{en_us, zh_cn, LanguageType};
 use icons::{
-    ConnectedIcon, ContactsIcon, DisconnectIcon, HangUpLoadingIcon, MessagesIcon, SettingIcon,
+    CallsIcon, ConnectedIcon, ContactsIcon, DisconnectIcon, HangUpLoadingIcon, MessagesIcon,
+    SettingIcon,
 };
 use sandcat_sdk::{
     db,
@@ -20,8 +21,9 @@ use sandcat_sdk::{
 };
 use utils::tr;
 
+use crate::avatar_img::AvatarImg;
 use crate::{
-    constant::{CONTACTS, MSG},
+    constant::{CALLS, CONTACTS, MSG},
     self_info::SelfInfo,
 };
 
@@ -132,6 +134,12 @@ impl Component for Top {
             "top-icon-selected",
             "hover top-icon",
         );
+        let (calls_class, calls_onclick) = self.get_top_icon_class_and_callback(
+            ctx,
+            ComponentType::Calls,
+            "top-icon-selected",
+            "hover top-icon",
+        );
         let (setting_class, setting_onclick) = self.get_top_icon_class_and_callback(
             ctx,
             ComponentType::Setting,
@@ -141,6 +149,7 @@ impl Component for Top {
 
         let msg_count = self.get_unread_count(self.unread_state.msg_count);
         let contact_count = self.get_unread_count(self.unread_state.contacts_count);
+        let missed_call_count = self.get_unread_count(self.unread_state.missed_call_count);
         let info_panel = if self.show_info {
             let close = ctx.link().callback(|_| TopMsg::ShowInfoPanel);
             let submit = ctx.link().callback(TopMsg::SubmitInfo);
@@ -162,6 +171,10 @@ impl Component for Top {
                         <ContactsIcon fill={"var(--color-text)"}/>
                         { contact_count }
                     </span>
+                    <span class={calls_class} onclick={calls_onclick}>
+                        <CallsIcon fill={"var(--color-text)"}/>
+                        { missed_call_count }
+                    </span>
                     <span class={setting_class} onclick={setting_onclick}>
                         <SettingIcon fill={"var(--color-text)"}/>
                     </span>
@@ -178,6 +191,10 @@ impl Component for Top {
                         { tr!(self.i18n, CONTACTS) }
                         { contact_count }
                     </div>
+                    <div class={classes!(calls_class, "top-down-style")} onclick={calls_onclick}>
+                        { tr!(self.i18n, CALLS) }
+                        { missed_call_count }
+                    </div>
                 </div>
             }
         };
@@ -189,11 +206,10 @@ impl Component for Top {
                 <div class="top-up">
                     { info_panel }
                     <div class="top-left pointer" {onclick}>
-                        <img
+                        <AvatarImg
                             class="avatar"
-                            alt="avatar"
-                            title={&self.app_state.login_user.name}
-                            src={utils::get_avatar_url(&self.app_state.login_user.avatar)} />
+                            title={self.app_state.login_user.name.clone()}
+                            avatar={self.app_state.login_user.avatar.clone()} />
                         <div class="top-left-name">
                             <span><b>{&self.app_state.login_user.name}</b></span>
                             { connection_state }
@@ -13,6 +13,7 @@ use sandcat_sdk::state::MobileState;
 use utils::tr;
 
 use crate::action::Action;
+use crate::avatar_img::AvatarImg;
 use crate::constant::ACCOUNT;
 use crate::constant::APPLIED;
 use crate::constant::APPLY;
@@ -187,7 +188,7 @@ impl Component for UserInfoCom {
         <>
         <div class={"user-info"} ref={self.node.clone()}>
             <div class="friend-card-header">
-                    <img alt="avatar" src={utils::get_avatar_url(&ctx.props().info.avatar)} class="friend-card-avatar"/>
+                    <AvatarImg avatar={ctx.props().info.avatar.clone()} class="friend-card-avatar" />
                     <div class="friend-card-info">
                         // <span><b>{&self.friend.remark}</b></span>
                         <span>{tr!(self.i18n, NICKNAME)}{&ctx.props().info.name}</span>
@@ -2,9 +2,11 @@ use sandcat_sdk::state::MobileState;
 
 pub mod action;
 mod avatar;
+pub mod avatar_img;
 pub mod call;
 mod change_pwd;
 pub mod constant;
+mod device_picker;
 pub mod dialog;
 pub mod left;
 pub mod notification;
@@ -12,6 +14,7 @@ pub mod right;
 pub mod select_friends;
 pub mod self_info;
 pub mod top_bar;
+mod touch;
 
 pub fn get_platform(is_mobile: bool) -> i32 {
     if is_mobile {
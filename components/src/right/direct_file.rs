@@ -0,0 +1,514 @@
+//! send the file to the friend over the data channel instead of the file server,
+//! the signalling goes through the websocket like the single call;
+//! it falls back to the chunked upload when no path can be made
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use futures_channel::oneshot;
+use gloo::timers::callback::Timeout;
+use js_sys::{ArrayBuffer, Uint8Array};
+use log::{error, warn};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{Blob, Event, File, MessageEvent, RtcDataChannel};
+use yew::{AttrValue, Callback};
+use yewdux::Dispatch;
+
+use sandcat_sdk::db;
+use sandcat_sdk::error::{Error, Result};
+use sandcat_sdk::model::attachment::{Attachment, AttachmentKind};
+use sandcat_sdk::model::direct_file::{crc32_update, direct_server_name, DirectFileFrame};
+use sandcat_sdk::model::message::{DirectFile, DirectFileCandidate, DirectFileSdp, Message, Msg};
+use sandcat_sdk::model::notification::Notification;
+use sandcat_sdk::model::upload::UploadTask;
+use sandcat_sdk::model::{ContentType, RightContentType};
+use sandcat_sdk::state::{SendMessageState, UploadProgressState, UploadStatus};
+use web_rtc::{DataPeer, LocalCandidate};
+
+use crate::right::upload;
+
+const CHANNEL_LABEL: &str = "file";
+/// the size of every binary message, the larger ones are not supported by all browsers
+const CHUNK_SIZE: usize = 16 * 1024;
+/// the size read from the file at once
+const READ_SIZE: f64 = 1024.0 * 1024.0;
+/// stop sending until the buffered data drops below the low threshold
+const HIGH_BUFFERED: u32 = 8 * 1024 * 1024;
+const LOW_BUFFERED: u32 = 1024 * 1024;
+/// fall back to the upload if the channel is not open in time
+const CONNECT_TIMEOUT: u32 = 15_000;
+/// the friend is considered gone if the transfer stalls longer than it
+const STALL_TIMEOUT: u32 = 30_000;
+
+struct Transfer {
+    peer: Rc<DataPeer>,
+    channel: Option<RtcDataChannel>,
+    is_sender: bool,
+    canceled: bool,
+    /// the handlers of the received channel
+    _closures: Vec<Closure<dyn FnMut(Event)>>,
+}
+
+thread_local! {
+    /// the files being sent or received, keyed by the local id of the message
+    static TRANSFERS: RefCell<HashMap<String, Transfer>> = RefCell::new(HashMap::new());
+}
+
+/// whether the file of the message is being sent directly,
+/// its upload task is only kept for the page reloading
+pub fn is_sending(local_id: &str) -> bool {
+    TRANSFERS.with(|t| t.borrow().get(local_id).is_some_and(|t| t.is_sender))
+}
+
+/// return false if the file is not being sent directly
+pub fn cancel(local_id: &str) -> bool {
+    TRANSFERS.with(|t| {
+        let mut transfers = t.borrow_mut();
+        let Some(transfer) = transfers.get_mut(local_id).filter(|t| t.is_sender) else {
+            return false;
+        };
+        transfer.canceled = true;
+        // wake up the waiting sender
+        if let (Some(channel), Ok(event)) = (transfer.channel.as_ref(), Event::new("close")) {
+            let _ = channel.dispatch_event(&event);
+        }
+        true
+    })
+}
+
+/// send the file of the pending message to the friend directly,
+/// the message is sent after the friend verified the file
+pub fn start_send(conv_type: RightContentType, msg: Message, file: File) {
+    spawn_local(async move {
+        let local_id = msg.local_id.to_string();
+        // upload the file instead if the page is reloaded before the file arrived
+        let task = UploadTask::new(
+            String::new(),
+            conv_type.clone(),
+            msg.clone(),
+            file.size() as u64,
+        );
+        if let Err(err) = db::db_ins().uploads.put(&task, &file).await {
+            error!("save upload task error: {:?}", err);
+        }
+
+        let result = send(&msg, &file).await;
+        let canceled = TRANSFERS
+            .with(|t| t.borrow_mut().remove(&local_id))
+            .map(|transfer| {
+                transfer.peer.close();
+                transfer.canceled
+            })
+            .unwrap_or_default();
+
+        if canceled {
+            upload::clean_canceled(task).await;
+            return;
+        }
+        if let Err(err) = result {
+            warn!("send file directly error: {:?}, upload it instead", err);
+            upload::start_upload(conv_type, msg, file);
+            return;
+        }
+        // the sender can't download it from the file server either
+        if let Err(err) = cache(&msg, &file).await {
+            error!("cache direct file error: {:?}", err);
+        }
+        if let Err(err) = upload::complete(task, direct_server_name(&local_id)).await {
+            error!("complete direct file error: {:?}", err);
+            Notification::error(err).notify();
+            UploadProgressState::notify(local_id.into(), 0, 0, UploadStatus::Paused);
+        }
+    });
+}
+
+/// the signalling from the friend
+pub fn handle_signal(signal: DirectFile) {
+    match signal {
+        DirectFile::Offer(offer) => {
+            if let Err(err) = receive(offer) {
+                error!("receive direct file error: {:?}", err);
+            }
+        }
+        DirectFile::Answer(answer) => {
+            let Some(peer) = get_peer(&answer.local_id) else {
+                return;
+            };
+            spawn_local(async move {
+                if let Err(err) = peer.set_answer(&answer.sdp).await {
+                    error!("set direct file answer error: {:?}", err);
+                }
+            });
+        }
+        DirectFile::NewIceCandidate(candidate) => {
+            let Some(peer) = get_peer(&candidate.local_id) else {
+                return;
+            };
+            spawn_local(async move {
+                if let Err(err) = peer
+                    .add_candidate(
+                        &candidate.candidate,
+                        candidate.sdp_mid.as_deref(),
+                        candidate.sdp_m_index,
+                    )
+                    .await
+                {
+                    error!("add direct file candidate error: {:?}", err);
+                }
+            });
+        }
+    }
+}
+
+fn get_peer(local_id: &str) -> Option<Rc<DataPeer>> {
+    TRANSFERS.with(|t| t.borrow().get(local_id).map(|t| t.peer.clone()))
+}
+
+async fn send(msg: &Message, file: &File) -> Result<()> {
+    let local_id = msg.local_id.to_string();
+    let signal = Signal {
+        local_id: msg.local_id.clone(),
+        send_id: msg.send_id.clone(),
+        friend_id: msg.friend_id.clone(),
+    };
+    let peer = Rc::new(DataPeer::new(signal.candidate_callback())?);
+    let channel = peer.create_channel(CHANNEL_LABEL);
+    TRANSFERS.with(|t| {
+        t.borrow_mut().insert(
+            local_id.clone(),
+            Transfer {
+                peer: peer.clone(),
+                channel: Some(channel.clone()),
+                is_sender: true,
+                canceled: false,
+                _closures: Vec::new(),
+            },
+        )
+    });
+
+    // the receiver only replies the verified frame
+    let verified = Rc::new(Cell::new(false));
+    let verified_clone = verified.clone();
+    let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+        if let Some(DirectFileFrame::Verified(ok)) = parse_frame(&event) {
+            verified_clone.set(ok);
+        }
+    });
+    channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+    let sdp = peer.create_offer().await?;
+    signal.send(DirectFile::Offer(signal.sdp(sdp)));
+    if !wait_event(&channel, "open", CONNECT_TIMEOUT).await {
+        return Err(Error::internal_with_details("direct file channel not open"));
+    }
+
+    let header = DirectFileFrame::Header {
+        size: file.size() as u64,
+        mime: file.type_(),
+        kind: attachment_kind(msg.content_type),
+    };
+    send_frame(&channel, &header)?;
+    let checksum = send_data(&channel, &local_id, file).await?;
+    send_frame(&channel, &DirectFileFrame::Checksum(checksum))?;
+
+    if !wait_event(&channel, "message", STALL_TIMEOUT).await || !verified.get() {
+        return Err(Error::internal_with_details("direct file not verified"));
+    }
+    Ok(())
+}
+
+/// send the data with the flow control, returns the checksum
+async fn send_data(channel: &RtcDataChannel, local_id: &str, file: &File) -> Result<u32> {
+    let size = file.size();
+    let mut offset = 0.0;
+    let mut checksum = 0;
+    channel.set_buffered_amount_low_threshold(LOW_BUFFERED);
+    UploadProgressState::notify(
+        local_id.to_string().into(),
+        0,
+        size as u64,
+        UploadStatus::Uploading,
+    );
+
+    while offset < size {
+        let end = (offset + READ_SIZE).min(size);
+        let data = read_bytes(&file.slice_with_f64_and_f64(offset, end)?).await?;
+        checksum = crc32_update(checksum, &data);
+        for chunk in data.chunks(CHUNK_SIZE) {
+            if channel.buffered_amount() > HIGH_BUFFERED
+                && !wait_event(channel, "bufferedamountlow", STALL_TIMEOUT).await
+            {
+                return Err(Error::internal_with_details("direct file stalled"));
+            }
+            channel.send_with_u8_array(chunk)?;
+        }
+
+        offset = end;
+        UploadProgressState::notify(
+            local_id.to_string().into(),
+            offset as u64,
+            size as u64,
+            UploadStatus::Uploading,
+        );
+    }
+    Ok(checksum)
+}
+
+/// put the sent file into the attachment cache like the received one
+async fn cache(msg: &Message, file: &File) -> Result<()> {
+    let attachment = Attachment::new(
+        direct_server_name(&msg.local_id),
+        msg.friend_id.to_string(),
+        attachment_kind(msg.content_type),
+        file.type_(),
+        read_bytes(file).await?,
+    );
+    db::db_ins().attachments.put(&attachment).await
+}
+
+/// the file received by the channel
+#[derive(Default)]
+struct Incoming {
+    header: Option<(u64, String, AttachmentKind)>,
+    data: Vec<u8>,
+    checksum: u32,
+}
+
+/// answer the offer and receive the file into the attachment cache,
+/// the message of the file arrives after it is verified
+fn receive(offer: DirectFileSdp) -> Result<()> {
+    let local_id = offer.local_id.to_string();
+    // reply to the sender
+    let signal = Signal {
+        local_id: offer.local_id.clone(),
+        send_id: offer.friend_id.clone(),
+        friend_id: offer.send_id.clone(),
+    };
+    let mut peer = DataPeer::new(signal.candidate_callback())?;
+    let id = local_id.clone();
+    let conv_id = offer.send_id.to_string();
+    peer.on_channel(Callback::from(move |channel: RtcDataChannel| {
+        on_channel(id.clone(), conv_id.clone(), channel)
+    }));
+    let peer = Rc::new(peer);
+    TRANSFERS.with(|t| {
+        t.borrow_mut().insert(
+            local_id.clone(),
+            Transfer {
+                peer: peer.clone(),
+                channel: None,
+                is_sender: false,
+                canceled: false,
+                _closures: Vec::new(),
+            },
+        )
+    });
+
+    spawn_local(async move {
+        match peer.create_answer(&offer.sdp).await {
+            Ok(sdp) => signal.send(DirectFile::Answer(signal.sdp(sdp))),
+            Err(err) => {
+                error!("create direct file answer error: {:?}", err);
+                remove_received(&local_id);
+                return;
+            }
+        }
+        // the sender has given up if the channel is not open in time
+        sleep(CONNECT_TIMEOUT).await;
+        let opened = TRANSFERS.with(|t| t.borrow().get(&local_id).map(|t| t.channel.is_some()));
+        if opened == Some(false) {
+            remove_received(&local_id);
+        }
+    });
+    Ok(())
+}
+
+fn on_channel(local_id: String, conv_id: String, channel: RtcDataChannel) {
+    let incoming = Rc::new(RefCell::new(Incoming::default()));
+    let id = local_id.clone();
+    let channel_clone = channel.clone();
+    let on_message = Closure::<dyn FnMut(Event)>::new(move |event: Event| {
+        let Ok(event) = event.dyn_into::<MessageEvent>() else {
+            return;
+        };
+        if let Ok(buffer) = event.data().dyn_into::<ArrayBuffer>() {
+            let array = Uint8Array::new(&buffer);
+            let mut incoming = incoming.borrow_mut();
+            let Incoming { data, checksum, .. } = &mut *incoming;
+            let offset = data.len();
+            data.resize(offset + array.length() as usize, 0);
+            array.copy_to(&mut data[offset..]);
+            *checksum = crc32_update(*checksum, &data[offset..]);
+            return;
+        }
+        match parse_frame(&event) {
+            Some(DirectFileFrame::Header { size, mime, kind }) => {
+                incoming.borrow_mut().header = Some((size, mime, kind));
+            }
+            Some(DirectFileFrame::Checksum(checksum)) => {
+                let incoming = incoming.take();
+                let channel = channel_clone.clone();
+                let local_id = id.clone();
+                let conv_id = conv_id.clone();
+                spawn_local(async move {
+                    let verified = store(&local_id, conv_id, incoming, checksum).await;
+                    if let Err(err) = send_frame(&channel, &DirectFileFrame::Verified(verified)) {
+                        error!("send direct file verified error: {:?}", err);
+                    }
+                });
+            }
+            _ => warn!("unexpected direct file frame: {:?}", event.data()),
+        }
+    });
+    // the sender closes the connection after the file is verified,
+    // remove it later, the handler can't be dropped while running
+    let id = local_id.clone();
+    let on_close = Closure::<dyn FnMut(Event)>::new(move |_| {
+        let id = id.clone();
+        spawn_local(async move { remove_received(&id) });
+    });
+    channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    channel.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+    TRANSFERS.with(|t| {
+        if let Some(transfer) = t.borrow_mut().get_mut(&local_id) {
+            transfer.channel = Some(channel);
+            transfer._closures = vec![on_message, on_close];
+        }
+    });
+}
+
+/// check the size and the checksum before caching the file
+async fn store(local_id: &str, conv_id: String, incoming: Incoming, checksum: u32) -> bool {
+    let Some((size, mime, kind)) = incoming.header else {
+        warn!("direct file header missing");
+        return false;
+    };
+    if incoming.data.len() as u64 != size || incoming.checksum != checksum {
+        warn!("direct file corrupted: {}", local_id);
+        return false;
+    }
+    let attachment = Attachment::new(
+        direct_server_name(local_id),
+        conv_id,
+        kind,
+        mime,
+        incoming.data,
+    );
+    if let Err(err) = db::db_ins().attachments.put(&attachment).await {
+        error!("cache direct file error: {:?}", err);
+        return false;
+    }
+    true
+}
+
+fn remove_received(local_id: &str) {
+    // drop the closures after the borrow is released
+    let transfer = TRANSFERS.with(|t| t.borrow_mut().remove(local_id));
+    if let Some(transfer) = transfer {
+        if let Some(channel) = transfer.channel.as_ref() {
+            channel.set_onmessage(None);
+            channel.set_onclose(None);
+        }
+        transfer.peer.close();
+    }
+}
+
+#[derive(Clone)]
+struct Signal {
+    local_id: AttrValue,
+    send_id: AttrValue,
+    friend_id: AttrValue,
+}
+
+impl Signal {
+    fn sdp(&self, sdp: String) -> DirectFileSdp {
+        DirectFileSdp {
+            local_id: self.local_id.clone(),
+            sdp: sdp.into(),
+            send_id: self.send_id.clone(),
+            friend_id: self.friend_id.clone(),
+            create_time: chrono::Utc::now().timestamp_millis(),
+        }
+    }
+
+    fn candidate_callback(&self) -> Callback<LocalCandidate> {
+        let signal = self.clone();
+        Callback::from(move |(candidate, sdp_mid, sdp_m_index): LocalCandidate| {
+            signal.send(DirectFile::NewIceCandidate(DirectFileCandidate {
+                local_id: signal.local_id.clone(),
+                candidate: candidate.into(),
+                sdp_mid,
+                sdp_m_index,
+                send_id: signal.send_id.clone(),
+                friend_id: signal.friend_id.clone(),
+                create_time: chrono::Utc::now().timestamp_millis(),
+            }))
+        })
+    }
+
+    /// the conversations component sends it by the websocket
+    fn send(&self, msg: DirectFile) {
+        Dispatch::<SendMessageState>::global().reduce_mut(|s| s.msg = Msg::DirectFile(msg));
+    }
+}
+
+fn attachment_kind(content_type: ContentType) -> AttachmentKind {
+    match content_type {
+        ContentType::Image => AttachmentKind::Image,
+        ContentType::Video => AttachmentKind::Video,
+        _ => AttachmentKind::File,
+    }
+}
+
+fn parse_frame(event: &MessageEvent) -> Option<DirectFileFrame> {
+    event
+        .data()
+        .as_string()
+        .and_then(|text| serde_json::from_str(&text).ok())
+}
+
+fn send_frame(channel: &RtcDataChannel, frame: &DirectFileFrame) -> Result<()> {
+    channel.send_with_str(&serde_json::to_string(frame).unwrap())?;
+    Ok(())
+}
+
+async fn read_bytes(blob: &Blob) -> Result<Vec<u8>> {
+    let buffer = JsFuture::from(blob.array_buffer()).await?;
+    Ok(Uint8Array::new(&buffer).to_vec())
+}
+
+/// wait for the event of the channel, false if it is closed or timed out first
+async fn wait_event(channel: &RtcDataChannel, event: &str, millis: u32) -> bool {
+    let (tx, rx) = oneshot::channel();
+    let tx = Rc::new(RefCell::new(Some(tx)));
+    let notify = |result: bool| {
+        let tx = tx.clone();
+        move || {
+            if let Some(tx) = tx.borrow_mut().take() {
+                let _ = tx.send(result);
+            }
+        }
+    };
+    let on_event = Closure::<dyn FnMut()>::new(notify(true));
+    let on_close = Closure::<dyn FnMut()>::new(notify(false));
+    let _timeout = Timeout::new(millis, notify(false));
+    let _ = channel.add_event_listener_with_callback(event, on_event.as_ref().unchecked_ref());
+    let _ = channel.add_event_listener_with_callback("close", on_close.as_ref().unchecked_ref());
+
+    let result = rx.await.unwrap_or_default();
+    let _ = channel.remove_event_listener_with_callback(event, on_event.as_ref().unchecked_ref());
+    let _ = channel.remove_event_listener_with_callback("close", on_close.as_ref().unchecked_ref());
+    result
+}
+
+async fn sleep(millis: u32) {
+    let (tx, rx) = oneshot::channel::<()>();
+    let _timeout = Timeout::new(millis, move || {
+        let _ = tx.send(());
+    });
+    let _ = rx.await;
+}
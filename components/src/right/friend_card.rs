@@ -7,6 +7,7 @@ use i18n::{en_us, zh_cn, LanguageType};
 use sandcat_sdk::model::{friend::Friend, RightContentType};
 use utils::tr;
 
+use crate::avatar_img::AvatarImg;
 use crate::{
     action::Action,
     constant::{ACCOUNT, ADD_FRIEND, NICKNAME, REGION},
@@ -91,7 +92,7 @@ impl Component for FriendCard {
                 onblur={ctx.link().callback(|_| FriendCardMsg::Destroy)}
                 >
                 <div class="friend-card-header">
-                    <img alt="avatar" src={utils::get_avatar_url(&friend.avatar)} class="friend-card-avatar"/>
+                    <AvatarImg avatar={friend.avatar.clone()} class="friend-card-avatar" />
                     <div class="friend-card-info">
                         {remark}
                         <span>{tr!(self.i18n, NICKNAME)}{"  "}{&friend.name}</span>
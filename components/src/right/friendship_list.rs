@@ -21,6 +21,7 @@ use sandcat_sdk::model::FriendShipStateType;
 use sandcat_sdk::state::FriendShipState;
 use utils::tr;
 
+use crate::avatar_img::AvatarImg;
 use crate::constant::{
     ACCEPT, ADDED, APPLY_MSG, CANCEL, GO_VERIFY, MESSAGE, REMARK, REQUESTED, TITLE,
 };
@@ -269,7 +270,7 @@ impl Component for FriendShipList {
                 html! {
                     <div class="friendship-item" /* {onclick} */>
                         <div class="item-left">
-                            <img class="avatar" alt="avatar" src={utils::get_avatar_url(&item.avatar)} />
+                            <AvatarImg avatar={item.avatar.clone()} class="avatar" />
                         // </div>
                         <div class="item-info">
                         //     <div class="name-time">
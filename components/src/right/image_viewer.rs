@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+
+use fluent::{FluentBundle, FluentResource};
+use web_sys::{HtmlElement, Url};
+use yew::prelude::*;
+
+use i18n::{en_us, zh_cn, LanguageType};
+use icons::{CloseIcon, ForwardIcon, MsgLoadingIcon};
+use sandcat_sdk::db;
+use sandcat_sdk::model::attachment::AttachmentKind;
+use sandcat_sdk::model::file_msg::FileMsg;
+use sandcat_sdk::model::message::Message;
+use sandcat_sdk::model::{ContentType, RightContentType};
+use sandcat_sdk::state::{I18nState, ItemType, Notify};
+use utils::tr;
+
+use crate::constant::{DOWNLOAD, FORWARD, LOADING, ROTATE, ZOOM_RESET};
+use crate::right::util::{forward_msg, load_attachment};
+use crate::select_friends::SelectFriendList;
+use crate::touch::{TouchGesture, Touches};
+
+const MIN_SCALE: f64 = 0.2;
+const MAX_SCALE: f64 = 10.0;
+
+/// full screen viewer of the images in the conversation
+pub struct ImageViewer {
+    node: NodeRef,
+    i18n: FluentBundle<FluentResource>,
+    /// images of the conversation ordered by create time
+    images: Vec<Message>,
+    index: usize,
+    /// object urls of the loaded originals, keyed by local id
+    urls: HashMap<AttrValue, AttrValue>,
+    scale: f64,
+    x: f64,
+    y: f64,
+    /// degrees, clockwise
+    rotation: i32,
+    dragging: bool,
+    start_x: f64,
+    start_y: f64,
+    touches: Touches,
+    show_friendlist: bool,
+}
+
+pub enum ImageViewerMsg {
+    ImagesLoaded(Vec<Message>),
+    UrlLoaded(AttrValue, AttrValue),
+    Prev,
+    Next,
+    Rotate,
+    ResetZoom,
+    Wheel(WheelEvent),
+    MouseDown(MouseEvent),
+    MouseMove(MouseEvent),
+    MouseUp,
+    TouchStart(TouchEvent),
+    TouchMove(TouchEvent),
+    TouchEnd(TouchEvent),
+    KeyDown(KeyboardEvent),
+    ShowForward,
+    Forward(Vec<String>),
+    None,
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct ImageViewerProps {
+    pub user_id: AttrValue,
+    /// the image clicked
+    pub msg: Message,
+    pub conv_type: RightContentType,
+    pub close: Callback<()>,
+}
+
+impl ImageViewer {
+    fn current(&self) -> &Message {
+        &self.images[self.index]
+    }
+
+    fn reset_transform(&mut self) {
+        self.scale = 1.0;
+        self.x = 0.0;
+        self.y = 0.0;
+        self.rotation = 0;
+    }
+
+    /// load the original of the current image from the attachment cache
+    fn load_current(&self, ctx: &Context<Self>) {
+        let msg = self.current().clone();
+        if self.urls.contains_key(&msg.local_id) {
+            return;
+        }
+        if !msg.file_content.is_empty() {
+            // the image is still being uploaded
+            ctx.link().send_message(ImageViewerMsg::UrlLoaded(
+                msg.local_id.clone(),
+                msg.file_content.clone(),
+            ));
+            return;
+        }
+        let file = FileMsg::from(&msg.content);
+        if file.server_name.is_empty() {
+            return;
+        }
+        ctx.link().send_future(async move {
+            match load_attachment(
+                &file.server_name,
+                &msg.friend_id,
+                AttachmentKind::Image,
+                "image/*",
+            )
+            .await
+            {
+                Ok(url) => ImageViewerMsg::UrlLoaded(msg.local_id, url.into()),
+                Err(err) => {
+                    log::error!("load image error: {:?}", err);
+                    // fallback to the file server
+                    let url = format!("/api/file/get/{}", file.server_name);
+                    ImageViewerMsg::UrlLoaded(msg.local_id, url.into())
+                }
+            }
+        });
+    }
+
+    fn zoom(&mut self, factor: f64) {
+        self.scale = (self.scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+        if self.scale <= 1.0 {
+            self.x = 0.0;
+            self.y = 0.0;
+        }
+    }
+
+    fn step(&mut self, ctx: &Context<Self>, forward: bool) -> bool {
+        let index = if forward {
+            if self.index + 1 >= self.images.len() {
+                return false;
+            }
+            self.index + 1
+        } else {
+            if self.index == 0 {
+                return false;
+            }
+            self.index - 1
+        };
+        self.index = index;
+        self.reset_transform();
+        self.load_current(ctx);
+        true
+    }
+}
+
+impl Component for ImageViewer {
+    type Message = ImageViewerMsg;
+    type Properties = ImageViewerProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let res = match I18nState::get().lang {
+            LanguageType::ZhCN => zh_cn::IMAGE_VIEWER,
+            LanguageType::EnUS => en_us::IMAGE_VIEWER,
+        };
+        let i18n = utils::create_bundle(res);
+
+        // query all images of the conversation
+        let conv_type = ctx.props().conv_type.clone();
+        let conv_id = ctx.props().msg.friend_id.clone();
+        ctx.link().send_future(async move {
+            let result = match conv_type {
+                RightContentType::Group => {
+                    db::db_ins()
+                        .group_msgs
+                        .get_by_type(&conv_id, ContentType::Image)
+                        .await
+                }
+                _ => {
+                    db::db_ins()
+                        .messages
+                        .get_by_type(&conv_id, ContentType::Image)
+                        .await
+                }
+            };
+            match result {
+                Ok(list) => ImageViewerMsg::ImagesLoaded(list),
+                Err(err) => {
+                    log::error!("query images error: {:?}", err);
+                    ImageViewerMsg::None
+                }
+            }
+        });
+
+        let viewer = Self {
+            node: NodeRef::default(),
+            i18n,
+            images: vec![ctx.props().msg.clone()],
+            index: 0,
+            urls: HashMap::new(),
+            scale: 1.0,
+            x: 0.0,
+            y: 0.0,
+            rotation: 0,
+            dragging: false,
+            start_x: 0.0,
+            start_y: 0.0,
+            touches: Touches::default(),
+            show_friendlist: false,
+        };
+        viewer.load_current(ctx);
+        viewer
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            ImageViewerMsg::ImagesLoaded(list) => {
+                let local_id = self.current().local_id.clone();
+                // the clicked image may not be stored yet, keep it anyway
+                if let Some(index) = list.iter().position(|m| m.local_id == local_id) {
+                    self.images = list;
+                    self.index = index;
+                }
+                true
+            }
+            ImageViewerMsg::UrlLoaded(local_id, url) => {
+                self.urls.insert(local_id, url);
+                true
+            }
+            ImageViewerMsg::Prev => self.step(ctx, false),
+            ImageViewerMsg::Next => self.step(ctx, true),
+            ImageViewerMsg::Rotate => {
+                self.rotation = (self.rotation + 90) % 360;
+                true
+            }
+            ImageViewerMsg::ResetZoom => {
+                self.reset_transform();
+                true
+            }
+            ImageViewerMsg::Wheel(event) => {
+                event.prevent_default();
+                let factor = if event.delta_y() < 0.0 {
+                    1.1
+                } else {
+                    1.0 / 1.1
+                };
+                self.zoom(factor);
+                true
+            }
+            ImageViewerMsg::MouseDown(event) => {
+                event.prevent_default();
+                self.dragging = true;
+                self.start_x = event.client_x() as f64;
+                self.start_y = event.client_y() as f64;
+                false
+            }
+            ImageViewerMsg::MouseMove(event) => {
+                if !self.dragging {
+                    return false;
+                }
+                self.x += event.client_x() as f64 - self.start_x;
+                self.y += event.client_y() as f64 - self.start_y;
+                self.start_x = event.client_x() as f64;
+                self.start_y = event.client_y() as f64;
+                true
+            }
+            ImageViewerMsg::MouseUp => {
+                self.dragging = false;
+                false
+            }
+            ImageViewerMsg::TouchStart(event) => {
+                self.touches.update(&event);
+                false
+            }
+            ImageViewerMsg::TouchMove(event) => {
+                event.prevent_default();
+                let gesture = self.touches.gesture(&event);
+                self.touches.update(&event);
+                match gesture {
+                    Some(TouchGesture::Pinch { delta, .. }) => {
+                        // 移动端缩放灵敏度，调整值避免过于灵敏
+                        self.zoom(1.0 + delta * 0.01);
+                        true
+                    }
+                    Some(TouchGesture::Pan { dx, dy }) => {
+                        self.x += dx;
+                        self.y += dy;
+                        true
+                    }
+                    None => false,
+                }
+            }
+            ImageViewerMsg::TouchEnd(event) => {
+                self.touches.update(&event);
+                false
+            }
+            ImageViewerMsg::KeyDown(event) => match event.key().as_str() {
+                "ArrowLeft" => self.step(ctx, false),
+                "ArrowRight" => self.step(ctx, true),
+                "Escape" => {
+                    ctx.props().close.emit(());
+                    false
+                }
+                _ => false,
+            },
+            ImageViewerMsg::ShowForward => {
+                self.show_friendlist = !self.show_friendlist;
+                true
+            }
+            ImageViewerMsg::Forward(list) => {
+                forward_msg(self.current().clone(), ctx.props().user_id.clone(), list);
+                self.show_friendlist = false;
+                true
+            }
+            ImageViewerMsg::None => false,
+        }
+    }
+
+    fn rendered(&mut self, _ctx: &Context<Self>, first_render: bool) {
+        // receive the key events
+        if first_render {
+            if let Some(node) = self.node.cast::<HtmlElement>() {
+                let _ = node.focus();
+            }
+        }
+    }
+
+    fn destroy(&mut self, _ctx: &Context<Self>) {
+        for url in self.urls.values() {
+            if url.starts_with("blob:") {
+                let _ = Url::revoke_object_url(url);
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let msg = self.current();
+        let file = FileMsg::from(&msg.content);
+
+        let stop = Callback::from(|e: MouseEvent| e.stop_propagation());
+        let close = ctx.props().close.reform(|_: MouseEvent| ());
+
+        let image = match self.urls.get(&msg.local_id) {
+            Some(src) => {
+                let style = format!(
+                    "transform: translate({}px, {}px) scale({}) rotate({}deg);",
+                    self.x, self.y, self.scale, self.rotation
+                );
+                html! {
+                    <img
+                        class="image-viewer-img"
+                        alt="image"
+                        src={src.clone()}
+                        {style}
+                        draggable="false"
+                        onclick={stop.clone()}
+                        ondblclick={ctx.link().callback(|_| ImageViewerMsg::ResetZoom)}
+                        onwheel={ctx.link().callback(ImageViewerMsg::Wheel)}
+                        onmousedown={ctx.link().callback(ImageViewerMsg::MouseDown)}
+                        onmousemove={ctx.link().callback(ImageViewerMsg::MouseMove)}
+                        onmouseup={ctx.link().callback(|_| ImageViewerMsg::MouseUp)}
+                        onmouseleave={ctx.link().callback(|_| ImageViewerMsg::MouseUp)}
+                        ontouchstart={ctx.link().callback(ImageViewerMsg::TouchStart)}
+                        ontouchmove={ctx.link().callback(ImageViewerMsg::TouchMove)}
+                        ontouchend={ctx.link().callback(ImageViewerMsg::TouchEnd)}
+                    />
+                }
+            }
+            None => html! {
+                <div class="image-viewer-loading">
+                    <MsgLoadingIcon />
+                    {tr!(self.i18n, LOADING)}
+                </div>
+            },
+        };
+
+        let prev = if self.index > 0 {
+            let onclick = ctx.link().callback(|e: MouseEvent| {
+                e.stop_propagation();
+                ImageViewerMsg::Prev
+            });
+            html!(<span class="image-viewer-nav image-viewer-prev" {onclick}>{"‹"}</span>)
+        } else {
+            html!()
+        };
+        let next = if self.index + 1 < self.images.len() {
+            let onclick = ctx.link().callback(|e: MouseEvent| {
+                e.stop_propagation();
+                ImageViewerMsg::Next
+            });
+            html!(<span class="image-viewer-nav image-viewer-next" {onclick}>{"›"}</span>)
+        } else {
+            html!()
+        };
+
+        let download = self
+            .urls
+            .get(&msg.local_id)
+            .map(|href| {
+                html! {
+                    <a href={href.clone()} download={file.name.clone()} onclick={stop.clone()}>
+                        {tr!(self.i18n, DOWNLOAD)}
+                    </a>
+                }
+            })
+            .unwrap_or_default();
+
+        let mut friendlist = html!();
+        if self.show_friendlist {
+            let from = if ctx.props().conv_type == RightContentType::Group {
+                ItemType::Group
+            } else {
+                ItemType::Friend
+            };
+            friendlist = html! {
+                <div onclick={stop.clone()}>
+                    <SelectFriendList
+                        except={&msg.friend_id}
+                        close_back={ctx.link().callback(|_| ImageViewerMsg::ShowForward)}
+                        submit_back={ctx.link().callback(ImageViewerMsg::Forward)}
+                        lang={I18nState::get().lang}
+                        {from} />
+                </div>
+            };
+        }
+
+        let onclick_btn = |msg_fn: fn() -> ImageViewerMsg| {
+            ctx.link().callback(move |e: MouseEvent| {
+                e.stop_propagation();
+                msg_fn()
+            })
+        };
+
+        html! {
+            <div class="image-viewer"
+                ref={self.node.clone()}
+                tabindex="0"
+                onclick={close.clone()}
+                onkeydown={ctx.link().callback(ImageViewerMsg::KeyDown)}>
+                <div class="image-viewer-toolbar" onclick={stop.clone()}>
+                    <span class="image-viewer-index">
+                        {format!("{} / {}", self.index + 1, self.images.len())}
+                    </span>
+                    <span onclick={onclick_btn(|| ImageViewerMsg::Rotate)}>
+                        {tr!(self.i18n, ROTATE)}
+                    </span>
+                    <span onclick={onclick_btn(|| ImageViewerMsg::ResetZoom)}>
+                        {tr!(self.i18n, ZOOM_RESET)}
+                    </span>
+                    {download}
+                    <span onclick={onclick_btn(|| ImageViewerMsg::ShowForward)}
+                        title={tr!(self.i18n, FORWARD)}>
+                        <ForwardIcon />
+                    </span>
+                    <span onclick={close}>
+                        <CloseIcon />
+                    </span>
+                </div>
+                {prev}
+                {image}
+                {next}
+                {friendlist}
+            </div>
+        }
+    }
+}
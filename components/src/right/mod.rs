@@ -1,16 +1,22 @@
+pub mod direct_file;
 pub mod emoji;
 pub mod friend_card;
 pub mod friendship_list;
+mod image_viewer;
 pub mod msg_item;
 pub mod msg_list;
 mod msg_right_click;
 pub mod postcard;
-mod recorder;
+pub mod recorder;
 pub mod sender;
 pub mod set_drawer;
 pub mod set_window;
 pub mod setting;
+mod shared_content;
+pub mod upload;
 pub mod util;
+mod video_recorder;
+pub mod voice_queue;
 
 use std::rc::Rc;
 
@@ -486,6 +492,7 @@ impl Component for Right {
                     }
                 }
                 ComponentType::Setting => html! {<Setting lang={self.lang_state.lang} />},
+                ComponentType::Calls => html!(<div class="cat-head-icon"><CatHeadIcon/></div>),
                 ComponentType::Default => html!(),
             };
         }
@@ -1,7 +1,9 @@
+use std::rc::Rc;
+
 use gloo::timers::callback::Timeout;
 use gloo::utils::{document, window};
 use log::error;
-use web_sys::Node;
+use web_sys::{Node, Url};
 use yew::platform::spawn_local;
 use yew::prelude::*;
 use yewdux::Dispatch;
@@ -9,16 +11,22 @@ use yewdux::Dispatch;
 use i18n::LanguageType;
 use icons::HangUpLoadingIcon;
 use sandcat_sdk::db;
+use sandcat_sdk::model::file_msg::FileMsg;
 use sandcat_sdk::model::friend::Friend;
 use sandcat_sdk::model::message::{GroupMsg, InviteType, Message, Msg, SendStatus, ServerResponse};
-use sandcat_sdk::model::notification::Notification;
 use sandcat_sdk::model::ContentType;
 use sandcat_sdk::model::RightContentType;
-use sandcat_sdk::state::{I18nState, ItemType, Notify, RelatedMsgState, SendMessageState};
+use sandcat_sdk::state::{
+    I18nState, ItemType, Notify, RelatedMsgState, SendMessageState, UploadProgressState,
+    UploadStatus,
+};
 
+use crate::avatar_img::AvatarImg;
 use crate::right::friend_card::FriendCard;
 use crate::right::msg_item::related_msg::RelatedMsg;
 use crate::right::msg_right_click::MsgRightClick;
+use crate::right::upload::{cancel_upload, is_uploading};
+use crate::right::util::{download_voice, forward_msg, save_file};
 use crate::select_friends::SelectFriendList;
 
 use super::{AudioDownloadStage, MsgItem};
@@ -34,7 +42,13 @@ pub enum MsgItemMsg {
     QueryGroupMember(AttrValue, AttrValue),
     CloseFriendCard,
     TextDoubleClick(MouseEvent),
-    PlayAudio,
+    /// start position in ratio
+    PlayAudio(f64),
+    VoiceLoaded(Vec<u8>, bool),
+    /// dragging the waveform, only the view is updated
+    Scrub(f64),
+    Seek(f64),
+    SwitchSpeed,
     ShowAudioDownload,
     AudioDownloadTimeout,
     OnContextMenu(MouseEvent),
@@ -43,7 +57,13 @@ pub enum MsgItemMsg {
     ShowForwardMsg,
     ForwardMsg(Vec<String>),
     RelatedMsg,
-    ShowVideoPlayer,
+    AttachmentLoaded(AttrValue),
+    /// play the video or save the file
+    LoadOriginal,
+    OriginalLoaded(Option<AttrValue>),
+    UploadProgress(Rc<UploadProgressState>),
+    CancelUpload,
+    None,
 }
 
 type FriendCardProps = (Friend, i32, i32);
@@ -57,7 +77,17 @@ pub struct MsgItemProps {
     pub msg: Message,
     pub conv_type: RightContentType,
     pub del_item: Callback<AttrValue>,
-    pub play_audio: Option<Callback<(AttrValue, Vec<u8>)>>,
+    /// local id, voice data and the start position in ratio
+    pub play_audio: Option<Callback<(AttrValue, Vec<u8>, f64)>>,
+    /// played ratio, only the playing voice has it
+    #[prop_or_default]
+    pub audio_progress: Option<f64>,
+    #[prop_or_default]
+    pub seek_audio: Option<Callback<f64>>,
+    #[prop_or_default]
+    pub switch_speed: Option<Callback<()>>,
+    #[prop_or(1.0)]
+    pub playback_rate: f64,
     pub send_timeout: Callback<AttrValue>,
 }
 
@@ -223,24 +253,80 @@ impl Component for MsgItem {
                 }
                 false
             }
-            MsgItemMsg::PlayAudio => {
+            MsgItemMsg::PlayAudio(start) => {
                 if let Some(paly_audio) = ctx.props().play_audio.clone() {
-                    let voice_id = ctx.props().msg.local_id.clone();
+                    let mut msg = ctx.props().msg.clone();
+                    let conv_type = ctx.props().conv_type.clone();
                     self.play_audio_animation();
                     spawn_local(async move {
-                        let voice = match db::db_ins().voices.get(&voice_id).await {
+                        let mut voice = match db::db_ins().voices.get(&msg.local_id).await {
                             Ok(voice) => voice,
                             Err(e) => {
-                                error!("Failed to get voice: {:?}", e);
-                                // todo download voice again
-                                return;
+                                // failed to download when received, download it again
+                                log::warn!("Failed to get voice: {:?}", e);
+                                match download_voice(
+                                    &msg.content,
+                                    &msg.local_id,
+                                    msg.audio_duration,
+                                )
+                                .await
+                                {
+                                    Ok(voice) => {
+                                        msg.audio_downloaded = true;
+                                        let result = match conv_type {
+                                            RightContentType::Group => {
+                                                db::db_ins().group_msgs.put(&msg).await
+                                            }
+                                            _ => db::db_ins().messages.add_message(&msg).await,
+                                        };
+                                        if let Err(e) = result {
+                                            error!("update voice message error: {:?}", e);
+                                        }
+                                        voice
+                                    }
+                                    Err(e) => {
+                                        error!("download voice error: {:?}", e);
+                                        return;
+                                    }
+                                }
                             }
                         };
-                        paly_audio.emit((voice_id, voice.data));
+                        if !voice.played {
+                            voice.played = true;
+                            if let Err(e) = db::db_ins().voices.save(&voice).await {
+                                error!("save voice error: {:?}", e);
+                            }
+                        }
+                        paly_audio.emit((msg.local_id, voice.data, start));
                     });
                 }
                 false
             }
+            MsgItemMsg::VoiceLoaded(waveform, played) => {
+                self.waveform = waveform;
+                self.voice_played = played;
+                true
+            }
+            MsgItemMsg::Scrub(ratio) => {
+                self.scrub = Some(ratio);
+                true
+            }
+            MsgItemMsg::Seek(ratio) => {
+                self.scrub = None;
+                match ctx.props().seek_audio {
+                    Some(ref seek_audio) if ctx.props().audio_progress.is_some() => {
+                        seek_audio.emit(ratio)
+                    }
+                    _ => ctx.link().send_message(MsgItemMsg::PlayAudio(ratio)),
+                }
+                true
+            }
+            MsgItemMsg::SwitchSpeed => {
+                if let Some(ref switch_speed) = ctx.props().switch_speed {
+                    switch_speed.emit(());
+                }
+                false
+            }
             MsgItemMsg::ShowAudioDownload => {
                 self.download_stage = AudioDownloadStage::Downloading;
                 let ctx = ctx.link().clone();
@@ -268,6 +354,7 @@ impl Component for MsgItem {
                 let del_item = ctx.props().del_item.clone();
                 let local_id = ctx.props().msg.local_id.clone();
                 let content_type = ctx.props().msg.content_type;
+                let conv_type = ctx.props().conv_type.clone();
                 spawn_local(async move {
                     if content_type == ContentType::Audio {
                         // delete audio file
@@ -276,7 +363,11 @@ impl Component for MsgItem {
                             return;
                         }
                     }
-                    if let Err(e) = db::db_ins().messages.delete(&local_id).await {
+                    let result = match conv_type {
+                        RightContentType::Group => db::db_ins().group_msgs.delete(&local_id).await,
+                        _ => db::db_ins().messages.delete(&local_id).await,
+                    };
+                    if let Err(e) = result {
                         error!("delete message error: {:?}", e);
                         return;
                     }
@@ -291,24 +382,7 @@ impl Component for MsgItem {
             }
             MsgItemMsg::ForwardMsg(list) => {
                 log::info!("forward msg: {:?}", list);
-                let mut msg = ctx.props().msg.clone();
-                let user_id = ctx.props().user_id.clone();
-                spawn_local(async move {
-                    for item in list.into_iter() {
-                        msg.send_id.clone_from(&user_id);
-                        msg.friend_id = item.into();
-                        msg.server_id = AttrValue::default();
-                        msg.local_id = nanoid::nanoid!().into();
-                        msg.is_read = 1;
-                        msg.is_self = true;
-                        if let Err(err) = db::db_ins().messages.add_message(&msg).await {
-                            error!("forword message error: store message error{:?}", err);
-                            Notification::error(err).notify();
-                        }
-                        Dispatch::<SendMessageState>::global()
-                            .reduce_mut(|s| s.msg = Msg::Single(msg.clone()));
-                    }
-                });
+                forward_msg(ctx.props().msg.clone(), ctx.props().user_id.clone(), list);
                 self.show_friendlist = false;
                 true
             }
@@ -319,19 +393,84 @@ impl Component for MsgItem {
                 RelatedMsgState::notify(nickname, msg);
                 true
             }
-            MsgItemMsg::ShowVideoPlayer => {
-                self.show_video_palyer = !self.show_video_palyer;
-                log::debug!("show video player:{:?}", self.show_video_palyer);
+            MsgItemMsg::AttachmentLoaded(url) => {
+                self.attachment_url = Some(url);
+                true
+            }
+            MsgItemMsg::LoadOriginal => {
+                if let Some(url) = &self.original_url {
+                    if ctx.props().msg.content_type == ContentType::File {
+                        save_file(url, &FileMsg::from(&ctx.props().msg.content).name);
+                    }
+                    return false;
+                }
+                self.load_original(ctx)
+            }
+            MsgItemMsg::OriginalLoaded(url) => {
+                self.loading_original = false;
+                if let Some(url) = &url {
+                    if ctx.props().msg.content_type == ContentType::File {
+                        save_file(url, &FileMsg::from(&ctx.props().msg.content).name);
+                    }
+                }
+                self.original_url = url;
                 true
             }
+            MsgItemMsg::UploadProgress(state) => {
+                if state.local_id != ctx.props().msg.local_id {
+                    return false;
+                }
+                match state.status {
+                    UploadStatus::Uploading | UploadStatus::Paused => {
+                        self.upload_progress = Some((state.uploaded, state.total, state.status));
+                    }
+                    UploadStatus::Finished => {
+                        self.upload_progress = None;
+                        self._upload_dis = None;
+                    }
+                    UploadStatus::Canceled => {
+                        self.upload_progress = None;
+                        self._upload_dis = None;
+                        ctx.props().del_item.emit(state.local_id.clone());
+                    }
+                }
+                true
+            }
+            MsgItemMsg::CancelUpload => {
+                cancel_upload(ctx.props().msg.local_id.clone());
+                false
+            }
+            MsgItemMsg::None => false,
         }
     }
 
-    fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+    fn destroy(&mut self, _ctx: &Context<Self>) {
+        for url in [self.attachment_url.take(), self.original_url.take()]
+            .into_iter()
+            .flatten()
+        {
+            let _ = Url::revoke_object_url(&url);
+        }
+    }
+
+    fn changed(&mut self, ctx: &Context<Self>, old_props: &Self::Properties) -> bool {
+        if ctx.props().msg.content_type == ContentType::Audio {
+            if ctx.props().msg.audio_downloaded && !old_props.msg.audio_downloaded {
+                Self::load_voice(ctx);
+            }
+            if ctx.props().audio_progress.is_some() {
+                self.voice_played = true;
+            } else if old_props.audio_progress.is_some() {
+                self.scrub = None;
+            }
+        }
         if ctx.props().msg.send_status == SendStatus::Success {
             self.show_send_fail = false;
             self.timeout = None;
             self.show_sending = false;
+        } else if is_uploading(&old_props.msg) && !is_uploading(&ctx.props().msg) {
+            // upload completed, the message is being sent now
+            self.timeout = Some(Self::send_timeout(ctx));
         }
         true
     }
@@ -368,6 +507,8 @@ impl Component for MsgItem {
                     </span>
                 </div>
             };
+        } else if self.upload_progress.is_some() {
+            send_status = self.upload_progress_html(ctx);
         } else if self.show_sending {
             send_status = html! {
                 <div class="msg-sending">
@@ -396,9 +537,9 @@ impl Component for MsgItem {
         let mut avatar = html!();
         if !self.avatar.is_empty() {
             avatar = if ctx.props().msg.is_self {
-                html!(<img class="avatar" alt="avatar" src={utils::get_avatar_url(&self.avatar)} />)
+                html!(<AvatarImg avatar={self.avatar.clone()} class="avatar" />)
             } else {
-                html!(<img class="avatar pointer" alt="avatar" src={utils::get_avatar_url(&self.avatar)} onclick={avatar_click} />)
+                html!(<AvatarImg avatar={self.avatar.clone()} class="avatar pointer" onclick={avatar_click} />)
             };
         }
 
@@ -438,9 +579,15 @@ impl Component for MsgItem {
                     {from} />)
         }
 
-        // related message
+        // related message, the voicemail relates to the call instead
         let mut content = html!(<>{content}{send_status}</>);
-        if let Some(ref local_id) = ctx.props().msg.related_msg_id {
+        if let Some(local_id) = ctx
+            .props()
+            .msg
+            .related_msg_id
+            .as_ref()
+            .filter(|_| !ctx.props().msg.voicemail)
+        {
             log::debug!("related msg: {:?}", ctx.props().msg.related_msg_id);
             let (position, float) = if ctx.props().msg.is_self {
                 ("related-msg-right", "colunm-float-right")
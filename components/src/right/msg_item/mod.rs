@@ -7,21 +7,28 @@ use gloo::timers::callback::Timeout;
 use nanoid::nanoid;
 use utils::tr;
 use wasm_bindgen::JsCast;
-use web_sys::HtmlDivElement;
+use web_sys::{HtmlDivElement, HtmlInputElement};
 use yew::prelude::*;
 use yewdux::Dispatch;
 
 use i18n::{en_us, zh_cn, LanguageType};
 use icons::{CloseIcon, ExclamationIcon, MsgLoadingIcon, MsgPhoneIcon, VideoRecordIcon};
 use sandcat_sdk::db;
+use sandcat_sdk::model::attachment::AttachmentKind;
 use sandcat_sdk::model::file_msg::FileMsg;
 use sandcat_sdk::model::friend::Friend;
 use sandcat_sdk::model::message::{InviteMsg, InviteType, Message, SendStatus};
 use sandcat_sdk::model::ContentType;
 use sandcat_sdk::model::RightContentType;
-use sandcat_sdk::state::{I18nState, MobileState, Notify, SendCallState};
+use sandcat_sdk::state::{
+    I18nState, MobileState, Notify, SendCallState, UploadProgressState, UploadStatus,
+};
 
+use crate::constant::VOICEMAIL;
 use crate::get_platform;
+use crate::right::image_viewer::ImageViewer;
+use crate::right::upload::is_uploading;
+use crate::right::util::{compute_waveform, load_attachment, WAVEFORM_BARS};
 
 pub struct MsgItem {
     avatar: AttrValue,
@@ -47,7 +54,19 @@ pub struct MsgItem {
     show_context_menu: bool,
     /// hold right click item position
     context_menu_pos: (i32, i32),
-    show_video_palyer: bool,
+    /// object url of the cached attachment, it is the thumbnail if the image has one
+    attachment_url: Option<AttrValue>,
+    /// object url of the video or the file, loaded when the user plays or opens it
+    original_url: Option<AttrValue>,
+    loading_original: bool,
+    /// uploaded size, total size and status of the pending file message
+    upload_progress: Option<(u64, u64, UploadStatus)>,
+    _upload_dis: Option<Dispatch<UploadProgressState>>,
+    /// peaks of the voice, empty before the voice is loaded
+    waveform: Vec<u8>,
+    voice_played: bool,
+    /// the position while dragging the waveform
+    scrub: Option<f64>,
 }
 
 enum AudioDownloadStage {
@@ -78,25 +97,66 @@ impl MsgItem {
         let avatar = ctx.props().avatar.clone();
         let nickname = ctx.props().nickname.clone();
         let mut timeout = None;
-        if ctx.props().msg.is_self && ctx.props().msg.send_status == SendStatus::Sending {
-            let ctx = ctx.link().clone();
-            timeout = Some(Timeout::new(3000, move || {
-                ctx.send_message(MsgItemMsg::SendTimeout);
-            }));
+        let mut upload_progress = None;
+        let mut _upload_dis = None;
+        if is_uploading(&ctx.props().msg) {
+            // the message will be sent after the upload completed
+            upload_progress = Some((0, 0, UploadStatus::Uploading));
+            _upload_dis = Some(
+                Dispatch::global()
+                    .subscribe_silent(ctx.link().callback(MsgItemMsg::UploadProgress)),
+            );
+        } else if ctx.props().msg.is_self && ctx.props().msg.send_status == SendStatus::Sending {
+            timeout = Some(Self::send_timeout(ctx));
         }
 
         let mut timer = None;
         if ctx.props().msg.content_type == ContentType::Audio {
+            if ctx.props().msg.audio_downloaded {
+                Self::load_voice(ctx);
+            }
             let ctx = ctx.link().clone();
             timer = Some(Timeout::new(350, move || {
                 ctx.send_message(MsgItemMsg::ShowAudioDownload);
             }));
         }
 
+        // the image and the poster of the video are shown in the list, load them now;
+        // the video and the file are loaded when the user plays or opens them
+        let msg = &ctx.props().msg;
+        if matches!(msg.content_type, ContentType::Image | ContentType::Video)
+            && msg.file_content.is_empty()
+        {
+            let file = FileMsg::from(&msg.content);
+            // render the thumbnail first, the original is loaded when previewing
+            let server_name = if !file.thumbnail.is_empty() {
+                file.thumbnail
+            } else if msg.content_type == ContentType::Image {
+                file.server_name
+            } else {
+                String::new()
+            };
+            if !server_name.is_empty() {
+                let conv_id = msg.friend_id.clone();
+                ctx.link().send_future(async move {
+                    match load_attachment(&server_name, &conv_id, AttachmentKind::Image, "image/*")
+                        .await
+                    {
+                        Ok(url) => MsgItemMsg::AttachmentLoaded(url.into()),
+                        Err(err) => {
+                            log::error!("load attachment error: {:?}", err);
+                            MsgItemMsg::None
+                        }
+                    }
+                });
+            }
+        }
+
         // i18n
         let mut i18n = None;
         if ctx.props().msg.content_type == ContentType::VideoCall
             || ctx.props().msg.content_type == ContentType::AudioCall
+            || ctx.props().msg.voicemail
         {
             let res = match I18nState::get().lang {
                 LanguageType::ZhCN => zh_cn::MSG_ITEM,
@@ -108,7 +168,6 @@ impl MsgItem {
         Self {
             timeout,
             show_img_preview: false,
-            show_video_palyer: false,
             show_friend_card: false,
             show_friendlist: false,
             avatar,
@@ -125,9 +184,102 @@ impl MsgItem {
             i18n,
             show_context_menu: false,
             context_menu_pos: (0, 0),
+            attachment_url: None,
+            original_url: None,
+            loading_original: false,
+            upload_progress,
+            _upload_dis,
+            waveform: Vec::new(),
+            // avoid the unplayed dot flashing before the voice is loaded
+            voice_played: true,
+            scrub: None,
         }
     }
 
+    /// load the waveform and the played flag of the voice,
+    /// the waveform is computed for the voice saved without it
+    fn load_voice(ctx: &Context<Self>) {
+        let local_id = ctx.props().msg.local_id.clone();
+        ctx.link().send_future(async move {
+            let mut voice = match db::db_ins().voices.get(&local_id).await {
+                Ok(voice) => voice,
+                Err(e) => {
+                    log::warn!("load voice error: {:?}", e);
+                    return MsgItemMsg::None;
+                }
+            };
+            if voice.waveform.is_empty() && !voice.data.is_empty() {
+                match compute_waveform(&voice.data).await {
+                    Ok(waveform) => {
+                        voice.waveform = waveform;
+                        if let Err(e) = db::db_ins().voices.save(&voice).await {
+                            log::error!("save voice error: {:?}", e);
+                        }
+                    }
+                    Err(e) => log::error!("compute waveform error: {:?}", e),
+                }
+            }
+            MsgItemMsg::VoiceLoaded(voice.waveform, voice.played)
+        });
+    }
+
+    fn send_timeout(ctx: &Context<Self>) -> Timeout {
+        let ctx = ctx.link().clone();
+        Timeout::new(3000, move || {
+            ctx.send_message(MsgItemMsg::SendTimeout);
+        })
+    }
+
+    fn upload_progress_html(&self, ctx: &Context<Self>) -> Html {
+        let Some((uploaded, total, status)) = self.upload_progress else {
+            return html!();
+        };
+        let percent = if total == 0 {
+            0
+        } else {
+            uploaded * 100 / total
+        };
+        let class = if status == UploadStatus::Paused {
+            "msg-upload-progress msg-upload-paused"
+        } else {
+            "msg-upload-progress"
+        };
+        let onclick = ctx.link().callback(|_| MsgItemMsg::CancelUpload);
+        html! {
+            <div class="msg-upload">
+                <div {class}>
+                    <div style={format!("width: {}%", percent)}></div>
+                </div>
+                <span class="pointer" {onclick}><CloseIcon /></span>
+            </div>
+        }
+    }
+
+    /// load the video or the file from the cache, download it if it is not cached
+    fn load_original(&mut self, ctx: &Context<Self>) -> bool {
+        let file = FileMsg::from(&ctx.props().msg.content);
+        if self.original_url.is_some() || self.loading_original || file.server_name.is_empty() {
+            return false;
+        }
+        let (kind, mime) = if ctx.props().msg.content_type == ContentType::Video {
+            (AttachmentKind::Video, video_mime(&file.name))
+        } else {
+            (AttachmentKind::File, "application/octet-stream")
+        };
+        let conv_id = ctx.props().msg.friend_id.clone();
+        ctx.link().send_future(async move {
+            match load_attachment(&file.server_name, &conv_id, kind, mime).await {
+                Ok(url) => MsgItemMsg::OriginalLoaded(Some(url.into())),
+                Err(err) => {
+                    log::error!("load attachment error: {:?}", err);
+                    MsgItemMsg::OriginalLoaded(None)
+                }
+            }
+        });
+        self.loading_original = true;
+        true
+    }
+
     fn get_call_hint(&self, ctx: &Context<Self>) -> String {
         let full_original = ctx.props().msg.content.clone();
         let mut parts = full_original.split("||");
@@ -141,6 +293,11 @@ impl MsgItem {
         }
     }
 
+    /// the quality summary of the call, shown as the tooltip
+    fn get_call_stats(&self, ctx: &Context<Self>) -> Option<String> {
+        ctx.props().msg.content.split("||").nth(2).map(String::from)
+    }
+
     fn make_call(&self, ctx: &Context<Self>, invite_type: InviteType) {
         Dispatch::<SendCallState>::global().reduce_mut(|s| {
             s.msg = InviteMsg {
@@ -169,6 +326,62 @@ impl MsgItem {
         }
     }
 
+    /// waveform of the voice, drag it to seek
+    fn waveform_html(&self, ctx: &Context<Self>) -> Html {
+        let progress = self
+            .scrub
+            .or(ctx.props().audio_progress)
+            .unwrap_or_default();
+        let bars = if self.waveform.is_empty() {
+            vec![u8::MAX / 4; WAVEFORM_BARS]
+        } else {
+            self.waveform.clone()
+        };
+        let count = bars.len() as f64;
+        let bars = bars
+            .into_iter()
+            .enumerate()
+            .map(|(index, peak)| {
+                let class = if (index as f64 + 0.5) / count <= progress {
+                    "voice-bar voice-bar-played"
+                } else {
+                    "voice-bar"
+                };
+                // keep the silent part visible
+                let height = (peak as f64 / u8::MAX as f64 * 100.0).max(10.0);
+                html!(<div {class} style={format!("height: {height:.0}%")}></div>)
+            })
+            .collect::<Html>();
+
+        let oninput = ctx.link().callback(|e: InputEvent| {
+            let value = e
+                .target_unchecked_into::<HtmlInputElement>()
+                .value_as_number();
+            MsgItemMsg::Scrub(value / 1000.0)
+        });
+        let onchange = ctx.link().callback(|e: Event| {
+            let value = e
+                .target_unchecked_into::<HtmlInputElement>()
+                .value_as_number();
+            MsgItemMsg::Seek(value / 1000.0)
+        });
+        let disabled = !ctx.props().msg.audio_downloaded && self.waveform.is_empty();
+        html! {
+            <div class="voice-waveform">
+                {bars}
+                <input
+                    class="voice-seek"
+                    type="range"
+                    min="0"
+                    max="1000"
+                    value={((progress * 1000.0).round() as i32).to_string()}
+                    {disabled}
+                    {oninput}
+                    {onchange} />
+            </div>
+        }
+    }
+
     fn play_audio_animation(&self) {
         if let Some(div) = self.audio_icon_node.cast::<HtmlDivElement>() {
             for index in 0..div.child_element_count() {
@@ -229,39 +442,61 @@ impl MsgItem {
             }
             ContentType::Image => {
                 let onclick = ctx.link().callback(|_| MsgItemMsg::PreviewImg);
-                get_img_html(msg, oncontextmenu, self.show_img_preview, onclick, None)
+                let viewer = if self.show_img_preview {
+                    html! {
+                        <ImageViewer
+                            user_id={ctx.props().user_id.clone()}
+                            msg={msg.clone()}
+                            conv_type={ctx.props().conv_type.clone()}
+                            close={ctx.link().callback(|_| MsgItemMsg::PreviewImg)} />
+                    }
+                } else {
+                    html!()
+                };
+                html! {
+                    <>
+                    {viewer}
+                    {get_img_html(msg, oncontextmenu, false, onclick, None, self.attachment_url.clone())}
+                    </>
+                }
             }
             ContentType::Video => {
-                let file = FileMsg::from(&msg.content);
-                let src = format!("/api/file/get/{}", file.server_name);
-
-                let onclick = ctx.link().callback(|event: MouseEvent| {
-                    event.stop_propagation();
-                    MsgItemMsg::ShowVideoPlayer
-                });
-                let mut video_player = html!();
-                if self.show_video_palyer {
-                    video_player = html! {
-                        <div class="video-player" onclick={onclick.clone()} >
-                            <span onclick={onclick.clone()}><CloseIcon/></span>
-                            <video controls={true}>
-                                <source src={src.clone()} type="video/mp4" />
-                            </video>
-                        </div>
+                let poster = if msg.file_content.is_empty() {
+                    self.attachment_url.clone()
+                } else {
+                    Some(msg.file_content.clone())
+                };
+                let video = if let Some(src) = self.original_url.clone() {
+                    html! {
+                        <video class="msg-item-video" {src} {poster}
+                            controls={true} autoplay={true} playsinline={true} />
+                    }
+                } else {
+                    let onclick = ctx.link().callback(|_| MsgItemMsg::LoadOriginal);
+                    let cover = if self.loading_original {
+                        html!(<MsgLoadingIcon />)
+                    } else {
+                        html!()
                     };
-                }
+                    html! {
+                        <div class="msg-item-video-cover pointer" {onclick}>
+                            <video class="msg-item-video" {poster} preload="none" playsinline={true} />
+                            {cover}
+                        </div>
+                    }
+                };
                 html! {
-                    <>
-                    {video_player}
-                    <div class="msg-item-content" {oncontextmenu} {onclick}>
-                        <video class="msg-item-video">
-                            <source {src} type="video/mp4" />
-                        </video>
+                    <div class="msg-item-content" {oncontextmenu}>
+                        {video}
                     </div>
-                    </>
                 }
             }
-            ContentType::File => get_file_html(msg, msg_content_classes.to_string(), oncontextmenu),
+            ContentType::File => get_file_html(
+                msg,
+                msg_content_classes.to_string(),
+                oncontextmenu,
+                ctx.link().callback(|_| MsgItemMsg::LoadOriginal),
+            ),
             ContentType::Emoji => {
                 html! {
                     <div class="msg-item-emoji" {oncontextmenu}>
@@ -272,8 +507,9 @@ impl MsgItem {
             ContentType::VideoCall => {
                 let onclick = ctx.link().callback(|_| MsgItemMsg::CallVideo);
                 let text = self.get_call_hint(ctx);
+                let title = self.get_call_stats(ctx);
                 html! {
-                    <div class={msg_content_classes} {oncontextmenu} {onclick} style="cursor: pointer; user-select: none;">
+                    <div class={msg_content_classes} {title} {oncontextmenu} {onclick} style="cursor: pointer; user-select: none;">
                         {text}
                         {"\t"}
                         <VideoRecordIcon/>
@@ -283,8 +519,9 @@ impl MsgItem {
             ContentType::AudioCall => {
                 let onclick = ctx.link().callback(|_| MsgItemMsg::CallAudio);
                 let text = self.get_call_hint(ctx);
+                let title = self.get_call_stats(ctx);
                 html! {
-                    <div class={msg_content_classes} {oncontextmenu} {onclick} style="cursor: pointer; user-select: none;">
+                    <div class={msg_content_classes} {title} {oncontextmenu} {onclick} style="cursor: pointer; user-select: none;">
                         {text}
                         {"\t"}
                          <MsgPhoneIcon />
@@ -297,26 +534,56 @@ impl MsgItem {
                 let (icon, onclick) = if msg.audio_downloaded {
                     (
                         self.voice_in_msg_icon(),
-                        Some(ctx.link().callback(|_| MsgItemMsg::PlayAudio)),
+                        Some(ctx.link().callback(|_| MsgItemMsg::PlayAudio(0.0))),
                     )
                 } else {
                     match self.download_stage {
                         AudioDownloadStage::Hidden => (
                             self.voice_in_msg_icon(),
-                            Some(ctx.link().callback(|_| MsgItemMsg::PlayAudio)),
+                            Some(ctx.link().callback(|_| MsgItemMsg::PlayAudio(0.0))),
                         ),
                         AudioDownloadStage::Downloading => (html!(<MsgLoadingIcon />), None),
-                        AudioDownloadStage::Timeout => (html!(<ExclamationIcon />), None),
+                        // click to download again
+                        AudioDownloadStage::Timeout => (
+                            html!(<ExclamationIcon />),
+                            Some(ctx.link().callback(|_| MsgItemMsg::PlayAudio(0.0))),
+                        ),
                     }
                 };
 
                 let duration = msg.audio_duration;
                 msg_content_classes.push("audio-msg-item");
+                let voicemail = if msg.voicemail {
+                    msg_content_classes.push("voicemail-msg-item");
+                    html!(<span class="voicemail-tag">{tr!(self.i18n.as_ref().unwrap(), VOICEMAIL)}</span>)
+                } else {
+                    html!()
+                };
+
+                // speed switcher for the playing voice, unplayed dot for the received one
+                let extra = if ctx.props().audio_progress.is_some() {
+                    let onclick = ctx.link().callback(|e: MouseEvent| {
+                        e.stop_propagation();
+                        MsgItemMsg::SwitchSpeed
+                    });
+                    html! {
+                        <span class="voice-speed" {onclick}>
+                            {format!("{}x", ctx.props().playback_rate)}
+                        </span>
+                    }
+                } else if !msg.is_self && !self.voice_played {
+                    html!(<span class="voice-unplayed"></span>)
+                } else {
+                    html!()
+                };
 
                 html! {
-                    <div class={msg_content_classes} {oncontextmenu} {onclick}>
-                        {icon}
+                    <div class={msg_content_classes} {oncontextmenu}>
+                        {voicemail}
+                        <span class="voice-play" {onclick}>{icon}</span>
+                        {self.waveform_html(ctx)}
                         <span>{format!("{}''", duration)}</span>
+                        {extra}
                     </div>
                 }
             }
@@ -330,6 +597,7 @@ fn get_file_html(
     msg: &Message,
     class: String,
     oncontextmenu: Option<Callback<MouseEvent>>,
+    onclick: Callback<MouseEvent>,
 ) -> Html {
     let file = FileMsg::from(&msg.content);
 
@@ -339,16 +607,15 @@ fn get_file_html(
         "Mobile"
     };
 
-    let href = format!("/api/file/get/{}", file.server_name);
     html! {
         <div {class} {oncontextmenu} >
-            <a {href} download="" class="msg-item-file-name">
+            <div class="msg-item-file-name pointer" {onclick}>
                 <div>
                     <p>{&file.name}</p>
                     <p>{&file.get_size()}</p>
                 </div>
                 {file.ext.get_icon()}
-            </a>
+            </div>
             <div class="msg-item-platform">{platform}</div>
         </div>
     }
@@ -360,15 +627,25 @@ fn get_img_html(
     show_preview: bool,
     onclick: Callback<MouseEvent>,
     nickname: Option<String>,
+    cached_url: Option<AttrValue>,
 ) -> Html {
-    let img_url = if msg.file_content.is_empty() {
-        let file = FileMsg::from(&msg.content);
-        &AttrValue::from(format!("/api/file/get/{}", file.server_name))
+    let file = FileMsg::from(&msg.content);
+    let img_url = if !msg.file_content.is_empty() {
+        msg.file_content.clone()
+    } else if let Some(url) = cached_url {
+        url
+    } else if !file.thumbnail.is_empty() {
+        AttrValue::from(format!("/api/file/get/{}", file.thumbnail))
     } else {
-        &msg.file_content.clone()
+        AttrValue::from(format!("/api/file/get/{}", file.server_name))
     };
 
-    let src = img_url.clone();
+    // preview the original
+    let src = if file.thumbnail.is_empty() || file.server_name.is_empty() {
+        img_url.clone()
+    } else {
+        AttrValue::from(format!("/api/file/get/{}", file.server_name))
+    };
 
     let img_preview = if show_preview {
         html! {
@@ -391,3 +668,14 @@ fn get_img_html(
     </>
     }
 }
+
+/// the recorder saves webm if the browser supports it, mp4 otherwise,
+/// so the type of the video comes from the extension of its name
+fn video_mime(name: &str) -> &'static str {
+    match name.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()) {
+        Some(ext) if ext == "webm" => "video/webm",
+        Some(ext) if ext == "ogv" || ext == "ogg" => "video/ogg",
+        Some(ext) if ext == "mov" => "video/quicktime",
+        _ => "video/mp4",
+    }
+}
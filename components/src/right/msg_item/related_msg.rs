@@ -129,14 +129,16 @@ impl Component for RelatedMsg {
             ContentType::Image => {
                 let onclick = ctx.link().callback(|_| Self::Message::PreviewImg);
                 let nickname = Some(format!("{}:  ", ctx.props().nickname));
-                get_img_html(msg, None, self.show_img_preview, onclick, nickname)
+                get_img_html(msg, None, self.show_img_preview, onclick, nickname, None)
             }
             ContentType::Video => html! {
                 <video class="msg-item-video">
                     <source src={&msg.content} type="video/mp4" />
                 </video>
             },
-            ContentType::File => get_file_html(msg, "msg-item-text".to_string(), None),
+            ContentType::File => {
+                get_file_html(msg, "msg-item-text".to_string(), None, Callback::noop())
+            }
             ContentType::Emoji => {
                 html! {
                     <img class="emoji" alt="emoji" src={msg.content.clone()} />
@@ -1,5 +1,6 @@
 use std::rc::Rc;
 
+use gloo::timers::callback::Timeout;
 use gloo::utils::document;
 use indexmap::IndexMap;
 use log::error;
@@ -21,7 +22,7 @@ use sandcat_sdk::model::message::SendStatus;
 use sandcat_sdk::model::message::{GroupMsg, Message, Msg, SingleCall};
 use sandcat_sdk::model::notification::Notification;
 use sandcat_sdk::model::{ContentType, ItemInfo, ItemInfoBox, RightContentType};
-use sandcat_sdk::state::AudioDownloadedState;
+use sandcat_sdk::state::{AudioDownloadedState, JumpToMsgState};
 use sandcat_sdk::state::{
     MobileState, RecMessageState, RefreshMsgListState, SendAudioMsgState, SendMessageState,
     SendResultState,
@@ -46,13 +47,20 @@ pub struct MessageList {
     new_msg_count: u32,
     is_black: bool,
     audio_on_stop: Option<Closure<dyn FnMut(Event)>>,
+    audio_on_time_update: Option<Closure<dyn FnMut(Event)>>,
     audio_data_url: Option<String>,
+    /// played ratio of the playing voice
+    audio_progress: f64,
+    playback_rate: f64,
     is_mobile: bool,
     need_set_observer: bool,
     mouse_move: Option<Closure<dyn FnMut(MouseEvent)>>,
     mouse_up: Option<Closure<dyn FnMut(MouseEvent)>>,
     observer: Option<IntersectionObserver>,
     observer_callback: Option<Closure<dyn FnMut(Vec<IntersectionObserverEntry>)>>,
+    /// local id of the message to scroll to, older pages are loaded until it is found
+    jump_to: Option<AttrValue>,
+    highlight_timer: Option<Timeout>,
 
     // listen sync offline message, query message list
     _sync_msg_dis: Dispatch<RefreshMsgListState>,
@@ -64,6 +72,7 @@ pub struct MessageList {
     _sent_audio_dis: Dispatch<SendAudioMsgState>,
     // listen audio downloaded state when content type is audio
     _audio_dis: Dispatch<AudioDownloadedState>,
+    _jump_dis: Dispatch<JumpToMsgState>,
 }
 
 #[derive(Debug)]
@@ -78,14 +87,19 @@ pub enum MessageListMsg {
     SyncOfflineMsg,
     GoBottom,
     QueryFriend(Option<Box<dyn ItemInfo>>),
-    PlayAudio((AttrValue, Vec<u8>)),
+    /// local id, voice data and the start position in ratio
+    PlayAudio((AttrValue, Vec<u8>, f64)),
     AudioOnStop,
+    AudioTimeUpdate,
+    SeekAudio(f64),
+    SwitchSpeed,
     AudioDownloaded(Rc<AudioDownloadedState>),
     DelItem(AttrValue),
     MsgSendTimeout(AttrValue),
     ResizerMouseDown(MouseEvent),
     ResizerMouseUp,
     OnScroll(WheelEvent),
+    JumpTo(Rc<JumpToMsgState>),
 }
 
 /// 接收对方用户信息即可，
@@ -132,6 +146,10 @@ impl Component for MessageList {
 
         let audio_dis = Dispatch::global()
             .subscribe_silent(ctx.link().callback(MessageListMsg::AudioDownloaded));
+        let _jump_dis: Dispatch<JumpToMsgState> =
+            Dispatch::global().subscribe_silent(ctx.link().callback(MessageListMsg::JumpTo));
+        // the jump may be requested from the contacts before the list is created
+        let jump_to = Some(_jump_dis.get().local_id.clone()).filter(|id| !id.is_empty());
         Self {
             list: IndexMap::new(),
             is_playing_audio: AttrValue::default(),
@@ -148,12 +166,17 @@ impl Component for MessageList {
             need_set_observer: true,
             is_black: false,
             audio_on_stop: None,
+            audio_on_time_update: None,
             audio_data_url: None,
+            audio_progress: 0.0,
+            playback_rate: 1.0,
             is_mobile: MobileState::is_mobile(),
             mouse_move: None,
             mouse_up: None,
             observer: None,
             observer_callback: None,
+            jump_to,
+            highlight_timer: None,
 
             _sync_msg_dis,
             _rec_msg_dis,
@@ -161,6 +184,7 @@ impl Component for MessageList {
             _sent_audio_dis,
             _sent_msg_dis,
             _audio_dis: audio_dis,
+            _jump_dis,
         }
     }
 
@@ -184,6 +208,15 @@ impl Component for MessageList {
                 self.scroll_state = ScrollState::None;
                 // list.reverse();
                 self.list.extend(list);
+                if let Some(id) = self.jump_to.as_ref() {
+                    if !self.list.contains_key(id) {
+                        if self.is_all {
+                            self.finish_jump();
+                        } else {
+                            self.query(ctx);
+                        }
+                    }
+                }
                 true
             }
             MessageListMsg::SendFile(msg) => {
@@ -197,10 +230,8 @@ impl Component for MessageList {
                 self.handle_rec_msg(ctx, msg, friend_id)
             }
             MessageListMsg::SentMsg(msg_state) => {
-                if let Msg::Single(msg) = &msg_state.msg {
-                    if msg.content_type == ContentType::Audio {
-                        return false;
-                    }
+                if msg_state.msg.is_audio() {
+                    return false;
                 }
                 let msg = msg_state.msg.clone();
                 self.handle_rec_msg(ctx, msg, friend_id)
@@ -234,20 +265,47 @@ impl Component for MessageList {
                 }
                 true
             }
-            MessageListMsg::PlayAudio((id, data)) => {
-                self.play_audio(ctx, id, data);
-                false
+            MessageListMsg::PlayAudio((id, data, start)) => {
+                self.play_audio(ctx, id, data, start);
+                true
             }
             MessageListMsg::AudioOnStop => {
-                self.is_playing_audio = AttrValue::default();
-                self.audio_on_stop = None;
-                if let Some(ref url) = self.audio_data_url {
-                    if let Err(e) = Url::revoke_object_url(url) {
-                        error!("revoke object url error: {:?}", e);
-                    };
+                let finished = std::mem::take(&mut self.is_playing_audio);
+                self.audio_progress = 0.0;
+                self.revoke_audio_url();
+                self.play_next_audio(ctx, &finished);
+                true
+            }
+            MessageListMsg::AudioTimeUpdate => {
+                let Some(audio) = self.audio_ref.cast::<HtmlAudioElement>() else {
+                    return false;
+                };
+                let duration = self.audio_duration(&audio);
+                if duration <= 0.0 {
+                    return false;
                 }
-                self.audio_data_url = None;
-                false
+                self.audio_progress = (audio.current_time() / duration).min(1.0);
+                true
+            }
+            MessageListMsg::SeekAudio(ratio) => {
+                if let Some(audio) = self.audio_ref.cast::<HtmlAudioElement>() {
+                    let duration = self.audio_duration(&audio);
+                    audio.set_current_time(ratio.clamp(0.0, 1.0) * duration);
+                    self.audio_progress = ratio.clamp(0.0, 1.0);
+                }
+                true
+            }
+            MessageListMsg::SwitchSpeed => {
+                self.playback_rate = match self.playback_rate {
+                    r if r < 1.5 => 1.5,
+                    r if r < 2.0 => 2.0,
+                    _ => 1.0,
+                };
+                if let Some(audio) = self.audio_ref.cast::<HtmlAudioElement>() {
+                    audio.set_default_playback_rate(self.playback_rate);
+                    audio.set_playback_rate(self.playback_rate);
+                }
+                true
             }
             MessageListMsg::AudioDownloaded(state) => {
                 if let Some(item) = self.list.get_mut(&state.local_id) {
@@ -343,6 +401,19 @@ impl Component for MessageList {
                 }
                 false
             }
+            MessageListMsg::JumpTo(state) => {
+                if state.local_id.is_empty() {
+                    return false;
+                }
+                self.jump_to = Some(state.local_id.clone());
+                if self.list.contains_key(&state.local_id) {
+                    return true;
+                }
+                if !self.is_all {
+                    self.query(ctx);
+                }
+                false
+            }
         }
     }
 
@@ -413,6 +484,13 @@ impl Component for MessageList {
             }
         }
 
+        if let Some(local_id) = self.jump_to.as_ref() {
+            if let Some(msg) = self.list.get(local_id) {
+                self.scroll_to(msg.create_time);
+                self.finish_jump();
+            }
+        }
+
         // set intersection observer event
         if self.need_set_observer {
             self.set_observer(ctx);
@@ -421,6 +499,22 @@ impl Component for MessageList {
 }
 
 impl MessageList {
+    /// the id of the message item is the create time
+    fn scroll_to(&mut self, create_time: i64) {
+        if let Some(item) = document().get_element_by_id(&create_time.to_string()) {
+            item.scroll_into_view();
+            let _ = item.class_list().add_1("msg-item-highlight");
+            self.highlight_timer = Some(Timeout::new(1500, move || {
+                let _ = item.class_list().remove_1("msg-item-highlight");
+            }));
+        }
+    }
+
+    fn finish_jump(&mut self) {
+        self.jump_to = None;
+        self._jump_dis.set(JumpToMsgState::default());
+    }
+
     fn set_observer(&mut self, ctx: &Context<Self>) {
         let load = self.load_ref.cast::<HtmlDivElement>().unwrap();
         let callback = if let Some(callback) = self.observer_callback.take() {
@@ -561,6 +655,7 @@ impl MessageList {
         self.is_all = false;
         self.friend = None;
         self.scroll_state = ScrollState::None;
+        self.jump_to = None;
     }
 
     fn insert_msg(&mut self, msg: Message, friend_id: AttrValue) -> bool {
@@ -572,6 +667,8 @@ impl MessageList {
         // user send msg but failed and resend again
         if let Some(item) = self.list.get_mut(&msg.local_id) {
             item.send_status = msg.send_status;
+            // the file message content is filled after the upload completed
+            item.content = msg.content;
         } else {
             self.list.shift_insert(0, msg.local_id.clone(), msg);
         }
@@ -638,13 +735,18 @@ impl MessageList {
             | Msg::ReadNotice(_)
             | Msg::SingleDeliveredNotice(_)
             | Msg::OfflineSync(_)
+            | Msg::GroupCall(_)
+            | Msg::DirectFile(_)
             | Msg::RelationshipRes(_)
-            | Msg::FriendshipDeliveredNotice(_) => false,
+            | Msg::FriendshipDeliveredNotice(_)
+            | Msg::Notification(_)
+            | Msg::Service(_)
+            | Msg::Unknown(_) => false,
             // todo query list item , update state
             Msg::ServerRecResp(_) => false,
-            Msg::RecRelationshipDel((friend_id, _)) => {
+            Msg::RecRelationshipDel((friend_id, _)) | Msg::RecRelationshipBlack((friend_id, _)) => {
                 log::debug!(
-                    "rec friendship del in msg list {}, ctx friend id {:?}",
+                    "rec friendship del or black in msg list {}, ctx friend id {:?}",
                     friend_id,
                     ctx.props().friend.id()
                 );
@@ -658,16 +760,20 @@ impl MessageList {
         }
     }
 
-    fn play_audio(&mut self, ctx: &Context<Self>, id: AttrValue, data: Vec<u8>) {
+    fn play_audio(&mut self, ctx: &Context<Self>, id: AttrValue, data: Vec<u8>, start: f64) {
         let audio = self.audio_ref.clone();
         // query audio data
         if let Some(audio) = audio.cast::<HtmlAudioElement>() {
-            if self.is_playing_audio == id {
+            if self.is_playing_audio == id && start == 0.0 {
                 let _ = audio.pause();
                 audio.set_src("");
                 self.is_playing_audio = AttrValue::default();
+                self.audio_progress = 0.0;
+                self.revoke_audio_url();
                 return;
             }
+            // the previous voice is replaced
+            self.revoke_audio_url();
             self.is_playing_audio = id;
             let u8_array = js_sys::Uint8Array::from(data.as_slice());
 
@@ -696,6 +802,13 @@ impl MessageList {
             };
 
             audio.set_src(&data_url);
+            // loading the new source resets the playback rate to the default one
+            audio.set_default_playback_rate(self.playback_rate);
+            audio.set_playback_rate(self.playback_rate);
+            self.audio_progress = start;
+            if start > 0.0 {
+                audio.set_current_time(start * self.audio_duration(&audio));
+            }
 
             self.audio_data_url = Some(data_url);
 
@@ -706,8 +819,16 @@ impl MessageList {
                     ctx.send_message(MessageListMsg::AudioOnStop);
                 }) as Box<dyn FnMut(Event)>)
             });
-
             audio.set_onended(Some(on_stop.as_ref().unchecked_ref()));
+
+            let on_time_update = self.audio_on_time_update.get_or_insert_with(|| {
+                let ctx = ctx.link().clone();
+                Closure::wrap(Box::new(move |_: Event| {
+                    ctx.send_message(MessageListMsg::AudioTimeUpdate);
+                }) as Box<dyn FnMut(Event)>)
+            });
+            audio.set_ontimeupdate(Some(on_time_update.as_ref().unchecked_ref()));
+
             // todo handle error
             if let Err(e) = audio.play() {
                 error!("play audio error: {:?}", e);
@@ -716,6 +837,61 @@ impl MessageList {
         }
     }
 
+    /// the recorded webm has no duration in its metadata, use the duration of the message
+    fn audio_duration(&self, audio: &HtmlAudioElement) -> f64 {
+        let duration = audio.duration();
+        if duration.is_finite() && duration > 0.0 {
+            return duration;
+        }
+        self.list
+            .get(&self.is_playing_audio)
+            .map(|msg| msg.audio_duration as f64)
+            .unwrap_or_default()
+    }
+
+    fn revoke_audio_url(&mut self) {
+        if let Some(url) = self.audio_data_url.take() {
+            if let Err(e) = Url::revoke_object_url(&url) {
+                error!("revoke object url error: {:?}", e);
+            };
+        }
+    }
+
+    /// play the next voice received from others which has not been played,
+    /// the newer message has the smaller index
+    fn play_next_audio(&self, ctx: &Context<Self>, finished: &AttrValue) {
+        let Some(index) = self.list.get_index_of(finished) else {
+            return;
+        };
+        let candidates: Vec<AttrValue> = self
+            .list
+            .values()
+            .take(index)
+            .rev()
+            .filter(|msg| msg.content_type == ContentType::Audio && !msg.is_self)
+            .map(|msg| msg.local_id.clone())
+            .collect();
+        if candidates.is_empty() {
+            return;
+        }
+        ctx.link().send_future_batch(async move {
+            for id in candidates {
+                let Ok(mut voice) = db::db_ins().voices.get(&id).await else {
+                    continue;
+                };
+                if voice.played {
+                    continue;
+                }
+                voice.played = true;
+                if let Err(e) = db::db_ins().voices.save(&voice).await {
+                    error!("save voice error: {:?}", e);
+                }
+                return vec![MessageListMsg::PlayAudio((id, voice.data, 0.0))];
+            }
+            vec![]
+        });
+    }
+
     fn get_list_html(&self, ctx: &Context<Self>) -> Html {
         let friend_avatar = ctx.props().friend.avatar();
         let friend_nickname = ctx.props().friend.name();
@@ -730,8 +906,16 @@ impl MessageList {
                     (&friend_avatar, &friend_nickname)
                 };
                 let mut play_audio = None;
+                let mut audio_progress = None;
+                let mut seek_audio = None;
+                let mut switch_speed = None;
                 if msg.content_type == ContentType::Audio {
                     play_audio = Some(ctx.link().callback(MessageListMsg::PlayAudio));
+                    if self.is_playing_audio == *key {
+                        audio_progress = Some(self.audio_progress);
+                        seek_audio = Some(ctx.link().callback(MessageListMsg::SeekAudio));
+                        switch_speed = Some(ctx.link().callback(|_| MessageListMsg::SwitchSpeed));
+                    }
                 }
                 let del_item = ctx.link().callback(MessageListMsg::DelItem);
 
@@ -745,6 +929,10 @@ impl MessageList {
                         nickname={nickname}
                         conv_type={ctx.props().conv_type.clone()}
                         {play_audio}
+                        {audio_progress}
+                        {seek_audio}
+                        {switch_speed}
+                        playback_rate={self.playback_rate}
                         {del_item}
                         {send_timeout}
                         key={key.as_str()}
@@ -10,21 +10,25 @@ use sandcat_sdk::api;
 use sandcat_sdk::db;
 use sandcat_sdk::model::friend::Friend;
 use sandcat_sdk::model::group::{Group, GroupDelete};
-use sandcat_sdk::model::RightContentType;
+use sandcat_sdk::model::{ComponentType, CurrentItem, RightContentType};
 use sandcat_sdk::pb::message::FriendInfo;
 use sandcat_sdk::state::MobileState;
 use sandcat_sdk::state::Notify;
-use sandcat_sdk::state::{ItemType, RemoveConvState, RemoveFriendState};
+use sandcat_sdk::state::{
+    ComponentTypeState, ConvState, ItemType, RemoveConvState, RemoveFriendState,
+};
 use utils::tr;
 use yewdux::Dispatch;
 
 use crate::action::Action;
+use crate::avatar_img::AvatarImg;
 use crate::constant::ACCOUNT;
 use crate::constant::ANNOUNCEMENT;
 use crate::constant::REGION;
 use crate::constant::REMARK;
 use crate::constant::SIGNATURE;
 use crate::right::set_drawer::SetDrawer;
+use crate::right::shared_content::SharedContent;
 
 use super::util;
 
@@ -45,6 +49,7 @@ pub enum PostCardMsg {
     ShowSetDrawer,
     QueryFriendByHttp(QueryState<FriendInfo>),
     UpdateRemark,
+    OpenConv,
 }
 
 pub enum QueryState<T> {
@@ -158,6 +163,17 @@ impl Component for PostCard {
                 }
                 false
             }
+            PostCardMsg::OpenConv => {
+                Dispatch::<ComponentTypeState>::global()
+                    .reduce_mut(|s| s.component_type = ComponentType::Messages);
+                Dispatch::<ConvState>::global().reduce_mut(|s| {
+                    s.conv = CurrentItem {
+                        item_id: ctx.props().id.clone(),
+                        content_type: ctx.props().conv_type.clone(),
+                    }
+                });
+                false
+            }
         }
     }
 
@@ -207,7 +223,7 @@ impl PostCard {
         let avatar = iter
             .map(|v| {
                 html! {
-                    <img class="avatar" alt="avatar" src={utils::get_avatar_url(v)} />
+                    <AvatarImg avatar={v.to_string()} class="avatar" />
                 }
             })
             .collect::<Html>();
@@ -449,6 +465,12 @@ impl PostCard {
                     <b>{tr!(self.i18n, SIGNATURE)}{":\t\t"}</b>{friend.signature.clone()}
                 </div>
 
+                <SharedContent
+                    conv_type={ctx.props().conv_type.clone()}
+                    id={ctx.props().id.clone()}
+                    on_jump={ctx.link().callback(|_| PostCardMsg::OpenConv)}
+                    lang={ctx.props().lang} />
+
                 <Action friend_id={&friend.friend_id}
                     user_id={&ctx.props().user_id}
                     avatar={&ctx.props().avatar}
@@ -498,6 +520,12 @@ impl PostCard {
                     <b>{tr!(self.i18n, ANNOUNCEMENT)}{":\t\t"}</b>{&group.announcement}
                 </div>
 
+                <SharedContent
+                    conv_type={ctx.props().conv_type.clone()}
+                    id={ctx.props().id.clone()}
+                    on_jump={ctx.link().callback(|_| PostCardMsg::OpenConv)}
+                    lang={ctx.props().lang} />
+
                 <Action friend_id={&group.id}
                     user_id={&ctx.props().user_id}
                     avatar={&ctx.props().avatar}
@@ -15,7 +15,7 @@ use i18n::{en_us, zh_cn, LanguageType};
 use sandcat_sdk::{
     error::Error,
     model::{notification::Notification, voice::Voice},
-    state::{I18nState, MobileState, Notify},
+    state::{I18nState, MediaDeviceState, MobileState, Notify},
 };
 use utils::tr;
 
@@ -107,7 +107,8 @@ impl Component for Recorder {
                 // prepare audio stream
                 self.record_state = RecorderState::Prepare;
                 ctx.link().send_future(async {
-                    match utils::get_audio_stream().await {
+                    let devices = MediaDeviceState::get();
+                    match utils::get_audio_stream(devices.microphone()).await {
                         Ok(stream) => RecorderMsg::Start(stream),
                         Err(e) => RecorderMsg::PrepareError(e),
                     }
@@ -3,14 +3,14 @@ use std::rc::Rc;
 
 use wasm_bindgen::{JsCast, JsValue};
 use web_sys::{
-    ClipboardEvent, DataTransferItem, DataTransferItemList, HtmlElement, HtmlInputElement,
+    ClipboardEvent, DataTransferItem, DataTransferItemList, File, HtmlElement, HtmlInputElement,
     HtmlTextAreaElement,
 };
 use yew::prelude::*;
 use yewdux::Dispatch;
 
 use i18n::{en_us, zh_cn, LanguageType};
-use icons::{CloseIcon, FileIcon, KeyboardIcon, SmileIcon, VoiceIcon};
+use icons::{CloseIcon, FileIcon, KeyboardIcon, SmileIcon, VideoRecordIcon, VoiceIcon};
 use sandcat_sdk::model::file_msg::FileMsg;
 use sandcat_sdk::model::voice::Voice;
 use sandcat_sdk::{
@@ -23,6 +23,7 @@ use utils::tr;
 use crate::right::emoji::Emoji;
 use crate::right::recorder::Recorder;
 use crate::right::sender::INPUT_MAX_LEN;
+use crate::right::video_recorder::VideoRecorder;
 
 use super::{FileListItem, FileType, Sender};
 
@@ -34,7 +35,10 @@ pub enum SenderMsg {
     SendFileIconClicked,
     FileInputChanged(Event),
     SendFile,
-    FileOnload(FileMsg, ContentType, JsValue),
+    /// send the files to the friend over the data channel
+    SendFileDirectly,
+    /// the last one is whether to send the file directly
+    FileOnload(FileMsg, ContentType, JsValue, File, bool),
     OnEnterKeyUp(KeyboardEvent),
     OnEnterKeyDown(KeyboardEvent),
     OnPaste(Event),
@@ -45,6 +49,8 @@ pub enum SenderMsg {
     OnTextInput,
     VoiceIconClicked,
     SendVoice(Voice),
+    ShowVideoRecorder,
+    SendVideo(File),
     RelatedMsgStateChanged(Rc<RelatedMsgState>),
     DelRelatMsg,
 }
@@ -92,6 +98,7 @@ impl Component for Sender {
             enter_key_down: 0,
             is_key_down: false,
             is_voice_mode: false,
+            show_video_recorder: false,
             related_msg: None,
             _related_msg_state,
         }
@@ -126,7 +133,7 @@ impl Component for Sender {
                     let file_list = file_list;
                     for i in 0..file_list.length() {
                         if let Some(file) = file_list.get(i) {
-                            self.send_file(ctx, file);
+                            self.send_file(ctx, file, false);
                         }
                     }
                 }
@@ -135,13 +142,22 @@ impl Component for Sender {
             SenderMsg::SendFile => {
                 let file_list = mem::take(&mut self.file_list);
                 for item in file_list.into_iter() {
-                    self.send_file(ctx, item.file);
+                    self.send_file(ctx, item.file, false);
                 }
 
                 self.show_file_sender = false;
                 false
             }
-            SenderMsg::FileOnload(file, content_type, file_content) => {
+            SenderMsg::SendFileDirectly => {
+                let file_list = mem::take(&mut self.file_list);
+                for item in file_list.into_iter() {
+                    self.send_file(ctx, item.file, true);
+                }
+
+                self.show_file_sender = false;
+                false
+            }
+            SenderMsg::FileOnload(file, content_type, file_content, raw_file, direct) => {
                 let time = chrono::Utc::now().timestamp_millis();
                 let file_content = if let Some(file_content) = file_content.as_string() {
                     file_content.into()
@@ -164,7 +180,7 @@ impl Component for Sender {
                     ..Default::default()
                 };
 
-                self.store_send_msg(ctx, msg);
+                self.store_pending_file_msg(ctx, msg, raw_file, direct);
                 true
             }
 
@@ -215,6 +231,14 @@ impl Component for Sender {
                 }
                 true
             }
+            SenderMsg::SendVideoCall if ctx.props().conv_type == RightContentType::Group => {
+                self.send_group_call(ctx, InviteType::Video);
+                false
+            }
+            SenderMsg::SendAudioCall if ctx.props().conv_type == RightContentType::Group => {
+                self.send_group_call(ctx, InviteType::Audio);
+                false
+            }
             SenderMsg::SendVideoCall => {
                 Dispatch::<SendCallState>::global().reduce_mut(|s| {
                     s.msg = InviteMsg {
@@ -297,6 +321,14 @@ impl Component for Sender {
                 self.send_voice_msg(ctx, voice);
                 false
             }
+            SenderMsg::ShowVideoRecorder => {
+                self.show_video_recorder = !self.show_video_recorder;
+                true
+            }
+            SenderMsg::SendVideo(file) => {
+                self.send_file(ctx, file, false);
+                false
+            }
             SenderMsg::RelatedMsgStateChanged(state) => {
                 if state.msg.is_none() {
                     self.related_msg = None;
@@ -361,6 +393,16 @@ impl Component for Sender {
             html!()
         };
 
+        let video_recorder_html = if self.show_video_recorder {
+            html! {
+                <VideoRecorder
+                    send_video={ctx.link().callback(SenderMsg::SendVideo)}
+                    close={ctx.link().callback(|_| SenderMsg::ShowVideoRecorder)} />
+            }
+        } else {
+            html!()
+        };
+
         // related message
         let mut related_msg_html = html!();
 
@@ -377,6 +419,7 @@ impl Component for Sender {
                 {file_sender_html}
                 <div class={sender_class} ref={self.sender_ref.clone()} >
                     {emojis}
+                    {video_recorder_html}
                     <div class="send-bar">
                         <div class="send-bar-left">
                             <span onclick={ctx.link().callback(|event: MouseEvent| {
@@ -395,6 +438,9 @@ impl Component for Sender {
                             <span onclick={ctx.link().callback(|_| SenderMsg::VoiceIconClicked)}>
                                 {voice_icon_html}
                             </span>
+                            <span onclick={ctx.link().callback(|_| SenderMsg::ShowVideoRecorder)}>
+                                <VideoRecordIcon />
+                            </span>
                         </div>
                         <div class="send-bar-right">
                             {phone_call_icons}
@@ -0,0 +1,317 @@
+use futures_channel::oneshot;
+use gloo::utils::{document, window};
+use js_sys::{Array, Uint8Array};
+use log::error;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    Blob, CanvasRenderingContext2d, File, FilePropertyBag, HtmlCanvasElement, ImageBitmap,
+    ImageBitmapOptions, ImageOrientation,
+};
+
+use sandcat_sdk::{
+    api, db,
+    error::{Error, Result},
+    model::attachment::{Attachment, AttachmentKind},
+};
+
+/// the longest side of the uploaded image
+const MAX_SIDE: u32 = 2048;
+/// the longest side of the thumbnail rendered in the message list
+pub(super) const THUMBNAIL_SIDE: u32 = 320;
+const IMAGE_QUALITY: f64 = 0.85;
+pub(super) const THUMBNAIL_QUALITY: f64 = 0.7;
+/// images smaller than this and needing no resize or rotation are sent as they are
+const KEEP_ORIGINAL_SIZE: f64 = 512.0 * 1024.0;
+
+pub struct CompressedImage {
+    /// the downscaled and re-encoded image, it is the original file if nothing changed
+    pub file: File,
+    pub thumbnail: File,
+    /// data url of the thumbnail, used to render the pending message
+    pub thumbnail_url: String,
+}
+
+/// downscale the image, fix the exif orientation and generate the thumbnail
+pub async fn compress_image(file: &File) -> Result<CompressedImage> {
+    let data = read_bytes(file).await?;
+    // the rotated image is re-encoded, so that it shows right without the exif
+    let rotated = exif_orientation(&data) != 1;
+    let bitmap = load_bitmap(file).await?;
+    let (width, height) = (bitmap.width(), bitmap.height());
+    if width == 0 || height == 0 {
+        return Err(Error::internal_with_details("image has no size"));
+    }
+
+    // png may be transparent, keep it
+    let mime = if file.type_() == "image/png" {
+        "image/png"
+    } else {
+        "image/jpeg"
+    };
+
+    let need_resize = width.max(height) > MAX_SIDE;
+    let file_out = if !need_resize && !rotated && file.size() <= KEEP_ORIGINAL_SIZE {
+        file.clone()
+    } else {
+        let canvas = draw(&bitmap, MAX_SIDE)?;
+        let blob = to_blob(&canvas, mime, IMAGE_QUALITY).await?;
+        // re-encoding a small image may make it bigger
+        if !need_resize && !rotated && blob.size() >= file.size() {
+            file.clone()
+        } else {
+            to_file(&blob, &file.name(), mime)?
+        }
+    };
+
+    let canvas = draw(&bitmap, THUMBNAIL_SIDE)?;
+    bitmap.close();
+    let thumbnail = to_blob(&canvas, mime, THUMBNAIL_QUALITY).await?;
+    let thumbnail = to_file(&thumbnail, &format!("thumb_{}", file.name()), mime)?;
+    let thumbnail_url = canvas
+        .to_data_url_with_type_and_encoder_options(mime, &JsValue::from_f64(THUMBNAIL_QUALITY))?;
+
+    Ok(CompressedImage {
+        file: file_out,
+        thumbnail,
+        thumbnail_url,
+    })
+}
+
+async fn read_bytes(blob: &Blob) -> Result<Vec<u8>> {
+    let buffer = JsFuture::from(blob.array_buffer()).await?;
+    Ok(Uint8Array::new(&buffer).to_vec())
+}
+
+/// decode the image with the exif orientation applied by the browser,
+/// the width and height of the bitmap are the rotated ones
+async fn load_bitmap(file: &File) -> Result<ImageBitmap> {
+    let mut options = ImageBitmapOptions::new();
+    options.image_orientation(ImageOrientation::FromImage);
+    let promise =
+        window().create_image_bitmap_with_blob_and_image_bitmap_options(file, &options)?;
+    let bitmap = JsFuture::from(promise)
+        .await
+        .map_err(|_| Error::internal_with_details("load image error"))?;
+    Ok(bitmap.dyn_into::<ImageBitmap>()?)
+}
+
+/// draw the image on a new canvas whose longest side is not greater than `max_side`
+fn draw(bitmap: &ImageBitmap, max_side: u32) -> Result<HtmlCanvasElement> {
+    let (width, height) = (bitmap.width(), bitmap.height());
+    let scale = (max_side as f64 / width.max(height) as f64).min(1.0);
+    let w = (width as f64 * scale).round().max(1.0);
+    let h = (height as f64 * scale).round().max(1.0);
+
+    let canvas = document()
+        .create_element("canvas")?
+        .dyn_into::<HtmlCanvasElement>()
+        .map_err(JsValue::from)?;
+    canvas.set_width(w as u32);
+    canvas.set_height(h as u32);
+
+    let context = canvas
+        .get_context("2d")?
+        .ok_or_else(|| Error::internal_with_details("get canvas context error"))?
+        .dyn_into::<CanvasRenderingContext2d>()
+        .map_err(JsValue::from)?;
+    context.draw_image_with_image_bitmap_and_dw_and_dh(bitmap, 0.0, 0.0, w, h)?;
+    Ok(canvas)
+}
+
+pub(super) async fn to_blob(canvas: &HtmlCanvasElement, mime: &str, quality: f64) -> Result<Blob> {
+    let (tx, rx) = oneshot::channel();
+    let callback = Closure::once(move |blob: JsValue| {
+        let _ = tx.send(blob);
+    });
+    canvas.to_blob_with_type_and_encoder_options(
+        callback.as_ref().unchecked_ref(),
+        mime,
+        &JsValue::from_f64(quality),
+    )?;
+    let blob = rx
+        .await
+        .map_err(|_| Error::internal_with_details("encode image canceled"))?;
+    blob.dyn_into::<Blob>()
+        .map_err(|_| Error::internal_with_details("encode image error"))
+}
+
+pub(super) fn to_file(blob: &Blob, name: &str, mime: &str) -> Result<File> {
+    let array = Array::new_with_length(1);
+    array.set(0, blob.into());
+    let mut options = FilePropertyBag::new();
+    options.type_(mime);
+    Ok(File::new_with_blob_sequence_and_options(
+        &array, name, &options,
+    )?)
+}
+
+/// read the orientation tag from the exif of a jpeg,
+/// return 1 which means no transform if there is no such tag
+fn exif_orientation(data: &[u8]) -> u8 {
+    // jpeg starts with SOI marker
+    if data.len() < 4 || data[0] != 0xFF || data[1] != 0xD8 {
+        return 1;
+    }
+
+    let mut offset = 2;
+    while offset + 4 <= data.len() {
+        if data[offset] != 0xFF {
+            return 1;
+        }
+        let marker = data[offset + 1];
+        let len = u16::from_be_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        // APP1 with exif header
+        if marker == 0xE1 && data[offset + 4..].starts_with(b"Exif\0\0") {
+            let end = (offset + 2 + len).min(data.len());
+            if end <= offset + 10 {
+                return 1;
+            }
+            return parse_tiff_orientation(&data[offset + 10..end]).unwrap_or(1);
+        }
+        // start of scan, there is no more metadata
+        if marker == 0xDA {
+            return 1;
+        }
+        offset += 2 + len;
+    }
+    1
+}
+
+fn parse_tiff_orientation(tiff: &[u8]) -> Option<u8> {
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let read_u16 = |pos: usize| -> Option<u16> {
+        let bytes = [*tiff.get(pos)?, *tiff.get(pos + 1)?];
+        Some(if little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+    let read_u32 = |pos: usize| -> Option<u32> {
+        let bytes = [
+            *tiff.get(pos)?,
+            *tiff.get(pos + 1)?,
+            *tiff.get(pos + 2)?,
+            *tiff.get(pos + 3)?,
+        ];
+        Some(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+
+    let ifd = read_u32(4)? as usize;
+    let count = read_u16(ifd)? as usize;
+    for i in 0..count {
+        let entry = ifd + 2 + i * 12;
+        // 0x0112 is the orientation tag
+        if read_u16(entry)? == 0x0112 {
+            let value = read_u16(entry + 8)?;
+            return (1..=8).contains(&value).then_some(value as u8);
+        }
+    }
+    None
+}
+
+/// upload the thumbnail and put it into the attachment cache,
+/// so that the sender doesn't need to download it again
+pub async fn upload_thumbnail(thumbnail: &File, conv_id: &str) -> Result<String> {
+    let server_name = api::file().upload_file(thumbnail).await?;
+    let data = read_bytes(thumbnail).await?;
+    let attachment = Attachment::new(
+        server_name.clone(),
+        conv_id.to_string(),
+        AttachmentKind::Image,
+        thumbnail.type_(),
+        data,
+    );
+    if let Err(err) = db::db_ins().attachments.put(&attachment).await {
+        error!("cache thumbnail error: {:?}", err);
+    }
+    Ok(server_name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// jpeg with an APP1 segment holding the tiff
+    fn jpeg(tiff: &[u8]) -> Vec<u8> {
+        let len = (2 + 6 + tiff.len()) as u16;
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        data.extend_from_slice(&len.to_be_bytes());
+        data.extend_from_slice(b"Exif\0\0");
+        data.extend_from_slice(tiff);
+        data.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02]);
+        data
+    }
+
+    /// tiff with one ifd entry, the orientation tag
+    fn tiff(little_endian: bool, orientation: u16) -> Vec<u8> {
+        let u16_bytes = |v: u16| {
+            if little_endian {
+                v.to_le_bytes()
+            } else {
+                v.to_be_bytes()
+            }
+        };
+        let mut tiff = if little_endian {
+            b"II".to_vec()
+        } else {
+            b"MM".to_vec()
+        };
+        tiff.extend_from_slice(&u16_bytes(42));
+        let ifd = if little_endian {
+            8u32.to_le_bytes()
+        } else {
+            8u32.to_be_bytes()
+        };
+        tiff.extend_from_slice(&ifd);
+        tiff.extend_from_slice(&u16_bytes(1));
+        // tag, type short, count 1, value
+        tiff.extend_from_slice(&u16_bytes(0x0112));
+        tiff.extend_from_slice(&u16_bytes(3));
+        tiff.extend_from_slice(&if little_endian {
+            1u32.to_le_bytes()
+        } else {
+            1u32.to_be_bytes()
+        });
+        tiff.extend_from_slice(&u16_bytes(orientation));
+        tiff.extend_from_slice(&[0, 0]);
+        tiff
+    }
+
+    #[test]
+    fn test_exif_orientation() {
+        assert_eq!(exif_orientation(&jpeg(&tiff(true, 6))), 6);
+        assert_eq!(exif_orientation(&jpeg(&tiff(false, 8))), 8);
+        // out of range
+        assert_eq!(exif_orientation(&jpeg(&tiff(true, 9))), 1);
+
+        // no APP1 before the start of scan
+        let data = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xDA, 0x00, 0x02,
+        ];
+        assert_eq!(exif_orientation(&data), 1);
+        // not a jpeg
+        assert_eq!(exif_orientation(b"\x89PNG\r\n\x1a\n"), 1);
+    }
+
+    #[test]
+    fn test_exif_orientation_truncated() {
+        let data = jpeg(&tiff(true, 6));
+        // the last six bytes are the padding of the entry and the start of scan
+        for len in 0..data.len() - 6 {
+            assert_eq!(exif_orientation(&data[..len]), 1, "len: {len}");
+        }
+        assert_eq!(parse_tiff_orientation(b"II"), None);
+        assert_eq!(parse_tiff_orientation(&tiff(false, 3)[..12]), None);
+    }
+}
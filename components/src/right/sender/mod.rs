@@ -1,5 +1,7 @@
 mod component;
 mod emoji;
+mod image;
+mod video;
 use std::str::FromStr;
 
 pub use component::*;
@@ -28,10 +30,11 @@ use yew::NodeRef;
 use yewdux::Dispatch;
 
 use icons::{CloseIcon, FileIcon, ImageIcon, PhoneIcon, VideoIcon};
-use sandcat_sdk::api;
 use sandcat_sdk::db;
 use sandcat_sdk::model::file_msg::FileMsg;
+use sandcat_sdk::model::message::GroupCallMember;
 use sandcat_sdk::model::message::GroupMsg;
+use sandcat_sdk::model::message::InviteType;
 use sandcat_sdk::model::message::Message;
 use sandcat_sdk::model::message::Msg;
 use sandcat_sdk::model::message::SendStatus;
@@ -40,7 +43,7 @@ use sandcat_sdk::model::ContentType;
 use sandcat_sdk::model::RightContentType;
 use sandcat_sdk::state::MobileState;
 use sandcat_sdk::state::RelatedMsgState;
-use sandcat_sdk::state::SendAudioMsgState;
+use sandcat_sdk::state::SendGroupCallState;
 use sandcat_sdk::state::SendMessageState;
 
 use crate::constant::AUDIO;
@@ -53,11 +56,15 @@ use crate::constant::FILE;
 use crate::constant::GROUP_DISMISSED;
 use crate::constant::IMAGE;
 use crate::constant::SEND;
+use crate::constant::SEND_DIRECTLY;
 use crate::constant::SUBMIT;
 use crate::constant::VERIFY_NEEDED;
 use crate::constant::VIDEO;
 use crate::constant::VIDEO_CALL;
+use crate::right::direct_file;
 use crate::right::sender::emoji::EmojiPanel;
+use crate::right::upload::start_upload;
+use crate::right::util::send_voice_msg;
 
 use super::emoji::Emoji;
 
@@ -93,6 +100,7 @@ pub struct Sender {
     enter_key_down: i64,
     is_key_down: bool,
     is_voice_mode: bool,
+    show_video_recorder: bool,
     /// nickname, local_id, message type,content
     related_msg: Option<(AttrValue, AttrValue, ContentType, AttrValue)>,
     _related_msg_state: Dispatch<RelatedMsgState>,
@@ -107,6 +115,22 @@ impl Sender {
         }
     }
 
+    /// start a call in the group, or join the ongoing one
+    fn send_group_call(&self, ctx: &Context<Self>, invite_type: InviteType) {
+        Dispatch::<SendGroupCallState>::global().reduce_mut(|s| {
+            s.msg = GroupCallMember {
+                local_id: nanoid::nanoid!().into(),
+                send_id: ctx.props().cur_user_id.clone(),
+                group_id: ctx.props().friend_id.clone(),
+                create_time: chrono::Utc::now().timestamp_millis(),
+                invite_type,
+                platform: self.get_platform(),
+                avatar: ctx.props().avatar.clone(),
+                nickname: ctx.props().nickname.clone(),
+            }
+        });
+    }
+
     fn send_emoji(&self, ctx: &Context<Self>, emoji: Emoji) -> bool {
         if emoji.is_inline {
             // insert to the textarea
@@ -256,6 +280,17 @@ impl Sender {
             .collect::<Html>();
         let onclick = ctx.link().callback(|_| SenderMsg::CloseFileSender);
         let send = ctx.link().callback(|_| SenderMsg::SendFile);
+        // the file is sent peer to peer, only in the single chat
+        let send_directly = if ctx.props().conv_type == RightContentType::Friend {
+            let onclick = ctx.link().callback(|_| SenderMsg::SendFileDirectly);
+            html! {
+                <button {onclick} >
+                    {tr!(self.i18n, SEND_DIRECTLY)}
+                </button>
+            }
+        } else {
+            html!()
+        };
         html! {
             <div class="file-sender">
                 <div class="file-sender-content" >
@@ -265,6 +300,7 @@ impl Sender {
                     <button onclick={send} >
                         {tr!(self.i18n, SUBMIT)}
                     </button>
+                    {send_directly}
                     <button {onclick} >
                         {tr!(self.i18n, CANCEL)}
                     </button>
@@ -273,51 +309,55 @@ impl Sender {
         }
     }
 
-    // todo upload file by behind task and update the upload state
-    fn send_file(&self, ctx: &Context<Self>, file: File) {
-        let mut content_type = ContentType::File;
+    /// read the file info and create a pending message,
+    /// the file will be uploaded by chunks in background or sent directly
+    fn send_file(&self, ctx: &Context<Self>, file: File, direct: bool) {
+        let conv_id = ctx.props().friend_id.clone();
 
         ctx.link().send_future(async move {
-            let file_name_src = file.name();
-            let file_name = api::file()
-                .upload_file(&file)
-                .await
-                .map_err(|err| log::error!("上传文件错误: {:?}", err))
-                .unwrap();
-
-            let size = file.size() as usize;
-            let ext = FileExt::from_str(file_name_src.split('.').last().unwrap_or(""))
-                .unwrap_or_default();
-            let file_obj = FileMsg::new(file_name_src, file_name, size, ext);
+            let (file_obj, content_type, file_content, file) = read_file_msg(file, &conv_id).await;
+            SenderMsg::FileOnload(file_obj, content_type, file_content, file, direct)
+        });
+    }
 
-            let mut file_content = JsValue::default();
-            // 判断文件类型
+    async fn read_as_data_url(file: &File) -> JsValue {
+        // 读取文件内容
+        let file_reader = FileReader::new().expect("create file reader error");
+        // 声明一个channel用来获取闭包中的数据
+        let (tx, rx) = oneshot::channel();
+        let mut tx = Some(tx);
+        let reader = file_reader.clone();
+        let onload = Closure::wrap(Box::new(move || {
+            tx.take()
+                .unwrap()
+                .send(reader.result().expect("获取文件内容错误"))
+                .expect("文件内容发送失败");
+        }) as Box<dyn FnMut()>);
+        file_reader.read_as_data_url(file).expect("文件读取错误");
+
+        file_reader.set_onload(Some(onload.as_ref().unchecked_ref()));
+        onload.forget();
+        rx.await.expect("获取文件内容错误")
+    }
 
-            // 判断是否是视频类型
-            if file.type_() == "video/mp4" {
-                content_type = ContentType::Video;
+    /// save the pending message and start uploading or sending directly
+    fn store_pending_file_msg(&self, ctx: &Context<Self>, msg: Message, file: File, direct: bool) {
+        let conv_type = ctx.props().conv_type.clone();
+        ctx.props().on_file_send.emit(msg.clone());
+        spawn_local(async move {
+            let result = match conv_type {
+                RightContentType::Group => db::db_ins().group_msgs.put(&msg).await,
+                _ => db::db_ins().messages.add_message(&msg).await,
+            };
+            if let Err(err) = result {
+                error!("save pending message error: {:?}", err);
+                return;
             }
-            if file.type_() == "image/png" || file.type_() == "image/jpeg" {
-                content_type = ContentType::Image;
-                // 读取文件内容
-                let file_reader = FileReader::new().expect("create file reader error");
-                // 声明一个channel用来获取闭包中的数据
-                let (tx, rx) = oneshot::channel();
-                let mut tx = Some(tx);
-                let reader = file_reader.clone();
-                let onload = Closure::wrap(Box::new(move || {
-                    tx.take()
-                        .unwrap()
-                        .send(reader.result().expect("获取文件内容错误"))
-                        .expect("文件内容发送失败");
-                }) as Box<dyn FnMut()>);
-                file_reader.read_as_data_url(&file).expect("文件读取错误");
-
-                file_reader.set_onload(Some(onload.as_ref().unchecked_ref()));
-                onload.forget();
-                file_content = rx.await.expect("获取文件内容错误");
+            if direct {
+                direct_file::start_send(conv_type, msg, file);
+            } else {
+                start_upload(conv_type, msg, file);
             }
-            SenderMsg::FileOnload(file_obj, content_type, file_content)
         });
     }
 
@@ -438,42 +478,23 @@ impl Sender {
         let nickname = ctx.props().nickname.clone();
         let user_id = ctx.props().cur_user_id.clone();
         let platform = self.get_platform();
-        spawn_local(async move {
-            if let Err(e) = db::db_ins().voices.save(&voice).await {
-                error!("save voice error:{:?}", e);
-            }
-
-            let time = chrono::Utc::now().timestamp_millis();
-            let mut msg = Message {
-                local_id: voice.local_id.into(),
-                is_self: true,
-                create_time: time,
-                friend_id,
-                send_id: user_id,
-                is_read: 1,
-                content_type: ContentType::Audio,
-                platform,
-                send_status: SendStatus::Sending,
-                audio_duration: voice.duration,
-                audio_downloaded: true,
-                avatar,
-                nickname,
-                ..Default::default()
-            };
-            Dispatch::<SendAudioMsgState>::global().set(SendAudioMsgState { msg: msg.clone() });
-
-            // send to file server
-            let name = match api::file().upload_voice(&voice.data).await {
-                Ok(name) => name,
-                Err(e) => {
-                    error!("send to file server error:{:?}", e);
-                    return;
-                }
-            };
-            msg.content = name.into();
-            // send to message server
-            Self::store_send_msg_(conv_type, msg).await;
-        });
+        let msg = Message {
+            local_id: voice.local_id.clone().into(),
+            is_self: true,
+            create_time: chrono::Utc::now().timestamp_millis(),
+            friend_id,
+            send_id: user_id,
+            is_read: 1,
+            content_type: ContentType::Audio,
+            platform,
+            send_status: SendStatus::Sending,
+            audio_duration: voice.duration,
+            audio_downloaded: true,
+            avatar,
+            nickname,
+            ..Default::default()
+        };
+        spawn_local(send_voice_msg(conv_type, voice, msg));
     }
 
     fn get_sender_classes(
@@ -516,7 +537,10 @@ impl Sender {
     }
 
     fn get_phone_call_icons(&self, ctx: &Context<Self>, conv_type: &RightContentType) -> Html {
-        if *conv_type == RightContentType::Friend {
+        if matches!(
+            conv_type,
+            RightContentType::Friend | RightContentType::Group
+        ) {
             html!(
                 <>
                     <span onclick={ctx.link().callback(|_| SenderMsg::SendAudioCall)}>
@@ -558,3 +582,55 @@ impl Sender {
         }
     }
 }
+
+/// the file message of the file, the image is compressed and the video gets a poster;
+/// also returns the content to render the pending message and the file to upload
+pub(crate) async fn read_file_msg(
+    mut file: File,
+    conv_id: &str,
+) -> (FileMsg, ContentType, JsValue, File) {
+    let mut content_type = ContentType::File;
+    let file_name_src = file.name();
+    let ext = FileExt::from_str(file_name_src.split('.').last().unwrap_or("")).unwrap_or_default();
+    // the server name will be filled after the upload completed
+    let mut file_obj = FileMsg::new(file_name_src, String::new(), 0, ext);
+
+    let mut file_content = JsValue::default();
+    // 判断文件类型
+
+    // 判断是否是视频类型
+    if file.type_().starts_with("video/") {
+        content_type = ContentType::Video;
+        match video::capture_poster(&file).await {
+            Ok(poster) => {
+                match image::upload_thumbnail(&poster.thumbnail, conv_id).await {
+                    Ok(name) => file_obj.thumbnail = name,
+                    Err(err) => error!("upload poster error: {:?}", err),
+                }
+                file_content = poster.thumbnail_url.into();
+            }
+            Err(err) => error!("capture poster error: {:?}", err),
+        }
+    }
+    if file.type_() == "image/png" || file.type_() == "image/jpeg" {
+        content_type = ContentType::Image;
+        match image::compress_image(&file).await {
+            Ok(compressed) => {
+                // the thumbnail is small, upload it directly so that
+                // the receiver can render the message before the original is downloaded
+                match image::upload_thumbnail(&compressed.thumbnail, conv_id).await {
+                    Ok(name) => file_obj.thumbnail = name,
+                    Err(err) => error!("upload thumbnail error: {:?}", err),
+                }
+                file_content = compressed.thumbnail_url.into();
+                file = compressed.file;
+            }
+            Err(err) => {
+                error!("compress image error: {:?}", err);
+                file_content = Sender::read_as_data_url(&file).await;
+            }
+        }
+    }
+    file_obj.size = file.size() as usize;
+    (file_obj, content_type, file_content, file)
+}
@@ -0,0 +1,105 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use futures_channel::oneshot;
+use gloo::utils::document;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, File, HtmlCanvasElement, HtmlVideoElement, Url};
+
+use sandcat_sdk::error::{Error, Result};
+
+use super::image::{to_blob, to_file, THUMBNAIL_QUALITY, THUMBNAIL_SIDE};
+
+/// the time of the frame used as the poster, the first frame may be black
+const POSTER_TIME: f64 = 0.1;
+
+pub struct VideoPoster {
+    pub thumbnail: File,
+    /// data url of the thumbnail, used to render the pending message
+    pub thumbnail_url: String,
+}
+
+/// capture a frame of the video as the poster
+pub async fn capture_poster(file: &File) -> Result<VideoPoster> {
+    let url = Url::create_object_url_with_blob(file)?;
+    let result = capture(&url).await;
+    Url::revoke_object_url(&url)?;
+    let canvas = result?;
+
+    let mime = "image/jpeg";
+    let thumbnail = to_blob(&canvas, mime, THUMBNAIL_QUALITY).await?;
+    let thumbnail = to_file(&thumbnail, &format!("poster_{}.jpg", file.name()), mime)?;
+    let thumbnail_url = canvas
+        .to_data_url_with_type_and_encoder_options(mime, &JsValue::from_f64(THUMBNAIL_QUALITY))?;
+    Ok(VideoPoster {
+        thumbnail,
+        thumbnail_url,
+    })
+}
+
+async fn capture(url: &str) -> Result<HtmlCanvasElement> {
+    let video = document()
+        .create_element("video")?
+        .dyn_into::<HtmlVideoElement>()
+        .map_err(JsValue::from)?;
+    video.set_muted(true);
+    video.set_preload("auto");
+
+    if !wait_event(&video, "loadeddata", || video.set_src(url)).await {
+        return Err(Error::internal_with_details("load video error"));
+    }
+    if video.duration() > POSTER_TIME
+        && !wait_event(&video, "seeked", || video.set_current_time(POSTER_TIME)).await
+    {
+        return Err(Error::internal_with_details("seek video error"));
+    }
+
+    let (width, height) = (video.video_width(), video.video_height());
+    if width == 0 || height == 0 {
+        return Err(Error::internal_with_details("video has no size"));
+    }
+    let scale = (THUMBNAIL_SIDE as f64 / width.max(height) as f64).min(1.0);
+    let w = (width as f64 * scale).round().max(1.0);
+    let h = (height as f64 * scale).round().max(1.0);
+
+    let canvas = document()
+        .create_element("canvas")?
+        .dyn_into::<HtmlCanvasElement>()
+        .map_err(JsValue::from)?;
+    canvas.set_width(w as u32);
+    canvas.set_height(h as u32);
+    let context = canvas
+        .get_context("2d")?
+        .ok_or_else(|| Error::internal_with_details("get canvas context error"))?
+        .dyn_into::<CanvasRenderingContext2d>()
+        .map_err(JsValue::from)?;
+    context.draw_image_with_html_video_element_and_dw_and_dh(&video, 0.0, 0.0, w, h)?;
+    video.remove_attribute("src")?;
+    Ok(canvas)
+}
+
+/// trigger the action and wait for the event, return false if the video failed
+async fn wait_event(video: &HtmlVideoElement, event: &str, action: impl FnOnce()) -> bool {
+    let (tx, rx) = oneshot::channel();
+    let tx = Rc::new(RefCell::new(Some(tx)));
+    let tx_err = tx.clone();
+    let on_event = Closure::once(move || {
+        if let Some(tx) = tx.borrow_mut().take() {
+            let _ = tx.send(true);
+        }
+    });
+    let on_error = Closure::once(move || {
+        if let Some(tx) = tx_err.borrow_mut().take() {
+            let _ = tx.send(false);
+        }
+    });
+    let _ = video.add_event_listener_with_callback(event, on_event.as_ref().unchecked_ref());
+    video.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+    action();
+
+    let result = rx.await.unwrap_or(false);
+    let _ = video.remove_event_listener_with_callback(event, on_event.as_ref().unchecked_ref());
+    video.set_onerror(None);
+    result
+}
@@ -24,10 +24,12 @@ use sandcat_sdk::{
 };
 use utils::tr;
 
+use crate::avatar_img::AvatarImg;
 use crate::constant::{
     ADD, DELETE, GROUP_ANNOUNCEMENT, GROUP_DESC, GROUP_NAME, MUTE, REMARK, REMOVE,
 };
 
+use super::shared_content::SharedContent;
 use super::util;
 
 pub struct SetWindow {
@@ -272,7 +274,7 @@ impl Component for SetWindow {
                 if let Some(friend) = self.friend.as_ref() {
                     avatars = html! {
                         <div class="avatar-name">
-                            <img alt="avatar" src={utils::get_avatar_url(&friend.avatar)} />
+                            <AvatarImg avatar={friend.avatar.clone()} />
                             <span>{&friend.name}</span>
                         </div>
                     };
@@ -294,7 +296,7 @@ impl Component for SetWindow {
                     .map(|item| {
                         html! {
                             <div class="avatar-name">
-                                <img alt="avatar" src={utils::get_avatar_url(&item.avatar)} />
+                                <AvatarImg avatar={item.avatar.clone()} />
                                 <span>{&item.group_name}</span>
                             </div>
                         }
@@ -383,6 +385,11 @@ impl Component for SetWindow {
                 <div class="info">
                 {info}
                 </div>
+                <SharedContent
+                    conv_type={ctx.props().conv_type.clone()}
+                    id={ctx.props().id.clone()}
+                    on_jump={ctx.props().close.clone()}
+                    lang={ctx.props().lang} />
                 <div class="set-window-setting">
                     {setting}
                 </div>
@@ -1,20 +1,39 @@
 use std::rc::Rc;
 
 use fluent::{FluentBundle, FluentResource};
+use gloo::timers::callback::Interval;
 use wasm_bindgen::JsCast;
-use web_sys::HtmlInputElement;
-use yew::{classes, html, Component, Event, InputEvent, Properties};
+use web_sys::{
+    AnalyserNode, AudioContext, HtmlInputElement, HtmlSelectElement, HtmlVideoElement,
+    MediaDeviceKind, MediaStream, MediaStreamTrack,
+};
+use yew::{classes, html, AttrValue, Component, Event, Html, InputEvent, NodeRef, Properties};
 use yewdux::Dispatch;
 
 use i18n::{self, en_us, zh_cn, LanguageType};
+use sandcat_sdk::db;
+use sandcat_sdk::error::Error;
+use sandcat_sdk::model::attachment::AttachmentUsage;
+use sandcat_sdk::model::notification::Notification;
 use sandcat_sdk::state::{
-    FontSizeState, I18nState, MobileState, Notify, ThemeState, TransparentState,
+    AttachmentQuotaState, FontSizeState, I18nState, IceServerOverrideState, MediaDeviceState,
+    MobileState, Notify, ThemeState, TransparentState,
 };
 use utils::tr;
 
 use crate::constant::{
-    DARK, FONT_SIZE, LANGUAGE, LARGE, LARGER, LIGHT, MEDUIM, SETTING, SMALL, THEME, TRANSPARENT,
+    AVATARS, CACHE_QUOTA, CAMERA, CLEAR_ALL_CACHE, CLEAR_CACHE, DARK, DEFAULT_DEVICE, DEVICES,
+    FONT_SIZE, ICE_CREDENTIAL, ICE_SERVER, ICE_URLS, ICE_USERNAME, LANGUAGE, LARGE, LARGER, LIGHT,
+    MEDUIM, MICROPHONE, SETTING, SMALL, SPEAKER, STOP_TEST, STORAGE, TEST_DEVICE, THEME,
+    TRANSPARENT,
 };
+use crate::device_picker::DevicePicker;
+
+/// cache quota options in MB
+const QUOTA_OPTIONS: [u64; 5] = [128, 256, 512, 1024, 2048];
+const MB: u64 = 1024 * 1024;
+/// refresh interval of the microphone level meter in milliseconds
+const METER_INTERVAL: u32 = 100;
 
 pub struct Setting {
     i18n: FluentBundle<FluentResource>,
@@ -22,6 +41,35 @@ pub struct Setting {
     theme: Rc<ThemeState>,
     font_size: Rc<FontSizeState>,
     transparent: f32,
+    /// attachment cache usage with the conversation name
+    usage: Vec<(AttrValue, AttachmentUsage)>,
+    quota: u64,
+    /// ice server configured by the self-hosted user
+    ice_server: IceServerOverrideState,
+    devices: MediaDeviceState,
+    device_test: Option<DeviceTest>,
+    preview_node: NodeRef,
+    /// microphone level from 0 to 1
+    mic_level: f64,
+}
+
+/// the stream used to test the camera and microphone before a call
+struct DeviceTest {
+    stream: MediaStream,
+    audio_ctx: Option<AudioContext>,
+    analyser: Option<AnalyserNode>,
+    _meter: Option<Interval>,
+}
+
+impl Drop for DeviceTest {
+    fn drop(&mut self) {
+        self.stream.get_tracks().iter().for_each(|track| {
+            track.dyn_into::<MediaStreamTrack>().unwrap().stop();
+        });
+        if let Some(audio_ctx) = self.audio_ctx.as_ref() {
+            let _ = audio_ctx.close();
+        }
+    }
 }
 
 pub enum SettingMsg {
@@ -29,6 +77,21 @@ pub enum SettingMsg {
     SwitchTheme(Event),
     SwitchFontSize(Event),
     TransparentChange(InputEvent),
+    QueryUsage,
+    QueryUsageSuccess(Vec<(AttrValue, AttachmentUsage)>),
+    QuotaChange(Event),
+    ClearConvCache(String),
+    ClearAllCache,
+    IceUrlsChange(Event),
+    IceUsernameChange(Event),
+    IceCredentialChange(Event),
+    MicrophoneChange(String),
+    CameraChange(String),
+    SpeakerChange(String),
+    SwitchDeviceTest,
+    DeviceTestReady(MediaStream),
+    DeviceTestFailed,
+    MeterTick,
 }
 
 #[derive(Debug, Clone, PartialEq, Properties)]
@@ -53,16 +116,24 @@ impl Component for Setting {
 
         let font_size = FontSizeState::get();
         let transparent = TransparentState::get();
+        ctx.link().send_message(SettingMsg::QueryUsage);
         Self {
             i18n,
             lang,
             theme,
             font_size,
             transparent,
+            usage: Vec::new(),
+            quota: AttachmentQuotaState::get(),
+            ice_server: (*IceServerOverrideState::get()).clone(),
+            devices: (*MediaDeviceState::get()).clone(),
+            device_test: None,
+            preview_node: NodeRef::default(),
+            mic_level: 0.0,
         }
     }
 
-    fn update(&mut self, _ctx: &yew::prelude::Context<Self>, msg: Self::Message) -> bool {
+    fn update(&mut self, ctx: &yew::prelude::Context<Self>, msg: Self::Message) -> bool {
         match msg {
             SettingMsg::SwitchLanguage(event) => {
                 let input = event
@@ -119,9 +190,171 @@ impl Component for Setting {
                 TransparentState::set(self.transparent);
                 true
             }
+            SettingMsg::QueryUsage => {
+                ctx.link().send_future(async {
+                    let usage = match db::db_ins().attachments.usage().await {
+                        Ok(usage) => usage,
+                        Err(err) => {
+                            log::error!("query attachment usage error: {:?}", err);
+                            Vec::new()
+                        }
+                    };
+                    let mut list = Vec::with_capacity(usage.len());
+                    for item in usage {
+                        let name = match db::db_ins().convs.get_by_frined_id(&item.conv_id).await {
+                            Ok(Some(conv)) => conv.name,
+                            _ => AttrValue::from(item.conv_id.clone()),
+                        };
+                        list.push((name, item));
+                    }
+                    SettingMsg::QueryUsageSuccess(list)
+                });
+                false
+            }
+            SettingMsg::QueryUsageSuccess(usage) => {
+                self.usage = usage;
+                true
+            }
+            SettingMsg::QuotaChange(event) => {
+                let select = event
+                    .target()
+                    .unwrap()
+                    .dyn_into::<HtmlSelectElement>()
+                    .unwrap();
+                self.quota = select.value().parse::<u64>().unwrap_or_default() * MB;
+                AttachmentQuotaState::set(self.quota);
+                let quota = self.quota;
+                ctx.link().send_future(async move {
+                    if let Err(err) = db::db_ins().attachments.evict(quota, None).await {
+                        Notification::error(err).notify();
+                    }
+                    SettingMsg::QueryUsage
+                });
+                true
+            }
+            SettingMsg::ClearConvCache(conv_id) => {
+                ctx.link().send_future(async move {
+                    if let Err(err) = db::db_ins().attachments.clear_conv(&conv_id).await {
+                        Notification::error(err).notify();
+                    }
+                    SettingMsg::QueryUsage
+                });
+                false
+            }
+            SettingMsg::ClearAllCache => {
+                ctx.link().send_future(async {
+                    if let Err(err) = db::db_ins().attachments.clear().await {
+                        Notification::error(err).notify();
+                    }
+                    SettingMsg::QueryUsage
+                });
+                false
+            }
+            SettingMsg::IceUrlsChange(event) => {
+                self.ice_server.urls = input_value(&event);
+                IceServerOverrideState::set(self.ice_server.clone());
+                false
+            }
+            SettingMsg::IceUsernameChange(event) => {
+                self.ice_server.username = input_value(&event);
+                IceServerOverrideState::set(self.ice_server.clone());
+                false
+            }
+            SettingMsg::IceCredentialChange(event) => {
+                self.ice_server.credential = input_value(&event);
+                IceServerOverrideState::set(self.ice_server.clone());
+                false
+            }
+            SettingMsg::MicrophoneChange(id) => {
+                self.devices.microphone = id.clone();
+                MediaDeviceState::set_microphone(id);
+                self.restart_device_test(ctx);
+                true
+            }
+            SettingMsg::CameraChange(id) => {
+                self.devices.camera = id.clone();
+                MediaDeviceState::set_camera(id);
+                self.restart_device_test(ctx);
+                true
+            }
+            SettingMsg::SpeakerChange(id) => {
+                // the speaker is used by the next call, the test doesn't play any sound
+                self.devices.speaker = id.clone();
+                MediaDeviceState::set_speaker(id);
+                true
+            }
+            SettingMsg::SwitchDeviceTest => {
+                if self.device_test.take().is_some() {
+                    self.mic_level = 0.0;
+                } else {
+                    self.start_device_test(ctx);
+                }
+                true
+            }
+            SettingMsg::DeviceTestReady(stream) => {
+                let (audio_ctx, analyser) = match create_analyser(&stream) {
+                    Ok((audio_ctx, analyser)) => (Some(audio_ctx), Some(analyser)),
+                    Err(e) => {
+                        log::warn!("create analyser error: {:?}", e);
+                        (None, None)
+                    }
+                };
+                let meter = analyser.as_ref().map(|_| {
+                    let link = ctx.link().clone();
+                    Interval::new(METER_INTERVAL, move || {
+                        link.send_message(SettingMsg::MeterTick)
+                    })
+                });
+                self.device_test = Some(DeviceTest {
+                    stream,
+                    audio_ctx,
+                    analyser,
+                    _meter: meter,
+                });
+                true
+            }
+            SettingMsg::DeviceTestFailed => {
+                self.device_test = None;
+                true
+            }
+            SettingMsg::MeterTick => {
+                let Some(analyser) = self.device_test.as_ref().and_then(|t| t.analyser.as_ref())
+                else {
+                    return false;
+                };
+                let mut data = vec![0u8; analyser.fft_size() as usize];
+                analyser.get_byte_time_domain_data(&mut data);
+                // root mean square of the samples centered at 128
+                let sum = data
+                    .iter()
+                    .map(|v| {
+                        let v = (*v as f64 - 128.0) / 128.0;
+                        v * v
+                    })
+                    .sum::<f64>();
+                let rms = (sum / data.len() as f64).sqrt();
+                self.mic_level = (rms * 4.0).min(1.0);
+                true
+            }
         }
     }
 
+    fn rendered(&mut self, _ctx: &yew::prelude::Context<Self>, _first_render: bool) {
+        // the preview is rendered after the stream is ready
+        if let (Some(test), Some(video)) = (
+            self.device_test.as_ref(),
+            self.preview_node.cast::<HtmlVideoElement>(),
+        ) {
+            if video.src_object().is_none() {
+                video.set_src_object(Some(&test.stream));
+            }
+        }
+    }
+
+    fn destroy(&mut self, _ctx: &yew::prelude::Context<Self>) {
+        self.device_test = None;
+    }
+
     fn view(&self, ctx: &yew::prelude::Context<Self>) -> yew::prelude::Html {
         let onchange = ctx.link().callback(SettingMsg::SwitchLanguage);
         let on_font_size_change = ctx.link().callback(SettingMsg::SwitchFontSize);
@@ -247,8 +480,211 @@ impl Component for Setting {
                         </label>
                             <span>{self.transparent}</span>
                     </div>
+
+                    {self.storage_html(ctx)}
+
+                    {self.device_html(ctx)}
+
+                    {self.ice_server_html(ctx)}
+                </div>
+            </div>
+        }
+    }
+}
+
+impl Setting {
+    fn storage_html(&self, ctx: &yew::prelude::Context<Self>) -> Html {
+        let total: u64 = self.usage.iter().map(|(_, u)| u.size).sum();
+        let on_quota_change = ctx.link().callback(SettingMsg::QuotaChange);
+        let options = QUOTA_OPTIONS
+            .iter()
+            .map(|size| {
+                html! {
+                    <option value={size.to_string()} selected={*size * MB == self.quota}>
+                        {format_size(size * MB)}
+                    </option>
+                }
+            })
+            .collect::<Html>();
+
+        let list = self
+            .usage
+            .iter()
+            .map(|(name, usage)| {
+                let conv_id = usage.conv_id.clone();
+                let onclick = ctx
+                    .link()
+                    .callback(move |_| SettingMsg::ClearConvCache(conv_id.clone()));
+                let name = if usage.conv_id.is_empty() {
+                    tr!(self.i18n, AVATARS)
+                } else {
+                    name.to_string()
+                };
+                html! {
+                    <div class="setting-storage-item">
+                        <span>{name}</span>
+                        <span>{format!("{} ({})", format_size(usage.size), usage.count)}</span>
+                        <button {onclick}>{tr!(self.i18n, CLEAR_CACHE)}</button>
+                    </div>
+                }
+            })
+            .collect::<Html>();
+
+        html! {
+            <div class="setting-storage">
+                <div class="setting-storage-header">
+                    <b>{tr!(self.i18n, STORAGE)}</b>
+                    <span>{format!("{} / {}", format_size(total), format_size(self.quota))}</span>
+                    <button onclick={ctx.link().callback(|_| SettingMsg::ClearAllCache)}>
+                        {tr!(self.i18n, CLEAR_ALL_CACHE)}
+                    </button>
                 </div>
+                <div class="setting-storage-header">
+                    <b>{tr!(self.i18n, CACHE_QUOTA)}</b>
+                    <select onchange={on_quota_change}>
+                        {options}
+                    </select>
+                </div>
+                <div class="setting-storage-list">
+                    {list}
+                </div>
+            </div>
+        }
+    }
+}
+
+impl Setting {
+    fn ice_server_html(&self, ctx: &yew::prelude::Context<Self>) -> Html {
+        html! {
+            <div class="setting-ice">
+                <b>{tr!(self.i18n, ICE_SERVER)}</b>
+                <input type="text"
+                    placeholder={tr!(self.i18n, ICE_URLS)}
+                    value={self.ice_server.urls.clone()}
+                    onchange={ctx.link().callback(SettingMsg::IceUrlsChange)} />
+                <input type="text"
+                    placeholder={tr!(self.i18n, ICE_USERNAME)}
+                    value={self.ice_server.username.clone()}
+                    onchange={ctx.link().callback(SettingMsg::IceUsernameChange)} />
+                <input type="password"
+                    placeholder={tr!(self.i18n, ICE_CREDENTIAL)}
+                    value={self.ice_server.credential.clone()}
+                    onchange={ctx.link().callback(SettingMsg::IceCredentialChange)} />
             </div>
         }
     }
 }
+
+impl Setting {
+    fn device_html(&self, ctx: &yew::prelude::Context<Self>) -> Html {
+        let testing = self.device_test.is_some();
+        let test_text = if testing {
+            tr!(self.i18n, STOP_TEST)
+        } else {
+            tr!(self.i18n, TEST_DEVICE)
+        };
+        let preview = if testing {
+            html! {
+                <>
+                    <video class="setting-device-preview"
+                        ref={self.preview_node.clone()}
+                        autoplay=true
+                        muted=true />
+                    <div class="setting-device-meter">
+                        <span style={format!("width: {:.0}%;", self.mic_level * 100.0)}></span>
+                    </div>
+                </>
+            }
+        } else {
+            html!()
+        };
+        html! {
+            <div class="setting-device">
+                <b>{tr!(self.i18n, DEVICES)}</b>
+                <label>
+                    {tr!(self.i18n, MICROPHONE)}
+                    <DevicePicker
+                        kind={MediaDeviceKind::Audioinput}
+                        value={AttrValue::from(self.devices.microphone.clone())}
+                        default_label={tr!(self.i18n, DEFAULT_DEVICE)}
+                        name={tr!(self.i18n, MICROPHONE)}
+                        onchange={ctx.link().callback(SettingMsg::MicrophoneChange)} />
+                </label>
+                <label>
+                    {tr!(self.i18n, SPEAKER)}
+                    <DevicePicker
+                        kind={MediaDeviceKind::Audiooutput}
+                        value={AttrValue::from(self.devices.speaker.clone())}
+                        default_label={tr!(self.i18n, DEFAULT_DEVICE)}
+                        name={tr!(self.i18n, SPEAKER)}
+                        onchange={ctx.link().callback(SettingMsg::SpeakerChange)} />
+                </label>
+                <label>
+                    {tr!(self.i18n, CAMERA)}
+                    <DevicePicker
+                        kind={MediaDeviceKind::Videoinput}
+                        value={AttrValue::from(self.devices.camera.clone())}
+                        default_label={tr!(self.i18n, DEFAULT_DEVICE)}
+                        name={tr!(self.i18n, CAMERA)}
+                        onchange={ctx.link().callback(SettingMsg::CameraChange)} />
+                </label>
+                <button onclick={ctx.link().callback(|_| SettingMsg::SwitchDeviceTest)}>
+                    {test_text}
+                </button>
+                {preview}
+            </div>
+        }
+    }
+
+    fn start_device_test(&mut self, ctx: &yew::prelude::Context<Self>) {
+        let devices = self.devices.clone();
+        ctx.link().send_future(async move {
+            match utils::get_video_stream(devices.camera(), devices.microphone()).await {
+                Ok(stream) => SettingMsg::DeviceTestReady(stream),
+                Err(e) => {
+                    Notification::error(Error::js_err(e)).notify();
+                    SettingMsg::DeviceTestFailed
+                }
+            }
+        });
+    }
+
+    /// reopen the stream with the new device if the test is running
+    fn restart_device_test(&mut self, ctx: &yew::prelude::Context<Self>) {
+        if self.device_test.take().is_some() {
+            self.mic_level = 0.0;
+            self.start_device_test(ctx);
+        }
+    }
+}
+
+fn create_analyser(
+    stream: &MediaStream,
+) -> Result<(AudioContext, AnalyserNode), wasm_bindgen::JsValue> {
+    let audio_ctx = AudioContext::new()?;
+    let source = audio_ctx.create_media_stream_source(stream)?;
+    let analyser = audio_ctx.create_analyser()?;
+    analyser.set_fft_size(256);
+    source.connect_with_audio_node(&analyser)?;
+    Ok((audio_ctx, analyser))
+}
+
+fn input_value(event: &Event) -> String {
+    event
+        .target()
+        .unwrap()
+        .dyn_into::<HtmlInputElement>()
+        .unwrap()
+        .value()
+}
+
+fn format_size(size: u64) -> String {
+    let size_in_mb = size as f64 / MB as f64;
+    if size_in_mb >= 1024.0 {
+        format!("{:.2} GB", size_in_mb / 1024.0)
+    } else if size_in_mb >= 1.0 {
+        format!("{:.2} MB", size_in_mb)
+    } else {
+        format!("{:.2} KB", size as f64 / 1024.0)
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+
+use chrono::TimeZone;
+use fluent::{FluentBundle, FluentResource};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlDivElement, IntersectionObserver, IntersectionObserverEntry, Url};
+use yew::prelude::*;
+
+use i18n::{en_us, zh_cn, LanguageType};
+use icons::{MsgLoadingIcon, VideoRecordIcon};
+use sandcat_sdk::db;
+use sandcat_sdk::error::Result;
+use sandcat_sdk::model::attachment::AttachmentKind;
+use sandcat_sdk::model::file_msg::FileMsg;
+use sandcat_sdk::model::message::Message;
+use sandcat_sdk::model::{ContentType, RightContentType};
+use sandcat_sdk::state::JumpToMsgState;
+use utils::tr;
+
+use crate::constant::{FILES, LINKS, LOADING, MEDIA, NO_SHARED};
+use crate::right::util::load_attachment;
+
+/// the images are loaded by pages when the end of the media list is scrolled into view
+const MEDIA_PAGE_SIZE: usize = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SharedTab {
+    Media,
+    Files,
+    Links,
+}
+
+/// images, videos, files and links shared in the conversation,
+/// every tab is queried when it is opened for the first time
+pub struct SharedContent {
+    i18n: FluentBundle<FluentResource>,
+    tab: Option<SharedTab>,
+    media: Option<Vec<Message>>,
+    files: Option<Vec<Message>>,
+    /// link and the message containing it
+    links: Option<Vec<(AttrValue, Message)>>,
+    /// object urls of the cached images by local id
+    images: HashMap<AttrValue, AttrValue>,
+    /// count of the media items shown, whose images are loaded
+    shown: usize,
+    /// the end of the media list, observed to show the next page
+    load_ref: NodeRef,
+    need_set_observer: bool,
+    observer: Option<IntersectionObserver>,
+    observer_callback: Option<Closure<dyn FnMut(Vec<IntersectionObserverEntry>)>>,
+}
+
+pub enum SharedContentMsg {
+    SwitchTab(SharedTab),
+    Loaded(SharedTab, Vec<Message>),
+    ImageLoaded(AttrValue, AttrValue),
+    LoadMore,
+    Jump(AttrValue),
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct SharedContentProps {
+    pub conv_type: RightContentType,
+    pub id: AttrValue,
+    /// called after jumping to the message
+    pub on_jump: Callback<()>,
+    pub lang: LanguageType,
+}
+
+impl SharedContent {
+    fn query(&self, ctx: &Context<Self>, tab: SharedTab) {
+        let conv_type = ctx.props().conv_type.clone();
+        let id = ctx.props().id.clone();
+        ctx.link().send_future(async move {
+            let types: &[ContentType] = match tab {
+                SharedTab::Media => &[ContentType::Image, ContentType::Video],
+                SharedTab::Files => &[ContentType::File],
+                SharedTab::Links => &[ContentType::Text],
+            };
+            let mut list = Vec::new();
+            for content_type in types {
+                match query_by_type(&conv_type, &id, *content_type).await {
+                    Ok(messages) => list.extend(messages),
+                    Err(err) => log::error!("query shared content error: {:?}", err),
+                }
+            }
+            // the latest first
+            list.sort_by(|a, b| b.create_time.cmp(&a.create_time));
+            SharedContentMsg::Loaded(tab, list)
+        });
+    }
+
+    /// show the next page of the media and load its images
+    fn show_more(&mut self, ctx: &Context<Self>) -> bool {
+        let Some(list) = self.media.as_ref() else {
+            return false;
+        };
+        if self.shown >= list.len() {
+            return false;
+        }
+        let end = (self.shown + MEDIA_PAGE_SIZE).min(list.len());
+        self.load_images(ctx, &list[self.shown..end]);
+        self.shown = end;
+        // observe the end again, it is still in view if the page is short
+        self.need_set_observer = true;
+        true
+    }
+
+    /// load the images from the attachment cache like the message list does
+    fn load_images(&self, ctx: &Context<Self>, list: &[Message]) {
+        for msg in list {
+            if msg.content_type != ContentType::Image {
+                continue;
+            }
+            let file = FileMsg::from(&msg.content);
+            let server_name = if file.thumbnail.is_empty() {
+                file.server_name
+            } else {
+                file.thumbnail
+            };
+            if server_name.is_empty() {
+                continue;
+            }
+            let local_id = msg.local_id.clone();
+            let conv_id = msg.friend_id.clone();
+            ctx.link().send_future_batch(async move {
+                match load_attachment(&server_name, &conv_id, AttachmentKind::Image, "image/*")
+                    .await
+                {
+                    Ok(url) => vec![SharedContentMsg::ImageLoaded(local_id, url.into())],
+                    Err(err) => {
+                        log::error!("load shared image error: {:?}", err);
+                        vec![]
+                    }
+                }
+            });
+        }
+    }
+
+    fn set_observer(&mut self, ctx: &Context<Self>) {
+        self.need_set_observer = false;
+        if let Some(observer) = self.observer.take() {
+            observer.disconnect();
+        }
+        // the end is not rendered if all the media are shown
+        let Some(end) = self.load_ref.cast::<HtmlDivElement>() else {
+            return;
+        };
+        let callback = self.observer_callback.get_or_insert_with(|| {
+            let call = ctx.link().callback(|_| SharedContentMsg::LoadMore);
+            Closure::wrap(Box::new(move |entries: Vec<IntersectionObserverEntry>| {
+                if entries.first().is_some_and(|entry| entry.is_intersecting()) {
+                    call.emit(());
+                }
+            })
+                as Box<dyn FnMut(Vec<IntersectionObserverEntry>)>)
+        });
+        match IntersectionObserver::new(callback.as_ref().unchecked_ref()) {
+            Ok(observer) => {
+                observer.observe(&end);
+                self.observer = Some(observer);
+            }
+            Err(err) => log::error!("create intersection observer error: {:?}", err),
+        }
+    }
+
+    fn media_html(&self, ctx: &Context<Self>, list: &[Message]) -> Html {
+        let items = list
+            .iter()
+            .take(self.shown)
+            .map(|msg| {
+                let local_id = msg.local_id.clone();
+                let onclick = ctx
+                    .link()
+                    .callback(move |_| SharedContentMsg::Jump(local_id.clone()));
+                let content = if msg.content_type == ContentType::Video {
+                    html!(<VideoRecordIcon />)
+                } else if let Some(src) = self.images.get(&msg.local_id) {
+                    html!(<img alt="image" src={src.clone()} />)
+                } else {
+                    html!(<MsgLoadingIcon />)
+                };
+                html! {
+                    <div class="shared-media-item pointer" key={msg.local_id.as_str()} {onclick}>
+                        {content}
+                    </div>
+                }
+            })
+            .collect::<Html>();
+        let end = if self.shown < list.len() {
+            html!(<div class="shared-media-end" ref={self.load_ref.clone()}></div>)
+        } else {
+            html!()
+        };
+        html! {
+            <>
+                <div class="shared-media">{items}</div>
+                {end}
+            </>
+        }
+    }
+
+    fn files_html(&self, ctx: &Context<Self>, list: &[Message]) -> Html {
+        list.iter()
+            .map(|msg| {
+                let file = FileMsg::from(&msg.content);
+                let local_id = msg.local_id.clone();
+                let onclick = ctx
+                    .link()
+                    .callback(move |_| SharedContentMsg::Jump(local_id.clone()));
+                html! {
+                    <div class="shared-file-item hover pointer" key={msg.local_id.as_str()} {onclick}>
+                        {file.ext.get_icon()}
+                        <div class="shared-file-info">
+                            <span>{&file.name}</span>
+                            <span>{file.get_size()}</span>
+                        </div>
+                    </div>
+                }
+            })
+            .collect::<Html>()
+    }
+
+    fn links_html(&self, ctx: &Context<Self>, list: &[(AttrValue, Message)]) -> Html {
+        list.iter()
+            .enumerate()
+            .map(|(i, (link, msg))| {
+                let local_id = msg.local_id.clone();
+                let onclick = ctx
+                    .link()
+                    .callback(move |_| SharedContentMsg::Jump(local_id.clone()));
+                html! {
+                    <div class="shared-link-item hover pointer" key={i} {onclick}>
+                        <a href={link.clone()} target="_blank" rel="noopener noreferrer"
+                            onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}>
+                            {link}
+                        </a>
+                        <span>{format_date(msg.create_time)}</span>
+                    </div>
+                }
+            })
+            .collect::<Html>()
+    }
+}
+
+impl Component for SharedContent {
+    type Message = SharedContentMsg;
+    type Properties = SharedContentProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let res = match ctx.props().lang {
+            LanguageType::ZhCN => zh_cn::SET_WINDOW,
+            LanguageType::EnUS => en_us::SET_WINDOW,
+        };
+        let i18n = utils::create_bundle(res);
+        Self {
+            i18n,
+            tab: None,
+            media: None,
+            files: None,
+            links: None,
+            images: HashMap::new(),
+            shown: 0,
+            load_ref: NodeRef::default(),
+            need_set_observer: false,
+            observer: None,
+            observer_callback: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            SharedContentMsg::SwitchTab(tab) => {
+                if self.tab == Some(tab) {
+                    self.tab = None;
+                    return true;
+                }
+                self.tab = Some(tab);
+                // the end of the media list is rendered again
+                self.need_set_observer = tab == SharedTab::Media;
+                let loaded = match tab {
+                    SharedTab::Media => self.media.is_some(),
+                    SharedTab::Files => self.files.is_some(),
+                    SharedTab::Links => self.links.is_some(),
+                };
+                if !loaded {
+                    self.query(ctx, tab);
+                }
+                true
+            }
+            SharedContentMsg::Loaded(tab, list) => {
+                match tab {
+                    SharedTab::Media => {
+                        self.media = Some(list);
+                        self.show_more(ctx);
+                    }
+                    SharedTab::Files => self.files = Some(list),
+                    SharedTab::Links => {
+                        let links = list
+                            .into_iter()
+                            .flat_map(|msg| {
+                                utils::extract_links(&msg.content)
+                                    .into_iter()
+                                    .map(|link| (AttrValue::from(link.to_string()), msg.clone()))
+                                    .collect::<Vec<_>>()
+                            })
+                            .collect();
+                        self.links = Some(links);
+                    }
+                }
+                true
+            }
+            SharedContentMsg::ImageLoaded(local_id, url) => {
+                self.images.insert(local_id, url);
+                true
+            }
+            SharedContentMsg::LoadMore => self.show_more(ctx),
+            SharedContentMsg::Jump(local_id) => {
+                JumpToMsgState::notify(local_id);
+                ctx.props().on_jump.emit(());
+                false
+            }
+        }
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, _first_render: bool) {
+        if self.need_set_observer {
+            self.set_observer(ctx);
+        }
+    }
+
+    fn destroy(&mut self, _ctx: &Context<Self>) {
+        if let Some(observer) = self.observer.take() {
+            observer.disconnect();
+        }
+        for url in self.images.values() {
+            let _ = Url::revoke_object_url(url);
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let tabs = [
+            (SharedTab::Media, MEDIA),
+            (SharedTab::Files, FILES),
+            (SharedTab::Links, LINKS),
+        ]
+        .into_iter()
+        .map(|(tab, key)| {
+            let class = if self.tab == Some(tab) {
+                "shared-tab shared-tab-active pointer"
+            } else {
+                "shared-tab pointer"
+            };
+            let onclick = ctx
+                .link()
+                .callback(move |_| SharedContentMsg::SwitchTab(tab));
+            html!(<span {class} {onclick}>{tr!(self.i18n, key)}</span>)
+        })
+        .collect::<Html>();
+
+        let loading = html! {
+            <div class="shared-empty">
+                <MsgLoadingIcon />
+                {tr!(self.i18n, LOADING)}
+            </div>
+        };
+        let empty = html!(<div class="shared-empty">{tr!(self.i18n, NO_SHARED)}</div>);
+
+        let content = match self.tab {
+            None => html!(),
+            Some(SharedTab::Media) => match self.media.as_ref() {
+                None => loading,
+                Some(list) if list.is_empty() => empty,
+                Some(list) => self.media_html(ctx, list),
+            },
+            Some(SharedTab::Files) => match self.files.as_ref() {
+                None => loading,
+                Some(list) if list.is_empty() => empty,
+                Some(list) => self.files_html(ctx, list),
+            },
+            Some(SharedTab::Links) => match self.links.as_ref() {
+                None => loading,
+                Some(list) if list.is_empty() => empty,
+                Some(list) => self.links_html(ctx, list),
+            },
+        };
+
+        html! {
+            <div class="shared-content">
+                <div class="shared-tabs">
+                    {tabs}
+                </div>
+                <div class="shared-list">
+                    {content}
+                </div>
+            </div>
+        }
+    }
+}
+
+fn format_date(timestamp: i64) -> String {
+    chrono::Utc
+        .timestamp_millis_opt(timestamp)
+        .single()
+        .map(|t| {
+            t.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+async fn query_by_type(
+    conv_type: &RightContentType,
+    id: &str,
+    content_type: ContentType,
+) -> Result<Vec<Message>> {
+    match conv_type {
+        RightContentType::Group => db::db_ins().group_msgs.get_by_type(id, content_type).await,
+        _ => db::db_ins().messages.get_by_type(id, content_type).await,
+    }
+}
@@ -0,0 +1,281 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use futures_channel::oneshot;
+use gloo::timers::callback::Timeout;
+use log::error;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{Blob, File};
+use yew::AttrValue;
+use yewdux::Dispatch;
+
+use sandcat_sdk::{
+    api, db,
+    error::Result,
+    model::{
+        file_msg::FileMsg,
+        message::{GroupMsg, Message, Msg},
+        notification::Notification,
+        upload::{UploadTask, UPLOAD_CHUNK_SIZE},
+        ContentType, RightContentType,
+    },
+    state::{SendMessageState, UploadProgressState, UploadStatus},
+};
+
+use crate::right::direct_file;
+
+/// retry times of every chunk
+const MAX_RETRY: u32 = 3;
+/// the first retry delay, doubled for every retry
+const RETRY_DELAY: u32 = 1000;
+
+thread_local! {
+    /// tasks being uploaded, used to avoid uploading the same task twice
+    static RUNNING: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+    /// tasks canceled by user, checked before uploading every chunk
+    static CANCELED: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+}
+
+/// whether the file of the message is still being uploaded,
+/// the server name is filled after the upload completed
+pub fn is_uploading(msg: &Message) -> bool {
+    msg.is_self
+        && matches!(
+            msg.content_type,
+            ContentType::Image | ContentType::Video | ContentType::File
+        )
+        && FileMsg::from(&msg.content).server_name.is_empty()
+}
+
+/// save the task and upload the file by chunks in background
+pub fn start_upload(conv_type: RightContentType, msg: Message, file: File) {
+    spawn_local(async move {
+        let task = UploadTask::new(String::new(), conv_type, msg, file.size() as u64);
+        if let Err(err) = db::db_ins().uploads.put(&task, &file).await {
+            error!("save upload task error: {:?}", err);
+        }
+        run(task, file).await;
+    });
+}
+
+/// resume all unfinished tasks, called after the websocket connected
+pub fn resume_uploads() {
+    spawn_local(async {
+        let tasks = match db::db_ins().uploads.get_all().await {
+            Ok(tasks) => tasks,
+            Err(err) => {
+                error!("query upload tasks error: {:?}", err);
+                return;
+            }
+        };
+        for (task, file) in tasks {
+            spawn_local(run(task, file));
+        }
+    });
+}
+
+pub fn cancel_upload(local_id: AttrValue) {
+    if direct_file::cancel(&local_id) {
+        return;
+    }
+    let is_running = RUNNING.with(|r| r.borrow().contains(local_id.as_str()));
+    if is_running {
+        // the running task will clean itself before uploading the next chunk
+        CANCELED.with(|c| c.borrow_mut().insert(local_id.to_string()));
+        return;
+    }
+
+    spawn_local(async move {
+        let tasks = db::db_ins().uploads.get_all().await.unwrap_or_default();
+        if let Some((task, _)) = tasks.into_iter().find(|(t, _)| t.local_id == local_id) {
+            clean_canceled(task).await;
+        }
+    });
+}
+
+async fn run(mut task: UploadTask, file: File) {
+    let local_id = task.local_id.clone();
+    // the task of the file being sent directly is only kept for reloading
+    if direct_file::is_sending(&local_id) {
+        return;
+    }
+    if !RUNNING.with(|r| r.borrow_mut().insert(local_id.clone())) {
+        return;
+    }
+
+    let result = upload(&mut task, &file).await;
+    RUNNING.with(|r| r.borrow_mut().remove(&local_id));
+    let canceled = CANCELED.with(|c| c.borrow_mut().remove(&local_id));
+
+    match result {
+        Ok(Uploaded::Chunks) => {
+            if let Err(err) = finish(task).await {
+                error!("complete upload error: {:?}", err);
+                Notification::error(err).notify();
+                UploadProgressState::notify(local_id.into(), 0, 0, UploadStatus::Paused);
+            }
+        }
+        // the single request can't be stopped, drop the file after it
+        Ok(Uploaded::File(_)) if canceled => clean_canceled(task).await,
+        Ok(Uploaded::File(server_name)) => {
+            if let Err(err) = complete(task, server_name).await {
+                error!("complete upload error: {:?}", err);
+                Notification::error(err).notify();
+                UploadProgressState::notify(local_id.into(), 0, 0, UploadStatus::Paused);
+            }
+        }
+        Ok(Uploaded::Canceled) => clean_canceled(task).await,
+        // the user doesn't want it anymore
+        Err(_) if canceled => clean_canceled(task).await,
+        Err(err) => {
+            // keep the task, it will be resumed after reconnecting
+            error!("upload file error: {:?}", err);
+            UploadProgressState::notify(
+                local_id.into(),
+                task.uploaded(),
+                task.size,
+                UploadStatus::Paused,
+            );
+        }
+    }
+}
+
+enum Uploaded {
+    /// all chunks are uploaded, they need to be merged
+    Chunks,
+    /// uploaded by a single request, contains the server name
+    File(String),
+    Canceled,
+}
+
+async fn upload(task: &mut UploadTask, file: &File) -> Result<Uploaded> {
+    if task.upload_id.is_empty() {
+        match api::file()
+            .upload_init(&file.name(), task.size, UPLOAD_CHUNK_SIZE)
+            .await?
+        {
+            Some(upload_id) => {
+                task.upload_id = upload_id;
+                db::db_ins().uploads.update(task).await?;
+            }
+            // fall back to the single request, it can't be paused
+            None => {
+                UploadProgressState::notify(
+                    task.local_id.clone().into(),
+                    0,
+                    task.size,
+                    UploadStatus::Uploading,
+                );
+                let server_name = api::file().upload_file(file).await?;
+                return Ok(Uploaded::File(server_name));
+            }
+        }
+    }
+
+    UploadProgressState::notify(
+        task.local_id.clone().into(),
+        task.uploaded(),
+        task.size,
+        UploadStatus::Uploading,
+    );
+
+    while !task.is_finished() {
+        if CANCELED.with(|c| c.borrow().contains(&task.local_id)) {
+            return Ok(Uploaded::Canceled);
+        }
+        let (start, end) = task.chunk_range(task.next_chunk);
+        let chunk = file.slice_with_f64_and_f64(start, end)?;
+        upload_chunk(&task.upload_id, task.next_chunk, &chunk).await?;
+
+        task.next_chunk += 1;
+        db::db_ins().uploads.update(task).await?;
+        UploadProgressState::notify(
+            task.local_id.clone().into(),
+            task.uploaded(),
+            task.size,
+            UploadStatus::Uploading,
+        );
+    }
+    Ok(Uploaded::Chunks)
+}
+
+async fn upload_chunk(upload_id: &str, index: u32, chunk: &Blob) -> Result<()> {
+    let mut retry = 0;
+    loop {
+        match api::file().upload_chunk(upload_id, index, chunk).await {
+            Ok(()) => return Ok(()),
+            Err(err) if retry < MAX_RETRY => {
+                log::warn!("upload chunk {} error: {:?}, retry: {}", index, err, retry);
+                sleep(RETRY_DELAY << retry).await;
+                retry += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// merge the chunks and send the message with the server name
+async fn finish(task: UploadTask) -> Result<()> {
+    let server_name = api::file().upload_complete(&task.upload_id).await?;
+    complete(task, server_name).await
+}
+
+/// delete the task and send the message with the server name
+pub(crate) async fn complete(task: UploadTask, server_name: String) -> Result<()> {
+    let mut msg = task.msg;
+    let mut file_msg = FileMsg::from(&msg.content);
+    file_msg.server_name = server_name;
+    msg.content = file_msg.to_string().into();
+
+    db::db_ins().uploads.del(&task.local_id).await?;
+    UploadProgressState::notify(
+        msg.local_id.clone(),
+        task.size,
+        task.size,
+        UploadStatus::Finished,
+    );
+
+    match task.conv_type {
+        RightContentType::Friend => {
+            db::db_ins().messages.add_message(&msg).await?;
+            Dispatch::<SendMessageState>::global().reduce_mut(|s| s.msg = Msg::Single(msg));
+        }
+        RightContentType::Group => {
+            db::db_ins().group_msgs.put(&msg).await?;
+            Dispatch::<SendMessageState>::global()
+                .reduce_mut(|s| s.msg = Msg::Group(GroupMsg::Message(msg)));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// delete the task, the pending message and the uploaded chunks
+pub(crate) async fn clean_canceled(task: UploadTask) {
+    if let Err(err) = db::db_ins().uploads.del(&task.local_id).await {
+        error!("delete upload task error: {:?}", err);
+    }
+    let local_id = task.msg.local_id.clone();
+    let result = match task.conv_type {
+        RightContentType::Group => db::db_ins().group_msgs.delete(&local_id).await,
+        _ => db::db_ins().messages.delete(&local_id).await,
+    };
+    if let Err(err) = result {
+        error!("delete pending message error: {:?}", err);
+    }
+    UploadProgressState::notify(local_id, 0, task.size, UploadStatus::Canceled);
+
+    if !task.upload_id.is_empty() {
+        if let Err(err) = api::file().upload_cancel(&task.upload_id).await {
+            error!("cancel upload error: {:?}", err);
+        }
+    }
+}
+
+async fn sleep(millis: u32) {
+    let (tx, rx) = oneshot::channel::<()>();
+    let _timeout = Timeout::new(millis, move || {
+        let _ = tx.send(());
+    });
+    let _ = rx.await;
+}
@@ -1,13 +1,32 @@
+use js_sys::Uint8Array;
 use log::error;
-use wasm_bindgen_futures::spawn_local;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{AudioBuffer, AudioContext, Blob, BlobPropertyBag, HtmlElement, Url};
+use yew::AttrValue;
 use yewdux::Dispatch;
 
 use sandcat_sdk::{
     api, db,
-    model::friend::Friend,
-    state::{ItemType, UpdateFriendState},
+    error::{Error, Result},
+    model::{
+        attachment::{Attachment, AttachmentKind},
+        direct_file::is_direct_file,
+        friend::Friend,
+        message::{GroupMsg, Message, Msg},
+        notification::Notification,
+        voice::Voice,
+        RightContentType,
+    },
+    state::{
+        AttachmentQuotaState, AudioDownloadedState, ItemType, SendAudioMsgState, SendMessageState,
+        UpdateFriendState,
+    },
 };
 
+/// the count of the bars in the voice message
+pub const WAVEFORM_BARS: usize = 32;
+
 pub fn update_friend_remark(user_id: String, friend: Friend) {
     spawn_local(async move {
         let remark = friend.remark.as_ref().unwrap();
@@ -33,3 +52,210 @@ pub fn update_friend_remark(user_id: String, friend: Friend) {
         }
     });
 }
+
+/// forward the message to the selected friends
+pub fn forward_msg(mut msg: Message, user_id: AttrValue, friends: Vec<String>) {
+    spawn_local(async move {
+        for item in friends.into_iter() {
+            msg.send_id.clone_from(&user_id);
+            msg.friend_id = item.into();
+            msg.server_id = AttrValue::default();
+            msg.local_id = nanoid::nanoid!().into();
+            msg.is_read = 1;
+            msg.is_self = true;
+            if let Err(err) = db::db_ins().messages.add_message(&msg).await {
+                error!("forword message error: store message error{:?}", err);
+                Notification::error(err).notify();
+            }
+            Dispatch::<SendMessageState>::global().reduce_mut(|s| s.msg = Msg::Single(msg.clone()));
+        }
+    });
+}
+
+/// cache the voice and insert the message into the list,
+/// then send the message after the voice uploaded
+pub async fn send_voice_msg(conv_type: RightContentType, mut voice: Voice, mut msg: Message) {
+    voice.played = true;
+    match compute_waveform(&voice.data).await {
+        Ok(waveform) => voice.waveform = waveform,
+        Err(e) => error!("compute waveform error:{:?}", e),
+    }
+    if let Err(e) = db::db_ins().voices.save(&voice).await {
+        error!("save voice error:{:?}", e);
+    }
+    Dispatch::<SendAudioMsgState>::global().set(SendAudioMsgState { msg: msg.clone() });
+
+    // send to file server
+    let name = match api::file().upload_voice(&voice.data).await {
+        Ok(name) => name,
+        Err(e) => {
+            error!("send to file server error:{:?}", e);
+            return;
+        }
+    };
+    msg.content = name.into();
+
+    // send to message server
+    let msg = match conv_type {
+        RightContentType::Friend => {
+            if let Err(e) = db::db_ins().messages.add_message(&msg).await {
+                error!("save message error:{:?}", e);
+            }
+            Msg::Single(msg)
+        }
+        RightContentType::Group => {
+            if let Err(e) = db::db_ins().group_msgs.put(&msg).await {
+                error!("save message error:{:?}", e);
+            }
+            Msg::Group(GroupMsg::Message(msg))
+        }
+        _ => return,
+    };
+    Dispatch::<SendMessageState>::global().reduce_mut(|s| s.msg = msg);
+}
+
+/// download the voice from the file server and cache it,
+/// the message list is notified to mark the message as downloaded
+pub async fn download_voice(url: &str, local_id: &str, duration: u8) -> Result<Voice> {
+    let data = api::file().download_voice(url).await?;
+
+    let mut voice = Voice::new(local_id.to_string(), data, duration);
+    // it may be downloaded again, keep the played flag
+    if let Ok(old) = db::db_ins().voices.get(local_id).await {
+        voice.played = old.played;
+    }
+    voice.waveform = compute_waveform(&voice.data).await.unwrap_or_else(|err| {
+        error!("compute waveform error: {:?}", err);
+        Vec::new()
+    });
+    db::db_ins().voices.save(&voice).await?;
+
+    Dispatch::<AudioDownloadedState>::global()
+        .reduce_mut(|s| s.local_id = local_id.to_string().into());
+    Ok(voice)
+}
+
+/// decode the voice and take the peak of every bar
+pub async fn compute_waveform(data: &[u8]) -> Result<Vec<u8>> {
+    let context = AudioContext::new()?;
+    // the buffer is detached after decoding, so decode a copy
+    let buffer = Uint8Array::from(data).buffer();
+    let decoded = JsFuture::from(context.decode_audio_data(&buffer)?).await;
+    let _ = context.close();
+    let decoded = decoded?.dyn_into::<AudioBuffer>()?;
+    let samples = decoded.get_channel_data(0)?;
+    Ok(waveform(&samples))
+}
+
+/// the peak of every bar scaled to 0-255, the tail is in the last bar
+fn waveform(samples: &[f32]) -> Vec<u8> {
+    if samples.is_empty() {
+        return Vec::new();
+    }
+
+    let size = samples.len().div_ceil(WAVEFORM_BARS);
+    let peaks: Vec<f32> = samples
+        .chunks(size)
+        .map(|chunk| chunk.iter().fold(0.0f32, |max, s| max.max(s.abs())))
+        .collect();
+    let max = peaks.iter().cloned().fold(0.0f32, f32::max);
+    if max == 0.0 {
+        return vec![0; peaks.len()];
+    }
+    peaks
+        .into_iter()
+        .map(|peak| (peak / max * 255.0).round() as u8)
+        .collect()
+}
+
+/// load the attachment from the cache, download it from the file server if it is not cached,
+/// return an object url which should be revoked by the caller
+pub async fn load_attachment(
+    server_name: &str,
+    conv_id: &str,
+    kind: AttachmentKind,
+    mime: &str,
+) -> Result<String> {
+    let attachment = match db::db_ins().attachments.get(server_name).await? {
+        Some(attachment) => attachment,
+        // the file sent directly is not on the file server
+        None if is_direct_file(server_name) => {
+            return Err(Error::local_not_found(server_name));
+        }
+        None => {
+            let data = if kind == AttachmentKind::Avatar {
+                api::file().download_avatar(server_name).await?
+            } else {
+                api::file().download_file(server_name).await?
+            };
+            let attachment = Attachment::new(
+                server_name.to_string(),
+                conv_id.to_string(),
+                kind,
+                mime.to_string(),
+                data,
+            );
+            db::db_ins().attachments.put(&attachment).await?;
+
+            // keep the cache under the quota, except the one just written
+            let keep = server_name.to_string();
+            spawn_local(async move {
+                if let Err(err) = db::db_ins()
+                    .attachments
+                    .evict(AttachmentQuotaState::get(), Some(&keep))
+                    .await
+                {
+                    error!("evict attachments error:{:?}", err);
+                }
+            });
+            attachment
+        }
+    };
+
+    let u8_array = js_sys::Uint8Array::from(attachment.data.as_slice());
+    let array = js_sys::Array::new_with_length(1);
+    array.set(0, u8_array.buffer().into());
+
+    let mut property_bag = BlobPropertyBag::new();
+    property_bag.type_(&attachment.mime);
+    let blob = Blob::new_with_u8_array_sequence_and_options(&array, &property_bag)?;
+    Ok(Url::create_object_url_with_blob(&blob)?)
+}
+
+/// save the object url as a file with the given name
+pub fn save_file(url: &str, name: &str) {
+    let Ok(link) = gloo::utils::document().create_element("a") else {
+        return;
+    };
+    let _ = link.set_attribute("href", url);
+    let _ = link.set_attribute("download", name);
+    if let Some(link) = link.dyn_ref::<HtmlElement>() {
+        link.click();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_waveform() {
+        assert!(waveform(&[]).is_empty());
+
+        let samples: Vec<f32> = (0..64).map(|i| i as f32 / -64.0).collect();
+        let bars = waveform(&samples);
+        assert_eq!(bars.len(), WAVEFORM_BARS);
+        // the negative samples count by the amplitude
+        assert_eq!(bars[WAVEFORM_BARS - 1], 255);
+        assert!(bars.windows(2).all(|w| w[0] <= w[1]));
+
+        // the loud tail is not dropped when the samples are not a multiple of the bars
+        let mut samples = vec![0.1; 40];
+        samples[39] = 0.5;
+        let bars = waveform(&samples);
+        assert!(bars.len() <= WAVEFORM_BARS);
+        assert_eq!(bars.last(), Some(&255));
+
+        assert_eq!(waveform(&[0.0; 8]), vec![0; 8]);
+    }
+}
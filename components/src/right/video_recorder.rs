@@ -0,0 +1,395 @@
+use fluent::{FluentBundle, FluentResource};
+use gloo::timers::callback::{Interval, Timeout};
+use js_sys::Array;
+use log::error;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{
+    Blob, BlobEvent, File, FilePropertyBag, HtmlVideoElement, MediaRecorder, MediaRecorderOptions,
+    MediaStream, MediaStreamTrack, Url,
+};
+use yew::prelude::*;
+
+use i18n::{en_us, zh_cn, LanguageType};
+use icons::CloseIcon;
+use sandcat_sdk::{
+    error::Error,
+    model::notification::Notification,
+    state::{I18nState, MediaDeviceState, MobileState, Notify},
+};
+use utils::tr;
+
+use crate::constant::{CANCEL, ERROR, HOLD_TO_RECORD, RETAKE, SEND};
+
+/// the longest video can be recorded, in seconds
+const MAX_DURATION: u32 = 30;
+/// the recorded video shorter than this is dropped, in milliseconds
+const MIN_DURATION: i64 = 1000;
+/// the first supported type is used
+const MIME_TYPES: [&str; 3] = ["video/webm;codecs=vp8,opus", "video/webm", "video/mp4"];
+
+/// hold the button to record a short video,
+/// the video can be previewed before sending
+pub struct VideoRecorder {
+    i18n: FluentBundle<FluentResource>,
+    camera_node: NodeRef,
+    is_mobile: bool,
+    stream: Option<MediaStream>,
+    media_recorder: Option<MediaRecorder>,
+    on_data_available_closure: Option<Closure<dyn FnMut(BlobEvent)>>,
+    on_stop_closure: Option<Closure<dyn FnMut()>>,
+    mime_type: &'static str,
+    chunks: Array,
+    state: VideoRecorderState,
+    start_time: i64,
+    /// recording time in seconds, updated every second
+    time: u32,
+    timer: Option<Interval>,
+    max_timer: Option<Timeout>,
+    /// the recorded video and its object url
+    video: Option<(File, String)>,
+}
+
+#[derive(Clone, Properties, PartialEq)]
+pub struct VideoRecorderProps {
+    pub send_video: Callback<File>,
+    pub close: Callback<()>,
+}
+
+pub enum VideoRecorderMsg {
+    Ready(MediaStream),
+    PrepareError(JsValue),
+    Start,
+    Tick,
+    Stop,
+    DataAvailable(Blob),
+    Recorded,
+    Retake,
+    Send,
+    Cancel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum VideoRecorderState {
+    Prepare,
+    Ready,
+    Recording,
+    Recorded,
+    Error,
+}
+
+impl Component for VideoRecorder {
+    type Message = VideoRecorderMsg;
+    type Properties = VideoRecorderProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let res = match I18nState::get().lang {
+            LanguageType::ZhCN => zh_cn::RECORDER,
+            LanguageType::EnUS => en_us::RECORDER,
+        };
+        let i18n = utils::create_bundle(res);
+
+        ctx.link().send_future(async {
+            let devices = MediaDeviceState::get();
+            match utils::get_video_stream(devices.camera(), devices.microphone()).await {
+                Ok(stream) => VideoRecorderMsg::Ready(stream),
+                Err(e) => VideoRecorderMsg::PrepareError(e),
+            }
+        });
+
+        let mime_type = MIME_TYPES
+            .into_iter()
+            .find(|t| MediaRecorder::is_type_supported(t))
+            .unwrap_or("video/webm");
+
+        Self {
+            i18n,
+            camera_node: NodeRef::default(),
+            is_mobile: MobileState::is_mobile(),
+            stream: None,
+            media_recorder: None,
+            on_data_available_closure: None,
+            on_stop_closure: None,
+            mime_type,
+            chunks: Array::new(),
+            state: VideoRecorderState::Prepare,
+            start_time: 0,
+            time: 0,
+            timer: None,
+            max_timer: None,
+            video: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            VideoRecorderMsg::Ready(stream) => {
+                self.stream = Some(stream);
+                self.state = VideoRecorderState::Ready;
+                true
+            }
+            VideoRecorderMsg::PrepareError(e) => {
+                error!("video recorder error: {:?}", e);
+                self.state = VideoRecorderState::Error;
+                self.clean();
+                Notification::error(Error::js_err(e)).notify();
+                true
+            }
+            VideoRecorderMsg::Start => {
+                if self.state != VideoRecorderState::Ready {
+                    return false;
+                }
+                if let Err(e) = self.start(ctx) {
+                    ctx.link().send_message(VideoRecorderMsg::PrepareError(e));
+                    return false;
+                }
+                self.state = VideoRecorderState::Recording;
+                true
+            }
+            VideoRecorderMsg::Tick => {
+                self.time =
+                    ((chrono::Utc::now().timestamp_millis() - self.start_time) / 1000) as u32;
+                true
+            }
+            VideoRecorderMsg::Stop => {
+                if self.state != VideoRecorderState::Recording {
+                    return false;
+                }
+                self.timer = None;
+                self.max_timer = None;
+                if let Some(ref recorder) = self.media_recorder {
+                    let _ = recorder.stop();
+                }
+                false
+            }
+            VideoRecorderMsg::DataAvailable(blob) => {
+                self.chunks.push(&blob);
+                false
+            }
+            VideoRecorderMsg::Recorded => {
+                self.media_recorder = None;
+                self.on_data_available_closure = None;
+                self.on_stop_closure = None;
+                let chunks = std::mem::replace(&mut self.chunks, Array::new());
+
+                // too short, record again
+                if chrono::Utc::now().timestamp_millis() - self.start_time < MIN_DURATION {
+                    self.state = VideoRecorderState::Ready;
+                    return true;
+                }
+                match self.to_file(&chunks) {
+                    Ok(video) => {
+                        self.video = Some(video);
+                        self.state = VideoRecorderState::Recorded;
+                    }
+                    Err(e) => {
+                        error!("create video file error: {:?}", e);
+                        self.state = VideoRecorderState::Ready;
+                    }
+                }
+                true
+            }
+            VideoRecorderMsg::Retake => {
+                self.revoke_video();
+                self.time = 0;
+                self.state = VideoRecorderState::Ready;
+                true
+            }
+            VideoRecorderMsg::Send => {
+                if let Some((file, _)) = self.video.as_ref() {
+                    ctx.props().send_video.emit(file.clone());
+                }
+                ctx.props().close.emit(());
+                false
+            }
+            VideoRecorderMsg::Cancel => {
+                ctx.props().close.emit(());
+                false
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        if self.state == VideoRecorderState::Error {
+            return html! {
+                <div class="video-recorder box-shadow">
+                    {self.close_html(ctx)}
+                    <div class="error">
+                        {tr!(self.i18n, ERROR)}
+                    </div>
+                </div>
+            };
+        }
+
+        let content = if self.state == VideoRecorderState::Recorded {
+            let src = self.video.as_ref().map(|(_, url)| url.clone());
+            html! {
+                <>
+                    <video class="video-recorder-video" {src} controls={true} autoplay={true} />
+                    <div class="video-recorder-footer">
+                        <button class="btn" onclick={ctx.link().callback(|_| VideoRecorderMsg::Send)}>
+                            {tr!(self.i18n, SEND)}
+                        </button>
+                        <button class="btn" onclick={ctx.link().callback(|_| VideoRecorderMsg::Retake)}>
+                            {tr!(self.i18n, RETAKE)}
+                        </button>
+                    </div>
+                </>
+            }
+        } else {
+            let recording = self.state == VideoRecorderState::Recording;
+            let mut class = Classes::from("video-recorder-btn pointer");
+            if recording {
+                class.push("video-recorder-btn-recording");
+            }
+            let hint = if recording {
+                format!("{}s / {}s", self.time, MAX_DURATION)
+            } else {
+                tr!(self.i18n, HOLD_TO_RECORD)
+            };
+
+            // touch events for mobile, mouse events for desktop
+            let btn = if self.is_mobile {
+                let ontouchstart = ctx.link().callback(|e: TouchEvent| {
+                    e.prevent_default();
+                    VideoRecorderMsg::Start
+                });
+                let ontouchend = ctx.link().callback(|e: TouchEvent| {
+                    e.prevent_default();
+                    VideoRecorderMsg::Stop
+                });
+                html!(<div {class} {ontouchstart} {ontouchend}></div>)
+            } else {
+                let onmousedown = ctx.link().callback(|_| VideoRecorderMsg::Start);
+                let onmouseup = ctx.link().callback(|_| VideoRecorderMsg::Stop);
+                let onmouseleave = ctx.link().callback(|_| VideoRecorderMsg::Stop);
+                html!(<div {class} {onmousedown} {onmouseup} {onmouseleave}></div>)
+            };
+            html! {
+                <div class="video-recorder-footer">
+                    <span class="video-recorder-hint">{hint}</span>
+                    {btn}
+                </div>
+            }
+        };
+
+        // keep the camera element while previewing,
+        // so that the stream doesn't need to be attached again
+        let camera_style = (self.state == VideoRecorderState::Recorded).then_some("display: none;");
+        html! {
+            <div class="video-recorder box-shadow">
+                {self.close_html(ctx)}
+                <video class="video-recorder-video" ref={self.camera_node.clone()}
+                    style={camera_style} autoplay={true} muted={true} playsinline={true} />
+                {content}
+            </div>
+        }
+    }
+
+    fn rendered(&mut self, _ctx: &Context<Self>, _first_render: bool) {
+        if let (Some(video), Some(stream)) = (
+            self.camera_node.cast::<HtmlVideoElement>(),
+            self.stream.as_ref(),
+        ) {
+            if video.src_object().is_none() {
+                // the muted attribute doesn't work for the element created by script
+                video.set_muted(true);
+                video.set_src_object(Some(stream));
+            }
+        }
+    }
+
+    fn destroy(&mut self, _ctx: &Context<Self>) {
+        if let Some(ref recorder) = self.media_recorder {
+            recorder.set_onstop(None);
+            let _ = recorder.stop();
+        }
+        self.clean();
+        self.revoke_video();
+    }
+}
+
+impl VideoRecorder {
+    fn start(&mut self, ctx: &Context<Self>) -> Result<(), JsValue> {
+        let Some(stream) = self.stream.as_ref() else {
+            return Err(JsValue::from_str("no video stream"));
+        };
+        let mut options = MediaRecorderOptions::new();
+        options.mime_type(self.mime_type);
+        let recorder =
+            MediaRecorder::new_with_media_stream_and_media_recorder_options(stream, &options)?;
+
+        let link = ctx.link().clone();
+        let on_data_available_closure = Closure::wrap(Box::new(move |event: BlobEvent| {
+            if let Some(blob) = event.data() {
+                link.send_message(VideoRecorderMsg::DataAvailable(blob));
+            }
+        }) as Box<dyn FnMut(BlobEvent)>);
+        let link = ctx.link().clone();
+        let on_stop_closure = Closure::wrap(Box::new(move || {
+            link.send_message(VideoRecorderMsg::Recorded);
+        }) as Box<dyn FnMut()>);
+        recorder.set_ondataavailable(Some(on_data_available_closure.as_ref().unchecked_ref()));
+        recorder.set_onstop(Some(on_stop_closure.as_ref().unchecked_ref()));
+        recorder.start()?;
+
+        self.start_time = chrono::Utc::now().timestamp_millis();
+        self.time = 0;
+        let link = ctx.link().clone();
+        self.timer = Some(Interval::new(1000, move || {
+            link.send_message(VideoRecorderMsg::Tick);
+        }));
+        let link = ctx.link().clone();
+        self.max_timer = Some(Timeout::new(MAX_DURATION * 1000, move || {
+            link.send_message(VideoRecorderMsg::Stop);
+        }));
+
+        self.on_data_available_closure = Some(on_data_available_closure);
+        self.on_stop_closure = Some(on_stop_closure);
+        self.media_recorder = Some(recorder);
+        Ok(())
+    }
+
+    fn to_file(&self, chunks: &Array) -> Result<(File, String), JsValue> {
+        // drop the codecs part
+        let mime = self.mime_type.split(';').next().unwrap_or(self.mime_type);
+        let ext = if mime == "video/mp4" { "mp4" } else { "webm" };
+        let mut options = FilePropertyBag::new();
+        options.type_(mime);
+        let name = format!("video_{}.{}", chrono::Utc::now().timestamp_millis(), ext);
+        let file = File::new_with_blob_sequence_and_options(chunks, &name, &options)?;
+        let url = Url::create_object_url_with_blob(&file)?;
+        Ok((file, url))
+    }
+
+    fn close_html(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <span class="video-recorder-close pointer"
+                title={tr!(self.i18n, CANCEL)}
+                onclick={ctx.link().callback(|_| VideoRecorderMsg::Cancel)}>
+                <CloseIcon />
+            </span>
+        }
+    }
+
+    fn revoke_video(&mut self) {
+        if let Some((_, url)) = self.video.take() {
+            let _ = Url::revoke_object_url(&url);
+        }
+    }
+
+    fn clean(&mut self) {
+        self.media_recorder = None;
+        self.on_data_available_closure = None;
+        self.on_stop_closure = None;
+        self.timer = None;
+        self.max_timer = None;
+        // release the camera
+        if let Some(stream) = self.stream.take() {
+            for track in stream.get_tracks() {
+                if let Ok(track) = track.dyn_into::<MediaStreamTrack>() {
+                    track.stop();
+                }
+            }
+        }
+    }
+}
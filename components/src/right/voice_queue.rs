@@ -0,0 +1,73 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+
+use log::error;
+use wasm_bindgen_futures::spawn_local;
+
+use sandcat_sdk::{db, model::message::Message, model::RightContentType};
+
+use crate::right::util::download_voice;
+
+/// the voices downloaded at the same time
+const MAX_DOWNLOADS: usize = 2;
+
+thread_local! {
+    /// voice messages waiting to be downloaded
+    static QUEUE: RefCell<VecDeque<(RightContentType, Message)>> = const { RefCell::new(VecDeque::new()) };
+    static RUNNING: Cell<usize> = const { Cell::new(0) };
+}
+
+/// download the voices in background after the messages are saved,
+/// the message is updated after its voice is downloaded
+pub fn enqueue_voices(conv_type: RightContentType, msgs: Vec<Message>) {
+    if msgs.is_empty() {
+        return;
+    }
+    QUEUE.with(|q| {
+        q.borrow_mut()
+            .extend(msgs.into_iter().map(|msg| (conv_type.clone(), msg)))
+    });
+    while RUNNING.with(Cell::get) < MAX_DOWNLOADS {
+        RUNNING.with(|r| r.set(r.get() + 1));
+        spawn_local(run());
+    }
+}
+
+async fn run() {
+    while let Some((conv_type, msg)) = QUEUE.with(|q| q.borrow_mut().pop_front()) {
+        // it may be downloaded by playing it or deleted while waiting
+        match get_msg(&conv_type, &msg.local_id).await {
+            Some(msg) if !msg.audio_downloaded => {}
+            _ => continue,
+        }
+        // it is downloaded again when playing if failed
+        if let Err(e) = download_voice(&msg.content, &msg.local_id, msg.audio_duration).await {
+            error!("download voice error: {}", e);
+            continue;
+        }
+        // write the row read now, the snapshot is stale after downloading
+        let Some(mut msg) = get_msg(&conv_type, &msg.local_id).await else {
+            continue;
+        };
+        msg.audio_downloaded = true;
+        let result = match conv_type {
+            RightContentType::Group => db::db_ins().group_msgs.put(&msg).await,
+            _ => db::db_ins().messages.add_message(&msg).await,
+        };
+        if let Err(e) = result {
+            error!("update voice message error: {:?}", e);
+        }
+    }
+    RUNNING.with(|r| r.set(r.get() - 1));
+}
+
+async fn get_msg(conv_type: &RightContentType, local_id: &str) -> Option<Message> {
+    let result = match conv_type {
+        RightContentType::Group => db::db_ins().group_msgs.get(local_id).await,
+        _ => db::db_ins().messages.get(local_id).await,
+    };
+    result.unwrap_or_else(|e| {
+        error!("query voice message error: {:?}", e);
+        None
+    })
+}
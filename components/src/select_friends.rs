@@ -21,6 +21,7 @@ use sandcat_sdk::state::ItemType;
 use sandcat_sdk::state::MobileState;
 use utils::tr;
 
+use crate::avatar_img::AvatarImg;
 use crate::constant::CANCEL;
 use crate::constant::EMPTY_RESULT;
 use crate::constant::ERROR;
@@ -185,7 +186,7 @@ impl Component for SelectFriendList {
                             <div class="item" key={index.to_string()}>
                                 <input type="checkbox" id={index.to_string()} name="friend" value={index.to_string()} />
                                 <label for={index.to_string()}  class="item-card">
-                                    <img alt="avatar" src={utils::get_avatar_url(&item.avatar)}/>
+                                    <AvatarImg avatar={item.avatar.clone()} />
                                     {name}
                                 </label>
                             </div>
@@ -82,6 +82,7 @@ impl Component for TopBar {
             ComponentType::Contacts => "contacts",
             ComponentType::Messages => "messages",
             ComponentType::Setting => "setting",
+            ComponentType::Calls => "calls",
             ComponentType::Default => "default",
         };
 
@@ -95,7 +96,7 @@ impl Component for TopBar {
             ComponentType::Default => {
                 html!({ tr!(self.i18n, CANCEL) })
             }
-            ComponentType::Setting => html!(),
+            ComponentType::Setting | ComponentType::Calls => html!(),
         };
 
         let onchange = ctx.link().callback(|_| TopBarMsg::SearchInputChanged);
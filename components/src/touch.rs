@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use web_sys::TouchEvent;
+
+/// gesture recognized from two continuous touch events
+pub enum TouchGesture {
+    /// two fingers, delta is the change of the distance between them
+    Pinch {
+        delta: f64,
+        center_x: f64,
+        center_y: f64,
+    },
+    /// one finger
+    Pan { dx: f64, dy: f64 },
+}
+
+/// positions of the current touch points, keyed by the touch identifier
+#[derive(Default)]
+pub struct Touches(HashMap<i32, (f64, f64)>);
+
+impl Touches {
+    /// record the touch points, called after every touch event
+    pub fn update(&mut self, event: &TouchEvent) {
+        self.0.clear();
+        for i in 0..event.touches().length() {
+            if let Some(touch) = event.touches().item(i) {
+                self.0.insert(
+                    touch.identifier(),
+                    (touch.client_x() as f64, touch.client_y() as f64),
+                );
+            }
+        }
+    }
+
+    /// compare the touch move event with the recorded points
+    pub fn gesture(&self, event: &TouchEvent) -> Option<TouchGesture> {
+        let touches: Vec<_> = self.0.values().cloned().collect();
+        if touches.len() == 2 {
+            let new_touches: Vec<_> = (0..event.touches().length())
+                .filter_map(|i| event.touches().item(i))
+                .map(|touch| (touch.client_x() as f64, touch.client_y() as f64))
+                .collect();
+            if new_touches.len() < 2 {
+                return None;
+            }
+
+            let old_distance = ((touches[0].0 - touches[1].0).powi(2)
+                + (touches[0].1 - touches[1].1).powi(2))
+            .sqrt();
+            let new_distance = ((new_touches[0].0 - new_touches[1].0).powi(2)
+                + (new_touches[1].1 - new_touches[0].1).powi(2))
+            .sqrt();
+
+            let delta = new_distance - old_distance;
+            // 增加一个基本的阈值，避免误判
+            if delta.abs() > 1.0 {
+                return Some(TouchGesture::Pinch {
+                    delta,
+                    center_x: (new_touches[0].0 + new_touches[1].0) / 2.0,
+                    center_y: (new_touches[0].1 + new_touches[1].1) / 2.0,
+                });
+            }
+        } else if touches.len() == 1 {
+            let (sx, sy) = touches[0];
+            if let Some(touch) = event.touches().item(0) {
+                return Some(TouchGesture::Pan {
+                    dx: touch.client_x() as f64 - sx,
+                    dy: touch.client_y() as f64 - sy,
+                });
+            }
+        }
+        None
+    }
+}
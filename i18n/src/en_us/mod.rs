@@ -12,6 +12,7 @@ to_register = REGISTER NOW
 pub const TOP: &str = r#"
 msg = Messages
 contact = Contacts
+calls = Calls
 "#;
 
 pub const SEARCH_DOCK: &str = r#"
@@ -62,6 +63,29 @@ stream_error = Cannot detect related device
 other_error = Other error
 unkonw_error = Unkonw error
 busy = Busy
+group_call_invite = started a group call
+call_full = The call is full
+share_screen = Share screen
+sharing_screen = You are sharing your screen
+reconnecting = Reconnecting...
+user_busy = User is busy
+call_waiting = Call waiting:
+decline = Decline
+end_and_accept = End current call and accept
+devices = Devices
+microphone = Microphone
+camera = Camera
+speaker = Speaker
+default_device = Default
+not_available = is not available
+leave_voicemail = Leave a voice message
+mini_window = Minimize
+pip = Picture in picture
+record_call = Record the call
+friend_recording = The other side is recording the call
+call_recorded = The call is recorded
+send_as_video = Send as video
+send_as_file = Send as file
 "#;
 
 pub const CONVERSATION: &str = r#"
@@ -79,6 +103,7 @@ audio_call = [Voice Call]
 error = [ERROR]
 knock_off_msg = Another device has logged in your account, if it is not you, please check your account password.
 ok = OK
+syncing = Syncing messages
 "#;
 
 pub const CONTACTS: &str = r#"
@@ -88,12 +113,29 @@ no_groups = No Groups
 no_result = No Result
 "#;
 
+pub const CALLS: &str = r#"
+calls = Calls
+no_calls = No Calls
+incoming = Incoming
+outgoing = Outgoing
+video_call = video call
+voice_call = voice call
+missed = Missed
+declined = Declined
+canceled = Canceled
+no_answer = Not answered
+busy = Busy
+call_back = Call back
+voicemail = Voicemail
+"#;
+
 pub const MSG_ITEM: &str = r#"
 cancel = Canceled
 duration = Duration:
 deny = Denied
-busy = Busy
+busy = User is busy
 not_answer = Not answered
+voicemail = Voicemail
 "#;
 
 pub const ADD_FRIEND: &str = r#"
@@ -121,6 +163,14 @@ forward = Forward
 related = Quote
 "#;
 
+pub const IMAGE_VIEWER: &str = r#"
+download = Download
+forward = Forward
+rotate = Rotate
+zoom_reset = Original Size
+loading = Loading
+"#;
+
 pub const SELECT_FRIENDS: &str = r#"
 querying = Querying
 error = Query Error
@@ -167,6 +217,22 @@ small = Small
 medium = Medium
 large = Large
 larger = Larger
+storage = Storage:
+cache_quota = Cache Limit:
+clear_cache = Clear
+clear_all_cache = Clear All
+avatars = Avatars
+ice_server = Call Server:
+ice_urls = stun:host:3478,turn:host:3478
+ice_username = Username
+ice_credential = Credential
+devices = Devices:
+microphone = Microphone
+camera = Camera
+speaker = Speaker
+default_device = Default
+test_device = Test
+stop_test = Stop
 "#;
 
 pub const POSTCARD: &str = r#"
@@ -189,6 +255,7 @@ send = Send
 #file = File
 submit = Submit
 cancel = Cancel
+send_directly = Send directly
 no_empty = CAN NOT SEND AN EMPTY MESSAGE
 input_max_len = input len more than
 disabled = CAN NOT SEND FOR NOW
@@ -214,6 +281,8 @@ send_mobile = Release to send
 cancel = cancel
 cancel_mobile = Release to cancel
 error = ERROR: check your recorder
+hold_to_record = Hold to record
+retake = retake
 "#;
 
 pub const REGISTER: &str = r#"
@@ -256,6 +325,11 @@ remark = Remark
 group_desc = GroupDesc
 group_name = GroupName
 group_announcement = GroupAnno
+media = Media
+files = Files
+links = Links
+no_shared = Nothing shared yet
+loading = Loading
 "#;
 
 // 改成英文
@@ -51,6 +51,7 @@ cancel = 取消
 pub const TOP: &str = r#"
 msg = 消息
 contact = 联系人
+calls = 通话
 "#;
 
 pub const ADD_FRIEND: &str = r#"
@@ -76,6 +77,29 @@ stream_error = 没有检测到音视频设备
 other_error = 其他错误
 unkonw_error = 未知错误
 busy = 您正在通话中！
+group_call_invite = 发起了群通话
+call_full = 通话人数已满
+share_screen = 共享屏幕
+sharing_screen = 你正在共享屏幕
+reconnecting = 重新连接中...
+user_busy = 对方忙线中
+call_waiting = 来电等待：
+decline = 拒绝
+end_and_accept = 结束当前通话并接听
+devices = 设备
+microphone = 麦克风
+camera = 摄像头
+speaker = 扬声器
+default_device = 默认
+not_available = 暂时无法接听
+leave_voicemail = 留言
+mini_window = 缩小
+pip = 画中画
+record_call = 录制通话
+friend_recording = 对方正在录制通话
+call_recorded = 通话已录制
+send_as_video = 作为视频发送
+send_as_file = 作为文件发送
 "#;
 
 pub const CONVERSATION: &str = r#"
@@ -91,6 +115,7 @@ audio_call = [语音通话]
 error = [错误]
 knock_off_msg = 另一个设备登录了你的账号，如果不是你本人，请检查账号密码。
 ok = 确定
+syncing = 正在同步消息
 "#;
 
 pub const CONTACTS: &str = r#"
@@ -99,6 +124,22 @@ no_friends = 没有搜索结果
 no_result = 没有搜索结果
 "#;
 
+pub const CALLS: &str = r#"
+calls = 通话记录
+no_calls = 没有通话记录
+incoming = 呼入
+outgoing = 呼出
+video_call = 视频通话
+voice_call = 语音通话
+missed = 未接来电
+declined = 已拒绝
+canceled = 已取消
+no_answer = 对方未接听
+busy = 对方忙线
+call_back = 回拨
+voicemail = 语音留言
+"#;
+
 pub const RIGHT_CLICK_PANEL: &str = r#"
 delete = 删除
 mute = 消息免打扰
@@ -109,12 +150,21 @@ forward = 转发
 related = 引用
 "#;
 
+pub const IMAGE_VIEWER: &str = r#"
+download = 下载
+forward = 转发
+rotate = 旋转
+zoom_reset = 原始大小
+loading = 加载中
+"#;
+
 pub const MSG_ITEM: &str = r#"
 cancel = 已取消
 duration = 时间:
-busy = 占线
+busy = 对方忙线中
 deny = 已拒绝
 not_answer = 未接听
+voicemail = 语音留言
 "#;
 
 pub const SELECT_FRIENDS: &str = r#"
@@ -162,6 +212,22 @@ small = 小
 medium = 中
 large = 大
 larger = 更大
+storage = 存储空间:
+cache_quota = 缓存上限:
+clear_cache = 清理
+clear_all_cache = 全部清理
+avatars = 头像
+ice_server = 通话服务器：
+ice_urls = stun:host:3478,turn:host:3478
+ice_username = 用户名
+ice_credential = 密码
+devices = 设备：
+microphone = 麦克风
+camera = 摄像头
+speaker = 扬声器
+default_device = 默认
+test_device = 测试
+stop_test = 停止
 "#;
 
 pub const POSTCARD: &str = r#"
@@ -184,6 +250,7 @@ send = 发送
 #file = 文件
 submit = 确定
 cancel = 取消
+send_directly = 直接发送
 no_empty = 发送内容不能为空
 input_max_len = 发送内容不能超过
 disabled = 暂时无法发送消息
@@ -208,6 +275,8 @@ send_mobile = 松开发送
 cancel_mobile = 松开取消
 cancel = 取消
 error = 错误，请检查音频设备
+hold_to_record = 按住录制
+retake = 重拍
 "#;
 
 pub const REGISTER: &str = r#"
@@ -249,6 +318,11 @@ remark = 备注
 group_desc = 群描述
 group_name = 群名称
 group_announcement = 群公告
+media = 图片与视频
+files = 文件
+links = 链接
+no_shared = 暂无内容
+loading = 加载中
 "#;
 
 /// notification error type
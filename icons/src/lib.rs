@@ -384,6 +384,53 @@ pub fn video_record_icon() -> Html {
     }
 }
 
+#[function_component(ScreenShareIcon)]
+pub fn screen_share_icon() -> Html {
+    html! {
+        <svg xmlns="http://www.w3.org/2000/svg" width="1rem" height="1rem" viewBox="0 0 24 24">
+            <g fill="none" stroke="#000000" stroke-linecap="round" stroke-linejoin="round" stroke-width="1.5">
+                <path d="M20 4H4a1 1 0 0 0-1 1v11a1 1 0 0 0 1 1h16a1 1 0 0 0 1-1V5a1 1 0 0 0-1-1M8 21h8m-4-4v4"/>
+                <path d="M12 13.5v-6m-2.5 2.5L12 7.5l2.5 2.5"/>
+            </g>
+        </svg>
+    }
+}
+
+#[function_component(PipIcon)]
+pub fn pip_icon() -> Html {
+    html! {
+        <svg xmlns="http://www.w3.org/2000/svg" width="1rem" height="1rem" viewBox="0 0 24 24">
+            <g fill="none" stroke="#000000" stroke-linecap="round" stroke-linejoin="round" stroke-width="1.5">
+                <path d="M21 11V5a1 1 0 0 0-1-1H4a1 1 0 0 0-1 1v13a1 1 0 0 0 1 1h6"/>
+                <path d="M14 14h7v6h-7z"/>
+            </g>
+        </svg>
+    }
+}
+
+#[function_component(CallRecordIcon)]
+pub fn call_record_icon() -> Html {
+    html! {
+        <svg xmlns="http://www.w3.org/2000/svg" width="1rem" height="1rem" viewBox="0 0 24 24">
+            <g fill="none" stroke="#000000" stroke-linecap="round" stroke-linejoin="round" stroke-width="1.5">
+                <circle cx="12" cy="12" r="9"/>
+                <circle cx="12" cy="12" r="4" fill="#000000"/>
+            </g>
+        </svg>
+    }
+}
+
+#[function_component(MiniWindowIcon)]
+pub fn mini_window_icon() -> Html {
+    html! {
+        <svg xmlns="http://www.w3.org/2000/svg" width="1rem" height="1rem" viewBox="0 0 24 24">
+            <g fill="none" stroke="#000000" stroke-linecap="round" stroke-linejoin="round" stroke-width="1.5">
+                <path d="M4 14h6v6M20 10h-6V4M14 10l7-7M3 21l7-7"/>
+            </g>
+        </svg>
+    }
+}
+
 #[function_component(ImageIcon)]
 pub fn image_icon() -> Html {
     html! {
@@ -489,6 +536,16 @@ pub fn messages_icon(props: &SvgFillProps) -> Html {
     }
 }
 
+#[function_component(CallsIcon)]
+pub fn calls_icon(props: &SvgFillProps) -> Html {
+    html! {
+        <svg class="icon" width="1rem" height="1rem" viewBox="0 0 48 48" fill="none">
+            <path d="M16.9961 7.68583C17.7227 7.68583 18.3921 8.07985 18.7448 8.71509L21.1912 13.1219C21.5115 13.6989 21.5266 14.3968 21.2314 14.9871L18.8746 19.7008C18.8746 19.7008 19.5576 23.2122 22.416 26.0706C25.2744 28.929 28.7741 29.6002 28.7741 29.6002L33.487 27.2438C34.0777 26.9484 34.7761 26.9637 35.3533 27.2846L39.7726 29.7416C40.4072 30.0945 40.8008 30.7635 40.8008 31.4896L40.8008 36.5631C40.8008 39.1468 38.4009 41.0129 35.9528 40.1868C30.9249 38.4903 23.1202 35.2601 18.1734 30.3132C13.2265 25.3664 9.99631 17.5617 8.29977 12.5338C7.47375 10.0857 9.33984 7.68583 11.9235 7.68583L16.9961 7.68583Z"
+                stroke={&props.fill} stroke-width="3" stroke-linejoin="round"/>
+        </svg>
+    }
+}
+
 #[function_component(SettingIcon)]
 pub fn setting_icon(props: &SvgFillProps) -> Html {
     html! {
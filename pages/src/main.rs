@@ -3,6 +3,7 @@ mod login;
 mod oauth2;
 mod register;
 
+use components::call::CallWindow;
 use oauth2::OAuth2;
 use sandcat_sdk::model::page::Page;
 use yew::prelude::*;
@@ -21,7 +22,8 @@ fn app() -> Html {
                     Page::Login => html!{<Login/>},
                     Page::ThirdLoginCallback{tp} => html!{<OAuth2 {tp}/>},
                     Page::Register => html!{<Register />},
-                    Page::Redirect => html!{<Login />}}
+                    Page::Redirect => html!{<Login />},
+                    Page::CallWindow => html!{<CallWindow />}}
             }/>
         </BrowserRouter>
     }
@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use web_sys::File;
+use web_sys::{Blob, File};
 
 use crate::error::Result;
 
@@ -12,4 +12,20 @@ pub trait FileApi {
     async fn upload_voice(&self, data: &[u8]) -> Result<String>;
 
     async fn download_voice(&self, name: &str) -> Result<Vec<u8>>;
+
+    async fn download_file(&self, name: &str) -> Result<Vec<u8>>;
+
+    async fn download_avatar(&self, name: &str) -> Result<Vec<u8>>;
+
+    /// create a chunked upload task, return the upload id;
+    /// return none if the server doesn't support the chunked upload
+    async fn upload_init(&self, name: &str, size: u64, chunk_size: u64) -> Result<Option<String>>;
+
+    /// upload one chunk, the server will overwrite the chunk with the same index
+    async fn upload_chunk(&self, upload_id: &str, index: u32, chunk: &Blob) -> Result<()>;
+
+    /// merge all chunks, return the server name of the file
+    async fn upload_complete(&self, upload_id: &str) -> Result<String>;
+
+    async fn upload_cancel(&self, upload_id: &str) -> Result<()>;
 }
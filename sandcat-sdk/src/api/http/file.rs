@@ -1,5 +1,6 @@
 use async_trait::async_trait;
 use gloo_net::http::Request;
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{Blob, BlobPropertyBag, FormData, Headers};
@@ -13,7 +14,26 @@ use super::RespStatus;
 
 pub struct FileHttp;
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadInitReq {
+    pub name: String,
+    pub size: u64,
+    pub chunk_size: u64,
+}
+
 impl FileHttp {
+    async fn download(&self, url: &str) -> Result<Vec<u8>> {
+        let result = Request::get(url)
+            .header(AUTHORIZE_HEADER, &token())
+            .send()
+            .await?
+            .success()
+            .await?
+            .binary()
+            .await?;
+        Ok(result)
+    }
+
     async fn upload_file_inner(&self, url: &str, file: &File) -> Result<String> {
         let form = FormData::new()?;
         form.append_with_blob("file", file)?;
@@ -85,15 +105,70 @@ impl FileApi for FileHttp {
     }
 
     async fn download_voice(&self, name: &str) -> Result<Vec<u8>> {
-        let url = format!("/api/file/get/{}", name);
-        let result = Request::get(&url)
+        self.download_file(name).await
+    }
+
+    async fn download_file(&self, name: &str) -> Result<Vec<u8>> {
+        self.download(&format!("/api/file/get/{}", name)).await
+    }
+
+    async fn download_avatar(&self, name: &str) -> Result<Vec<u8>> {
+        self.download(&format!("/api/file/avatar/get/{}", name))
+            .await
+    }
+
+    async fn upload_init(&self, name: &str, size: u64, chunk_size: u64) -> Result<Option<String>> {
+        let request = UploadInitReq {
+            name: name.to_string(),
+            size,
+            chunk_size,
+        };
+        let response = Request::post("/api/file/upload/init")
+            .header(AUTHORIZE_HEADER, &token())
+            .json(&request)?
+            .send()
+            .await?;
+        // the server without the chunk endpoints
+        if matches!(response.status(), 404 | 405) {
+            return Ok(None);
+        }
+        let upload_id = response.success().await?.text().await?;
+        Ok(Some(upload_id))
+    }
+
+    async fn upload_chunk(&self, upload_id: &str, index: u32, chunk: &Blob) -> Result<()> {
+        let url = format!("/api/file/upload/{}/{}", upload_id, index);
+        Request::put(&url)
             .header(AUTHORIZE_HEADER, &token())
+            .body(chunk)?
             .send()
             .await?
             .success()
+            .await?;
+        Ok(())
+    }
+
+    async fn upload_complete(&self, upload_id: &str) -> Result<String> {
+        let url = format!("/api/file/upload/{}/complete", upload_id);
+        let name = Request::post(&url)
+            .header(AUTHORIZE_HEADER, &token())
+            .send()
             .await?
-            .binary()
+            .success()
+            .await?
+            .text()
             .await?;
-        Ok(result)
+        Ok(name)
+    }
+
+    async fn upload_cancel(&self, upload_id: &str) -> Result<()> {
+        let url = format!("/api/file/upload/{}", upload_id);
+        Request::delete(&url)
+            .header(AUTHORIZE_HEADER, &token())
+            .send()
+            .await?
+            .success()
+            .await?;
+        Ok(())
     }
 }
@@ -6,6 +6,7 @@ pub use friend::*;
 pub use group::*;
 pub use msg::*;
 pub use oauth2::*;
+pub use rtc::*;
 pub use seq::*;
 pub use user::*;
 
@@ -16,6 +17,7 @@ mod friend;
 mod group;
 mod msg;
 mod oauth2;
+mod rtc;
 mod seq;
 mod user;
 
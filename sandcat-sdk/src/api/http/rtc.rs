@@ -0,0 +1,26 @@
+use async_trait::async_trait;
+use gloo_net::http::Request;
+
+use crate::api::rtc::RtcApi;
+use crate::api::{token, AUTHORIZE_HEADER};
+use crate::error::Result;
+use crate::model::configurations::IceConfig;
+
+use super::RespStatus;
+
+pub struct RtcHttp;
+
+#[async_trait(?Send)]
+impl RtcApi for RtcHttp {
+    async fn ice_servers(&self) -> Result<IceConfig> {
+        let config = Request::get("/api/rtc/ice_servers")
+            .header(AUTHORIZE_HEADER, &token())
+            .send()
+            .await?
+            .success()
+            .await?
+            .json()
+            .await?;
+        Ok(config)
+    }
+}
@@ -5,9 +5,10 @@ use self::{
     friend::FriendApi,
     group::GroupApi,
     http::OAuth2Http,
-    http::{FileHttp, FriendHttp, GroupHttp, MsgHttp, SeqHttp, UserHttp},
+    http::{FileHttp, FriendHttp, GroupHttp, MsgHttp, RtcHttp, SeqHttp, UserHttp},
     message::MsgApi,
     oauth2::OAuth2Api,
+    rtc::RtcApi,
     seq::SeqApi,
     user::UserApi,
 };
@@ -18,6 +19,7 @@ mod group;
 mod http;
 mod message;
 mod oauth2;
+mod rtc;
 mod seq;
 mod user;
 
@@ -55,3 +57,7 @@ pub fn seq() -> Box<dyn SeqApi> {
 pub fn file() -> Box<dyn FileApi> {
     Box::new(FileHttp)
 }
+
+pub fn rtc() -> Box<dyn RtcApi> {
+    Box::new(RtcHttp)
+}
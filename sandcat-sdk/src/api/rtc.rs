@@ -0,0 +1,10 @@
+use async_trait::async_trait;
+
+use crate::error::Result;
+use crate::model::configurations::IceConfig;
+
+#[async_trait(?Send)]
+pub trait RtcApi {
+    /// get the stun and turn servers with the short-lived credential
+    async fn ice_servers(&self) -> Result<IceConfig>;
+}
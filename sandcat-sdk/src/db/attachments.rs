@@ -0,0 +1,26 @@
+use std::fmt::Debug;
+
+use crate::{
+    error::Result,
+    model::attachment::{Attachment, AttachmentUsage},
+};
+
+#[async_trait::async_trait(?Send)]
+pub trait Attachments: Debug {
+    async fn put(&self, attachment: &Attachment) -> Result<()>;
+
+    /// get the attachment by server name and refresh its last access time
+    async fn get(&self, server_name: &str) -> Result<Option<Attachment>>;
+
+    /// cache usage grouped by conversation, the biggest one first
+    async fn usage(&self) -> Result<Vec<AttachmentUsage>>;
+
+    async fn clear_conv(&self, conv_id: &str) -> Result<()>;
+
+    async fn clear(&self) -> Result<()>;
+
+    /// delete the least recently used attachments until the total size is under the quota,
+    /// return the size that has been released;
+    /// `keep` is the attachment just written, which is not read yet
+    async fn evict(&self, quota: u64, keep: Option<&str>) -> Result<u64>;
+}
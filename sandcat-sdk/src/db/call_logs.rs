@@ -0,0 +1,18 @@
+use std::fmt::Debug;
+
+use crate::{error::Result, model::call_log::CallLog};
+
+#[async_trait::async_trait(?Send)]
+pub trait CallLogs: Debug {
+    async fn put(&self, log: &CallLog) -> Result<()>;
+
+    async fn get(&self, local_id: &str) -> Result<Option<CallLog>>;
+
+    /// the latest logs first
+    async fn get_list(&self, limit: usize) -> Result<Vec<CallLog>>;
+
+    /// mark all the missed calls as read, return the count of them
+    async fn read_all(&self) -> Result<usize>;
+
+    async fn clear(&self) -> Result<()>;
+}
@@ -5,10 +5,12 @@ use yew::AttrValue;
 
 use crate::error::Result;
 use crate::model::message::{Message, ServerResponse};
+use crate::model::ContentType;
 
 #[async_trait::async_trait(?Send)]
 pub trait GroupMessages: Debug {
-    async fn put(&self, group: &Message) -> Result<()>;
+    /// merged like the `Messages::add_message`, returns whether the message is new
+    async fn put(&self, group: &Message) -> Result<bool>;
 
     async fn get(&self, local_id: &str) -> Result<Option<Message>>;
 
@@ -21,9 +23,14 @@ pub trait GroupMessages: Debug {
 
     async fn get_last_msg(&self, group_id: &str) -> Result<Option<Message>>;
 
+    /// messages of the given content type in the group, ordered by create time
+    async fn get_by_type(&self, group_id: &str, content_type: ContentType) -> Result<Vec<Message>>;
+
     async fn update_msg_status(&self, msg: &ServerResponse) -> Result<()>;
 
     async fn update_read_status(&self, friend_id: &str) -> Result<Vec<i64>>;
 
     async fn delete_batch(&self, group_id: &str) -> Result<()>;
+
+    async fn delete(&self, local_id: &AttrValue) -> Result<()>;
 }
@@ -0,0 +1,275 @@
+use std::cell::RefCell;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use futures_channel::oneshot;
+use indexmap::IndexMap;
+use log::error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{IdbCursorWithValue, IdbKeyRange, IdbObjectStore, IdbRequest};
+use yew::Event;
+
+use crate::db::attachments::Attachments;
+use crate::error::Result;
+use crate::model::attachment::{Attachment, AttachmentMeta, AttachmentUsage};
+
+use super::{
+    repository::Repository, SuccessCallback, ATTACHMENT_CONV_ID_INDEX,
+    ATTACHMENT_LAST_ACCESS_INDEX, ATTACHMENT_META_TABLE_NAME, ATTACHMENT_TABLE_NAME,
+};
+
+/// the cached data, its size and access time are in the attachment meta table
+#[derive(Serialize, Deserialize)]
+struct AttachmentData {
+    server_name: String,
+    data: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct AttachmentRepo {
+    repo: Repository,
+    on_err_callback: Closure<dyn FnMut(&Event)>,
+    on_usage_success: SuccessCallback,
+    on_clear_success: SuccessCallback,
+    on_evict_success: SuccessCallback,
+}
+
+impl Deref for AttachmentRepo {
+    type Target = Repository;
+
+    fn deref(&self) -> &Self::Target {
+        &self.repo
+    }
+}
+
+impl AttachmentRepo {
+    pub fn new(repo: Repository) -> Self {
+        let on_err_callback =
+            Closure::once(move |event: &Event| error!("attachment operate error: {:?}", event));
+        Self {
+            repo,
+            on_err_callback,
+            on_usage_success: Rc::new(RefCell::new(None)),
+            on_clear_success: Rc::new(RefCell::new(None)),
+            on_evict_success: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// the meta store and the data store in the same transaction
+    async fn stores(&self) -> Result<(IdbObjectStore, IdbObjectStore)> {
+        let transaction = self
+            .transaction(&[ATTACHMENT_META_TABLE_NAME, ATTACHMENT_TABLE_NAME])
+            .await?;
+        Ok((
+            transaction.object_store(ATTACHMENT_META_TABLE_NAME)?,
+            transaction.object_store(ATTACHMENT_TABLE_NAME)?,
+        ))
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Attachments for AttachmentRepo {
+    async fn put(&self, attachment: &Attachment) -> Result<()> {
+        let (meta_store, data_store) = self.stores().await?;
+        meta_store.put(&serde_wasm_bindgen::to_value(&AttachmentMeta::from(
+            attachment,
+        ))?)?;
+        data_store.put(&serde_wasm_bindgen::to_value(&AttachmentData {
+            server_name: attachment.server_name.clone(),
+            data: attachment.data.clone(),
+        })?)?;
+        Ok(())
+    }
+
+    async fn get(&self, server_name: &str) -> Result<Option<Attachment>> {
+        let (meta_store, data_store) = self.stores().await?;
+        let key = JsValue::from(server_name);
+        let meta_request = meta_store.get(&key)?;
+        let data_request = data_store.get(&key)?;
+
+        let (meta_tx, meta_rx) = oneshot::channel::<Option<AttachmentMeta>>();
+        let on_meta_success = Closure::once(move |event: &Event| {
+            let meta = request_value::<AttachmentMeta>(event).map(|mut meta| {
+                // refresh the access time in the same transaction
+                meta.last_access = chrono::Utc::now().timestamp_millis();
+                if let Ok(value) = serde_wasm_bindgen::to_value(&meta) {
+                    let _ = meta_store.put(&value);
+                }
+                meta
+            });
+            let _ = meta_tx.send(meta);
+        });
+        meta_request.set_onsuccess(Some(on_meta_success.as_ref().unchecked_ref()));
+        meta_request.set_onerror(Some(self.on_err_callback.as_ref().unchecked_ref()));
+
+        let (data_tx, data_rx) = oneshot::channel::<Option<AttachmentData>>();
+        let on_data_success = Closure::once(move |event: &Event| {
+            let _ = data_tx.send(request_value(event));
+        });
+        data_request.set_onsuccess(Some(on_data_success.as_ref().unchecked_ref()));
+        data_request.set_onerror(Some(self.on_err_callback.as_ref().unchecked_ref()));
+
+        let meta = meta_rx.await.unwrap_or_default();
+        let data = data_rx.await.unwrap_or_default();
+        Ok(meta
+            .zip(data)
+            .map(|(meta, data)| meta.into_attachment(data.data)))
+    }
+
+    async fn usage(&self) -> Result<Vec<AttachmentUsage>> {
+        // only the metadata is read, the data may be large
+        let store = self.store(ATTACHMENT_META_TABLE_NAME).await?;
+        let request = store.open_cursor()?;
+
+        let (tx, rx) = oneshot::channel::<Vec<AttachmentUsage>>();
+        let mut tx = Some(tx);
+        let mut usage: IndexMap<String, AttachmentUsage> = IndexMap::new();
+
+        let onsuccess = Closure::wrap(Box::new(move |event: &Event| {
+            let target = event.target().expect("msg");
+            let req = target
+                .dyn_ref::<IdbRequest>()
+                .expect("Event target is IdbRequest; qed");
+            let result = req.result().unwrap_or(JsValue::null());
+
+            if !result.is_null() {
+                let cursor = result
+                    .dyn_ref::<IdbCursorWithValue>()
+                    .expect("result is IdbCursorWithValue; qed");
+                if let Ok(item) =
+                    serde_wasm_bindgen::from_value::<AttachmentMeta>(cursor.value().unwrap())
+                {
+                    let entry =
+                        usage
+                            .entry(item.conv_id.clone())
+                            .or_insert_with(|| AttachmentUsage {
+                                conv_id: item.conv_id,
+                                ..Default::default()
+                            });
+                    entry.count += 1;
+                    entry.size += item.size;
+                }
+                let _ = cursor.continue_();
+            } else if let Some(tx) = tx.take() {
+                let mut list: Vec<AttachmentUsage> = usage.values().cloned().collect();
+                list.sort_by(|a, b| b.size.cmp(&a.size));
+                let _ = tx.send(list);
+            }
+        }) as Box<dyn FnMut(&Event)>);
+
+        request.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
+        request.set_onerror(Some(self.on_err_callback.as_ref().unchecked_ref()));
+        *self.on_usage_success.borrow_mut() = Some(onsuccess);
+
+        Ok(rx.await.unwrap_or_default())
+    }
+
+    async fn clear_conv(&self, conv_id: &str) -> Result<()> {
+        let (meta_store, data_store) = self.stores().await?;
+        let index = meta_store.index(ATTACHMENT_CONV_ID_INDEX)?;
+        let range = IdbKeyRange::only(&JsValue::from(conv_id))?;
+        let request = index.open_cursor_with_range(&range)?;
+
+        let (tx, rx) = oneshot::channel::<()>();
+        let mut tx = Some(tx);
+
+        let onsuccess = Closure::wrap(Box::new(move |event: &Event| {
+            let target = event.target().expect("msg");
+            let req = target
+                .dyn_ref::<IdbRequest>()
+                .expect("Event target is IdbRequest; qed");
+            let result = req.result().unwrap_or(JsValue::null());
+
+            if !result.is_null() {
+                let cursor = result
+                    .dyn_ref::<IdbCursorWithValue>()
+                    .expect("result is IdbCursorWithValue; qed");
+                let _ = delete(&data_store, cursor);
+                let _ = cursor.continue_();
+            } else if let Some(tx) = tx.take() {
+                let _ = tx.send(());
+            }
+        }) as Box<dyn FnMut(&Event)>);
+
+        request.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
+        request.set_onerror(Some(self.on_err_callback.as_ref().unchecked_ref()));
+        *self.on_clear_success.borrow_mut() = Some(onsuccess);
+
+        rx.await.unwrap_or_default();
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<()> {
+        let (meta_store, data_store) = self.stores().await?;
+        meta_store.clear()?;
+        data_store.clear()?;
+        Ok(())
+    }
+
+    async fn evict(&self, quota: u64, keep: Option<&str>) -> Result<u64> {
+        let mut total: u64 = self.usage().await?.iter().map(|u| u.size).sum();
+        if total <= quota {
+            return Ok(0);
+        }
+
+        let (meta_store, data_store) = self.stores().await?;
+        let index = meta_store.index(ATTACHMENT_LAST_ACCESS_INDEX)?;
+        // the oldest access time comes first
+        let request = index.open_cursor()?;
+
+        let (tx, rx) = oneshot::channel::<u64>();
+        let mut tx = Some(tx);
+        let mut released = 0;
+        let keep = keep.map(String::from);
+
+        let onsuccess = Closure::wrap(Box::new(move |event: &Event| {
+            let target = event.target().expect("msg");
+            let req = target
+                .dyn_ref::<IdbRequest>()
+                .expect("Event target is IdbRequest; qed");
+            let result = req.result().unwrap_or(JsValue::null());
+
+            if !result.is_null() && total > quota {
+                let cursor = result
+                    .dyn_ref::<IdbCursorWithValue>()
+                    .expect("result is IdbCursorWithValue; qed");
+                if let Some(item) =
+                    serde_wasm_bindgen::from_value::<AttachmentMeta>(cursor.value().unwrap())
+                        .ok()
+                        .filter(|item| keep.as_ref() != Some(&item.server_name))
+                {
+                    if delete(&data_store, cursor).is_ok() {
+                        total = total.saturating_sub(item.size);
+                        released += item.size;
+                    }
+                }
+                let _ = cursor.continue_();
+            } else if let Some(tx) = tx.take() {
+                let _ = tx.send(released);
+            }
+        }) as Box<dyn FnMut(&Event)>);
+
+        request.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
+        request.set_onerror(Some(self.on_err_callback.as_ref().unchecked_ref()));
+        *self.on_evict_success.borrow_mut() = Some(onsuccess);
+
+        Ok(rx.await.unwrap_or_default())
+    }
+}
+
+/// delete the data of the meta the cursor points to, then the meta itself
+fn delete(data_store: &IdbObjectStore, cursor: &IdbCursorWithValue) -> Result<()> {
+    data_store.delete(&cursor.primary_key()?)?;
+    cursor.delete()?;
+    Ok(())
+}
+
+fn request_value<T: DeserializeOwned>(event: &Event) -> Option<T> {
+    event
+        .target()
+        .and_then(|target| target.dyn_into::<IdbRequest>().ok())
+        .and_then(|req| req.result().ok())
+        .filter(|result| !result.is_undefined() && !result.is_null())
+        .and_then(|result| serde_wasm_bindgen::from_value(result).ok())
+}
@@ -0,0 +1,167 @@
+use std::cell::RefCell;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use futures_channel::oneshot;
+use log::error;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{IdbCursorDirection, IdbRequest};
+use yew::Event;
+
+use crate::db::call_logs::CallLogs;
+use crate::error::Result;
+use crate::model::call_log::CallLog;
+
+use super::{
+    repository::Repository, SuccessCallback, CALL_LOG_START_TIME_INDEX, CALL_LOG_TABLE_NAME,
+};
+
+#[derive(Debug)]
+pub struct CallLogRepo {
+    repo: Repository,
+    on_err_callback: Closure<dyn FnMut(&Event)>,
+    on_list_success: SuccessCallback,
+    on_read_success: SuccessCallback,
+}
+
+impl Deref for CallLogRepo {
+    type Target = Repository;
+
+    fn deref(&self) -> &Self::Target {
+        &self.repo
+    }
+}
+
+impl CallLogRepo {
+    pub fn new(repo: Repository) -> Self {
+        let on_err_callback =
+            Closure::once(move |event: &Event| error!("call log operate error: {:?}", event));
+        Self {
+            repo,
+            on_err_callback,
+            on_list_success: Rc::new(RefCell::new(None)),
+            on_read_success: Rc::new(RefCell::new(None)),
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl CallLogs for CallLogRepo {
+    async fn put(&self, log: &CallLog) -> Result<()> {
+        let store = self.store(CALL_LOG_TABLE_NAME).await?;
+        store.put(&serde_wasm_bindgen::to_value(log)?)?;
+        Ok(())
+    }
+
+    async fn get(&self, local_id: &str) -> Result<Option<CallLog>> {
+        let store = self.store(CALL_LOG_TABLE_NAME).await?;
+        let request = store.get(&JsValue::from(local_id))?;
+
+        let (tx, rx) = oneshot::channel::<Option<CallLog>>();
+        let onsuccess = Closure::once(move |event: &Event| {
+            let result = event
+                .target()
+                .unwrap()
+                .dyn_ref::<IdbRequest>()
+                .unwrap()
+                .result()
+                .unwrap();
+            let mut log = None;
+            if !result.is_undefined() && !result.is_null() {
+                log = serde_wasm_bindgen::from_value::<CallLog>(result).ok();
+            }
+            let _ = tx.send(log);
+        });
+        request.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
+        request.set_onerror(Some(self.on_err_callback.as_ref().unchecked_ref()));
+        Ok(rx.await.unwrap_or_default())
+    }
+
+    async fn get_list(&self, limit: usize) -> Result<Vec<CallLog>> {
+        let store = self.store(CALL_LOG_TABLE_NAME).await?;
+        let index = store.index(CALL_LOG_START_TIME_INDEX)?;
+        let request = index
+            .open_cursor_with_range_and_direction(&JsValue::null(), IdbCursorDirection::Prev)?;
+
+        let (tx, rx) = oneshot::channel::<Vec<CallLog>>();
+        let mut tx = Some(tx);
+        let mut list = Vec::new();
+
+        let onsuccess = Closure::wrap(Box::new(move |event: &Event| {
+            let target = event.target().expect("msg");
+            let req = target
+                .dyn_ref::<IdbRequest>()
+                .expect("Event target is IdbRequest; qed");
+            let result = req.result().unwrap_or(JsValue::null());
+
+            if !result.is_null() && list.len() < limit {
+                let cursor = result
+                    .dyn_ref::<web_sys::IdbCursorWithValue>()
+                    .expect("result is IdbCursorWithValue; qed");
+                match serde_wasm_bindgen::from_value::<CallLog>(cursor.value().unwrap()) {
+                    Ok(log) => list.push(log),
+                    Err(e) => error!("invalid call log: {:?}", e),
+                }
+                let _ = cursor.continue_();
+            } else if let Some(tx) = tx.take() {
+                let _ = tx.send(std::mem::take(&mut list));
+            }
+        }) as Box<dyn FnMut(&Event)>);
+
+        request.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
+        request.set_onerror(Some(self.on_err_callback.as_ref().unchecked_ref()));
+        *self.on_list_success.borrow_mut() = Some(onsuccess);
+
+        Ok(rx.await.unwrap_or_default())
+    }
+
+    async fn read_all(&self) -> Result<usize> {
+        let store = self.store(CALL_LOG_TABLE_NAME).await?;
+        let request = store.open_cursor()?;
+
+        let (tx, rx) = oneshot::channel::<usize>();
+        let mut tx = Some(tx);
+        let mut count = 0;
+
+        let onsuccess = Closure::wrap(Box::new(move |event: &Event| {
+            let target = event.target().expect("msg");
+            let req = target
+                .dyn_ref::<IdbRequest>()
+                .expect("Event target is IdbRequest; qed");
+            let result = req.result().unwrap_or(JsValue::null());
+
+            if !result.is_null() {
+                let cursor = result
+                    .dyn_ref::<web_sys::IdbCursorWithValue>()
+                    .expect("result is IdbCursorWithValue; qed");
+                if let Ok(mut log) =
+                    serde_wasm_bindgen::from_value::<CallLog>(cursor.value().unwrap())
+                {
+                    if !log.is_read {
+                        log.is_read = true;
+                        if let Ok(value) = serde_wasm_bindgen::to_value(&log) {
+                            if cursor.update(&value).is_ok() {
+                                count += 1;
+                            }
+                        }
+                    }
+                }
+                let _ = cursor.continue_();
+            } else if let Some(tx) = tx.take() {
+                let _ = tx.send(count);
+            }
+        }) as Box<dyn FnMut(&Event)>);
+
+        request.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
+        request.set_onerror(Some(self.on_err_callback.as_ref().unchecked_ref()));
+        *self.on_read_success.borrow_mut() = Some(onsuccess);
+
+        Ok(rx.await.unwrap_or_default())
+    }
+
+    async fn clear(&self) -> Result<()> {
+        let store = self.store(CALL_LOG_TABLE_NAME).await?;
+        store.clear()?;
+        Ok(())
+    }
+}
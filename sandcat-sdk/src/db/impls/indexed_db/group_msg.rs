@@ -4,15 +4,17 @@ use std::rc::Rc;
 
 use indexmap::IndexMap;
 use log::error;
-use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{closure::Closure, JsValue};
 use yew::{AttrValue, Event};
 
 use crate::db::group_msg::GroupMessages;
 use crate::error::Result;
 use crate::model::message::{Message, ServerResponse};
+use crate::model::ContentType;
 
 use super::message::{
-    add, delete_batch, get, get_last_msg, get_messages, update_msg_status, update_read_status,
+    add, delete_batch, get, get_by_type, get_last_msg, get_messages, update_msg_status,
+    update_read_status,
 };
 use super::SuccessCallback;
 use super::{repository::Repository, GROUP_MSG_TABLE_NAME};
@@ -53,9 +55,9 @@ impl GroupMsgRepo {
 
 #[async_trait::async_trait(?Send)]
 impl GroupMessages for GroupMsgRepo {
-    async fn put(&self, msg: &Message) -> Result<()> {
+    async fn put(&self, msg: &Message) -> Result<bool> {
         let store = self.store(GROUP_MSG_TABLE_NAME).await?;
-        add(store, msg, &self.on_err_callback).await
+        add(store, msg).await
     }
 
     /// friend id is group id
@@ -78,6 +80,11 @@ impl GroupMessages for GroupMsgRepo {
         get_last_msg(store, group_id, &self.on_err_callback).await
     }
 
+    async fn get_by_type(&self, group_id: &str, content_type: ContentType) -> Result<Vec<Message>> {
+        let store = self.store(GROUP_MSG_TABLE_NAME).await?;
+        get_by_type(store, group_id, content_type).await
+    }
+
     async fn get(&self, local_id: &str) -> Result<Option<Message>> {
         let store = self.store(GROUP_MSG_TABLE_NAME).await?;
         get(store, local_id).await
@@ -106,4 +113,10 @@ impl GroupMessages for GroupMsgRepo {
 
         Ok(())
     }
+
+    async fn delete(&self, local_id: &AttrValue) -> Result<()> {
+        let store = self.store(GROUP_MSG_TABLE_NAME).await?;
+        store.delete(&JsValue::from(local_id.as_str()))?;
+        Ok(())
+    }
 }
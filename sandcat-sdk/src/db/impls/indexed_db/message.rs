@@ -1,4 +1,5 @@
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::ops::Deref;
 use std::rc::Rc;
 
@@ -6,17 +7,20 @@ use futures_channel::oneshot;
 use indexmap::IndexMap;
 use log::error;
 use wasm_bindgen::{closure::Closure, JsCast, JsValue};
-use web_sys::{Event, IdbKeyRange, IdbObjectStore, IdbRequest};
+use web_sys::{Event, IdbKeyRange, IdbObjectStore, IdbRequest, IdbTransaction};
 use yew::AttrValue;
 
 use crate::db::messages::Messages;
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::model::message::{Message, ServerResponse};
+use crate::model::seq::Seq;
+use crate::model::ContentType;
 
 use super::{
-    repository::Repository, SuccessCallback, MESSAGE_FRIEND_AND_IS_READ_INDEX,
-    MESSAGE_FRIEND_AND_SEND_TIME_INDEX, MESSAGE_FRIEND_ID_INDEX, MESSAGE_IS_READ_INDEX,
-    MESSAGE_TABLE_NAME,
+    repository::Repository, SuccessCallback, GROUP_MSG_TABLE_NAME,
+    MESSAGE_FRIEND_AND_IS_READ_INDEX, MESSAGE_FRIEND_AND_SEND_TIME_INDEX, MESSAGE_FRIEND_ID_INDEX,
+    MESSAGE_FRIEND_TYPE_AND_TIME_INDEX, MESSAGE_IS_READ_INDEX, MESSAGE_SERVER_ID_INDEX,
+    MESSAGE_TABLE_NAME, SEQ_TABLE_NAME,
 };
 
 #[derive(Debug)]
@@ -78,9 +82,51 @@ impl Messages for MessageRepo {
         Ok(result)
     }
 
-    async fn add_message(&self, msg: &Message) -> Result<()> {
+    async fn get_by_type(
+        &self,
+        friend_id: &str,
+        content_type: ContentType,
+    ) -> Result<Vec<Message>> {
         let store = self.store(MESSAGE_TABLE_NAME).await?;
-        add(store, msg, &self.on_err_callback).await
+        get_by_type(store, friend_id, content_type).await
+    }
+
+    async fn add_message(&self, msg: &Message) -> Result<bool> {
+        let store = self.store(MESSAGE_TABLE_NAME).await?;
+        add(store, msg).await
+    }
+
+    async fn save_sync_page(
+        &self,
+        friend_msgs: &[Message],
+        group_msgs: &[Message],
+        seq: Option<&Seq>,
+    ) -> Result<(Vec<bool>, Vec<bool>)> {
+        let transaction = self
+            .transaction(&[MESSAGE_TABLE_NAME, GROUP_MSG_TABLE_NAME, SEQ_TABLE_NAME])
+            .await?;
+        if let Some(seq) = seq {
+            let value = serde_wasm_bindgen::to_value(seq)?;
+            if let Err(err) = transaction.object_store(SEQ_TABLE_NAME)?.put(&value) {
+                // nothing of the page is written
+                let _ = transaction.abort();
+                return Err(err.into());
+            }
+        }
+        let msg_store = transaction.object_store(MESSAGE_TABLE_NAME)?;
+        let group_store = transaction.object_store(GROUP_MSG_TABLE_NAME)?;
+        let msgs = friend_msgs
+            .iter()
+            .map(|msg| (msg_store.clone(), msg.clone()))
+            .chain(
+                group_msgs
+                    .iter()
+                    .map(|msg| (group_store.clone(), msg.clone())),
+            )
+            .collect();
+        let mut is_new = add_batch(&transaction, msgs).await?;
+        let group_is_new = is_new.split_off(friend_msgs.len());
+        Ok((is_new, group_is_new))
     }
 
     async fn update_msg_status(&self, msg: &ServerResponse) -> Result<()> {
@@ -263,26 +309,170 @@ pub(super) async fn get_messages(
     Ok((rx.await.unwrap(), success))
 }
 
-pub(super) async fn add(
+pub(super) async fn get_by_type(
     store: IdbObjectStore,
-    msg: &Message,
-    on_err_callback: &Closure<dyn FnMut(&Event)>,
-) -> Result<()> {
-    let request = store.put(&serde_wasm_bindgen::to_value(&msg)?)?;
+    friend_id: &str,
+    content_type: ContentType,
+) -> Result<Vec<Message>> {
+    let index = store.index(MESSAGE_FRIEND_TYPE_AND_TIME_INDEX)?;
+    let content_type = serde_wasm_bindgen::to_value(&content_type)?;
 
-    let (tx, rx) = oneshot::channel::<u8>();
+    let start_key = js_sys::Array::new();
+    start_key.push(&JsValue::from(friend_id));
+    start_key.push(&content_type);
+    start_key.push(&JsValue::from_f64(f64::NEG_INFINITY));
+
+    let end_key = js_sys::Array::new();
+    end_key.push(&JsValue::from(friend_id));
+    end_key.push(&content_type);
+    end_key.push(&JsValue::from_f64(f64::INFINITY));
+
+    let range = IdbKeyRange::bound(&JsValue::from(start_key), &JsValue::from(end_key))?;
+    let request = index.get_all_with_key(&range)?;
 
-    let onsuccess = Closure::once(move |_event: &Event| {
-        tx.send(1).unwrap();
+    let (tx, rx) = oneshot::channel::<Vec<Message>>();
+    let onsuccess = Closure::once(move |event: &Event| {
+        let result = event
+            .target()
+            .unwrap()
+            .dyn_ref::<IdbRequest>()
+            .unwrap()
+            .result()
+            .unwrap_or(JsValue::null());
+        let list: Vec<Message> = js_sys::Array::from(&result)
+            .iter()
+            .filter_map(|value| serde_wasm_bindgen::from_value(value).ok())
+            .collect();
+        let _ = tx.send(list);
     });
     request.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
 
-    request.set_onerror(Some(on_err_callback.as_ref().unchecked_ref()));
+    let onerror = Closure::once(move |event: &Event| error!("query by type error: {:?}", event));
+    request.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+
+    Ok(rx.await.unwrap_or_default())
+}
+
+/// put the message, the one delivered again is merged into the stored one;
+/// returns whether the message is new
+pub(super) async fn add(store: IdbObjectStore, msg: &Message) -> Result<bool> {
+    let transaction = store.transaction();
+    let is_new = add_batch(&transaction, vec![(store, msg.clone())]).await?;
+    Ok(is_new.first().copied().unwrap_or_default())
+}
 
-    // wait for the result
-    rx.await.unwrap();
+/// put the messages one by one in the transaction like the `add`, the result is resolved
+/// once the transaction is committed; returns whether each message is new, in order
+async fn add_batch(
+    transaction: &IdbTransaction,
+    msgs: Vec<(IdbObjectStore, Message)>,
+) -> Result<Vec<bool>> {
+    let put = MergePut {
+        queue: Rc::new(RefCell::new(msgs.into())),
+        is_new: Rc::default(),
+        callbacks: Rc::default(),
+    };
+    put.next();
+    let result = Repository::commit(transaction).await;
+    // the callbacks hold the put, drop them to break the cycle
+    put.callbacks.borrow_mut().clear();
+    result?;
+    Ok(put.is_new.take())
+}
+
+type Callbacks = Rc<RefCell<Vec<Closure<dyn FnMut(&Event)>>>>;
+
+/// find the stored message by the local id, then by the server id, and put the merged one;
+/// every request is made in the success event of the previous one, even across the messages,
+/// so that the transaction is still active and no other write comes between them
+#[derive(Clone)]
+struct MergePut {
+    /// the messages left with the store they are put in
+    queue: Rc<RefCell<VecDeque<(IdbObjectStore, Message)>>>,
+    is_new: Rc<RefCell<Vec<bool>>>,
+    callbacks: Callbacks,
+}
 
-    Ok(())
+impl MergePut {
+    /// start the next message, the transaction is committed after the last one
+    fn next(&self) {
+        let Some((store, msg)) = self.queue.borrow_mut().pop_front() else {
+            return;
+        };
+        if let Err(err) = self.start(&store, msg) {
+            Self::abort(&store, err);
+        }
+    }
+
+    fn start(&self, store: &IdbObjectStore, msg: Message) -> Result<()> {
+        let request = store.get(&JsValue::from(msg.local_id.as_str()))?;
+        let this = self.clone();
+        let store_clone = store.clone();
+        self.on_result(&request, move |stored| {
+            let result = match stored {
+                Some(stored) => this.put(&store_clone, stored.merge(msg), false),
+                None if msg.server_id.is_empty() => this.put(&store_clone, msg, true),
+                None => this.find_by_server_id(&store_clone, msg),
+            };
+            if let Err(err) = result {
+                Self::abort(&store_clone, err);
+            }
+        });
+        Ok(())
+    }
+
+    fn find_by_server_id(&self, store: &IdbObjectStore, msg: Message) -> Result<()> {
+        let request = store
+            .index(MESSAGE_SERVER_ID_INDEX)?
+            .get(&JsValue::from(msg.server_id.as_str()))?;
+        let this = self.clone();
+        let store_clone = store.clone();
+        self.on_result(&request, move |stored| {
+            let result = match stored {
+                Some(stored) => this.put(&store_clone, stored.merge(msg), false),
+                None => this.put(&store_clone, msg, true),
+            };
+            if let Err(err) = result {
+                Self::abort(&store_clone, err);
+            }
+        });
+        Ok(())
+    }
+
+    fn put(&self, store: &IdbObjectStore, msg: Message, is_new: bool) -> Result<()> {
+        let request = store.put(&serde_wasm_bindgen::to_value(&msg)?)?;
+        let this = self.clone();
+        self.on_result(&request, move |_| {
+            this.is_new.borrow_mut().push(is_new);
+            this.next();
+        });
+        Ok(())
+    }
+
+    /// call `then` with the message the request found,
+    /// the failed request aborts the transaction
+    fn on_result(&self, request: &IdbRequest, then: impl FnOnce(Option<Message>) + 'static) {
+        let onsuccess = Closure::once(move |event: &Event| {
+            let result = event
+                .target()
+                .and_then(|target| target.dyn_into::<IdbRequest>().ok())
+                .and_then(|req| req.result().ok())
+                .filter(|result| !result.is_undefined() && !result.is_null());
+            then(result.and_then(|result| serde_wasm_bindgen::from_value(result).ok()));
+        });
+        let onerror = Closure::once(move |event: &Event| {
+            error!("put message error: {:?}", event);
+        });
+        request.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
+        request.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+        self.callbacks.borrow_mut().extend([onsuccess, onerror]);
+    }
+
+    /// nothing of the batch is written
+    fn abort(store: &IdbObjectStore, err: Error) {
+        error!("put message error: {:?}", err);
+        let _ = store.transaction().abort();
+    }
 }
 
 pub(super) async fn update_msg_status(
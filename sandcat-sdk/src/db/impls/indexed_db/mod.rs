@@ -1,3 +1,5 @@
+pub mod attachment;
+pub mod call_log;
 pub mod conv;
 pub mod friend;
 pub mod friend_ship;
@@ -8,6 +10,7 @@ pub mod message;
 pub mod offline_time;
 pub mod repository;
 pub mod seq;
+pub mod upload;
 pub mod user;
 pub mod voice;
 
@@ -36,6 +39,10 @@ pub const GROUP_MSG_TABLE_NAME: &str = "group_messages";
 pub const GROUP_MEMBERS_TABLE_NAME: &str = "group_members";
 pub const SEQ_TABLE_NAME: &str = "seq";
 pub const VOICE_TABLE_NAME: &str = "voices";
+pub const ATTACHMENT_TABLE_NAME: &str = "attachments";
+pub const ATTACHMENT_META_TABLE_NAME: &str = "attachment_meta";
+pub const UPLOAD_TABLE_NAME: &str = "uploads";
+pub const CALL_LOG_TABLE_NAME: &str = "call_logs";
 
 pub const FRIENDSHIP_UNREAD_INDEX: &str = "read";
 pub const FRIENDSHIP_ID_INDEX: &str = "fs_id";
@@ -59,6 +66,7 @@ pub const CONVERSATION_IS_PINED_WITH_TIME_INDEX: &str = "is_pined_with_time";
 // pub const MESSAGE_USER_ID_INDEX: &str = "user_id";
 pub const MESSAGE_FRIEND_AND_SEND_TIME_INDEX: &str = "friend_id_and_send_time";
 pub const MESSAGE_FRIEND_AND_IS_READ_INDEX: &str = "friend_id_and_is_read";
+pub const MESSAGE_FRIEND_TYPE_AND_TIME_INDEX: &str = "friend_id_and_type_and_time";
 pub const MESSAGE_FRIEND_ID_INDEX: &str = "friend_id";
 pub const MESSAGE_ID_INDEX: &str = "local_id";
 // pub const MESSAGE_SEND_ID_INDEX: &str = "send_id";
@@ -66,6 +74,12 @@ pub const MESSAGE_TIME_INDEX: &str = "time";
 pub const MESSAGE_CONTENT_INDEX: &str = "content";
 pub const MESSAGE_TYPE_INDEX: &str = "type";
 pub const MESSAGE_IS_READ_INDEX: &str = "is_read";
+pub const MESSAGE_SERVER_ID_INDEX: &str = "server_id";
+
+pub const ATTACHMENT_CONV_ID_INDEX: &str = "conv_id";
+pub const ATTACHMENT_LAST_ACCESS_INDEX: &str = "last_access";
+
+pub const CALL_LOG_START_TIME_INDEX: &str = "start_time";
 
 // 定义数据库查询状态
 #[derive(Debug)]
@@ -1,14 +1,19 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use futures_channel::oneshot;
 use js_sys::Array;
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::{JsCast, JsValue};
 use web_sys::{
     IdbDatabase, IdbIndexParameters, IdbObjectStore, IdbObjectStoreParameters, IdbRequest,
-    IdbTransactionMode,
+    IdbTransaction, IdbTransactionMode, IdbVersionChangeEvent,
 };
 use yew::prelude::*;
 
 use crate::db::{
+    ATTACHMENT_CONV_ID_INDEX, ATTACHMENT_LAST_ACCESS_INDEX, ATTACHMENT_META_TABLE_NAME,
+    ATTACHMENT_TABLE_NAME, CALL_LOG_START_TIME_INDEX, CALL_LOG_TABLE_NAME,
     CONVERSATION_IS_PINED_WITH_TIME_INDEX, CONVERSATION_LAST_MSG_TIME_INDEX,
     CONVERSATION_TABLE_NAME, FRIENDSHIP_ID_INDEX, FRIENDSHIP_TABLE_NAME, FRIENDSHIP_UNREAD_INDEX,
     FRIEND_ADDRESS_INDEX, FRIEND_GENDER_INDEX, FRIEND_NAME_INDEX, FRIEND_PHONE_INDEX,
@@ -16,14 +21,15 @@ use crate::db::{
     GROUP_ID_AND_IS_DELETE, GROUP_ID_AND_USER_ID, GROUP_ID_INDEX, GROUP_MEMBERS_TABLE_NAME,
     GROUP_MSG_TABLE_NAME, GROUP_TABLE_NAME, MESSAGE_CONTENT_INDEX,
     MESSAGE_FRIEND_AND_IS_READ_INDEX, MESSAGE_FRIEND_AND_SEND_TIME_INDEX, MESSAGE_FRIEND_ID_INDEX,
-    MESSAGE_ID_INDEX, MESSAGE_IS_READ_INDEX, MESSAGE_TABLE_NAME, MESSAGE_TIME_INDEX,
-    MESSAGE_TYPE_INDEX, OFFLINE_TIME_TABLE_NAME, SEQ_TABLE_NAME, USER_TABLE_NAME, VOICE_TABLE_NAME,
+    MESSAGE_FRIEND_TYPE_AND_TIME_INDEX, MESSAGE_ID_INDEX, MESSAGE_IS_READ_INDEX,
+    MESSAGE_SERVER_ID_INDEX, MESSAGE_TABLE_NAME, MESSAGE_TIME_INDEX, MESSAGE_TYPE_INDEX,
+    OFFLINE_TIME_TABLE_NAME, SEQ_TABLE_NAME, UPLOAD_TABLE_NAME, USER_TABLE_NAME, VOICE_TABLE_NAME,
 };
-use crate::error::Result;
+use crate::error::{Error, Result};
 
 use super::DB_NAME;
 
-const DB_VERSION: u32 = 1;
+const DB_VERSION: u32 = 6;
 
 type Func = Option<Closure<dyn FnMut(&Event)>>;
 
@@ -76,55 +82,101 @@ impl Repository {
                 .expect("IndexedDB.onsuccess should have a valid result; qed");
             assert!(result.is_instance_of::<IdbDatabase>());
             let db = IdbDatabase::from(result);
-            let mut parameters: IdbObjectStoreParameters = IdbObjectStoreParameters::new();
-            parameters.key_path(Some(&JsValue::from_str("local_id")));
-
-            db.create_object_store_with_optional_parameters(
-                &String::from(VOICE_TABLE_NAME),
-                &parameters,
-            )
-            .unwrap();
-
-            let mut parameters: IdbObjectStoreParameters = IdbObjectStoreParameters::new();
-            parameters.key_path(Some(&JsValue::from_str("id")));
-            parameters.auto_increment(true);
-
-            db.create_object_store_with_optional_parameters(
-                &String::from(USER_TABLE_NAME),
-                &parameters,
-            )
-            .unwrap();
-            // store.create_index_with_str("login", "login").unwrap();
-            create_msg_table(&db, MESSAGE_TABLE_NAME).expect("create message table panic");
-            create_msg_table(&db, GROUP_MSG_TABLE_NAME).expect("create group message table panic");
-            create_friend_table(&db).expect("create friend table panic");
-            create_friendship_table(&db).expect("create friendship table panic");
-            create_group_members_table(&db).expect("create group members table panic");
-            create_conv_table(&db).expect("create conversations table panic");
-
-            // create sequence table
-            db.create_object_store_with_optional_parameters(
-                &String::from(SEQ_TABLE_NAME),
-                &parameters,
-            )
-            .unwrap();
-
-            let mut id_key: IdbObjectStoreParameters = IdbObjectStoreParameters::new();
-            id_key.key_path(Some(&JsValue::from_str("id")));
-
-            // create groups table
-            db.create_object_store_with_optional_parameters(
-                &String::from(GROUP_TABLE_NAME),
-                &id_key,
-            )
-            .unwrap();
-
-            // create offline time table
-            db.create_object_store_with_optional_parameters(
-                &String::from(OFFLINE_TIME_TABLE_NAME),
-                &parameters,
-            )
-            .unwrap();
+            // upgrade the database step by step, so that the existing data will be kept
+            let old_version = event
+                .dyn_ref::<IdbVersionChangeEvent>()
+                .map(|e| e.old_version() as u32)
+                .unwrap_or_default();
+
+            if old_version < 1 {
+                let mut parameters: IdbObjectStoreParameters = IdbObjectStoreParameters::new();
+                parameters.key_path(Some(&JsValue::from_str("local_id")));
+
+                db.create_object_store_with_optional_parameters(
+                    &String::from(VOICE_TABLE_NAME),
+                    &parameters,
+                )
+                .unwrap();
+
+                let mut parameters: IdbObjectStoreParameters = IdbObjectStoreParameters::new();
+                parameters.key_path(Some(&JsValue::from_str("id")));
+                parameters.auto_increment(true);
+
+                db.create_object_store_with_optional_parameters(
+                    &String::from(USER_TABLE_NAME),
+                    &parameters,
+                )
+                .unwrap();
+                // store.create_index_with_str("login", "login").unwrap();
+                create_msg_table(&db, MESSAGE_TABLE_NAME).expect("create message table panic");
+                create_msg_table(&db, GROUP_MSG_TABLE_NAME)
+                    .expect("create group message table panic");
+                create_friend_table(&db).expect("create friend table panic");
+                create_friendship_table(&db).expect("create friendship table panic");
+                create_group_members_table(&db).expect("create group members table panic");
+                create_conv_table(&db).expect("create conversations table panic");
+
+                // create sequence table
+                db.create_object_store_with_optional_parameters(
+                    &String::from(SEQ_TABLE_NAME),
+                    &parameters,
+                )
+                .unwrap();
+
+                let mut id_key: IdbObjectStoreParameters = IdbObjectStoreParameters::new();
+                id_key.key_path(Some(&JsValue::from_str("id")));
+
+                // create groups table
+                db.create_object_store_with_optional_parameters(
+                    &String::from(GROUP_TABLE_NAME),
+                    &id_key,
+                )
+                .unwrap();
+
+                // create offline time table
+                db.create_object_store_with_optional_parameters(
+                    &String::from(OFFLINE_TIME_TABLE_NAME),
+                    &parameters,
+                )
+                .unwrap();
+            }
+
+            if old_version < 2 {
+                create_attachment_table(&db).expect("create attachment table panic");
+            }
+
+            if old_version < 3 {
+                // use local_id of the pending message as primary key
+                let mut parameters = IdbObjectStoreParameters::new();
+                parameters.key_path(Some(&JsValue::from_str("local_id")));
+                db.create_object_store_with_optional_parameters(
+                    &String::from(UPLOAD_TABLE_NAME),
+                    &parameters,
+                )
+                .unwrap();
+            }
+
+            if old_version < 4 {
+                // the message stores already exist, get them from the upgrade transaction
+                let transaction = req.transaction().expect("upgrade transaction; qed");
+                for name in [MESSAGE_TABLE_NAME, GROUP_MSG_TABLE_NAME] {
+                    let store = transaction.object_store(name).unwrap();
+                    create_msg_type_index(&store).expect("create message type index panic");
+                }
+            }
+
+            if old_version < 5 {
+                create_call_log_table(&db).expect("create call log table panic");
+            }
+
+            if old_version < 6 {
+                let transaction = req.transaction().expect("upgrade transaction; qed");
+                for name in [MESSAGE_TABLE_NAME, GROUP_MSG_TABLE_NAME] {
+                    let store = transaction.object_store(name).unwrap();
+                    create_msg_server_id_index(&store)
+                        .expect("create message server id index panic");
+                }
+            }
         });
         open_request.set_onupgradeneeded(Some(on_upgradeneeded.as_ref().unchecked_ref()));
         // on_upgradeneeded.forget();
@@ -163,6 +215,44 @@ impl Repository {
         Ok(transaction.object_store(name)?)
     }
 
+    /// one readwrite transaction over several stores,
+    /// used to keep the stores consistent with each other
+    pub async fn transaction(&self, names: &[&str]) -> Result<IdbTransaction> {
+        let names = names
+            .iter()
+            .map(|name| JsValue::from(*name))
+            .collect::<Array>();
+        Ok(self
+            .db
+            .transaction_with_str_sequence_and_mode(&names, IdbTransactionMode::Readwrite)?)
+    }
+
+    /// wait for the transaction to be committed, the error of any request aborts it
+    pub async fn commit(transaction: &IdbTransaction) -> Result<()> {
+        let (tx, rx) = oneshot::channel::<bool>();
+        let tx = Rc::new(RefCell::new(Some(tx)));
+        let tx_clone = tx.clone();
+        let oncomplete = Closure::once(move |_event: &Event| {
+            if let Some(tx) = tx_clone.borrow_mut().take() {
+                let _ = tx.send(true);
+            }
+        });
+        let onabort = Closure::once(move |event: &Event| {
+            log::error!("transaction aborted: {:?}", event);
+            if let Some(tx) = tx.borrow_mut().take() {
+                let _ = tx.send(false);
+            }
+        });
+        transaction.set_oncomplete(Some(oncomplete.as_ref().unchecked_ref()));
+        transaction.set_onabort(Some(onabort.as_ref().unchecked_ref()));
+
+        if rx.await.unwrap_or_default() {
+            Ok(())
+        } else {
+            Err(Error::internal_with_details("transaction aborted"))
+        }
+    }
+
     pub async fn delete_db() {
         let db_name = DB_NAME.get().unwrap();
 
@@ -201,6 +291,25 @@ fn create_msg_table(db: &IdbDatabase, table_name: &str) -> Result<()> {
     Ok(())
 }
 
+/// used to query images, videos and files of a conversation
+fn create_msg_type_index(store: &IdbObjectStore) -> Result<()> {
+    let indexes = Array::new();
+    indexes.push(&JsValue::from("friend_id"));
+    indexes.push(&JsValue::from("content_type"));
+    indexes.push(&JsValue::from("create_time"));
+    let indexes = JsValue::from(indexes);
+    store.create_index_with_str_sequence(MESSAGE_FRIEND_TYPE_AND_TIME_INDEX, &indexes)?;
+    Ok(())
+}
+
+/// used to find the message delivered again with another local id,
+/// the server id is empty before the message is sent, so it can't be unique;
+/// the same local id is found by the key path
+fn create_msg_server_id_index(store: &IdbObjectStore) -> Result<()> {
+    store.create_index_with_str(MESSAGE_SERVER_ID_INDEX, "server_id")?;
+    Ok(())
+}
+
 fn create_conv_table(db: &IdbDatabase) -> Result<()> {
     // use friend_id as primary key
     let mut parameters: IdbObjectStoreParameters = IdbObjectStoreParameters::new();
@@ -295,3 +404,38 @@ fn create_friend_table(db: &IdbDatabase) -> Result<()> {
 
     Ok(())
 }
+
+/// the data and the metadata of the attachments are kept apart,
+/// so that the usage and the eviction don't have to read the data
+fn create_attachment_table(db: &IdbDatabase) -> Result<()> {
+    // use server_name as primary key
+    let mut parameter = IdbObjectStoreParameters::new();
+    parameter.key_path(Some(&JsValue::from_str("server_name")));
+    db.create_object_store_with_optional_parameters(
+        &String::from(ATTACHMENT_TABLE_NAME),
+        &parameter,
+    )?;
+
+    let store = db.create_object_store_with_optional_parameters(
+        &String::from(ATTACHMENT_META_TABLE_NAME),
+        &parameter,
+    )?;
+    store.create_index_with_str(ATTACHMENT_CONV_ID_INDEX, "conv_id")?;
+    store.create_index_with_str(ATTACHMENT_LAST_ACCESS_INDEX, "last_access")?;
+
+    Ok(())
+}
+
+fn create_call_log_table(db: &IdbDatabase) -> Result<()> {
+    // use the local_id of the call message as primary key
+    let mut parameter = IdbObjectStoreParameters::new();
+    parameter.key_path(Some(&JsValue::from_str("local_id")));
+    let store = db.create_object_store_with_optional_parameters(
+        &String::from(CALL_LOG_TABLE_NAME),
+        &parameter,
+    )?;
+
+    store.create_index_with_str(CALL_LOG_START_TIME_INDEX, "start_time")?;
+
+    Ok(())
+}
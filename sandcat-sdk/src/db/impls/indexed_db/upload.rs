@@ -0,0 +1,135 @@
+use std::cell::RefCell;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use futures_channel::oneshot;
+use log::error;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{File, IdbRequest};
+use yew::Event;
+
+use crate::db::uploads::Uploads;
+use crate::error::Result;
+use crate::model::upload::UploadTask;
+
+use super::{repository::Repository, SuccessCallback, UPLOAD_TABLE_NAME};
+
+/// the file is saved in this field, serde can not handle it
+const FILE_FIELD: &str = "file";
+
+#[derive(Debug)]
+pub struct UploadRepo {
+    repo: Repository,
+    on_err_callback: Closure<dyn FnMut(&Event)>,
+    on_get_list_success: SuccessCallback,
+}
+
+impl Deref for UploadRepo {
+    type Target = Repository;
+
+    fn deref(&self) -> &Self::Target {
+        &self.repo
+    }
+}
+
+impl UploadRepo {
+    pub fn new(repo: Repository) -> Self {
+        let on_err_callback =
+            Closure::once(move |event: &Event| error!("upload task operate error: {:?}", event));
+        Self {
+            repo,
+            on_err_callback,
+            on_get_list_success: Rc::new(RefCell::new(None)),
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Uploads for UploadRepo {
+    async fn put(&self, task: &UploadTask, file: &File) -> Result<()> {
+        let store = self.store(UPLOAD_TABLE_NAME).await?;
+        let value = serde_wasm_bindgen::to_value(task)?;
+        js_sys::Reflect::set(&value, &JsValue::from(FILE_FIELD), file)?;
+        store.put(&value)?;
+        Ok(())
+    }
+
+    async fn update(&self, task: &UploadTask) -> Result<()> {
+        let store = self.store(UPLOAD_TABLE_NAME).await?;
+        let request = store.get(&JsValue::from(task.local_id.as_str()))?;
+
+        let (tx, rx) = oneshot::channel::<Result<()>>();
+        let task = task.clone();
+        let onsuccess = Closure::once(move |event: &Event| {
+            let result = event
+                .target()
+                .unwrap()
+                .dyn_ref::<IdbRequest>()
+                .unwrap()
+                .result()
+                .unwrap();
+            // the task may be canceled
+            if result.is_undefined() || result.is_null() {
+                let _ = tx.send(Ok(()));
+                return;
+            }
+            let put = || -> Result<()> {
+                let file = js_sys::Reflect::get(&result, &JsValue::from(FILE_FIELD))?;
+                let value = serde_wasm_bindgen::to_value(&task)?;
+                js_sys::Reflect::set(&value, &JsValue::from(FILE_FIELD), &file)?;
+                store.put(&value)?;
+                Ok(())
+            };
+            let _ = tx.send(put());
+        });
+        request.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
+        request.set_onerror(Some(self.on_err_callback.as_ref().unchecked_ref()));
+        rx.await.unwrap_or(Ok(()))
+    }
+
+    async fn get_all(&self) -> Result<Vec<(UploadTask, File)>> {
+        let store = self.store(UPLOAD_TABLE_NAME).await?;
+        let request = store.open_cursor()?;
+
+        let (tx, rx) = oneshot::channel::<Vec<(UploadTask, File)>>();
+        let mut tx = Some(tx);
+        let mut tasks = Vec::new();
+
+        let onsuccess = Closure::wrap(Box::new(move |event: &Event| {
+            let target = event.target().expect("msg");
+            let req = target
+                .dyn_ref::<IdbRequest>()
+                .expect("Event target is IdbRequest; qed");
+            let result = req.result().unwrap_or(JsValue::null());
+
+            if !result.is_null() {
+                let cursor = result
+                    .dyn_ref::<web_sys::IdbCursorWithValue>()
+                    .expect("result is IdbCursorWithValue; qed");
+                let value = cursor.value().unwrap();
+                let file = js_sys::Reflect::get(&value, &JsValue::from(FILE_FIELD))
+                    .ok()
+                    .and_then(|file| file.dyn_into::<File>().ok());
+                match (serde_wasm_bindgen::from_value::<UploadTask>(value), file) {
+                    (Ok(task), Some(file)) => tasks.push((task, file)),
+                    _ => error!("invalid upload task"),
+                }
+                let _ = cursor.continue_();
+            } else if let Some(tx) = tx.take() {
+                let _ = tx.send(std::mem::take(&mut tasks));
+            }
+        }) as Box<dyn FnMut(&Event)>);
+
+        request.set_onsuccess(Some(onsuccess.as_ref().unchecked_ref()));
+        request.set_onerror(Some(self.on_err_callback.as_ref().unchecked_ref()));
+        *self.on_get_list_success.borrow_mut() = Some(onsuccess);
+
+        Ok(rx.await.unwrap_or_default())
+    }
+
+    async fn del(&self, local_id: &str) -> Result<()> {
+        let store = self.store(UPLOAD_TABLE_NAME).await?;
+        store.delete(&JsValue::from(local_id))?;
+        Ok(())
+    }
+}
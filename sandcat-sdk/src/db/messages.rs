@@ -5,6 +5,8 @@ use yew::AttrValue;
 
 use crate::error::Result;
 use crate::model::message::{Message, ServerResponse};
+use crate::model::seq::Seq;
+use crate::model::ContentType;
 
 #[async_trait::async_trait(?Send)]
 pub trait Messages: Debug {
@@ -19,7 +21,23 @@ pub trait Messages: Debug {
         page_size: u32,
     ) -> Result<IndexMap<AttrValue, Message>>;
 
-    async fn add_message(&self, msg: &Message) -> Result<()>;
+    /// messages of the given content type in the conversation, ordered by create time
+    async fn get_by_type(&self, friend_id: &str, content_type: ContentType)
+        -> Result<Vec<Message>>;
+
+    /// the message delivered again is merged into the stored one with the same local id,
+    /// or else with the same server id; returns whether the message is new
+    async fn add_message(&self, msg: &Message) -> Result<bool>;
+
+    /// save a page of the offline sync in one transaction, the friend messages,
+    /// the group messages and the seq are written together or not at all;
+    /// the messages are merged like the `add_message`, returns whether each one is new
+    async fn save_sync_page(
+        &self,
+        friend_msgs: &[Message],
+        group_msgs: &[Message],
+        seq: Option<&Seq>,
+    ) -> Result<(Vec<bool>, Vec<bool>)>;
 
     async fn update_msg_status(&self, msg: &ServerResponse) -> Result<()>;
 
@@ -4,6 +4,10 @@ use offline_time::OfflineTimes;
 use once_cell::sync::OnceCell;
 
 use self::{
+    attachment::AttachmentRepo,
+    attachments::Attachments,
+    call_log::CallLogRepo,
+    call_logs::CallLogs,
     conv::ConvRepo,
     conversations::Conversations,
     friend::FriendRepo,
@@ -15,16 +19,20 @@ use self::{
     group_msg::GroupMessages,
     groups::GroupInterface,
     impls::indexed_db::{
-        group_members::GroupMembersRepo, group_msg::GroupMsgRepo, seq::SeqRepo, voice::VoiceRepo,
+        group_members::GroupMembersRepo, group_msg::GroupMsgRepo, seq::SeqRepo, upload::UploadRepo,
+        voice::VoiceRepo,
     },
     message::MessageRepo,
     messages::Messages,
     seq::SeqInterface,
+    uploads::Uploads,
     user::UserRepo,
     users::Users,
     voice::Voices,
 };
 
+pub mod attachments;
+pub mod call_logs;
 pub mod conversations;
 pub mod friends;
 pub mod friendships;
@@ -35,6 +43,7 @@ pub mod impls;
 pub mod messages;
 pub mod offline_time;
 pub mod seq;
+pub mod uploads;
 pub mod users;
 pub mod voice;
 
@@ -69,6 +78,9 @@ pub struct Db {
     pub seq: Box<dyn SeqInterface>,
     pub voices: Box<dyn Voices>,
     pub offline_time: Box<dyn OfflineTimes>,
+    pub attachments: Box<dyn Attachments>,
+    pub uploads: Box<dyn Uploads>,
+    pub call_logs: Box<dyn CallLogs>,
 }
 
 impl Db {
@@ -85,7 +97,10 @@ impl Db {
             users: Box::new(UserRepo::new(repo.clone())),
             seq: Box::new(SeqRepo::new(repo.clone())),
             voices: Box::new(VoiceRepo::new(repo.clone())),
-            offline_time: Box::new(OfflineTimeRepo::new(repo)),
+            offline_time: Box::new(OfflineTimeRepo::new(repo.clone())),
+            attachments: Box::new(AttachmentRepo::new(repo.clone())),
+            uploads: Box::new(UploadRepo::new(repo.clone())),
+            call_logs: Box::new(CallLogRepo::new(repo)),
         }
     }
 }
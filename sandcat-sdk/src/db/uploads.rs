@@ -0,0 +1,18 @@
+use std::fmt::Debug;
+
+use web_sys::File;
+
+use crate::{error::Result, model::upload::UploadTask};
+
+#[async_trait::async_trait(?Send)]
+pub trait Uploads: Debug {
+    /// save the task with the file, the file is stored as it is
+    async fn put(&self, task: &UploadTask, file: &File) -> Result<()>;
+
+    /// update the task progress, the stored file will not be touched
+    async fn update(&self, task: &UploadTask) -> Result<()>;
+
+    async fn get_all(&self) -> Result<Vec<(UploadTask, File)>>;
+
+    async fn del(&self, local_id: &str) -> Result<()>;
+}
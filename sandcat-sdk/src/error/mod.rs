@@ -220,6 +220,12 @@ impl From<bincode::Error> for Error {
     }
 }
 
+impl From<prost::DecodeError> for Error {
+    fn from(value: prost::DecodeError) -> Self {
+        Self::internal(value)
+    }
+}
+
 impl From<serde_wasm_bindgen::Error> for Error {
     fn from(value: serde_wasm_bindgen::Error) -> Self {
         Self::internal(value)
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+/// what kind of file the cached attachment belongs to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachmentKind {
+    #[default]
+    File,
+    Image,
+    Video,
+    Avatar,
+}
+
+/// attachment downloaded from the file server and cached in indexed db,
+/// keyed by the server name stored in `FileMsg`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub server_name: String,
+    /// friend id or group id, empty for avatars
+    pub conv_id: String,
+    pub kind: AttachmentKind,
+    pub mime: String,
+    pub size: u64,
+    /// timestamp in millis, used to evict the least recently used attachments
+    pub last_access: i64,
+    pub data: Vec<u8>,
+}
+
+impl Attachment {
+    pub fn new(
+        server_name: String,
+        conv_id: String,
+        kind: AttachmentKind,
+        mime: String,
+        data: Vec<u8>,
+    ) -> Self {
+        Self {
+            server_name,
+            conv_id,
+            kind,
+            mime,
+            size: data.len() as u64,
+            last_access: chrono::Utc::now().timestamp_millis(),
+            data,
+        }
+    }
+}
+
+/// size and access time of the cached attachment, kept apart from the data
+/// so that the usage and the eviction don't have to read the blobs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AttachmentMeta {
+    pub server_name: String,
+    pub conv_id: String,
+    pub kind: AttachmentKind,
+    pub mime: String,
+    pub size: u64,
+    pub last_access: i64,
+}
+
+impl From<&Attachment> for AttachmentMeta {
+    fn from(value: &Attachment) -> Self {
+        Self {
+            server_name: value.server_name.clone(),
+            conv_id: value.conv_id.clone(),
+            kind: value.kind,
+            mime: value.mime.clone(),
+            size: value.size,
+            last_access: value.last_access,
+        }
+    }
+}
+
+impl AttachmentMeta {
+    pub fn into_attachment(self, data: Vec<u8>) -> Attachment {
+        Attachment {
+            server_name: self.server_name,
+            conv_id: self.conv_id,
+            kind: self.kind,
+            mime: self.mime,
+            size: self.size,
+            last_access: self.last_access,
+            data,
+        }
+    }
+}
+
+/// cache usage of one conversation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AttachmentUsage {
+    pub conv_id: String,
+    pub count: usize,
+    pub size: u64,
+}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+/// the text messages on the control data channel of the single call,
+/// they only matter while the call is connected so they skip the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallControl {
+    /// the friend started or stopped recording the call
+    Recording(bool),
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use yew::AttrValue;
+
+use super::message::{InviteType, Message};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CallDirection {
+    #[default]
+    Incoming,
+    Outgoing,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallOutcome {
+    #[default]
+    Answered,
+    /// the incoming call was canceled or timed out before we answered
+    Missed,
+    Declined,
+    /// we canceled the outgoing call
+    Canceled,
+    /// the friend didn't answer the outgoing call
+    NoAnswer,
+    Busy,
+}
+
+/// one record of the single call, saved beside the call message in the chat
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CallLog {
+    /// the local id of the call message
+    pub local_id: AttrValue,
+    pub friend_id: AttrValue,
+    pub direction: CallDirection,
+    pub invite_type: InviteType,
+    pub outcome: CallOutcome,
+    /// timestamp in milliseconds
+    pub start_time: i64,
+    /// duration in milliseconds, zero if not answered
+    pub duration: i64,
+    /// missed calls are unread until the calls tab is opened
+    pub is_read: bool,
+    /// the local id of the voice message left by the caller
+    #[serde(default)]
+    pub voicemail: Option<AttrValue>,
+}
+
+impl CallLog {
+    pub fn new(msg: &Message, direction: CallDirection, outcome: CallOutcome) -> Self {
+        let invite_type = match msg.content_type {
+            super::ContentType::VideoCall => InviteType::Video,
+            _ => InviteType::Audio,
+        };
+        Self {
+            local_id: msg.local_id.clone(),
+            friend_id: msg.friend_id.clone(),
+            direction,
+            invite_type,
+            outcome,
+            start_time: msg.create_time,
+            duration: 0,
+            is_read: !matches!(outcome, CallOutcome::Missed),
+            voicemail: None,
+        }
+    }
+
+    pub fn is_missed(&self) -> bool {
+        self.outcome == CallOutcome::Missed
+    }
+}
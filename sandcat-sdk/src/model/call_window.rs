@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use yew::AttrValue;
+
+/// the event the main window sends the call state by
+pub const CALL_STATE_EVENT: &str = "call-state";
+/// the event the call window sends the user action by
+pub const CALL_ACTION_EVENT: &str = "call-action";
+
+/// what the always-on-top call window of the tauri build shows,
+/// the media stays in the main window
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CallWindowState {
+    pub name: AttrValue,
+    pub avatar: AttrValue,
+    /// the duration or the connecting state
+    pub status: AttrValue,
+    pub microphone_mute: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallWindowAction {
+    /// the call window loaded, send the state to it
+    Ready,
+    SwitchMicrophoneMute,
+    HangUp,
+    /// back to the full call window in the app
+    Expand,
+}
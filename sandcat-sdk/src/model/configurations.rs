@@ -1,5 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+/// the fallback stun server, used when the server returns nothing
 #[cfg(debug_assertions)]
 pub const STUN_SERVER: &str = "stun:localhost:3478";
 
 #[cfg(not(debug_assertions))]
 pub const STUN_SERVER: &str = "stun:localhost:3478";
+
+/// a stun or turn server used by the peer connection,
+/// the turn server comes with the short-lived credential
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IceServer {
+    pub urls: Vec<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub credential: Option<String>,
+}
+
+impl IceServer {
+    pub fn stun(url: &str) -> Self {
+        Self {
+            urls: vec![url.to_string()],
+            ..Default::default()
+        }
+    }
+}
+
+/// ice servers returned by the server
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IceConfig {
+    pub ice_servers: Vec<IceServer>,
+    /// seconds the credentials are valid for
+    pub ttl: i64,
+}
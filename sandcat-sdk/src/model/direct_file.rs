@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+use super::attachment::AttachmentKind;
+
+/// the server name of the file sent directly starts with it,
+/// the file is only in the attachment cache of the two peers
+pub const DIRECT_FILE_PREFIX: &str = "direct-";
+
+pub fn direct_server_name(local_id: &str) -> String {
+    format!("{DIRECT_FILE_PREFIX}{local_id}")
+}
+
+pub fn is_direct_file(server_name: &str) -> bool {
+    server_name.starts_with(DIRECT_FILE_PREFIX)
+}
+
+/// the text messages on the data channel, the file data is sent as binary messages
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DirectFileFrame {
+    /// sent before the data
+    Header {
+        size: u64,
+        mime: String,
+        kind: AttachmentKind,
+    },
+    /// sent after the data, the crc32 of the whole file
+    Checksum(u32),
+    /// replied by the receiver after checking the size and the checksum
+    Verified(bool),
+}
+
+/// crc32 (ieee) updated chunk by chunk, start with 0
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
@@ -8,12 +8,19 @@ use icons::{
     CsvFileIcon, MdFileIcon, PdfFileIcon, TextFileIcon, UnknownFileIcon, XlsFileIcon, ZipFileIcon,
 };
 
+/// the thumbnail follows the ext in the fourth part, the old clients accept only four parts;
+/// only images and videos have the thumbnail, and their ext is not used by the old clients
+const THUMBNAIL_SEPARATOR: char = '|';
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FileMsg {
     pub name: String,
     pub server_name: String,
     pub size: usize,
     pub ext: FileExt,
+    /// server name of the downscaled preview, only images have it
+    #[serde(default)]
+    pub thumbnail: String,
 }
 
 impl FileMsg {
@@ -23,6 +30,7 @@ impl FileMsg {
             server_name,
             size,
             ext,
+            thumbnail: String::new(),
         }
     }
 
@@ -50,6 +58,7 @@ impl Default for FileMsg {
             server_name: Default::default(),
             size: Default::default(),
             ext: Default::default(),
+            thumbnail: Default::default(),
         }
     }
 }
@@ -70,11 +79,16 @@ impl From<&AttrValue> for FileMsg {
             } else {
                 0
             };
+            let (ext, thumbnail) = match parts[3].split_once(THUMBNAIL_SEPARATOR) {
+                Some((ext, thumbnail)) => (ext, thumbnail),
+                None => (parts[3], ""),
+            };
             return FileMsg {
                 server_name: parts[0].to_string(),
                 name: parts[1].to_string(),
                 size,
-                ext: FileExt::from_str(parts[3]).unwrap(),
+                ext: FileExt::from_str(ext).unwrap(),
+                thumbnail: thumbnail.to_string(),
             };
         }
 
@@ -89,7 +103,11 @@ impl Display for FileMsg {
             f,
             "{}||{}||{}||{}",
             self.server_name, self.name, self.size, self.ext
-        )
+        )?;
+        if !self.thumbnail.is_empty() {
+            write!(f, "{}{}", THUMBNAIL_SEPARATOR, self.thumbnail)?;
+        }
+        Ok(())
     }
 }
 
@@ -163,3 +181,22 @@ impl FileExt {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_file_msg_thumbnail() {
+        let mut file = FileMsg::new("a.png".to_string(), "123.png".to_string(), 10, FileExt::Txt);
+        file.thumbnail = "456.png".to_string();
+        let content = AttrValue::from(file.to_string());
+        // the old clients split it into four parts
+        assert_eq!(content.split("||").count(), 4);
+        assert_eq!(FileMsg::from(&content), file);
+
+        let old = FileMsg::from(&AttrValue::from("123.png||a.png||10||txt"));
+        assert!(old.thumbnail.is_empty());
+        assert_eq!(old.ext, FileExt::Txt);
+    }
+}
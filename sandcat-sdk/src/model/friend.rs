@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use yew::AttrValue;
 
+use crate::pb;
+
 use super::{group::GroupMember, ItemInfo, RightContentType};
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
@@ -46,6 +48,18 @@ pub struct FriendShipRequest {
     pub platform: i32,
 }
 
+impl From<FriendShipRequest> for pb::message::FsCreate {
+    fn from(value: FriendShipRequest) -> Self {
+        Self {
+            user_id: value.user_id.to_string(),
+            friend_id: value.friend_id.to_string(),
+            apply_msg: value.apply_msg.map(|v| v.to_string()),
+            req_remark: value.req_remark.map(|v| v.to_string()),
+            source: value.source.map(|v| v.to_string()).unwrap_or_default(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct FriendShipAgree {
     pub fs_id: AttrValue,
@@ -80,6 +94,28 @@ pub struct Friend {
     pub update_time: i64,
 }
 
+impl From<pb::message::Friend> for Friend {
+    fn from(value: pb::message::Friend) -> Self {
+        Self {
+            fs_id: value.fs_id.into(),
+            friend_id: value.friend_id.into(),
+            account: value.account.into(),
+            name: value.name.into(),
+            avatar: value.avatar.into(),
+            gender: value.gender.into(),
+            age: value.age,
+            region: value.region.map(|v| v.into()),
+            status: value.status,
+            remark: value.remark.map(|v| v.into()),
+            email: value.email.map(|v| v.into()),
+            source: value.source.into(),
+            signature: value.signature.into(),
+            create_time: value.create_time,
+            update_time: value.update_time,
+        }
+    }
+}
+
 impl From<GroupMember> for Friend {
     fn from(value: GroupMember) -> Self {
         Self {
@@ -148,6 +184,27 @@ pub struct FriendshipWithUser4Response {
     pub email: Option<AttrValue>,
 }
 
+impl From<pb::message::FriendshipWithUser> for FriendshipWithUser4Response {
+    fn from(value: pb::message::FriendshipWithUser) -> Self {
+        Self {
+            fs_id: value.fs_id.into(),
+            user_id: value.user_id.into(),
+            name: value.name.into(),
+            avatar: value.avatar.into(),
+            gender: value.gender.into(),
+            age: value.age,
+            region: value.region.map(|v| v.into()),
+            status: value.status,
+            apply_msg: value.apply_msg.map(|v| v.into()),
+            source: value.source.into(),
+            create_time: value.create_time,
+            account: value.account.into(),
+            remark: value.remark.map(|v| v.into()),
+            email: value.email.map(|v| v.into()),
+        }
+    }
+}
+
 impl From<FriendshipWithUser4Response> for FriendShipWithUser {
     fn from(value: FriendshipWithUser4Response) -> Self {
         Self {
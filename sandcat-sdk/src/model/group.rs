@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
 use yew::AttrValue;
 
-use crate::pb::message::GroupMemberRole;
+use crate::pb;
+use crate::pb::message::{GroupInfo, GroupMemberRole};
 
 use super::{
     friend::{Friend, FriendStatus},
@@ -37,6 +38,36 @@ pub struct Group {
     pub deleted: bool,
 }
 
+impl From<GroupInfo> for GroupFromServer {
+    fn from(value: GroupInfo) -> Self {
+        Self {
+            id: value.id.into(),
+            owner: value.owner.into(),
+            name: value.name.into(),
+            avatar: value.avatar.into(),
+            description: value.description.into(),
+            announcement: value.announcement.into(),
+            create_time: value.create_time,
+            update_time: value.update_time,
+        }
+    }
+}
+
+impl From<GroupFromServer> for GroupInfo {
+    fn from(value: GroupFromServer) -> Self {
+        Self {
+            id: value.id.to_string(),
+            owner: value.owner.to_string(),
+            name: value.name.to_string(),
+            avatar: value.avatar.to_string(),
+            description: value.description.to_string(),
+            announcement: value.announcement.to_string(),
+            create_time: value.create_time,
+            update_time: value.update_time,
+        }
+    }
+}
+
 impl From<GroupFromServer> for Group {
     fn from(value: GroupFromServer) -> Self {
         Self {
@@ -92,6 +123,43 @@ pub struct GroupMemberFromServer {
     pub role: i32,
 }
 
+impl From<pb::message::GroupMember> for GroupMemberFromServer {
+    fn from(value: pb::message::GroupMember) -> Self {
+        Self {
+            age: value.age,
+            group_id: value.group_id.into(),
+            user_id: value.user_id.into(),
+            group_name: value.group_name.into(),
+            avatar: value.avatar.into(),
+            joined_at: value.joined_at,
+            region: value.region.map(|v| v.into()),
+            gender: value.gender.into(),
+            remark: value.remark.map(|v| v.into()),
+            signature: value.signature.into(),
+            role: value.role,
+        }
+    }
+}
+
+impl From<GroupMemberFromServer> for pb::message::GroupMember {
+    fn from(value: GroupMemberFromServer) -> Self {
+        Self {
+            age: value.age,
+            group_id: value.group_id.to_string(),
+            user_id: value.user_id.to_string(),
+            group_name: value.group_name.to_string(),
+            avatar: value.avatar.to_string(),
+            joined_at: value.joined_at,
+            region: value.region.map(|v| v.to_string()),
+            gender: value.gender.to_string(),
+            is_friend: false,
+            remark: value.remark.map(|v| v.to_string()),
+            signature: value.signature.to_string(),
+            role: value.role,
+        }
+    }
+}
+
 impl GroupMemberFromServer {
     pub fn from_friend(group: &Group, value: Friend, time: i64) -> Self {
         Self {
@@ -1,8 +1,9 @@
-use log::debug;
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
 use yew::AttrValue;
 
 use crate::model::friend::{FriendShipRequest, FriendShipWithUser, FriendshipWithUser4Response};
+use crate::model::protocol::Codec;
 use crate::model::ContentType;
 use crate::pb;
 use crate::pb::message::{Msg as PbMsg, MsgType};
@@ -59,6 +60,10 @@ pub struct Message {
     pub file_content: AttrValue,
     /// related message id
     pub related_msg_id: Option<AttrValue>,
+    /// the voice message left after the call was not answered,
+    /// it relates to the call message by the `related_msg_id`
+    #[serde(default)]
+    pub voicemail: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
@@ -107,6 +112,7 @@ impl From<InviteCancelMsg> for Message {
             file_content: Default::default(),
             is_resend: value.is_resend,
             related_msg_id: None,
+            voicemail: false,
         }
     }
 }
@@ -119,6 +125,8 @@ impl From<InviteAnswerMsg> for Message {
         };
         let content = if value.agree {
             AttrValue::from("agreed")
+        } else if value.busy {
+            AttrValue::from("busy")
         } else {
             AttrValue::from("deny")
         };
@@ -144,6 +152,7 @@ impl From<InviteAnswerMsg> for Message {
             file_content: Default::default(),
             is_resend: value.is_resend,
             related_msg_id: None,
+            voicemail: false,
         }
     }
 }
@@ -177,6 +186,7 @@ impl From<InviteNotAnswerMsg> for Message {
             file_content: Default::default(),
             is_resend: value.is_resend,
             related_msg_id: None,
+            voicemail: false,
         }
     }
 }
@@ -187,7 +197,7 @@ impl From<Hangup> for Message {
             InviteType::Video => ContentType::VideoCall,
             InviteType::Audio => ContentType::AudioCall,
         };
-        let content = format!("duration||{}", utils::format_milliseconds(value.sustain)).into();
+        let content = hangup_content(value.sustain, value.stats.as_deref()).into();
 
         Message {
             seq: value.seq,
@@ -211,18 +221,57 @@ impl From<Hangup> for Message {
             file_content: Default::default(),
             is_resend: value.is_resend,
             related_msg_id: None,
+            voicemail: false,
         }
     }
 }
 
+/// the stats summary is appended after the duration if there is
+pub fn hangup_content(sustain: i64, stats: Option<&str>) -> String {
+    let duration = utils::format_milliseconds(sustain);
+    match stats {
+        Some(stats) => format!("duration||{}||{}", duration, stats),
+        None => format!("duration||{}", duration),
+    }
+}
+
 impl Message {
+    /// merge the message saved again into the stored one,
+    /// the new content is taken while the local state never goes back
+    pub fn merge(self, new: Message) -> Message {
+        Message {
+            local_id: self.local_id,
+            seq: if new.seq > 0 { new.seq } else { self.seq },
+            send_seq: if new.send_seq > 0 {
+                new.send_seq
+            } else {
+                self.send_seq
+            },
+            server_id: if new.server_id.is_empty() {
+                self.server_id
+            } else {
+                new.server_id
+            },
+            send_status: if self.send_status == SendStatus::Success {
+                SendStatus::Success
+            } else {
+                new.send_status
+            },
+            is_read: self.is_read.max(new.is_read),
+            audio_downloaded: self.audio_downloaded || new.audio_downloaded,
+            voicemail: self.voicemail || new.voicemail,
+            related_msg_id: new.related_msg_id.or(self.related_msg_id),
+            ..new
+        }
+    }
+
     pub fn from_hangup(value: Hangup) -> Self {
         let content_type = match value.invite_type {
             InviteType::Video => ContentType::VideoCall,
             InviteType::Audio => ContentType::AudioCall,
         };
         // 计算时间
-        let content = format!("duration||{}", utils::format_milliseconds(value.sustain)).into();
+        let content = hangup_content(value.sustain, value.stats.as_deref()).into();
         Message {
             seq: value.seq,
             send_seq: value.send_seq,
@@ -245,6 +294,7 @@ impl Message {
             file_content: Default::default(),
             is_resend: value.is_resend,
             related_msg_id: None,
+            voicemail: false,
         }
     }
     pub fn from_not_answer(msg: InviteNotAnswerMsg) -> Self {
@@ -275,6 +325,7 @@ impl Message {
             file_content: Default::default(),
             is_resend: msg.is_resend,
             related_msg_id: None,
+            voicemail: false,
         }
     }
 }
@@ -285,6 +336,24 @@ pub struct GroupInvitation {
     pub members: Vec<GroupMemberFromServer>,
 }
 
+impl From<pb::message::GroupInvitation> for GroupInvitation {
+    fn from(value: pb::message::GroupInvitation) -> Self {
+        Self {
+            info: value.info.map(GroupFromServer::from),
+            members: value.members.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<GroupInvitation> for pb::message::GroupInvitation {
+    fn from(value: GroupInvitation) -> Self {
+        Self {
+            info: value.info.map(Into::into),
+            members: value.members.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
 /// group invite new member response from server
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct GroupInviteNewResponse {
@@ -304,13 +373,22 @@ pub enum Msg {
     SendRelationshipReq(FriendShipRequest),
     RecRelationship((FriendShipWithUser, Sequence)),
     RecRelationshipDel((FriendID, Sequence)),
+    RecRelationshipBlack((FriendID, Sequence)),
     RelationshipRes((Friend, Sequence)),
     ReadNotice(ReadNotice),
     SingleDeliveredNotice(MessageID),
     FriendshipDeliveredNotice(MessageID),
     OfflineSync(Message),
     SingleCall(SingleCall),
+    GroupCall(GroupCall),
+    DirectFile(DirectFile),
     ServerRecResp(ServerResponse),
+    /// the text notification pushed by the server
+    Notification(String),
+    /// the service frame, the protocol handshake is taken by the websocket before converting
+    Service(PbMsg),
+    /// the type is unknown to this client, passed through untouched
+    Unknown(PbMsg),
 }
 
 impl Msg {
@@ -337,16 +415,53 @@ impl Msg {
             file_content: msg.file_content.clone(),
             is_resend: msg.is_resend,
             related_msg_id: None,
+            voicemail: false,
         }
     }
 
     pub fn split_audio(&self) -> Option<Self> {
-        if let Msg::Single(msg) = self {
-            if msg.content_type == ContentType::Audio {
-                return Some(Msg::Single(Self::new_audio_dataless_msg(msg)));
+        match self {
+            Msg::Single(msg) if msg.content_type == ContentType::Audio => {
+                Some(Msg::Single(Self::new_audio_dataless_msg(msg)))
             }
+            Msg::Group(GroupMsg::Message(msg)) if msg.content_type == ContentType::Audio => Some(
+                Msg::Group(GroupMsg::Message(Self::new_audio_dataless_msg(msg))),
+            ),
+            _ => None,
+        }
+    }
+
+    /// the voice message is inserted into the list by the `SendAudioMsgState` before uploading
+    pub fn is_audio(&self) -> bool {
+        match self {
+            Msg::Single(msg) | Msg::Group(GroupMsg::Message(msg)) => {
+                msg.content_type == ContentType::Audio
+            }
+            _ => false,
         }
-        None
+    }
+
+    /// the receive sequence of the message, none for the signalling and the notices
+    pub fn seq(&self) -> Option<Sequence> {
+        let seq = match self {
+            Msg::Single(msg) | Msg::Group(GroupMsg::Message(msg)) => msg.seq,
+            Msg::Group(GroupMsg::Update((_, seq)))
+            | Msg::Group(GroupMsg::Invitation((_, seq)))
+            | Msg::Group(GroupMsg::InviteNew((_, _, seq)))
+            | Msg::Group(GroupMsg::RemoveMember((_, _, seq)))
+            | Msg::Group(GroupMsg::MemberExit((_, _, seq)))
+            | Msg::Group(GroupMsg::Dismiss((_, seq)))
+            | Msg::RecRelationship((_, seq))
+            | Msg::RecRelationshipDel((_, seq))
+            | Msg::RecRelationshipBlack((_, seq))
+            | Msg::RelationshipRes((_, seq)) => *seq,
+            Msg::SingleCall(SingleCall::InviteCancel(msg)) => msg.seq,
+            Msg::SingleCall(SingleCall::NotAnswer(msg)) => msg.seq,
+            Msg::SingleCall(SingleCall::InviteAnswer(msg)) => msg.seq,
+            Msg::SingleCall(SingleCall::HangUp(msg)) => msg.seq,
+            _ => return None,
+        };
+        (seq > 0).then_some(seq)
     }
 }
 
@@ -496,6 +611,9 @@ pub struct InviteAnswerMsg {
     pub create_time: i64,
     pub send_time: i64,
     pub agree: bool,
+    /// rejected automatically because the friend is in another call
+    #[serde(default)]
+    pub busy: bool,
     pub invite_type: InviteType,
     pub send_status: SendStatus,
     // 主要区分发起端，因为接收端永远都是false不需要处理
@@ -526,6 +644,26 @@ pub struct CandidateData {
     pub sdp_m_index: Option<u16>,
 }
 
+impl From<CandidateData> for pb::message::Candidate {
+    fn from(value: CandidateData) -> Self {
+        Self {
+            candidate: value.candidate.to_string(),
+            sdp_mid: value.sdp_mid,
+            sdp_m_index: value.sdp_m_index.map(i32::from),
+        }
+    }
+}
+
+impl From<pb::message::Candidate> for CandidateData {
+    fn from(value: pb::message::Candidate) -> Self {
+        Self {
+            candidate: value.candidate.into(),
+            sdp_mid: value.sdp_mid,
+            sdp_m_index: value.sdp_m_index.and_then(|v| u16::try_from(v).ok()),
+        }
+    }
+}
+
 #[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Agree {
     pub sdp: Option<String>,
@@ -555,6 +693,101 @@ pub struct Hangup {
     pub platform: i32,
     #[serde(default)]
     pub is_resend: bool,
+    /// summary of the call quality, only kept in the local record
+    #[serde(default)]
+    pub stats: Option<AttrValue>,
+}
+
+/// signalling of the group call,
+/// every member in the call connects to each other member with a peer connection
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum GroupCall {
+    /// start a call in the group, broadcast to the group
+    Invite(GroupCallMember),
+    /// join the call, broadcast to the group;
+    /// the members already in the call send the offer to the new member
+    Join(GroupCallMember),
+    /// leave the call, broadcast to the group
+    Leave(GroupCallMember),
+    Offer(GroupCallSdp),
+    Answer(GroupCallSdp),
+    NewIceCandidate(GroupCallCandidate),
+}
+
+impl Default for GroupCall {
+    fn default() -> Self {
+        Self::Join(GroupCallMember::default())
+    }
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq)]
+pub struct GroupCallMember {
+    pub local_id: AttrValue,
+    pub send_id: AttrValue,
+    pub group_id: AttrValue,
+    pub create_time: i64,
+    pub invite_type: InviteType,
+    #[serde(default)]
+    pub platform: i32,
+    pub avatar: AttrValue,
+    pub nickname: AttrValue,
+}
+
+/// offer or answer between two members of the call
+#[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq)]
+pub struct GroupCallSdp {
+    pub sdp: AttrValue,
+    pub send_id: AttrValue,
+    pub friend_id: AttrValue,
+    pub group_id: AttrValue,
+    pub create_time: i64,
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq)]
+pub struct GroupCallCandidate {
+    pub candidate: AttrValue,
+    pub sdp_mid: Option<String>,
+    pub sdp_m_index: Option<u16>,
+    pub send_id: AttrValue,
+    pub friend_id: AttrValue,
+    pub group_id: AttrValue,
+    pub create_time: i64,
+}
+
+/// signalling of the file sent directly over the data channel,
+/// it goes through the single call message types tagged by the file content type;
+/// `local_id` is the id of the file message
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum DirectFile {
+    Offer(DirectFileSdp),
+    Answer(DirectFileSdp),
+    NewIceCandidate(DirectFileCandidate),
+}
+
+impl Default for DirectFile {
+    fn default() -> Self {
+        Self::Offer(DirectFileSdp::default())
+    }
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DirectFileSdp {
+    pub local_id: AttrValue,
+    pub sdp: AttrValue,
+    pub send_id: AttrValue,
+    pub friend_id: AttrValue,
+    pub create_time: i64,
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DirectFileCandidate {
+    pub local_id: AttrValue,
+    pub candidate: AttrValue,
+    pub sdp_mid: Option<String>,
+    pub sdp_m_index: Option<u16>,
+    pub send_id: AttrValue,
+    pub friend_id: AttrValue,
+    pub create_time: i64,
 }
 
 #[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq)]
@@ -579,6 +812,22 @@ pub struct ReadNotice {
     pub user_id: String,
 }
 
+impl From<ReadNotice> for pb::message::MsgRead {
+    fn from(value: ReadNotice) -> Self {
+        Self {
+            msg_seq: value.msg_seq,
+            user_id: value.user_id,
+        }
+    }
+}
+
+/// the members invited to or removed from the group
+impl From<pb::message::GroupInviteNew> for Vec<String> {
+    fn from(value: pb::message::GroupInviteNew) -> Self {
+        value.members
+    }
+}
+
 impl TryFrom<pb::message::Msg> for Message {
     type Error = String;
 
@@ -595,9 +844,13 @@ impl TryFrom<pb::message::Msg> for Message {
         } else {
             SendStatus::Success
         };
-        let duration = if value.msg_type == MsgType::SingleMsg as i32
+        // the first byte of the voice message is the duration
+        let duration = if (msg_type == MsgType::SingleMsg || msg_type == MsgType::GroupMsg)
             && value.content_type == ContentType::Audio as i32
         {
+            if value.content.is_empty() {
+                return Err(String::from("voice message without duration"));
+            }
             value.content.remove(0)
         } else {
             0
@@ -627,24 +880,32 @@ impl TryFrom<pb::message::Msg> for Message {
             file_content: AttrValue::default(),
             is_resend: false,
             related_msg_id: value.related_msg_id.map(|v| v.into()),
+            voicemail: false,
         })
     }
 }
 
-pub fn convert_server_msg(msg: PbMsg) -> Result<Msg, String> {
+/// the nested payloads are decoded by the codec the socket negotiated
+pub fn convert_server_msg(msg: PbMsg, codec: Codec) -> Result<Msg, String> {
     debug!("convert msg: {:?}", msg);
-    let msg_type = MsgType::try_from(msg.msg_type).unwrap();
+    let Ok(msg_type) = MsgType::try_from(msg.msg_type) else {
+        warn!("unknown msg type: {}", msg.msg_type);
+        return Ok(Msg::Unknown(msg));
+    };
     match msg_type {
         MsgType::SingleMsg => Ok(Msg::Single(Message::try_from(msg)?)),
         MsgType::GroupMsg => Ok(Msg::Group(GroupMsg::Message(Message::try_from(msg)?))),
         MsgType::GroupInvitation => {
             // decode content
-            let info = bincode::deserialize(&msg.content).map_err(|e| e.to_string())?;
+            let info = codec
+                .decode_payload::<_, pb::message::GroupInvitation>(&msg.content)
+                .map_err(|e| e.to_string())?;
             Ok(Msg::Group(GroupMsg::Invitation((info, msg.seq))))
         }
         MsgType::GroupInviteNew => {
-            let members: Vec<String> =
-                bincode::deserialize(&msg.content).map_err(|e| e.to_string())?;
+            let members = codec
+                .decode_payload::<Vec<String>, pb::message::GroupInviteNew>(&msg.content)
+                .map_err(|e| e.to_string())?;
             let resp = GroupInviteNewResponse {
                 group_id: msg.group_id,
                 members,
@@ -662,8 +923,9 @@ pub fn convert_server_msg(msg: PbMsg) -> Result<Msg, String> {
             msg.seq,
         )))),
         MsgType::GroupRemoveMember => {
-            let members: Vec<String> =
-                bincode::deserialize(&msg.content).map_err(|e| e.to_string())?;
+            let members = codec
+                .decode_payload::<Vec<String>, pb::message::GroupInviteNew>(&msg.content)
+                .map_err(|e| e.to_string())?;
             let resp = GroupInviteNewResponse {
                 group_id: msg.group_id,
                 members,
@@ -676,24 +938,36 @@ pub fn convert_server_msg(msg: PbMsg) -> Result<Msg, String> {
             ))))
         }
         MsgType::GroupDismiss => Ok(Msg::Group(GroupMsg::Dismiss((msg.group_id, msg.seq)))),
-        MsgType::GroupDismissOrExitReceived => todo!(),
-        MsgType::GroupInvitationReceived => todo!(),
+        MsgType::GroupDismissOrExitReceived => Ok(Msg::Group(GroupMsg::DismissOrExitReceived((
+            msg.send_id,
+            msg.group_id,
+        )))),
+        MsgType::GroupInvitationReceived => Ok(Msg::Group(GroupMsg::InvitationReceived((
+            msg.send_id,
+            msg.group_id,
+        )))),
         MsgType::GroupUpdate => {
-            let info: GroupFromServer =
-                bincode::deserialize(&msg.content).map_err(|e| e.to_string())?;
+            let info = codec
+                .decode_payload::<GroupFromServer, pb::message::GroupInfo>(&msg.content)
+                .map_err(|e| e.to_string())?;
             Ok(Msg::Group(GroupMsg::Update((Group::from(info), msg.seq))))
         }
         MsgType::FriendApplyReq => {
             // decode content
-            let info: FriendshipWithUser4Response =
-                bincode::deserialize(&msg.content).map_err(|e| e.to_string())?;
+            let info = codec
+                .decode_payload::<FriendshipWithUser4Response, pb::message::FriendshipWithUser>(
+                    &msg.content,
+                )
+                .map_err(|e| e.to_string())?;
             let mut friend = FriendShipWithUser::from(info);
             friend.msg_id = msg.server_id.into();
             Ok(Msg::RecRelationship((friend, msg.seq)))
         }
         MsgType::FriendApplyResp => {
             // decode content
-            let info = bincode::deserialize(&msg.content).map_err(|e| e.to_string())?;
+            let info = codec
+                .decode_payload::<Friend, pb::message::Friend>(&msg.content)
+                .map_err(|e| e.to_string())?;
             Ok(Msg::RelationshipRes((info, msg.seq)))
         }
         MsgType::SingleCallInvite => {
@@ -722,6 +996,7 @@ pub fn convert_server_msg(msg: PbMsg) -> Result<Msg, String> {
                 create_time: msg.create_time,
                 invite_type,
                 agree: false,
+                busy: msg.content == CALL_BUSY_CONTENT,
                 is_self: false,
                 send_time: msg.send_time,
                 send_status: SendStatus::Success,
@@ -767,6 +1042,9 @@ pub fn convert_server_msg(msg: PbMsg) -> Result<Msg, String> {
                 is_resend: false,
             })))
         }
+        MsgType::SingleCallOffer if is_direct_file(&msg) => Ok(Msg::DirectFile(DirectFile::Offer(
+            get_direct_file_sdp(msg)?,
+        ))),
         MsgType::SingleCallOffer => Ok(Msg::SingleCall(SingleCall::Offer(Offer {
             send_id: msg.send_id.into(),
             friend_id: msg.receiver_id.into(),
@@ -794,6 +1072,7 @@ pub fn convert_server_msg(msg: PbMsg) -> Result<Msg, String> {
             send_status: SendStatus::Success,
             platform: msg.platform,
             is_resend: false,
+            stats: None,
         }))),
         MsgType::AgreeSingleCall => {
             let invite_type = get_invite_type(msg.content_type)?;
@@ -807,6 +1086,7 @@ pub fn convert_server_msg(msg: PbMsg) -> Result<Msg, String> {
                 create_time: msg.create_time,
                 invite_type,
                 agree: true,
+                busy: false,
                 is_self: false,
                 send_time: msg.send_time,
                 send_status: SendStatus::Success,
@@ -816,6 +1096,9 @@ pub fn convert_server_msg(msg: PbMsg) -> Result<Msg, String> {
                 is_resend: false,
             })))
         }
+        MsgType::ConnectSingleCall if is_direct_file(&msg) => Ok(Msg::DirectFile(
+            DirectFile::Answer(get_direct_file_sdp(msg)?),
+        )),
         MsgType::ConnectSingleCall => Ok(Msg::SingleCall(SingleCall::Agree(Agree {
             send_id: msg.send_id.into(),
             friend_id: msg.receiver_id.into(),
@@ -823,9 +1106,27 @@ pub fn convert_server_msg(msg: PbMsg) -> Result<Msg, String> {
             sdp: String::from_utf8(msg.content).ok(),
             platform: msg.platform,
         }))),
+        MsgType::Candidate if is_direct_file(&msg) => {
+            let data = codec
+                .decode_payload::<CandidateData, pb::message::Candidate>(&msg.content)
+                .map_err(|e| e.to_string())?;
+
+            Ok(Msg::DirectFile(DirectFile::NewIceCandidate(
+                DirectFileCandidate {
+                    local_id: msg.local_id.into(),
+                    candidate: data.candidate,
+                    sdp_mid: data.sdp_mid,
+                    sdp_m_index: data.sdp_m_index,
+                    send_id: msg.send_id.into(),
+                    friend_id: msg.receiver_id.into(),
+                    create_time: msg.send_time,
+                },
+            )))
+        }
         MsgType::Candidate => {
-            let data: CandidateData =
-                bincode::deserialize(&msg.content).map_err(|e| e.to_string())?;
+            let data = codec
+                .decode_payload::<CandidateData, pb::message::Candidate>(&msg.content)
+                .map_err(|e| e.to_string())?;
 
             Ok(Msg::SingleCall(SingleCall::NewIceCandidate(Candidate {
                 candidate: data.candidate,
@@ -836,7 +1137,36 @@ pub fn convert_server_msg(msg: PbMsg) -> Result<Msg, String> {
                 create_time: msg.send_time,
             })))
         }
-        MsgType::Read => todo!(),
+        MsgType::GroupCallInvite => Ok(Msg::GroupCall(GroupCall::Invite(get_group_call_member(
+            msg,
+        )?))),
+        MsgType::GroupCallJoin => Ok(Msg::GroupCall(GroupCall::Join(get_group_call_member(msg)?))),
+        MsgType::GroupCallLeave => Ok(Msg::GroupCall(GroupCall::Leave(get_group_call_member(
+            msg,
+        )?))),
+        MsgType::GroupCallOffer => Ok(Msg::GroupCall(GroupCall::Offer(get_group_call_sdp(msg)?))),
+        MsgType::GroupCallAnswer => Ok(Msg::GroupCall(GroupCall::Answer(get_group_call_sdp(msg)?))),
+        MsgType::GroupCallCandidate => {
+            let data = codec
+                .decode_payload::<CandidateData, pb::message::Candidate>(&msg.content)
+                .map_err(|e| e.to_string())?;
+
+            Ok(Msg::GroupCall(GroupCall::NewIceCandidate(
+                GroupCallCandidate {
+                    candidate: data.candidate,
+                    sdp_mid: data.sdp_mid,
+                    sdp_m_index: data.sdp_m_index,
+                    send_id: msg.send_id.into(),
+                    friend_id: msg.receiver_id.into(),
+                    group_id: msg.group_id.into(),
+                    create_time: msg.send_time,
+                },
+            )))
+        }
+        MsgType::Read => {
+            let read = bincode::deserialize(&msg.content).map_err(|e| e.to_string())?;
+            Ok(Msg::ReadNotice(read))
+        }
         MsgType::MsgRecResp => {
             let msg_type = if msg.group_id.is_empty() {
                 RespMsgType::Single
@@ -857,14 +1187,61 @@ pub fn convert_server_msg(msg: PbMsg) -> Result<Msg, String> {
                 resp_msg_type: msg_type,
             }))
         }
-        MsgType::Notification => todo!(),
-        MsgType::Service => todo!(),
-        MsgType::FriendshipReceived => todo!(),
+        MsgType::Notification => Ok(Msg::Notification(
+            String::from_utf8(msg.content).map_err(|e| e.to_string())?,
+        )),
+        MsgType::Service => Ok(Msg::Service(msg)),
+        MsgType::FriendshipReceived => Ok(Msg::FriendshipDeliveredNotice(msg.server_id)),
         MsgType::FriendDelete => Ok(Msg::RecRelationshipDel((msg.send_id, msg.seq))),
-        MsgType::FriendBlack => todo!(),
+        MsgType::FriendBlack => Ok(Msg::RecRelationshipBlack((msg.send_id, msg.seq))),
     }
 }
 
+fn get_group_call_member(msg: PbMsg) -> Result<GroupCallMember, String> {
+    Ok(GroupCallMember {
+        local_id: msg.local_id.into(),
+        send_id: msg.send_id.into(),
+        group_id: msg.group_id.into(),
+        create_time: msg.send_time,
+        invite_type: get_invite_type(msg.content_type)?,
+        platform: msg.platform,
+        avatar: msg.avatar.into(),
+        nickname: msg.nickname.into(),
+    })
+}
+
+fn get_group_call_sdp(msg: PbMsg) -> Result<GroupCallSdp, String> {
+    Ok(GroupCallSdp {
+        sdp: String::from_utf8(msg.content)
+            .map_err(|e| e.to_string())?
+            .into(),
+        send_id: msg.send_id.into(),
+        friend_id: msg.receiver_id.into(),
+        group_id: msg.group_id.into(),
+        create_time: msg.send_time,
+    })
+}
+
+/// the single call signalling of the direct file is tagged by the file content type
+fn is_direct_file(msg: &PbMsg) -> bool {
+    msg.content_type == ContentType::File as i32
+}
+
+fn get_direct_file_sdp(msg: PbMsg) -> Result<DirectFileSdp, String> {
+    Ok(DirectFileSdp {
+        local_id: msg.local_id.into(),
+        sdp: String::from_utf8(msg.content)
+            .map_err(|e| e.to_string())?
+            .into(),
+        send_id: msg.send_id.into(),
+        friend_id: msg.receiver_id.into(),
+        create_time: msg.send_time,
+    })
+}
+
+/// content of the reject message replied automatically while in another call
+const CALL_BUSY_CONTENT: &[u8] = b"busy";
+
 fn get_invite_type(t: i32) -> Result<InviteType, String> {
     match ContentType::from(t) {
         ContentType::VideoCall => Ok(InviteType::Video),
@@ -873,9 +1250,10 @@ fn get_invite_type(t: i32) -> Result<InviteType, String> {
     }
 }
 
-impl From<Msg> for PbMsg {
-    fn from(value: Msg) -> Self {
-        match value {
+impl Msg {
+    /// the nested payloads are encoded by the codec the socket negotiated
+    pub fn into_pb_msg(self, codec: Codec) -> PbMsg {
+        match self {
             Msg::Single(msg) => {
                 let content = if msg.content_type == ContentType::Audio {
                     let mut content = msg.content.as_bytes().to_vec();
@@ -931,7 +1309,9 @@ impl From<Msg> for PbMsg {
                             .map(|v| v.owner.to_string())
                             .unwrap_or_default();
                         pb_msg.msg_type = MsgType::GroupInvitation as i32;
-                        pb_msg.content = bincode::serialize(&info).unwrap();
+                        pb_msg.content = codec
+                            .encode_payload::<_, pb::message::GroupInvitation>(info.0)
+                            .unwrap();
                     }
                     GroupMsg::MemberExit((send_id, group_id, _)) => {
                         pb_msg.msg_type = MsgType::GroupMemberExit as i32;
@@ -972,6 +1352,8 @@ impl From<Msg> for PbMsg {
                         pb_msg.msg_type = MsgType::RejectSingleCall as i32;
                         if answer.agree {
                             pb_msg.msg_type = MsgType::AgreeSingleCall as i32;
+                        } else if answer.busy {
+                            pb_msg.content = CALL_BUSY_CONTENT.to_vec();
                         }
                         pb_msg.local_id = answer.local_id.as_str().into();
                         pb_msg.send_id = answer.send_id.as_str().into();
@@ -1043,16 +1425,111 @@ impl From<Msg> for PbMsg {
                             sdp_mid: candidate.sdp_mid,
                             sdp_m_index: candidate.sdp_m_index,
                         };
-                        let data = bincode::serialize(&data).unwrap();
-                        pb_msg.content = data;
+                        pb_msg.content = codec
+                            .encode_payload::<_, pb::message::Candidate>(data)
+                            .unwrap();
                     }
                 }
                 pb_msg
             }
+            Msg::GroupCall(call) => {
+                let mut pb_msg = PbMsg::default();
+                let (msg_type, member) = match call {
+                    GroupCall::Invite(member) => (MsgType::GroupCallInvite, member),
+                    GroupCall::Join(member) => (MsgType::GroupCallJoin, member),
+                    GroupCall::Leave(member) => (MsgType::GroupCallLeave, member),
+                    GroupCall::Offer(offer) => {
+                        pb_msg.msg_type = MsgType::GroupCallOffer as i32;
+                        pb_msg.send_id = offer.send_id.as_str().into();
+                        pb_msg.receiver_id = offer.friend_id.as_str().into();
+                        pb_msg.group_id = offer.group_id.as_str().into();
+                        pb_msg.create_time = offer.create_time;
+                        pb_msg.content = offer.sdp.as_bytes().to_vec();
+                        return pb_msg;
+                    }
+                    GroupCall::Answer(answer) => {
+                        pb_msg.msg_type = MsgType::GroupCallAnswer as i32;
+                        pb_msg.send_id = answer.send_id.as_str().into();
+                        pb_msg.receiver_id = answer.friend_id.as_str().into();
+                        pb_msg.group_id = answer.group_id.as_str().into();
+                        pb_msg.create_time = answer.create_time;
+                        pb_msg.content = answer.sdp.as_bytes().to_vec();
+                        return pb_msg;
+                    }
+                    GroupCall::NewIceCandidate(candidate) => {
+                        pb_msg.msg_type = MsgType::GroupCallCandidate as i32;
+                        pb_msg.send_id = candidate.send_id.as_str().into();
+                        pb_msg.receiver_id = candidate.friend_id.as_str().into();
+                        pb_msg.group_id = candidate.group_id.as_str().into();
+                        pb_msg.create_time = candidate.create_time;
+                        let data = CandidateData {
+                            candidate: candidate.candidate,
+                            sdp_mid: candidate.sdp_mid,
+                            sdp_m_index: candidate.sdp_m_index,
+                        };
+                        pb_msg.content = codec
+                            .encode_payload::<_, pb::message::Candidate>(data)
+                            .unwrap();
+                        return pb_msg;
+                    }
+                };
+                // invite, join and leave are sent to the whole group
+                pb_msg.msg_type = msg_type as i32;
+                pb_msg.local_id = member.local_id.as_str().into();
+                pb_msg.send_id = member.send_id.as_str().into();
+                pb_msg.receiver_id = member.group_id.as_str().into();
+                pb_msg.group_id = member.group_id.as_str().into();
+                pb_msg.create_time = member.create_time;
+                pb_msg.content_type = match member.invite_type {
+                    InviteType::Video => ContentType::VideoCall as i32,
+                    InviteType::Audio => ContentType::AudioCall as i32,
+                };
+                pb_msg.platform = member.platform;
+                pb_msg.avatar = member.avatar.to_string();
+                pb_msg.nickname = member.nickname.to_string();
+                pb_msg
+            }
+            Msg::DirectFile(signal) => {
+                let (msg_type, sdp) = match signal {
+                    DirectFile::Offer(offer) => (MsgType::SingleCallOffer, offer),
+                    DirectFile::Answer(answer) => (MsgType::ConnectSingleCall, answer),
+                    DirectFile::NewIceCandidate(candidate) => {
+                        let data = CandidateData {
+                            candidate: candidate.candidate,
+                            sdp_mid: candidate.sdp_mid,
+                            sdp_m_index: candidate.sdp_m_index,
+                        };
+                        return PbMsg {
+                            msg_type: MsgType::Candidate as i32,
+                            local_id: candidate.local_id.as_str().into(),
+                            send_id: candidate.send_id.as_str().into(),
+                            receiver_id: candidate.friend_id.as_str().into(),
+                            create_time: candidate.create_time,
+                            content_type: ContentType::File as i32,
+                            content: codec
+                                .encode_payload::<_, pb::message::Candidate>(data)
+                                .unwrap(),
+                            ..Default::default()
+                        };
+                    }
+                };
+                PbMsg {
+                    msg_type: msg_type as i32,
+                    local_id: sdp.local_id.as_str().into(),
+                    send_id: sdp.send_id.as_str().into(),
+                    receiver_id: sdp.friend_id.as_str().into(),
+                    create_time: sdp.create_time,
+                    content_type: ContentType::File as i32,
+                    content: sdp.sdp.as_bytes().to_vec(),
+                    ..Default::default()
+                }
+            }
             Msg::SendRelationshipReq(msg) => PbMsg {
                 msg_type: MsgType::FriendApplyReq as i32,
-                content: bincode::serialize(&msg).unwrap(),
                 platform: msg.platform,
+                content: codec
+                    .encode_payload::<_, pb::message::FsCreate>(msg)
+                    .unwrap(),
                 ..Default::default()
             },
             Msg::RecRelationship(_) => PbMsg {
@@ -1064,7 +1541,9 @@ impl From<Msg> for PbMsg {
                 ..Default::default()
             },
             Msg::ReadNotice(read) => {
-                let data = bincode::serialize(&read).unwrap();
+                let data = codec
+                    .encode_payload::<_, pb::message::MsgRead>(read)
+                    .unwrap();
 
                 PbMsg {
                     msg_type: MsgType::Read as i32,
@@ -1078,6 +1557,106 @@ impl From<Msg> for PbMsg {
             Msg::OfflineSync(_) => PbMsg::default(),
             Msg::ServerRecResp(_) => PbMsg::default(),
             Msg::RecRelationshipDel(_) => PbMsg::default(),
+            Msg::RecRelationshipBlack(_) => PbMsg::default(),
+            Msg::Notification(_) => PbMsg::default(),
+            Msg::Service(msg) | Msg::Unknown(msg) => msg,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_convert_server_msg() {
+        // a type added by a newer server is passed through
+        let unknown = PbMsg {
+            msg_type: 10_000,
+            ..Default::default()
+        };
+        assert_eq!(
+            convert_server_msg(unknown.clone(), Codec::Bincode),
+            Ok(Msg::Unknown(unknown))
+        );
+
+        let notification = PbMsg {
+            msg_type: MsgType::Notification as i32,
+            content: b"hello".to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(
+            convert_server_msg(notification, Codec::Bincode),
+            Ok(Msg::Notification(String::from("hello")))
+        );
+
+        // the first byte of the voice message is the duration
+        let mut voice = PbMsg {
+            msg_type: MsgType::SingleMsg as i32,
+            content_type: ContentType::Audio as i32,
+            content: vec![3, b'a'],
+            ..Default::default()
+        };
+        let Ok(Msg::Single(msg)) = convert_server_msg(voice.clone(), Codec::Bincode) else {
+            panic!("voice message not converted");
+        };
+        assert_eq!(msg.audio_duration, 3);
+        assert_eq!(msg.content, "a");
+
+        voice.content.clear();
+        assert!(convert_server_msg(voice, Codec::Bincode).is_err());
+
+        let invalid = PbMsg {
+            msg_type: MsgType::GroupInvitation as i32,
+            content: vec![1],
+            ..Default::default()
+        };
+        assert!(convert_server_msg(invalid, Codec::Bincode).is_err());
+    }
+
+    #[test]
+    fn test_merge_message() {
+        let stored = Message {
+            local_id: "1".into(),
+            seq: 3,
+            server_id: "s1".into(),
+            send_status: SendStatus::Success,
+            is_read: 1,
+            audio_downloaded: true,
+            content: "old".into(),
+            ..Default::default()
+        };
+        // delivered again by the offline pulling, with another local id
+        let new = Message {
+            local_id: "2".into(),
+            send_status: SendStatus::Sending,
+            content: "new".into(),
+            ..Default::default()
+        };
+        let merged = stored.clone().merge(new);
+        assert_eq!(merged.local_id, "1");
+        assert_eq!(merged.seq, 3);
+        assert_eq!(merged.server_id, "s1");
+        assert_eq!(merged.send_status, SendStatus::Success);
+        assert_eq!(merged.is_read, 1);
+        assert!(merged.audio_downloaded);
+        assert_eq!(merged.content, "new");
+
+        // the server fills the seq and the server id of the pending one
+        let pending = Message {
+            local_id: "1".into(),
+            send_status: SendStatus::Sending,
+            ..Default::default()
+        };
+        let sent = Message {
+            seq: 4,
+            server_id: "s2".into(),
+            send_status: SendStatus::Failed,
+            ..pending.clone()
+        };
+        let merged = pending.merge(sent);
+        assert_eq!(merged.seq, 4);
+        assert_eq!(merged.server_id, "s2");
+        assert_eq!(merged.send_status, SendStatus::Failed);
+    }
+}
@@ -1,5 +1,10 @@
+pub mod attachment;
+pub mod call_control;
+pub mod call_log;
+pub mod call_window;
 pub mod configurations;
 pub mod conversation;
+pub mod direct_file;
 pub mod file_msg;
 pub mod friend;
 pub mod group;
@@ -7,7 +12,9 @@ pub mod message;
 pub mod notification;
 pub mod offline_time;
 pub mod page;
+pub mod protocol;
 pub mod seq;
+pub mod upload;
 pub mod user;
 pub mod voice;
 
@@ -203,6 +210,7 @@ pub enum ComponentType {
     #[default]
     Messages,
     Setting,
+    Calls,
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
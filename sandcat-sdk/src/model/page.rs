@@ -16,6 +16,9 @@ pub enum Page {
     Redirect,
     #[at("/third_login_callback/:tp")]
     ThirdLoginCallback { tp: ThirdLoginType },
+    /// the always-on-top call window of the tauri build
+    #[at("/call_window")]
+    CallWindow,
 }
 
 #[derive(Debug, Clone, PartialEq)]
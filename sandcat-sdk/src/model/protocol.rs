@@ -0,0 +1,192 @@
+use prost::Message as _;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::Result;
+use crate::pb::message::{Handshake, Msg as PbMsg, MsgType};
+
+/// the version of the socket protocol the client speaks,
+/// the servers without the handshake are treated as version 0
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// the codecs announced to the server, by preference
+pub const CODECS: [Codec; 2] = [Codec::Protobuf, Codec::Bincode];
+
+/// how the frames of the socket are encoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Codec {
+    /// what the servers without the handshake speak, kept as the fallback
+    #[default]
+    Bincode,
+    /// the real protobuf encoding of the `PbMsg`, new fields don't break the old clients
+    Protobuf,
+}
+
+impl Codec {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Codec::Bincode => "bincode",
+            Codec::Protobuf => "protobuf",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        CODECS.into_iter().find(|codec| codec.name() == name)
+    }
+
+    pub fn encode(&self, msg: &PbMsg) -> Result<Vec<u8>> {
+        match self {
+            Codec::Bincode => Ok(bincode::serialize(msg)?),
+            Codec::Protobuf => Ok(msg.encode_to_vec()),
+        }
+    }
+
+    pub fn decode(&self, body: &[u8]) -> Result<PbMsg> {
+        match self {
+            Codec::Bincode => Ok(bincode::deserialize(body)?),
+            Codec::Protobuf => Ok(PbMsg::decode(body)?),
+        }
+    }
+
+    /// the payloads nested in the content of a `PbMsg`, bincode keeps the models
+    /// the servers without the handshake know, protobuf sends the message `P` of the proto
+    pub fn encode_payload<T, P>(&self, payload: T) -> Result<Vec<u8>>
+    where
+        T: Serialize + Into<P>,
+        P: prost::Message,
+    {
+        match self {
+            Codec::Bincode => Ok(bincode::serialize(&payload)?),
+            Codec::Protobuf => Ok(payload.into().encode_to_vec()),
+        }
+    }
+
+    pub fn decode_payload<T, P>(&self, content: &[u8]) -> Result<T>
+    where
+        T: DeserializeOwned,
+        P: prost::Message + Default + Into<T>,
+    {
+        match self {
+            Codec::Bincode => Ok(bincode::deserialize(content)?),
+            Codec::Protobuf => Ok(P::decode(content)?.into()),
+        }
+    }
+}
+
+/// appended to the socket url, the servers without the handshake ignore it
+pub fn handshake_query() -> String {
+    let codecs = CODECS.map(|codec| codec.name()).join(",");
+    format!("version={PROTOCOL_VERSION}&codecs={codecs}")
+}
+
+/// the handshake is a service message carrying the protobuf encoded `Handshake`,
+/// none if the first frame is anything else; the other service messages may decode
+/// as well, so it must have a version and one of the announced codecs
+pub fn read_handshake(msg: &PbMsg) -> Option<Handshake> {
+    if msg.msg_type != MsgType::Service as i32 {
+        return None;
+    }
+    Handshake::decode(msg.content.as_slice())
+        .ok()
+        .filter(|handshake| handshake.version > 0 && Codec::from_name(&handshake.codec).is_some())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::message::CandidateData;
+    use crate::pb::message::Candidate;
+
+    fn text_msg() -> PbMsg {
+        PbMsg {
+            send_id: "a".to_string(),
+            receiver_id: "b".to_string(),
+            local_id: "1".to_string(),
+            seq: 2,
+            msg_type: MsgType::SingleMsg as i32,
+            content: b"hello".to_vec(),
+            ..Default::default()
+        }
+    }
+
+    fn handshake_msg(handshake: &Handshake) -> PbMsg {
+        PbMsg {
+            msg_type: MsgType::Service as i32,
+            content: handshake.encode_to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_codec_round_trip() {
+        let msg = text_msg();
+        for codec in CODECS {
+            let frame = codec.encode(&msg).unwrap();
+            assert_eq!(codec.decode(&frame).unwrap(), msg);
+        }
+    }
+
+    #[test]
+    fn test_codec_fallback() {
+        assert_eq!(Codec::default(), Codec::Bincode);
+        assert_eq!(Codec::from_name("protobuf"), Some(Codec::Protobuf));
+        assert_eq!(Codec::from_name("json"), None);
+        assert_eq!(handshake_query(), "version=1&codecs=protobuf,bincode");
+    }
+
+    #[test]
+    fn test_payload_round_trip() {
+        let data = CandidateData {
+            candidate: "candidate:1 1 udp 2122260223 192.168.1.2 54321 typ host".into(),
+            sdp_mid: Some("0".to_string()),
+            sdp_m_index: Some(0),
+        };
+        for codec in CODECS {
+            let content = codec.encode_payload::<_, Candidate>(data.clone()).unwrap();
+            let decoded = codec
+                .decode_payload::<CandidateData, Candidate>(&content)
+                .unwrap();
+            assert_eq!(decoded, data);
+        }
+        // the servers without the handshake still get the bincode of the model
+        assert_eq!(
+            Codec::Bincode
+                .encode_payload::<_, Candidate>(data.clone())
+                .unwrap(),
+            bincode::serialize(&data).unwrap()
+        );
+        assert_eq!(
+            Codec::Protobuf
+                .encode_payload::<_, Candidate>(data.clone())
+                .unwrap(),
+            Candidate::from(data).encode_to_vec()
+        );
+    }
+
+    #[test]
+    fn test_read_handshake() {
+        let handshake = Handshake {
+            version: 1,
+            codec: "protobuf".to_string(),
+            capabilities: vec![],
+        };
+        // the handshake is always sent by bincode
+        let frame = Codec::Bincode.encode(&handshake_msg(&handshake)).unwrap();
+        let msg = Codec::Bincode.decode(&frame).unwrap();
+        assert_eq!(read_handshake(&msg), Some(handshake));
+
+        // the messages of the servers without the handshake
+        assert_eq!(read_handshake(&text_msg()), None);
+        let service = PbMsg {
+            msg_type: MsgType::Service as i32,
+            content: b"notice".to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(read_handshake(&service), None);
+        let unknown = Handshake {
+            version: 1,
+            codec: "json".to_string(),
+            capabilities: vec![],
+        };
+        assert_eq!(read_handshake(&handshake_msg(&unknown)), None);
+    }
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+use super::{message::Message, RightContentType};
+
+/// 2MB per chunk
+pub const UPLOAD_CHUNK_SIZE: u64 = 2 * 1024 * 1024;
+
+/// chunked upload task of a pending file message,
+/// it is persisted with the file so that we can resume it after reconnecting or reloading
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UploadTask {
+    /// the same as the local id of the message
+    pub local_id: String,
+    /// returned by the file server
+    pub upload_id: String,
+    pub conv_type: RightContentType,
+    pub msg: Message,
+    pub size: u64,
+    pub chunk_size: u64,
+    /// index of the next chunk to upload
+    pub next_chunk: u32,
+}
+
+impl UploadTask {
+    pub fn new(upload_id: String, conv_type: RightContentType, msg: Message, size: u64) -> Self {
+        Self {
+            local_id: msg.local_id.to_string(),
+            upload_id,
+            conv_type,
+            msg,
+            size,
+            chunk_size: UPLOAD_CHUNK_SIZE,
+            next_chunk: 0,
+        }
+    }
+
+    pub fn total_chunks(&self) -> u32 {
+        self.size.div_ceil(self.chunk_size) as u32
+    }
+
+    pub fn uploaded(&self) -> u64 {
+        (self.next_chunk as u64 * self.chunk_size).min(self.size)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_chunk >= self.total_chunks()
+    }
+
+    /// start and end position of the chunk in the file
+    pub fn chunk_range(&self, index: u32) -> (f64, f64) {
+        let start = index as u64 * self.chunk_size;
+        let end = (start + self.chunk_size).min(self.size);
+        (start as f64, end as f64)
+    }
+}
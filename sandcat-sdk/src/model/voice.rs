@@ -5,6 +5,11 @@ pub struct Voice {
     pub local_id: String,
     pub data: Vec<u8>,
     pub duration: u8,
+    /// peak of every bar, 0-255, computed when recording or downloading
+    #[serde(default)]
+    pub waveform: Vec<u8>,
+    #[serde(default)]
+    pub played: bool,
 }
 
 impl Voice {
@@ -13,6 +18,8 @@ impl Voice {
             local_id,
             data,
             duration,
+            waveform: Vec::new(),
+            played: false,
         }
     }
 }
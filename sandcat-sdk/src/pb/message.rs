@@ -76,6 +76,21 @@ pub struct MsgReadReq {
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct MsgReadResp {}
+/// / the first frame the server sends after the socket opened, the frame is always bincode
+/// / so that the client can read it before knowing the codec
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Handshake {
+    /// / the protocol version the server speaks to this client
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    /// / the codec picked from the ones the client announced
+    #[prost(string, tag = "2")]
+    pub codec: ::prost::alloc::string::String,
+    /// / the optional features of the server
+    #[prost(string, repeated, tag = "3")]
+    pub capabilities: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
 #[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -920,6 +935,13 @@ pub enum MsgType {
     Notification = 25,
     Service = 26,
     FriendshipReceived = 27,
+    /// / group call operation
+    GroupCallInvite = 28,
+    GroupCallJoin = 29,
+    GroupCallLeave = 30,
+    GroupCallOffer = 31,
+    GroupCallAnswer = 32,
+    GroupCallCandidate = 33,
 }
 impl MsgType {
     /// String value of the enum field names used in the ProtoBuf definition.
@@ -956,6 +978,12 @@ impl MsgType {
             MsgType::Notification => "MsgTypeNotification",
             MsgType::Service => "MsgTypeService",
             MsgType::FriendshipReceived => "MsgTypeFriendshipReceived",
+            MsgType::GroupCallInvite => "MsgTypeGroupCallInvite",
+            MsgType::GroupCallJoin => "MsgTypeGroupCallJoin",
+            MsgType::GroupCallLeave => "MsgTypeGroupCallLeave",
+            MsgType::GroupCallOffer => "MsgTypeGroupCallOffer",
+            MsgType::GroupCallAnswer => "MsgTypeGroupCallAnswer",
+            MsgType::GroupCallCandidate => "MsgTypeGroupCallCandidate",
         }
     }
     /// Creates an enum from field names used in the ProtoBuf definition.
@@ -989,6 +1017,12 @@ impl MsgType {
             "MsgTypeNotification" => Some(Self::Notification),
             "MsgTypeService" => Some(Self::Service),
             "MsgTypeFriendshipReceived" => Some(Self::FriendshipReceived),
+            "MsgTypeGroupCallInvite" => Some(Self::GroupCallInvite),
+            "MsgTypeGroupCallJoin" => Some(Self::GroupCallJoin),
+            "MsgTypeGroupCallLeave" => Some(Self::GroupCallLeave),
+            "MsgTypeGroupCallOffer" => Some(Self::GroupCallOffer),
+            "MsgTypeGroupCallAnswer" => Some(Self::GroupCallAnswer),
+            "MsgTypeGroupCallCandidate" => Some(Self::GroupCallCandidate),
             _ => None,
         }
     }
@@ -10,10 +10,13 @@ use yewdux::{Dispatch, Store};
 use i18n::LanguageType;
 
 use crate::model::{
+    call_log::CallLog,
+    configurations::{IceConfig, IceServer, STUN_SERVER},
     conversation::Conversation,
     friend::{Friend, FriendShipWithUser},
     group::Group,
-    message::{InviteMsg, Message, Msg, ServerResponse},
+    message::{GroupCallMember, InviteMsg, Message, Msg, ServerResponse},
+    protocol::Codec,
     user::User,
     ComponentType, CurrentItem, FriendShipStateType, RightContentType,
 };
@@ -78,6 +81,8 @@ impl From<ComponentType> for ComponentTypeState {
 pub struct UnreadState {
     pub msg_count: usize,
     pub contacts_count: usize,
+    #[serde(default)]
+    pub missed_call_count: usize,
 }
 
 impl UnreadState {
@@ -97,6 +102,15 @@ impl UnreadState {
         Dispatch::<Self>::global()
             .reduce_mut(|s| s.contacts_count = s.contacts_count.saturating_sub(count));
     }
+
+    pub fn incr_missed_call() {
+        Dispatch::<Self>::global()
+            .reduce_mut(|s| s.missed_call_count = s.missed_call_count.saturating_add(1));
+    }
+
+    pub fn clear_missed_call() {
+        Dispatch::<Self>::global().reduce_mut(|s| s.missed_call_count = 0);
+    }
 }
 /// notify other components after received a message
 #[derive(Default, Clone, PartialEq, Debug, Store)]
@@ -171,11 +185,67 @@ pub struct AudioDownloadedState {
     pub local_id: AttrValue,
 }
 
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub enum UploadStatus {
+    #[default]
+    Uploading,
+    /// network error, waiting for reconnecting
+    Paused,
+    Canceled,
+    Finished,
+}
+
+/// file upload progress of the pending message
+#[derive(Default, Clone, PartialEq, Debug, Store)]
+pub struct UploadProgressState {
+    pub local_id: AttrValue,
+    pub uploaded: u64,
+    pub total: u64,
+    pub status: UploadStatus,
+}
+
+impl UploadProgressState {
+    pub fn notify(local_id: AttrValue, uploaded: u64, total: u64, status: UploadStatus) {
+        Dispatch::<Self>::global().set(Self {
+            local_id,
+            uploaded,
+            total,
+            status,
+        });
+    }
+}
+
+/// scroll the message list to the message, used by the shared content of the conversation
+#[derive(Default, Clone, PartialEq, Debug, Store)]
+pub struct JumpToMsgState {
+    pub local_id: AttrValue,
+}
+
+impl JumpToMsgState {
+    pub fn notify(local_id: AttrValue) {
+        Dispatch::<Self>::global().set(Self { local_id });
+    }
+}
+
+/// a new call log is saved, the calls list prepends it
+#[derive(Default, Clone, PartialEq, Debug, Store)]
+pub struct CallLogState {
+    pub log: CallLog,
+}
+
+impl Notify for CallLogState {}
+
 #[derive(Default, Clone, PartialEq, Debug, Store)]
 pub struct SendCallState {
     pub msg: InviteMsg,
 }
 
+/// start or join the call of the group
+#[derive(Default, Clone, PartialEq, Debug, Store)]
+pub struct SendGroupCallState {
+    pub msg: GroupCallMember,
+}
+
 /// send message result, success or failed or timeout
 #[derive(Default, Debug, Clone, PartialEq, Store)]
 pub struct SendResultState {
@@ -339,6 +409,151 @@ impl TransparentState {
     }
 }
 
+/// the max size of the attachment cache in bytes
+#[derive(Clone, PartialEq, Debug, Store, Serialize, Deserialize)]
+#[store(storage = "local")]
+pub struct AttachmentQuotaState {
+    pub value: u64,
+}
+
+/// 512MB by default
+const DEFAULT_ATTACHMENT_QUOTA: u64 = 512 * 1024 * 1024;
+
+impl Default for AttachmentQuotaState {
+    fn default() -> Self {
+        Self {
+            value: DEFAULT_ATTACHMENT_QUOTA,
+        }
+    }
+}
+
+impl AttachmentQuotaState {
+    pub fn get() -> u64 {
+        Dispatch::<Self>::global().get().value
+    }
+
+    pub fn set(value: u64) {
+        Dispatch::<Self>::global().reduce_mut(|s| s.value = value);
+    }
+}
+
+/// ice servers fetched from the server, cached until the credentials expire
+#[derive(Default, Clone, PartialEq, Debug, Store, Serialize, Deserialize)]
+#[store(storage = "local")]
+pub struct IceServerState {
+    pub servers: Vec<IceServer>,
+    /// timestamp in milliseconds
+    pub expire_at: i64,
+}
+
+/// refresh the credentials a minute before they expire
+const ICE_EXPIRE_MARGIN: i64 = 60 * 1000;
+
+impl IceServerState {
+    pub fn is_valid() -> bool {
+        let state = Dispatch::<Self>::global().get();
+        !state.servers.is_empty()
+            && state.expire_at - ICE_EXPIRE_MARGIN > chrono::Utc::now().timestamp_millis()
+    }
+
+    pub fn set(config: IceConfig) {
+        let expire_at = chrono::Utc::now().timestamp_millis() + config.ttl * 1000;
+        Dispatch::<Self>::global().reduce_mut(|s| {
+            s.servers = config.ice_servers;
+            s.expire_at = expire_at;
+        });
+    }
+
+    /// the servers used to create the peer connection,
+    /// the override in the setting first, then the cache, then the default stun server
+    pub fn servers() -> Vec<IceServer> {
+        let custom = IceServerOverrideState::get();
+        if !custom.urls.trim().is_empty() {
+            return vec![custom.to_server()];
+        }
+        let state = Dispatch::<Self>::global().get();
+        if state.servers.is_empty() {
+            return vec![IceServer::stun(STUN_SERVER)];
+        }
+        state.servers.clone()
+    }
+}
+
+/// ice server configured by the user, used by the self-hosted server
+#[derive(Default, Clone, PartialEq, Debug, Store, Serialize, Deserialize)]
+#[store(storage = "local")]
+pub struct IceServerOverrideState {
+    /// separated by comma
+    pub urls: String,
+    pub username: String,
+    pub credential: String,
+}
+
+impl IceServerOverrideState {
+    pub fn get() -> Rc<Self> {
+        Dispatch::<Self>::global().get()
+    }
+
+    pub fn set(value: Self) {
+        Dispatch::<Self>::global().set(value);
+    }
+
+    pub fn to_server(&self) -> IceServer {
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        IceServer {
+            urls: self
+                .urls
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(String::from)
+                .collect(),
+            username: non_empty(self.username.trim()),
+            credential: non_empty(self.credential.trim()),
+        }
+    }
+}
+
+/// the camera, microphone and speaker picked by the user, empty means the default device
+#[derive(Default, Clone, PartialEq, Debug, Store, Serialize, Deserialize)]
+#[store(storage = "local")]
+pub struct MediaDeviceState {
+    pub microphone: String,
+    pub camera: String,
+    #[serde(default)]
+    pub speaker: String,
+}
+
+impl MediaDeviceState {
+    pub fn get() -> Rc<Self> {
+        Dispatch::<Self>::global().get()
+    }
+
+    pub fn microphone(&self) -> Option<&str> {
+        (!self.microphone.is_empty()).then_some(self.microphone.as_str())
+    }
+
+    pub fn camera(&self) -> Option<&str> {
+        (!self.camera.is_empty()).then_some(self.camera.as_str())
+    }
+
+    pub fn speaker(&self) -> Option<&str> {
+        (!self.speaker.is_empty()).then_some(self.speaker.as_str())
+    }
+
+    pub fn set_microphone(id: String) {
+        Dispatch::<Self>::global().reduce_mut(|s| s.microphone = id);
+    }
+
+    pub fn set_camera(id: String) {
+        Dispatch::<Self>::global().reduce_mut(|s| s.camera = id);
+    }
+
+    pub fn set_speaker(id: String) {
+        Dispatch::<Self>::global().reduce_mut(|s| s.speaker = id);
+    }
+}
+
 #[derive(Default, Clone, PartialEq, Debug, Store, Serialize, Deserialize)]
 #[store(storage = "local")]
 #[serde(rename_all = "lowercase")]
@@ -419,6 +634,40 @@ pub enum ConnectState {
 
 impl Notify for ConnectState {}
 
+/// negotiated by the handshake after the socket opened,
+/// the default is what the servers without the handshake speak
+#[derive(Default, Debug, Clone, PartialEq, Store)]
+pub struct ProtocolState {
+    /// 0 for the servers without the handshake
+    pub version: u32,
+    pub codec: Codec,
+    /// the optional features of the server
+    pub capabilities: Vec<String>,
+}
+
+impl Notify for ProtocolState {}
+
+impl ProtocolState {
+    pub fn supports(capability: &str) -> bool {
+        Self::get().capabilities.iter().any(|c| c == capability)
+    }
+}
+
+/// the progress of the offline sync in messages, nothing to show if the total is 0
+#[derive(Default, Debug, Clone, PartialEq, Store)]
+pub struct SyncState {
+    pub synced: usize,
+    pub total: usize,
+}
+
+impl Notify for SyncState {}
+
+impl SyncState {
+    pub fn is_syncing(&self) -> bool {
+        self.total > 0 && self.synced < self.total
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Store)]
 pub struct RelatedMsgState {
     pub nickname: Option<AttrValue>,
@@ -0,0 +1,31 @@
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder};
+
+/// the label of the call window, it is allowed in the capabilities
+const CALL_WINDOW: &str = "call";
+
+/// open the always-on-top window with the call controls,
+/// the commands creating windows must be async, or it deadlocks on windows
+#[tauri::command]
+pub async fn open_call_window(app: AppHandle, title: String) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window(CALL_WINDOW) {
+        return window.set_focus().map_err(|e| e.to_string());
+    }
+    WebviewWindowBuilder::new(&app, CALL_WINDOW, WebviewUrl::App("call_window".into()))
+        .title(title)
+        .inner_size(320.0, 72.0)
+        .resizable(false)
+        .decorations(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .build()
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn close_call_window(app: AppHandle) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window(CALL_WINDOW) {
+        window.close().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
@@ -57,6 +57,7 @@ impl Into<Message> for Msg {
             nickname: self.nickname.into(),
             is_resend: self.is_resend,
             related_msg_id: self.related_msg_id.map(|v| v.into()),
+            voicemail: false,
         }
     }
 }
@@ -1,5 +1,6 @@
 use sqlx::SqlitePool;
 
+mod call_window;
 mod db;
 
 pub struct AppState {
@@ -20,7 +21,11 @@ pub async fn run() {
 
     tauri::Builder::default()
         .manage(state)
-        .invoke_handler(tauri::generate_handler![db::message::get_messages])
+        .invoke_handler(tauri::generate_handler![
+            db::message::get_messages,
+            call_window::open_call_window,
+            call_window::close_call_window
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
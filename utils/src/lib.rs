@@ -1,9 +1,15 @@
+pub mod tauri;
+
 use fluent::{FluentBundle, FluentResource};
 use gloo::utils::{document, window};
+use js_sys::{Function, Promise, Reflect};
 use unic_langid::langid;
 use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{HtmlElement, MediaStream, MediaStreamConstraints, MediaStreamTrack};
+use web_sys::{
+    HtmlElement, HtmlMediaElement, MediaDeviceInfo, MediaDeviceKind, MediaStream,
+    MediaStreamConstraints, MediaStreamTrack, MediaTrackConstraints,
+};
 
 /// 格式化时间
 pub fn format_milliseconds(millis: i64) -> String {
@@ -21,13 +27,28 @@ pub fn format_milliseconds(millis: i64) -> String {
     }
 }
 
+/// the constraint of the picked device, none means the default device
+fn device_constraint(device_id: Option<&str>) -> JsValue {
+    match device_id {
+        Some(id) if !id.is_empty() => {
+            let mut constraints = MediaTrackConstraints::new();
+            constraints.device_id(&JsValue::from_str(id));
+            constraints.into()
+        }
+        _ => true.into(),
+    }
+}
+
 /// 获取视频流
-pub async fn get_video_stream() -> Result<MediaStream, JsValue> {
+pub async fn get_video_stream(
+    camera: Option<&str>,
+    microphone: Option<&str>,
+) -> Result<MediaStream, JsValue> {
     let navigator = window().navigator();
     let devices = navigator.media_devices()?;
     // 获取视频
     let mut constraints = MediaStreamConstraints::new();
-    constraints.video(&true.into());
+    constraints.video(&device_constraint(camera));
     let media = devices.get_user_media_with_constraints(&constraints)?;
     log::debug!("media : {:?}", media);
     web_sys::console::log_1(&media);
@@ -36,7 +57,7 @@ pub async fn get_video_stream() -> Result<MediaStream, JsValue> {
     let video_stream = JsCast::dyn_into::<MediaStream>(value)?;
     // 获取音频
     let mut audio_constraints = MediaStreamConstraints::new();
-    audio_constraints.audio(&true.into());
+    audio_constraints.audio(&device_constraint(microphone));
     let audio_value =
         match JsFuture::from(devices.get_user_media_with_constraints(&audio_constraints)?).await {
             Ok(audio_value) => audio_value,
@@ -57,12 +78,12 @@ pub async fn get_video_stream() -> Result<MediaStream, JsValue> {
 }
 
 /// 获取音频
-pub async fn get_audio_stream() -> Result<MediaStream, JsValue> {
+pub async fn get_audio_stream(microphone: Option<&str>) -> Result<MediaStream, JsValue> {
     let navigator = window().navigator();
     let devices = navigator.media_devices()?;
     // 获取音频
     let mut audio_constraints = MediaStreamConstraints::new();
-    audio_constraints.audio(&true.into());
+    audio_constraints.audio(&device_constraint(microphone));
     let audio_value =
         JsFuture::from(devices.get_user_media_with_constraints(&audio_constraints)?).await?;
     let audio_stream = JsCast::dyn_into::<MediaStream>(audio_value)?;
@@ -70,6 +91,107 @@ pub async fn get_audio_stream() -> Result<MediaStream, JsValue> {
     Ok(audio_stream)
 }
 
+/// only the camera, used to switch the camera in the call
+pub async fn get_camera_stream(camera: Option<&str>) -> Result<MediaStream, JsValue> {
+    let devices = window().navigator().media_devices()?;
+    let mut constraints = MediaStreamConstraints::new();
+    constraints.video(&device_constraint(camera));
+    let value = JsFuture::from(devices.get_user_media_with_constraints(&constraints)?).await?;
+    JsCast::dyn_into::<MediaStream>(value)
+}
+
+/// list the devices of the kind, the labels are empty before the user granted the permission
+pub async fn enumerate_devices(kind: MediaDeviceKind) -> Result<Vec<MediaDeviceInfo>, JsValue> {
+    let devices = window().navigator().media_devices()?;
+    let list = JsFuture::from(devices.enumerate_devices()?).await?;
+    let list = JsCast::dyn_into::<js_sys::Array>(list)?;
+    Ok(list
+        .iter()
+        .filter_map(|device| device.dyn_into::<MediaDeviceInfo>().ok())
+        .filter(|device| device.kind() == kind)
+        .collect())
+}
+
+/// play the media element on the picked speaker, none means the default speaker;
+/// `setSinkId` is missing in some browsers, which play on the default speaker
+pub async fn set_sink_id(element: &HtmlMediaElement, speaker: Option<&str>) -> Result<(), JsValue> {
+    let set_sink_id = js_sys::Reflect::get(element, &JsValue::from_str("setSinkId"))?;
+    let Some(set_sink_id) = set_sink_id.dyn_ref::<js_sys::Function>() else {
+        return Ok(());
+    };
+    let promise = set_sink_id.call1(element, &JsValue::from_str(speaker.unwrap_or_default()))?;
+    JsFuture::from(js_sys::Promise::from(promise)).await?;
+    Ok(())
+}
+
+/// capture the screen, the user picks the screen or window to share
+pub async fn get_display_stream() -> Result<MediaStream, JsValue> {
+    let navigator = window().navigator();
+    let devices = navigator.media_devices()?;
+    let value = JsFuture::from(devices.get_display_media()?).await?;
+    JsCast::dyn_into::<MediaStream>(value)
+}
+
+/// whether the browser is able to show the video in the picture-in-picture window
+pub fn is_pip_enabled() -> bool {
+    Reflect::get(&document(), &JsValue::from_str("pictureInPictureEnabled"))
+        .map(|enabled| enabled.is_truthy())
+        .unwrap_or(false)
+}
+
+/// float the video above the other windows, the web-sys has no binding for it
+pub async fn request_pip(video: &HtmlElement) -> Result<(), JsValue> {
+    let request = Reflect::get(video, &JsValue::from_str("requestPictureInPicture"))?
+        .dyn_into::<Function>()?;
+    let promise = request.call0(video)?.dyn_into::<Promise>()?;
+    JsFuture::from(promise).await?;
+    Ok(())
+}
+
+/// close the picture-in-picture window if there is one
+pub fn exit_pip() {
+    let document = document();
+    let has_pip = Reflect::get(&document, &JsValue::from_str("pictureInPictureElement"))
+        .is_ok_and(|element| !element.is_null() && !element.is_undefined());
+    if !has_pip {
+        return;
+    }
+    if let Ok(exit) = Reflect::get(&document, &JsValue::from_str("exitPictureInPicture"))
+        .and_then(|exit| exit.dyn_into::<Function>())
+    {
+        let _ = exit.call0(&document);
+    }
+}
+
+/// the button of the picture-in-picture window, e.g. `togglemicrophone` and `hangup`,
+/// only chromium shows them; remove the button by none
+pub fn set_media_session_action(action: &str, handler: Option<&Function>) {
+    let Ok(session) = Reflect::get(&window().navigator(), &JsValue::from_str("mediaSession"))
+    else {
+        return;
+    };
+    let handler = handler.map(JsValue::from).unwrap_or(JsValue::NULL);
+    if let Ok(set_handler) = Reflect::get(&session, &JsValue::from_str("setActionHandler"))
+        .and_then(|set_handler| set_handler.dyn_into::<Function>())
+    {
+        // unsupported actions throw, ignore them
+        let _ = set_handler.call2(&session, &JsValue::from_str(action), &handler);
+    }
+}
+
+/// the microphone state shown in the picture-in-picture window
+pub fn set_microphone_active(active: bool) {
+    let Ok(session) = Reflect::get(&window().navigator(), &JsValue::from_str("mediaSession"))
+    else {
+        return;
+    };
+    if let Ok(set_active) = Reflect::get(&session, &JsValue::from_str("setMicrophoneActive"))
+        .and_then(|set_active| set_active.dyn_into::<Function>())
+    {
+        let _ = set_active.call1(&session, &JsValue::from_bool(active));
+    }
+}
+
 #[macro_export]
 macro_rules! tr {
     ($model:expr, $key:expr $(, $arg:expr)*) => {{
@@ -139,6 +261,29 @@ pub fn set_transparent(trans: &str) {
         .unwrap()
 }
 
+/// find the http and https links in the text,
+/// bare domains are not links since they can't be told apart from file names
+pub fn extract_links(text: &str) -> Vec<&str> {
+    // the full width punctuation is not a part of the link
+    text.split(|c: char| c.is_whitespace() || "，。；：！？（）【】、".contains(c))
+        // the link may follow the text without a space
+        .filter_map(|word| {
+            let start = [word.find("http://"), word.find("https://")]
+                .into_iter()
+                .flatten()
+                .min()?;
+            Some(&word[start..])
+        })
+        // remove the trailing punctuation
+        .map(|word| word.trim_end_matches(|c: char| ",.;:!?)]}>\"'".contains(c)))
+        .filter(|link| {
+            link.split("://")
+                .nth(1)
+                .is_some_and(|host| !host.is_empty())
+        })
+        .collect()
+}
+
 pub fn get_avatar_url(avatar_id: &str) -> String {
     format!("/api/file/avatar/get/{avatar_id}")
 }
@@ -159,3 +304,24 @@ pub fn get_local_storage(name: &str) -> Result<String, JsValue> {
         .ok_or(JsValue::from("Token not found."))?;
     Ok(value)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_extract_links() {
+        assert_eq!(
+            extract_links("see https://a.com/x?y=1, and http://b.org."),
+            vec!["https://a.com/x?y=1", "http://b.org"]
+        );
+        // several links in the text of other languages
+        assert_eq!(
+            extract_links("(https://a.com/b) 看这个https://c.com。https://d.com！"),
+            vec!["https://a.com/b", "https://c.com", "https://d.com"]
+        );
+        // bare domains and the scheme alone
+        assert!(extract_links("a.com www.b.com report.pdf").is_empty());
+        assert!(extract_links("https:// http://").is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+//! the tauri api injected into the window by the `withGlobalTauri`,
+//! all of them fail in the browser, check `is_tauri` before calling them
+
+use gloo::utils::window;
+use js_sys::{Function, Promise, Reflect};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(catch, js_namespace = ["__TAURI__", "core"], js_name = invoke)]
+    fn tauri_invoke(cmd: &str, args: JsValue) -> Result<Promise, JsValue>;
+
+    #[wasm_bindgen(catch, js_namespace = ["__TAURI__", "event"], js_name = emit)]
+    fn tauri_emit(event: &str, payload: JsValue) -> Result<Promise, JsValue>;
+
+    #[wasm_bindgen(catch, js_namespace = ["__TAURI__", "event"], js_name = listen)]
+    fn tauri_listen(event: &str, handler: &Function) -> Result<Promise, JsValue>;
+}
+
+/// whether we are running in the tauri webview
+pub fn is_tauri() -> bool {
+    Reflect::has(&window(), &JsValue::from_str("__TAURI__")).unwrap_or(false)
+}
+
+/// call the command registered by the `invoke_handler`
+pub async fn invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue> {
+    JsFuture::from(tauri_invoke(cmd, args)?).await
+}
+
+/// send the event to all the windows
+pub async fn emit(event: &str, payload: &str) -> Result<(), JsValue> {
+    JsFuture::from(tauri_emit(event, JsValue::from_str(payload))?).await?;
+    Ok(())
+}
+
+/// the handler receives the event object, the payload is in the `payload` field;
+/// returns the function to stop listening
+pub async fn listen(event: &str, handler: &Function) -> Result<Function, JsValue> {
+    JsFuture::from(tauri_listen(event, handler)?)
+        .await?
+        .dyn_into::<Function>()
+}
+
+/// the string payload of the event object
+pub fn event_payload(event: &JsValue) -> Option<String> {
+    Reflect::get(event, &JsValue::from_str("payload"))
+        .ok()
+        .and_then(|payload| payload.as_string())
+}
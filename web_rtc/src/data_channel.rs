@@ -0,0 +1,187 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    Event, RtcConfiguration, RtcDataChannel, RtcDataChannelEvent, RtcDataChannelType,
+    RtcIceCandidateInit, RtcIceConnectionState, RtcPeerConnection, RtcPeerConnectionIceEvent,
+    RtcSdpType, RtcSessionDescription, RtcSessionDescriptionInit,
+};
+use yew::Callback;
+
+use sandcat_sdk::state::IceServerState;
+
+use crate::to_rtc_ice_server;
+
+/// the candidate to be sent by the signalling, candidate, sdp mid and sdp m line index
+pub type LocalCandidate = (String, Option<String>, Option<u16>);
+
+/// peer connection without media, it only carries the data channels;
+/// the signalling is left to the caller
+pub struct DataPeer {
+    pc: RtcPeerConnection,
+    /// the `close` event is sent to them when no path can be made,
+    /// the browser doesn't close them by itself
+    channels: Rc<RefCell<Vec<RtcDataChannel>>>,
+    /// the remote candidates arrived before the remote description
+    pending_candidates: Rc<RefCell<Option<Vec<RtcIceCandidateInit>>>>,
+    _on_ice_candidate: Closure<dyn FnMut(RtcPeerConnectionIceEvent)>,
+    _on_ice_connection_state_change: Closure<dyn FnMut()>,
+    on_data_channel: Option<Closure<dyn FnMut(RtcDataChannelEvent)>>,
+}
+
+impl DataPeer {
+    pub fn new(candidate: Callback<LocalCandidate>) -> Result<Self, JsValue> {
+        let ice_servers = IceServerState::servers()
+            .iter()
+            .map(to_rtc_ice_server)
+            .collect::<js_sys::Array>();
+        let mut config = RtcConfiguration::new();
+        config.ice_servers(&ice_servers);
+        let pc = RtcPeerConnection::new_with_configuration(&config)?;
+
+        let on_ice_candidate = Closure::<dyn FnMut(RtcPeerConnectionIceEvent)>::new(
+            move |event: RtcPeerConnectionIceEvent| {
+                if let Some(ice) = event.candidate() {
+                    candidate.emit((ice.candidate(), ice.sdp_mid(), ice.sdp_m_line_index()));
+                }
+            },
+        );
+
+        let channels: Rc<RefCell<Vec<RtcDataChannel>>> = Rc::default();
+        let channels_clone = channels.clone();
+        let pc_clone = pc.clone();
+        let on_ice_connection_state_change = Closure::<dyn FnMut()>::new(move || {
+            log::debug!(
+                "data peer ice connection state change: {:?}",
+                pc_clone.ice_connection_state()
+            );
+            if pc_clone.ice_connection_state() != RtcIceConnectionState::Failed {
+                return;
+            }
+            for channel in channels_clone.borrow().iter() {
+                if let Ok(event) = Event::new("close") {
+                    let _ = channel.dispatch_event(&event);
+                }
+            }
+        });
+
+        pc.set_onicecandidate(Some(on_ice_candidate.as_ref().unchecked_ref()));
+        pc.set_oniceconnectionstatechange(Some(
+            on_ice_connection_state_change.as_ref().unchecked_ref(),
+        ));
+
+        Ok(Self {
+            pc,
+            channels,
+            pending_candidates: Rc::new(RefCell::new(Some(Vec::new()))),
+            _on_ice_candidate: on_ice_candidate,
+            _on_ice_connection_state_change: on_ice_connection_state_change,
+            on_data_channel: None,
+        })
+    }
+
+    /// the channel is reliable and ordered, the binary messages are received as array buffers
+    pub fn create_channel(&self, label: &str) -> RtcDataChannel {
+        let channel = self.pc.create_data_channel(label);
+        channel.set_binary_type(RtcDataChannelType::Arraybuffer);
+        self.channels.borrow_mut().push(channel.clone());
+        channel
+    }
+
+    /// called with the channel created by the remote peer
+    pub fn on_channel(&mut self, callback: Callback<RtcDataChannel>) {
+        let channels = self.channels.clone();
+        let on_data_channel =
+            Closure::<dyn FnMut(RtcDataChannelEvent)>::new(move |event: RtcDataChannelEvent| {
+                let channel = event.channel();
+                channel.set_binary_type(RtcDataChannelType::Arraybuffer);
+                channels.borrow_mut().push(channel.clone());
+                callback.emit(channel);
+            });
+        self.pc
+            .set_ondatachannel(Some(on_data_channel.as_ref().unchecked_ref()));
+        self.on_data_channel = Some(on_data_channel);
+    }
+
+    /// create the offer after the channel is created, returns the sdp
+    pub async fn create_offer(&self) -> Result<String, JsValue> {
+        let offer = JsFuture::from(self.pc.create_offer()).await?;
+        let sdp = RtcSessionDescription::from(offer).sdp();
+        self.set_description(RtcSdpType::Offer, &sdp, false).await?;
+        Ok(sdp)
+    }
+
+    /// set the remote offer and returns the sdp of the answer
+    pub async fn create_answer(&self, offer: &str) -> Result<String, JsValue> {
+        self.set_description(RtcSdpType::Offer, offer, true).await?;
+        let answer = JsFuture::from(self.pc.create_answer()).await?;
+        let sdp = RtcSessionDescription::from(answer).sdp();
+        self.set_description(RtcSdpType::Answer, &sdp, false)
+            .await?;
+        Ok(sdp)
+    }
+
+    pub async fn set_answer(&self, answer: &str) -> Result<(), JsValue> {
+        self.set_description(RtcSdpType::Answer, answer, true).await
+    }
+
+    /// the candidate is kept until the remote description is set
+    pub async fn add_candidate(
+        &self,
+        candidate: &str,
+        sdp_mid: Option<&str>,
+        sdp_m_index: Option<u16>,
+    ) -> Result<(), JsValue> {
+        let mut init = RtcIceCandidateInit::new(candidate);
+        init.sdp_mid(sdp_mid);
+        init.sdp_m_line_index(sdp_m_index);
+        if let Some(pending) = self.pending_candidates.borrow_mut().as_mut() {
+            pending.push(init);
+            return Ok(());
+        }
+        JsFuture::from(
+            self.pc
+                .add_ice_candidate_with_opt_rtc_ice_candidate_init(Some(&init)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub fn close(&self) {
+        self.channels.borrow_mut().clear();
+        self.pc.set_onicecandidate(None);
+        self.pc.set_oniceconnectionstatechange(None);
+        self.pc.set_ondatachannel(None);
+        self.pc.close();
+    }
+
+    async fn set_description(
+        &self,
+        sdp_type: RtcSdpType,
+        sdp: &str,
+        remote: bool,
+    ) -> Result<(), JsValue> {
+        let mut description = RtcSessionDescriptionInit::new(sdp_type);
+        description.sdp(sdp);
+        if !remote {
+            JsFuture::from(self.pc.set_local_description(&description)).await?;
+            return Ok(());
+        }
+        JsFuture::from(self.pc.set_remote_description(&description)).await?;
+        let pending = self.pending_candidates.borrow_mut().take();
+        for init in pending.unwrap_or_default() {
+            if let Err(e) = JsFuture::from(
+                self.pc
+                    .add_ice_candidate_with_opt_rtc_ice_candidate_init(Some(&init)),
+            )
+            .await
+            {
+                log::error!("add pending candidate error: {:?}", e);
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,19 +1,42 @@
-use sandcat_sdk::model::configurations::STUN_SERVER;
-use std::cell::RefCell;
+mod data_channel;
+mod stats;
+
+pub use data_channel::*;
+pub use stats::*;
+
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use std::time::Duration;
+
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
-    RtcConfiguration, RtcIceConnectionState, RtcIceServer, RtcPeerConnection,
+    MessageEvent, RtcConfiguration, RtcDataChannel, RtcDataChannelInit, RtcDataChannelState,
+    RtcIceConnectionState, RtcIceServer, RtcOfferOptions, RtcPeerConnection, RtcRtpSender,
     RtcSessionDescriptionInit, RtcSignalingState,
 };
 use yew::platform::spawn_local;
+use yew::platform::time::sleep;
 use yew::{AttrValue, Callback};
 
-use sandcat_sdk::model::message::{Candidate, Msg, Offer, SingleCall};
+use sandcat_sdk::api;
+use sandcat_sdk::model::configurations::IceServer;
+use sandcat_sdk::model::message::{
+    Candidate, GroupCall, GroupCallCandidate, GroupCallSdp, Msg, Offer, SingleCall,
+};
+use sandcat_sdk::state::{IceServerOverrideState, IceServerState};
 use ws::WebSocketManager;
 
+/// wait for the connection to come back by itself before restarting the ice
+const DISCONNECT_GRACE: Duration = Duration::from_secs(3);
+/// hang up if the connection is still lost after the ice restart
+const RECOVERY_TIMEOUT: Duration = Duration::from_secs(20);
+const STATS_INTERVAL: Duration = Duration::from_secs(2);
+/// the negotiated data channel of the single call carrying the control messages
+const CONTROL_CHANNEL: &str = "control";
+const CONTROL_CHANNEL_ID: u16 = 0;
+
 pub struct WebRTC {
     on_ice_candidate: Option<Closure<dyn FnMut(web_sys::RtcPeerConnectionIceEvent)>>,
     on_track: Option<Closure<dyn FnMut(web_sys::RtcTrackEvent)>>,
@@ -24,6 +47,70 @@ pub struct WebRTC {
     pc: Option<RtcPeerConnection>,
     close_event: Callback<()>,
     conn_event: Callback<web_sys::RtcTrackEvent>,
+    /// emit true when the connection is lost and we are recovering it,
+    /// false when it recovered
+    reconnect_event: Callback<bool>,
+    /// the connection is one of the group call if it is set,
+    /// the signalling messages are sent as group call messages
+    group_id: Option<AttrValue>,
+    /// signalling info kept for the renegotiation
+    signal: Option<Signal>,
+    /// set false to stop sampling the stats
+    stats_running: Rc<Cell<bool>>,
+    /// emit the text received on the control channel
+    control_event: Callback<String>,
+    control_channel: Option<RtcDataChannel>,
+    on_control_message: Option<Closure<dyn FnMut(MessageEvent)>>,
+}
+
+#[derive(Clone)]
+struct Signal {
+    ws: Rc<RefCell<WebSocketManager>>,
+    group_id: Option<AttrValue>,
+    send_id: AttrValue,
+    friend_id: AttrValue,
+}
+
+impl Signal {
+    /// only one side restarts the ice to avoid the offer collision
+    fn restarts_ice(&self) -> bool {
+        self.send_id > self.friend_id
+    }
+
+    async fn send_offer(&self, pc: &RtcPeerConnection, ice_restart: bool) {
+        if pc.signaling_state() != RtcSignalingState::Stable {
+            log::debug!("send offer signaling state is : {:?}", pc.signaling_state());
+            return;
+        }
+        let promise = if ice_restart {
+            let mut options = RtcOfferOptions::new();
+            options.ice_restart(true);
+            pc.create_offer_with_rtc_offer_options(&options)
+        } else {
+            pc.create_offer()
+        };
+        let offer = match JsFuture::from(promise).await {
+            Ok(offer) => offer,
+            Err(e) => {
+                log::error!("create offer error: {:?}", e);
+                return;
+            }
+        };
+        if let Err(e) =
+            JsFuture::from(pc.set_local_description(&RtcSessionDescriptionInit::from(offer))).await
+        {
+            log::error!("set local description error: {:?}", e);
+            return;
+        }
+        let sdp = pc.local_description().unwrap().sdp();
+        let msg = offer_msg(
+            self.group_id.clone(),
+            sdp,
+            self.send_id.clone(),
+            self.friend_id.clone(),
+        );
+        WebRTC::send_msg1(self.ws.clone(), msg);
+    }
 }
 
 impl WebRTC {
@@ -38,6 +125,50 @@ impl WebRTC {
             pc: None,
             close_event,
             conn_event,
+            reconnect_event: Callback::noop(),
+            group_id: None,
+            signal: None,
+            stats_running: Rc::new(Cell::new(false)),
+            control_event: Callback::noop(),
+            control_channel: None,
+            on_control_message: None,
+        }
+    }
+
+    /// set before creating the peer connection
+    pub fn set_reconnect_event(&mut self, reconnect_event: Callback<bool>) {
+        self.reconnect_event = reconnect_event;
+    }
+
+    /// set before creating the peer connection, only for the single call
+    pub fn set_control_event(&mut self, control_event: Callback<String>) {
+        self.control_event = control_event;
+    }
+
+    /// send the text on the control channel, false if the channel is not open
+    pub fn send_control(&self, text: &str) -> bool {
+        let Some(channel) = self.control_channel.as_ref() else {
+            return false;
+        };
+        if channel.ready_state() != RtcDataChannelState::Open {
+            return false;
+        }
+        if let Err(e) = channel.send_with_str(text) {
+            log::error!("send control message error: {:?}", e);
+            return false;
+        }
+        true
+    }
+
+    /// connection to a member of the group call
+    pub fn new_in_group(
+        close_event: Callback<()>,
+        conn_event: Callback<web_sys::RtcTrackEvent>,
+        group_id: AttrValue,
+    ) -> Self {
+        Self {
+            group_id: Some(group_id),
+            ..Self::new(close_event, conn_event)
         }
     }
 
@@ -56,6 +187,70 @@ impl WebRTC {
         self.on_ice_connection_state_change = None;
         self.on_ice_gathering_state_change = None;
         self.on_track = None;
+        self.signal = None;
+        self.stats_running.set(false);
+        if let Some(channel) = self.control_channel.take() {
+            channel.set_onmessage(None);
+            channel.close();
+        }
+        self.on_control_message = None;
+    }
+
+    /// sample the stats periodically until the connection is closed
+    pub fn start_stats(&mut self, callback: Callback<CallStats>) {
+        let Some(pc) = self.pc.clone() else {
+            return;
+        };
+        self.stats_running.set(false);
+        let running = Rc::new(Cell::new(true));
+        self.stats_running = running.clone();
+        spawn_local(async move {
+            let mut prev = None;
+            while running.get() && pc.signaling_state() != RtcSignalingState::Closed {
+                match stats::get_stats(&pc, prev.as_ref()).await {
+                    Ok(stats) => {
+                        callback.emit(stats.clone());
+                        prev = Some(stats);
+                    }
+                    Err(e) => log::warn!("get stats error: {:?}", e),
+                }
+                sleep(STATS_INTERVAL).await;
+            }
+        });
+    }
+
+    /// the sender of the local video track, none if no video track was added
+    pub fn video_sender(&self) -> Option<RtcRtpSender> {
+        self.sender("video")
+    }
+
+    /// the sender of the local audio track, used to switch the microphone
+    pub fn audio_sender(&self) -> Option<RtcRtpSender> {
+        self.sender("audio")
+    }
+
+    fn sender(&self, kind: &str) -> Option<RtcRtpSender> {
+        let pc = self.pc.as_ref()?;
+        pc.get_senders()
+            .iter()
+            .filter_map(|sender| sender.dyn_into::<RtcRtpSender>().ok())
+            .find(|sender| {
+                sender
+                    .track()
+                    .map(|track| track.kind() == kind)
+                    .unwrap_or(false)
+            })
+    }
+
+    /// send a fresh offer after the tracks changed,
+    /// the negotiation needed handler only works for the first offer
+    pub fn renegotiate(&mut self) {
+        let (Some(pc), Some(signal)) = (self.pc.clone(), self.signal.clone()) else {
+            return;
+        };
+        pc.set_onnegotiationneeded(None);
+        self.on_negotiation = None;
+        spawn_local(async move { signal.send_offer(&pc, false).await });
     }
     pub fn send_msg1(ws: Rc<RefCell<WebSocketManager>>, msg: Msg) {
         // 发送已收到消息给服务器
@@ -73,33 +268,53 @@ impl WebRTC {
         send_id: AttrValue,
         friend_id: AttrValue,
     ) -> Result<(), JsValue> {
-        // 创建一个新的RtcIceServer
-        let mut ice_server = RtcIceServer::new();
-        ice_server.url(STUN_SERVER);
-
         // 将RtcIceServer添加到RtcConfiguration中
-        let ice_servers = js_sys::Array::from(&ice_server);
+        let ice_servers = IceServerState::servers()
+            .iter()
+            .map(to_rtc_ice_server)
+            .collect::<js_sys::Array>();
         let mut config = RtcConfiguration::new();
         config.ice_servers(&ice_servers);
 
         // 使用配置创建新的RtcPeerConnection
         let pc = RtcPeerConnection::new_with_configuration(&config)?;
+        let signal = Signal {
+            ws: ws.clone(),
+            group_id: self.group_id.clone(),
+            send_id: send_id.clone(),
+            friend_id: friend_id.clone(),
+        };
+        self.signal = Some(signal.clone());
         let send = send_id.clone();
         let friend = friend_id.clone();
+        let group = self.group_id.clone();
         let ws_clone = ws.clone();
         // 设定处理函数
         let on_ice_candidate =
             Closure::wrap(Box::new(move |event: web_sys::RtcPeerConnectionIceEvent| {
                 // handle ICE candidate event
                 if let Some(candidate) = event.candidate() {
-                    let msg_clone = Msg::SingleCall(SingleCall::NewIceCandidate(Candidate {
-                        candidate: candidate.candidate().into(),
-                        sdp_mid: candidate.sdp_mid(),
-                        sdp_m_index: candidate.sdp_m_line_index(),
-                        send_id: send.clone(),
-                        friend_id: friend.clone(),
-                        create_time: chrono::Utc::now().timestamp_millis(),
-                    }));
+                    let msg_clone = match group {
+                        Some(ref group_id) => {
+                            Msg::GroupCall(GroupCall::NewIceCandidate(GroupCallCandidate {
+                                candidate: candidate.candidate().into(),
+                                sdp_mid: candidate.sdp_mid(),
+                                sdp_m_index: candidate.sdp_m_line_index(),
+                                send_id: send.clone(),
+                                friend_id: friend.clone(),
+                                group_id: group_id.clone(),
+                                create_time: chrono::Utc::now().timestamp_millis(),
+                            }))
+                        }
+                        None => Msg::SingleCall(SingleCall::NewIceCandidate(Candidate {
+                            candidate: candidate.candidate().into(),
+                            sdp_mid: candidate.sdp_mid(),
+                            sdp_m_index: candidate.sdp_m_line_index(),
+                            send_id: send.clone(),
+                            friend_id: friend.clone(),
+                            create_time: chrono::Utc::now().timestamp_millis(),
+                        })),
+                    };
                     // log::debug!("on ice candidate send message:candidate:{:?}, ", &msg_clone);
                     WebRTC::send_msg1(ws_clone.clone(), msg_clone)
                 }
@@ -108,17 +323,39 @@ impl WebRTC {
 
         let pc_clone = pc.clone();
         let callback = self.close_event.clone();
+        let reconnect_event = self.reconnect_event.clone();
+        // the id of the current recovery, 0 means the connection is fine
+        let recovery = Rc::new(Cell::new(0u32));
+        let mut recovery_id = 0;
         let on_ice_connection_state_change = Closure::wrap(Box::new(move || {
             log::debug!(
                 "on ice connection state change:{:?}",
                 pc_clone.ice_connection_state()
             );
             match pc_clone.ice_connection_state() {
-                RtcIceConnectionState::Failed
-                | RtcIceConnectionState::Disconnected
-                | RtcIceConnectionState::Closed => {
+                RtcIceConnectionState::Failed | RtcIceConnectionState::Disconnected => {
+                    if recovery.get() != 0 {
+                        return;
+                    }
+                    // wifi blips, try to recover before closing the call
+                    recovery_id += 1;
+                    recovery.set(recovery_id);
+                    reconnect_event.emit(true);
+                    spawn_local(recover(
+                        pc_clone.clone(),
+                        signal.clone(),
+                        recovery.clone(),
+                        callback.clone(),
+                    ));
+                }
+                RtcIceConnectionState::Connected | RtcIceConnectionState::Completed => {
+                    if recovery.replace(0) != 0 {
+                        reconnect_event.emit(false);
+                    }
+                }
+                RtcIceConnectionState::Closed => {
                     // 关闭视频流
-                    callback.clone().emit(());
+                    callback.emit(());
                 }
                 _ => {}
             }
@@ -148,9 +385,9 @@ impl WebRTC {
         }) as Box<dyn FnMut()>);
 
         let pc_clone = pc.clone();
+        let group = self.group_id.clone();
         let on_negotiation_needed = Closure::once(Box::new(move || {
             // handle negotiation needed event
-            let ws = Rc::clone(&ws);
             spawn_local(async move {
                 let pc = pc_clone.clone();
                 let offer = JsFuture::from(pc.create_offer()).await.unwrap();
@@ -165,14 +402,9 @@ impl WebRTC {
                     .await
                     .unwrap();
                 let sdp = pc.local_description().unwrap().sdp();
-                let msg = Msg::SingleCall(SingleCall::Offer(Offer {
-                    sdp: sdp.into(),
-                    send_id,
-                    friend_id,
-                    create_time: chrono::Utc::now().timestamp_millis(),
-                }));
+                let msg = offer_msg(group, sdp, send_id, friend_id);
                 // log::debug!("on negotiation needed send message: {:?}", &msg);
-                WebRTC::send_msg1(ws.clone(), msg);
+                WebRTC::send_msg1(ws, msg);
             });
         }) as Box<dyn FnOnce()>);
         let conn_event = self.conn_event.clone();
@@ -199,7 +431,105 @@ impl WebRTC {
         self.on_ice_gathering_state_change = Some(on_ice_gathering_state_change);
         self.on_signaling_state_change = Some(on_signaling_state_change);
         self.on_negotiation = Some(on_negotiation_needed);
+
+        // created by both sides with the same id, it is in the first offer
+        // and nobody needs to wait for the other side to announce it
+        if self.group_id.is_none() {
+            let mut init = RtcDataChannelInit::new();
+            init.negotiated(true);
+            init.id(CONTROL_CHANNEL_ID);
+            let channel = pc.create_data_channel_with_data_channel_dict(CONTROL_CHANNEL, &init);
+            let control_event = self.control_event.clone();
+            let on_control_message = Closure::wrap(Box::new(move |event: MessageEvent| {
+                if let Some(text) = event.data().as_string() {
+                    control_event.emit(text);
+                }
+            }) as Box<dyn FnMut(MessageEvent)>);
+            channel.set_onmessage(Some(on_control_message.as_ref().unchecked_ref()));
+            self.control_channel = Some(channel);
+            self.on_control_message = Some(on_control_message);
+        }
         self.pc = Some(pc);
         Ok(())
     }
 }
+
+fn offer_msg(
+    group: Option<AttrValue>,
+    sdp: String,
+    send_id: AttrValue,
+    friend_id: AttrValue,
+) -> Msg {
+    let create_time = chrono::Utc::now().timestamp_millis();
+    match group {
+        Some(group_id) => Msg::GroupCall(GroupCall::Offer(GroupCallSdp {
+            sdp: sdp.into(),
+            send_id,
+            friend_id,
+            group_id,
+            create_time,
+        })),
+        None => Msg::SingleCall(SingleCall::Offer(Offer {
+            sdp: sdp.into(),
+            send_id,
+            friend_id,
+            create_time,
+        })),
+    }
+}
+
+/// restart the ice after the grace period,
+/// close the call if it is still not recovered after the timeout
+async fn recover(
+    pc: RtcPeerConnection,
+    signal: Signal,
+    recovery: Rc<Cell<u32>>,
+    close_event: Callback<()>,
+) {
+    let id = recovery.get();
+    let recovering = || recovery.get() == id && pc.signaling_state() != RtcSignalingState::Closed;
+
+    sleep(DISCONNECT_GRACE).await;
+    if !recovering() {
+        return;
+    }
+    if signal.restarts_ice() {
+        log::debug!("restart ice");
+        signal.send_offer(&pc, true).await;
+    }
+
+    sleep(RECOVERY_TIMEOUT).await;
+    if recovering() {
+        log::warn!("recover connection timeout");
+        close_event.emit(());
+    }
+}
+
+/// fetch the ice servers if the cache expired,
+/// called after login and before each call
+pub async fn refresh_ice_servers() {
+    if !IceServerOverrideState::get().urls.trim().is_empty() || IceServerState::is_valid() {
+        return;
+    }
+    match api::rtc().ice_servers().await {
+        Ok(config) => IceServerState::set(config),
+        Err(e) => log::error!("get ice servers error: {:?}", e),
+    }
+}
+
+fn to_rtc_ice_server(server: &IceServer) -> RtcIceServer {
+    let mut ice_server = RtcIceServer::new();
+    let urls = server
+        .urls
+        .iter()
+        .map(|url| JsValue::from_str(url))
+        .collect::<js_sys::Array>();
+    ice_server.urls(&urls);
+    if let Some(ref username) = server.username {
+        ice_server.username(username);
+    }
+    if let Some(ref credential) = server.credential {
+        ice_server.credential(credential);
+    }
+    ice_server
+}
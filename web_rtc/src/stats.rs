@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use js_sys::Reflect;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::RtcPeerConnection;
+
+/// quality of the call sampled from `RTCPeerConnection.getStats()`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CallStats {
+    /// round trip time in milliseconds
+    pub rtt: f64,
+    /// jitter of the received audio in milliseconds
+    pub jitter: f64,
+    /// percent of the received packets lost since the last sample
+    pub packet_loss: f64,
+    /// received bitrate in kbps
+    pub bitrate: f64,
+    /// resolution of the received video
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: f64,
+    pub candidate_type: CandidateType,
+    // counters used to compute the values since the last sample
+    bytes_received: f64,
+    packets_received: f64,
+    packets_lost: f64,
+    timestamp: f64,
+}
+
+/// type of the local candidate in the selected candidate pair
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CandidateType {
+    #[default]
+    Unknown,
+    Host,
+    Srflx,
+    Prflx,
+    Relay,
+}
+
+impl From<&str> for CandidateType {
+    fn from(value: &str) -> Self {
+        match value {
+            "host" => CandidateType::Host,
+            "srflx" => CandidateType::Srflx,
+            "prflx" => CandidateType::Prflx,
+            "relay" => CandidateType::Relay,
+            _ => CandidateType::Unknown,
+        }
+    }
+}
+
+impl Display for CandidateType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            CandidateType::Unknown => "unknown",
+            CandidateType::Host => "host",
+            CandidateType::Srflx => "srflx",
+            CandidateType::Prflx => "prflx",
+            CandidateType::Relay => "relay",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalLevel {
+    Good,
+    Fair,
+    Poor,
+}
+
+impl CallStats {
+    pub fn level(&self) -> SignalLevel {
+        if self.rtt > 400.0 || self.packet_loss > 10.0 {
+            SignalLevel::Poor
+        } else if self.rtt > 200.0 || self.packet_loss > 3.0 {
+            SignalLevel::Fair
+        } else {
+            SignalLevel::Good
+        }
+    }
+
+    /// one line summary, saved with the call record
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "rtt {:.0}ms, jitter {:.0}ms, loss {:.1}%, {:.0}kbps, {}",
+            self.rtt, self.jitter, self.packet_loss, self.bitrate, self.candidate_type
+        );
+        if self.width > 0 {
+            summary.push_str(&format!(
+                ", {}x{}@{:.0}fps",
+                self.width, self.height, self.frame_rate
+            ));
+        }
+        summary
+    }
+}
+
+/// sample the stats, the bitrate and packet loss are computed from the previous sample
+pub async fn get_stats(
+    pc: &RtcPeerConnection,
+    prev: Option<&CallStats>,
+) -> Result<CallStats, JsValue> {
+    let report = JsFuture::from(pc.get_stats()).await?;
+    let entries = js_sys::try_iter(&report)?
+        .ok_or_else(|| JsValue::from_str("stats report is not iterable"))?;
+
+    let mut stats = CallStats::default();
+    let mut pairs = HashMap::new();
+    let mut candidates = HashMap::new();
+    let mut selected_pair = None;
+    let mut nominated_pair = None;
+
+    for entry in entries {
+        let entry = entry?.unchecked_into::<js_sys::Array>();
+        let id = entry.get(0).as_string().unwrap_or_default();
+        let value = entry.get(1);
+        match get_str(&value, "type").as_deref() {
+            Some("transport") => {
+                if let Some(pair) = get_str(&value, "selectedCandidatePairId") {
+                    selected_pair = Some(pair);
+                }
+            }
+            Some("candidate-pair") => {
+                if get_bool(&value, "nominated")
+                    && get_str(&value, "state").as_deref() == Some("succeeded")
+                {
+                    nominated_pair = Some(id.clone());
+                }
+                pairs.insert(id, value);
+            }
+            Some("local-candidate") => {
+                if let Some(candidate_type) = get_str(&value, "candidateType") {
+                    candidates.insert(id, candidate_type);
+                }
+            }
+            Some("inbound-rtp") => {
+                stats.bytes_received += get_f64(&value, "bytesReceived");
+                stats.packets_received += get_f64(&value, "packetsReceived");
+                stats.packets_lost += get_f64(&value, "packetsLost");
+                stats.timestamp = stats.timestamp.max(get_f64(&value, "timestamp"));
+                match get_str(&value, "kind").as_deref() {
+                    Some("audio") => stats.jitter = get_f64(&value, "jitter") * 1000.0,
+                    Some("video") => {
+                        stats.width = get_f64(&value, "frameWidth") as u32;
+                        stats.height = get_f64(&value, "frameHeight") as u32;
+                        stats.frame_rate = get_f64(&value, "framesPerSecond");
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // firefox has no transport stats, use the nominated pair instead
+    if let Some(pair) = selected_pair
+        .or(nominated_pair)
+        .and_then(|id| pairs.get(&id))
+    {
+        stats.rtt = get_f64(pair, "currentRoundTripTime") * 1000.0;
+        stats.candidate_type = get_str(pair, "localCandidateId")
+            .and_then(|id| candidates.get(&id))
+            .map(|candidate_type| CandidateType::from(candidate_type.as_str()))
+            .unwrap_or_default();
+    }
+
+    let (bytes, received, lost, duration) = match prev {
+        Some(prev) => (
+            stats.bytes_received - prev.bytes_received,
+            stats.packets_received - prev.packets_received,
+            stats.packets_lost - prev.packets_lost,
+            stats.timestamp - prev.timestamp,
+        ),
+        None => (0.0, stats.packets_received, stats.packets_lost, 0.0),
+    };
+    if duration > 0.0 {
+        // bits per millisecond is kbps
+        stats.bitrate = (bytes * 8.0 / duration).max(0.0);
+    }
+    if received + lost > 0.0 {
+        stats.packet_loss = (lost / (received + lost) * 100.0).clamp(0.0, 100.0);
+    }
+    Ok(stats)
+}
+
+fn get_value(value: &JsValue, key: &str) -> Option<JsValue> {
+    Reflect::get(value, &JsValue::from_str(key))
+        .ok()
+        .filter(|v| !v.is_undefined())
+}
+
+fn get_str(value: &JsValue, key: &str) -> Option<String> {
+    get_value(value, key).and_then(|v| v.as_string())
+}
+
+fn get_f64(value: &JsValue, key: &str) -> f64 {
+    get_value(value, key)
+        .and_then(|v| v.as_f64())
+        .unwrap_or_default()
+}
+
+fn get_bool(value: &JsValue, key: &str) -> bool {
+    get_value(value, key)
+        .and_then(|v| v.as_bool())
+        .unwrap_or_default()
+}
@@ -1,24 +1,32 @@
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::time::Duration;
 
 use log::debug;
 use log::error;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{CloseEvent, ErrorEvent, MessageEvent, WebSocket};
+use yew::platform::spawn_local;
+use yew::platform::time::sleep;
 use yew::Callback;
 
 use sandcat_sdk::error::Error;
 use sandcat_sdk::error::Result;
 use sandcat_sdk::model::message::convert_server_msg;
 use sandcat_sdk::model::message::Msg;
+use sandcat_sdk::model::protocol::{handshake_query, read_handshake, Codec};
 use sandcat_sdk::model::TOKEN;
-use sandcat_sdk::pb::message::Msg as PbMsg;
+use sandcat_sdk::pb::message::Handshake;
 use sandcat_sdk::state::ConnectState;
 use sandcat_sdk::state::Notify;
+use sandcat_sdk::state::ProtocolState;
 
 const KNOCKOFF_CODE: u16 = 4001;
 pub const UNAUTHORIZED_CODE: u16 = 4002;
+/// the servers without the handshake send nothing after the socket opened,
+/// fall back to bincode if the handshake doesn't arrive in time
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
 
 #[derive(Debug)]
 pub struct WebSocketManager {
@@ -31,6 +39,18 @@ pub struct WebSocketManager {
     receive_callback: Callback<Msg>,
     knockoff_callback: Callback<()>,
     logout_callback: Callback<()>,
+    /// the frame encoding negotiated by the handshake
+    codec: Codec,
+    /// the codec the server negotiated last time, the next sockets send by it
+    /// right away instead of waiting for the handshake again
+    negotiated: Option<Codec>,
+    /// the socket opened and the handshake is not finished,
+    /// the messages are held until the codec is known for the first time;
+    /// they are kept for the next socket if this one is closed before it
+    handshaking: bool,
+    pending: RefCell<Vec<Msg>>,
+    /// increased for each opened socket, so the timeout of the old one is ignored
+    connection_id: u32,
     // prevent memory leaks
     on_timeout: Option<Closure<dyn FnMut()>>,
     on_open: Option<Closure<dyn FnMut()>>,
@@ -62,6 +82,11 @@ impl WebSocketManager {
             receive_callback,
             knockoff_callback,
             logout_callback,
+            codec: Codec::default(),
+            negotiated: None,
+            handshaking: false,
+            pending: RefCell::default(),
+            connection_id: 0,
             on_timeout: None,
             on_open: None,
             on_close: None,
@@ -87,9 +112,10 @@ impl WebSocketManager {
 
         let ws = WebSocket::new(
             format!(
-                "{}/{}",
+                "{}/{}?{}",
                 ws_manager.borrow().url,
-                utils::get_local_storage(TOKEN)?
+                utils::get_local_storage(TOKEN)?,
+                handshake_query()
             )
             .as_str(),
         )
@@ -103,8 +129,22 @@ impl WebSocketManager {
 
         let on_open = Closure::wrap(Box::new(move || {
             log::info!("WebSocket connection opened");
-            // set the count of reconnect to 0
-            cloned_ws.borrow_mut().reconnect_attempts = 0;
+            let connection_id = {
+                let mut manager = cloned_ws.borrow_mut();
+                // set the count of reconnect to 0
+                manager.reconnect_attempts = 0;
+                manager.codec = manager.negotiated.unwrap_or_default();
+                manager.handshaking = true;
+                manager.connection_id += 1;
+                manager.connection_id
+            };
+            let ws_manager = cloned_ws.clone();
+            spawn_local(async move {
+                sleep(HANDSHAKE_TIMEOUT).await;
+                if ws_manager.borrow().connection_id == connection_id {
+                    WebSocketManager::finish_handshake(&ws_manager, None);
+                }
+            });
             ConnectState::Connected.notify();
         }) as Box<dyn FnMut()>);
 
@@ -114,27 +154,13 @@ impl WebSocketManager {
                 let arr = js_sys::Uint8Array::new(&ab);
                 let mut body = vec![0; arr.length() as usize];
                 arr.copy_to(&mut body[..]);
-
-                match bincode::deserialize(&body) {
-                    Ok(msg) => match convert_server_msg(msg) {
-                        Ok(msg) => ws_manager_clone.borrow_mut().receive_callback.emit(msg),
-                        Err(e) => log::error!("convert message error {e}"),
-                    },
-                    Err(err) => log::error!("deserialize error: {:?}", err),
-                }
+                WebSocketManager::on_frame(&ws_manager_clone, &body);
             } else if let Ok(blob) = e.data().dyn_into::<web_sys::Blob>() {
                 // if message type is we need to convert it to ArrayBuffer
                 let arr = js_sys::Uint8Array::new(&blob);
                 let mut body = vec![0; arr.length() as usize];
                 arr.copy_to(&mut body[..]);
-
-                match bincode::deserialize(&body) {
-                    Ok(msg) => match convert_server_msg(msg) {
-                        Ok(msg) => ws_manager_clone.borrow().receive_callback.emit(msg),
-                        Err(e) => log::error!("convert message error {e}"),
-                    },
-                    Err(err) => log::error!("deserialize error: {:?}", err),
-                }
+                WebSocketManager::on_frame(&ws_manager_clone, &body);
             } else {
                 log::error!("Unexpected message format!")
             }
@@ -161,6 +187,7 @@ impl WebSocketManager {
                 }
                 _ => {
                     log::warn!("WebSocket closed: {:?}", e);
+                    ws_manager_clone.borrow_mut().stop_handshake();
                     ConnectState::DisConnect.notify();
                 }
             }
@@ -189,16 +216,89 @@ impl WebSocketManager {
     }
 
     pub fn send_message(&self, message: Msg) -> Result<()> {
-        if let Some(ws) = &self.ws {
-            // encode message
-            let msg = bincode::serialize(&PbMsg::from(message))?;
-            ws.send_with_u8_array(&msg).map_err(Error::send_err)?;
-            Ok(())
-        } else {
-            Err(Error::ws_closed())
+        if self.ws.is_none() {
+            return Err(Error::ws_closed());
+        }
+        if self.handshaking && self.negotiated.is_none() {
+            self.pending.borrow_mut().push(message);
+            return Ok(());
+        }
+        self.send_frame(message)
+    }
+
+    fn send_frame(&self, message: Msg) -> Result<()> {
+        let ws = self.ws.as_ref().ok_or_else(Error::ws_closed)?;
+        // encode message
+        let frame = self.codec.encode(&message.into_pb_msg(self.codec))?;
+        ws.send_with_u8_array(&frame).map_err(Error::send_err)?;
+        Ok(())
+    }
+
+    /// the first frame is read as bincode, it is the handshake of the new servers
+    fn on_frame(ws_manager: &Rc<RefCell<Self>>, body: &[u8]) {
+        if ws_manager.borrow().handshaking {
+            let handshake = Codec::Bincode
+                .decode(body)
+                .ok()
+                .and_then(|msg| read_handshake(&msg));
+            let is_handshake = handshake.is_some();
+            Self::finish_handshake(ws_manager, handshake);
+            if is_handshake {
+                return;
+            }
+        }
+        let codec = ws_manager.borrow().codec;
+        let msg = match codec.decode(body) {
+            Ok(msg) => msg,
+            Err(err) => {
+                log::error!("deserialize error: {:?}", err);
+                return;
+            }
+        };
+        match convert_server_msg(msg, codec) {
+            Ok(msg) => {
+                // don't hold the borrow, the receiver may send messages
+                let callback = ws_manager.borrow().receive_callback.clone();
+                callback.emit(msg);
+            }
+            Err(e) => log::error!("convert message error {e}"),
         }
     }
 
+    /// switch to the negotiated codec and send the held messages,
+    /// the handshake is none for the servers without it
+    fn finish_handshake(ws_manager: &Rc<RefCell<Self>>, handshake: Option<Handshake>) {
+        let mut manager = ws_manager.borrow_mut();
+        if !manager.handshaking {
+            return;
+        }
+        manager.handshaking = false;
+        let state = match handshake {
+            Some(handshake) => ProtocolState {
+                version: handshake.version,
+                codec: Codec::from_name(&handshake.codec).unwrap_or_default(),
+                capabilities: handshake.capabilities,
+            },
+            None => ProtocolState::default(),
+        };
+        debug!("protocol negotiated: {:?}", state);
+        manager.codec = state.codec;
+        manager.negotiated = Some(state.codec);
+        let pending = manager.pending.take();
+        for msg in pending {
+            if let Err(err) = manager.send_frame(msg) {
+                error!("send held message error: {:?}", err);
+            }
+        }
+        drop(manager);
+        state.notify();
+    }
+
+    /// the held messages are sent after the handshake of the next socket
+    fn stop_handshake(&mut self) {
+        self.handshaking = false;
+    }
+
     fn reconnect(&mut self, ws_manager: Rc<RefCell<Self>>) -> Result<()> {
         // log::debug!("第{}次重连", self.reconnect_attempts);
         self.is_reconnecting = true;
@@ -237,6 +337,11 @@ impl WebSocketManager {
             ws.set_onclose(None);
         }
 
+        self.stop_handshake();
+        let pending = self.pending.take();
+        if !pending.is_empty() {
+            log::warn!("drop {} held messages with the socket", pending.len());
+        }
         self.on_close = None;
         self.on_error = None;
         self.on_message = None;